@@ -0,0 +1,141 @@
+// Measures the time and the allocations of locate queries on a database file
+// with many entries. Run with `cargo bench`. The database file is written
+// without the optional parts of later versions, i.e. the benchmark compares
+// the hot loop of different versions on the same file.
+
+use fastvlq::WriteVu64Ext;
+use fsidx::{locate, FilterToken, LocateConfig, LocateEvent, VolumeInfo};
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::fs::{self, File};
+use std::io::{BufWriter, Result as IOResult, Write};
+use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+static ALLOCATIONS: AtomicU64 = AtomicU64::new(0);
+
+struct CountingAllocator;
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.realloc(ptr, layout, new_size)
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+const ENTRIES: usize = 1_000_000;
+const FILES_PER_DIRECTORY: usize = 1000;
+const RUNS: usize = 10;
+
+const NAMES: [&str; 4] = ["song", "report_final", "notes", "Übersicht"];
+const EXTENSIONS: [&str; 4] = ["flac", "pdf", "txt", "jpg"];
+
+fn entry_path(index: usize) -> Vec<u8> {
+    let directory = index / FILES_PER_DIRECTORY;
+    let name = NAMES[index % NAMES.len()];
+    let extension = EXTENSIONS[index / NAMES.len() % EXTENSIONS.len()];
+    format!(
+        "/bench/d{:04}/{}{:07}.{}",
+        directory, name, index, extension
+    )
+    .into_bytes()
+}
+
+// Magic bytes and Settings::WithFileSizes. Entries are delta encoded to the
+// previous path and followed by the size plus one.
+fn write_database(database: &Path) -> IOResult<()> {
+    let mut writer = BufWriter::new(File::create(database)?);
+    writer.write_all(b"fsix\x01")?;
+    let mut previous = Vec::new();
+    for index in 0..ENTRIES {
+        let path = entry_path(index);
+        let common = previous
+            .iter()
+            .zip(&path)
+            .take_while(|(a, b)| a == b)
+            .count();
+        writer.write_vu64((previous.len() - common) as u64)?;
+        writer.write_vu64((path.len() - common) as u64)?;
+        writer.write_all(&path[common..])?;
+        writer.write_vu64((index % 100_000) as u64 + 1)?;
+        previous = path;
+    }
+    writer.flush()
+}
+
+// Returns the number of matches, the fastest run and the allocations of a
+// run.
+fn measure(database: &Path, filter: &[FilterToken]) -> (usize, Duration, u64) {
+    let config = LocateConfig::default();
+    let mut fastest = Duration::MAX;
+    let mut allocations = 0;
+    let mut matches = 0;
+    for _ in 0..RUNS {
+        let volume_info = vec![VolumeInfo::new("/bench", database)];
+        let filter = filter.to_vec();
+        matches = 0;
+        let start = Instant::now();
+        let before = ALLOCATIONS.load(Ordering::Relaxed);
+        locate(volume_info, filter, &config, None, |event| {
+            if let LocateEvent::Entry(_, _) = event {
+                matches += 1;
+            }
+            Ok(())
+        })
+        .unwrap();
+        allocations = ALLOCATIONS.load(Ordering::Relaxed) - before;
+        fastest = fastest.min(start.elapsed());
+    }
+    (matches, fastest, allocations)
+}
+
+fn main() {
+    let database = std::env::temp_dir().join(format!("fsidx-bench-{}.fsdb", std::process::id()));
+    write_database(&database).unwrap();
+    let cases = [
+        (
+            "few matches",
+            vec![FilterToken::Text(String::from("0123456"))],
+        ),
+        (
+            "many matches",
+            vec![FilterToken::Text(String::from("song"))],
+        ),
+        (
+            "last element",
+            vec![
+                FilterToken::LastElement,
+                FilterToken::Text(String::from("flac")),
+            ],
+        ),
+        (
+            "non-ASCII",
+            vec![FilterToken::Text(String::from("übersicht"))],
+        ),
+        ("size only", vec![FilterToken::MinSize(99_000)]),
+    ];
+    println!("{} entries, fastest of {} runs", ENTRIES, RUNS);
+    for (name, filter) in &cases {
+        let (matches, fastest, allocations) = measure(&database, filter);
+        println!(
+            "{:<16}{:>8} matches{:>10.1} ms{:>10} allocations",
+            name,
+            matches,
+            fastest.as_secs_f64() * 1000.0,
+            allocations
+        );
+    }
+    fs::remove_file(database).unwrap();
+}
@@ -0,0 +1,199 @@
+use crate::checksum::verify_content;
+use crate::locate::{advise_sequential, LocateError};
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::{self, Cursor};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::SystemTime;
+
+/// DatabaseCache keeps the content of database files in memory.
+///
+/// The cache is used by [locate_cached](crate::locate_cached()). A cached
+/// database file is invalidated when its modification time or size changes,
+/// e.g. after running [update](crate::update()).
+#[derive(Debug, Default)]
+pub struct DatabaseCache {
+    databases: HashMap<PathBuf, CachedDatabase>,
+    limit: Option<u64>,
+}
+
+#[derive(Debug)]
+struct CachedDatabase {
+    modified: SystemTime,
+    len: u64,
+    content: Arc<[u8]>,
+    // The checksum is verified once, when the first query requires it.
+    verified: bool,
+}
+
+impl DatabaseCache {
+    /// Creates an empty cache.
+    pub fn new() -> DatabaseCache {
+        DatabaseCache::default()
+    }
+
+    /// Creates an empty cache which keeps at most limit bytes in memory.
+    ///
+    /// Database files which don't fit into the cache are read from disk for
+    /// each query.
+    pub fn with_limit(limit: u64) -> DatabaseCache {
+        DatabaseCache {
+            limit: Some(limit),
+            ..DatabaseCache::default()
+        }
+    }
+
+    /// Removes all cached database files.
+    pub fn clear(&mut self) {
+        self.databases.clear();
+    }
+
+    /// Reads a database file into the cache, unless the cached content is
+    /// up to date. Returns false, if the database file exceeds the limit of
+    /// the cache.
+    pub fn load(&mut self, database: &Path) -> Result<bool, LocateError> {
+        Ok(self.get(database, false)?.is_some())
+    }
+
+    // Returns None, if the database file exceeds the limit of the cache. With
+    // verify the checksum of the content is verified, unless it was verified
+    // before.
+    pub(crate) fn get(
+        &mut self,
+        database: &Path,
+        verify: bool,
+    ) -> Result<Option<Arc<[u8]>>, LocateError> {
+        let metadata = fs::metadata(database)
+            .map_err(|err| LocateError::ReadingFileFailed(database.to_owned(), err))?;
+        let modified = metadata
+            .modified()
+            .map_err(|err| LocateError::ReadingFileFailed(database.to_owned(), err))?;
+        let len = metadata.len();
+        if let Some(cached) = self.databases.get_mut(database) {
+            if cached.modified == modified && cached.len == len {
+                if verify && !cached.verified {
+                    verify_content(database, Cursor::new(&cached.content), len)?;
+                    cached.verified = true;
+                }
+                return Ok(Some(cached.content.clone()));
+            }
+        }
+        if let Some(limit) = self.limit {
+            let used: u64 = self
+                .databases
+                .iter()
+                .filter(|(path, _)| path.as_path() != database)
+                .map(|(_, cached)| cached.len)
+                .sum();
+            if used + len > limit {
+                self.databases.remove(database);
+                return Ok(None);
+            }
+        }
+        let content: Arc<[u8]> = fs::read(database)
+            .map_err(|err| LocateError::ReadingFileFailed(database.to_owned(), err))?
+            .into();
+        // A database file with checksum mismatch isn't cached.
+        if verify {
+            verify_content(database, Cursor::new(&content), content.len() as u64)?;
+        }
+        self.databases.insert(
+            database.to_owned(),
+            CachedDatabase {
+                modified,
+                len,
+                content: content.clone(),
+                verified: verify,
+            },
+        );
+        Ok(Some(content))
+    }
+}
+
+/// Reads a database file once, such that the operating system keeps it in
+/// its page cache and the next query doesn't wait for the disk. Returns the
+/// size of the database file.
+pub fn warm(database: &Path) -> Result<u64, LocateError> {
+    let map_err = |err| LocateError::ReadingFileFailed(database.to_owned(), err);
+    let mut file = File::open(database).map_err(map_err)?;
+    advise_sequential(&file);
+    io::copy(&mut file, &mut io::sink()).map_err(map_err)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::checksum::ChecksumWriter;
+    use crate::locate::FOOTER;
+    use crate::temp_dir::TempDir;
+    use std::io::Write;
+
+    #[test]
+    fn invalidate_modified_database() {
+        let dir = TempDir::new("cache");
+        let database = dir.join("cache.fsdb");
+        let mut cache = DatabaseCache::new();
+        fs::write(&database, b"fsix\x00").unwrap();
+        assert_eq!(&*cache.get(&database, false).unwrap().unwrap(), b"fsix\x00");
+        fs::write(&database, b"fsix\x01\x00\x01/").unwrap();
+        assert_eq!(
+            &*cache.get(&database, false).unwrap().unwrap(),
+            b"fsix\x01\x00\x01/"
+        );
+        fs::remove_file(&database).unwrap();
+        assert!(cache.get(&database, false).is_err());
+    }
+
+    #[test]
+    fn limit() {
+        let dir = TempDir::new("limit");
+        let database = dir.join("limit.fsdb");
+        let mut cache = DatabaseCache::with_limit(6);
+        fs::write(&database, b"fsix\x00").unwrap();
+        assert!(cache.get(&database, false).unwrap().is_some());
+        fs::write(&database, b"fsix\x01\x00\x01/").unwrap();
+        assert!(cache.get(&database, false).unwrap().is_none());
+        assert!(cache.databases.is_empty());
+    }
+
+    #[test]
+    fn verify_once() {
+        let dir = TempDir::new("verify");
+        let database = dir.join("verify.fsdb");
+        let mut content = Vec::new();
+        let mut writer = ChecksumWriter::new(&mut content);
+        writer.write_all(b"fsix").unwrap();
+        writer.write_all(&[FOOTER]).unwrap();
+        writer.write_all(b"\x00\x02/a").unwrap();
+        writer.finish().unwrap();
+        let mut damaged = content.clone();
+        damaged[7] = b'b';
+        let mut cache = DatabaseCache::new();
+        fs::write(&database, &damaged).unwrap();
+        // Cached without verification, verified by the first query requiring it.
+        assert!(cache.get(&database, false).unwrap().is_some());
+        let res = cache.get(&database, true);
+        assert!(matches!(res, Err(LocateError::ChecksumMismatch(_))));
+        cache.clear();
+        fs::write(&database, &content).unwrap();
+        assert!(cache.get(&database, false).unwrap().is_some());
+        assert!(!cache.databases[&database].verified);
+        let res = cache.get(&database, true);
+        assert_eq!(&*res.unwrap().unwrap(), &content[..]);
+        assert!(cache.databases[&database].verified);
+    }
+
+    #[test]
+    fn load_and_warm() {
+        let dir = TempDir::new("warm");
+        let database = dir.join("warm.fsdb");
+        fs::write(&database, b"fsix\x01\x00\x01/").unwrap();
+        let mut cache = DatabaseCache::with_limit(8);
+        assert!(cache.load(&database).unwrap());
+        assert_eq!(cache.databases.len(), 1);
+        assert_eq!(warm(&database).unwrap(), 8);
+        fs::remove_file(&database).unwrap();
+        assert!(warm(&database).is_err());
+    }
+}
@@ -0,0 +1,292 @@
+use crate::locate::{FileIndexReader, LocateError, FOOTER};
+use std::fs::File;
+use std::io::{BufReader, ErrorKind, Read, Result as IOResult, Write};
+use std::os::unix::ffi::OsStrExt;
+use std::path::Path;
+use std::time::UNIX_EPOCH;
+
+/// Verification reports the result of verifying a database file.
+#[derive(Debug, PartialEq)]
+#[non_exhaustive]
+pub enum Verification {
+    /// The checksum in the footer matches the content of the database file.
+    /// Contains the number of entries, unless only the checksum is verified.
+    Valid(Option<u64>),
+    /// The database file was written by an earlier version without
+    /// checksum. Contains the number of entries, unless only the checksum is
+    /// verified.
+    NoChecksum(Option<u64>),
+}
+
+/// The verify function checks the checksum in the footer of a database
+/// file.
+///
+/// With fast only the checksum is compared with the content. Otherwise all
+/// entries are decoded and counted, too. A damaged or truncated database file
+/// is reported with [LocateError::ChecksumMismatch] or
+/// [LocateError::UnexpectedEof].
+pub fn verify(database: &Path, fast: bool) -> Result<Verification, LocateError> {
+    let has_checksum = verify_checksum(database)?;
+    let entries = if fast {
+        None
+    } else {
+        let mut reader = FileIndexReader::new(database)?;
+        let mut entries = 0;
+        while reader.advance()? {
+            entries += 1;
+        }
+        Some(entries)
+    };
+    if has_checksum {
+        Ok(Verification::Valid(entries))
+    } else {
+        Ok(Verification::NoChecksum(entries))
+    }
+}
+
+/// Portability counts the entries of a database file, which are read
+/// differently on other platforms.
+#[derive(Debug, Default, PartialEq)]
+pub struct Portability {
+    /// Number of entries.
+    pub entries: u64,
+    /// Entries with a modification time after January 2038. Platforms with a
+    /// 32-bit time_t, e.g. older ARM systems, read these times as unknown.
+    pub times_after_2038: u64,
+    /// Entries with a path, which is not valid UTF-8. File systems of macOS
+    /// only store UTF-8 names, i.e. these paths can't exist there.
+    pub non_utf8_paths: u64,
+}
+
+impl Portability {
+    /// Returns true, if all entries are read the same way on all platforms.
+    pub fn is_portable(&self) -> bool {
+        self.times_after_2038 == 0 && self.non_utf8_paths == 0
+    }
+}
+
+/// The check_portability function decodes all entries of a database file
+/// and counts the values, which are not read the same way on all platforms.
+///
+/// The format itself doesn't depend on the platform. Integers are stored as
+/// vu64, the checksum in big-endian byte order and paths as bytes, i.e.
+/// database files written on macOS are read on Linux and vice versa,
+/// independent of byte order and word size.
+pub fn check_portability(database: &Path) -> Result<Portability, LocateError> {
+    let mut reader = FileIndexReader::new(database)?;
+    let mut portability = Portability::default();
+    while let Some((path, metadata)) = reader.next_entry()? {
+        portability.entries += 1;
+        if metadata
+            .modified
+            .and_then(|modified| modified.duration_since(UNIX_EPOCH).ok())
+            .is_some_and(|modified| modified.as_secs() > i32::MAX as u64)
+        {
+            portability.times_after_2038 += 1;
+        }
+        if std::str::from_utf8(path.as_os_str().as_bytes()).is_err() {
+            portability.non_utf8_paths += 1;
+        }
+    }
+    Ok(portability)
+}
+
+// Returns false for database files without footer.
+pub(crate) fn verify_checksum(database: &Path) -> Result<bool, LocateError> {
+    let map_err = |err| LocateError::ReadingFileFailed(database.to_owned(), err);
+    let file = File::open(database).map_err(map_err)?;
+    let length = file.metadata().map_err(map_err)?.len();
+    verify_content(database, BufReader::new(file), length)
+}
+
+// The checksum is stored in the last 4 bytes and covers all other bytes,
+// i.e. the header, the entries and the end marker.
+pub(crate) fn verify_content<R: Read>(
+    database: &Path,
+    mut reader: R,
+    length: u64,
+) -> Result<bool, LocateError> {
+    let map_err = |err: std::io::Error| match err.kind() {
+        ErrorKind::UnexpectedEof => LocateError::UnexpectedEof(database.to_owned()),
+        _ => LocateError::ReadingFileFailed(database.to_owned(), err),
+    };
+    let mut header = [0u8; 5];
+    reader.read_exact(&mut header).map_err(map_err)?;
+    if &header[..4] != "fsix".as_bytes() {
+        return Err(LocateError::ExpectedFsdbFile(database.to_owned()));
+    }
+    if header[4] & FOOTER == 0 {
+        return Ok(false);
+    }
+    let Some(content) = length.checked_sub(header.len() as u64 + 4) else {
+        return Err(LocateError::UnexpectedEof(database.to_owned()));
+    };
+    let mut crc = Crc32::new();
+    crc.update(&header);
+    let mut buffer = [0u8; 64 * 1024];
+    let mut content_reader = (&mut reader).take(content);
+    loop {
+        let len = content_reader.read(&mut buffer).map_err(map_err)?;
+        if len == 0 {
+            break;
+        }
+        crc.update(&buffer[..len]);
+    }
+    let mut checksum = [0u8; 4];
+    reader.read_exact(&mut checksum).map_err(map_err)?;
+    if crc.value() != u32::from_be_bytes(checksum) {
+        return Err(LocateError::ChecksumMismatch(database.to_owned()));
+    }
+    Ok(true)
+}
+
+// CRC-32 with the polynomial of zlib and PNG.
+pub(crate) struct Crc32(u32);
+
+const CRC_TABLE: [u32; 256] = crc_table();
+
+const fn crc_table() -> [u32; 256] {
+    let mut table = [0u32; 256];
+    let mut index = 0;
+    while index < 256 {
+        let mut value = index as u32;
+        let mut bit = 0;
+        while bit < 8 {
+            value = if value & 1 != 0 {
+                (value >> 1) ^ 0xedb88320
+            } else {
+                value >> 1
+            };
+            bit += 1;
+        }
+        table[index] = value;
+        index += 1;
+    }
+    table
+}
+
+impl Crc32 {
+    pub(crate) fn new() -> Crc32 {
+        Crc32(0xffffffff)
+    }
+
+    pub(crate) fn update(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.0 = CRC_TABLE[((self.0 ^ *byte as u32) & 0xff) as usize] ^ (self.0 >> 8);
+        }
+    }
+
+    pub(crate) fn value(&self) -> u32 {
+        !self.0
+    }
+}
+
+// Computes the checksum of the written bytes for the footer.
+pub(crate) struct ChecksumWriter<W: Write> {
+    writer: W,
+    crc: Crc32,
+}
+
+impl<W: Write> ChecksumWriter<W> {
+    pub(crate) fn new(writer: W) -> ChecksumWriter<W> {
+        ChecksumWriter {
+            writer,
+            crc: Crc32::new(),
+        }
+    }
+
+    // Writes the checksum, which isn't part of the checksum itself.
+    pub(crate) fn finish(mut self) -> IOResult<W> {
+        self.writer.write_all(&self.crc.value().to_be_bytes())?;
+        Ok(self.writer)
+    }
+}
+
+impl<W: Write> Write for ChecksumWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> IOResult<usize> {
+        let len = self.writer.write(buf)?;
+        self.crc.update(&buf[..len]);
+        Ok(len)
+    }
+
+    fn flush(&mut self) -> IOResult<()> {
+        self.writer.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::temp_dir::TempDir;
+    use crate::{DbWriter, Metadata, Settings};
+    use std::ffi::OsStr;
+    use std::fs;
+    use std::io::Cursor;
+    use std::time::Duration;
+
+    #[test]
+    fn crc32() {
+        let mut crc = Crc32::new();
+        crc.update(b"123456789");
+        assert_eq!(crc.value(), 0xcbf43926);
+    }
+
+    #[test]
+    fn content() {
+        let database = Path::new("test.fsdb");
+        let mut content = Vec::new();
+        let mut writer = ChecksumWriter::new(&mut content);
+        writer.write_all(b"fsix").unwrap();
+        writer.write_all(&[FOOTER]).unwrap();
+        writer.write_all(b"\x00\x02/a").unwrap();
+        writer.finish().unwrap();
+        let verify =
+            |content: &[u8]| verify_content(database, Cursor::new(content), content.len() as u64);
+        assert!(verify(&content).unwrap());
+        let mut damaged = content.clone();
+        damaged[7] = b'b';
+        assert!(matches!(
+            verify(&damaged),
+            Err(LocateError::ChecksumMismatch(_))
+        ));
+        assert!(matches!(
+            verify(&content[..6]),
+            Err(LocateError::UnexpectedEof(_))
+        ));
+        assert!(!verify(b"fsix\x01\x00\x02/a").unwrap());
+    }
+
+    #[test]
+    fn portability() {
+        let dir = TempDir::new("portability");
+        let database = dir.join("portability.fsdb");
+        let mut writer =
+            DbWriter::with_modification_times(Vec::new(), Settings::WithFileSizes).unwrap();
+        for (path, seconds) in [
+            (&b"/a"[..], 1 << 31),
+            (b"/a/b", i32::MAX as u64),
+            (b"/a/\xff", 0),
+        ] {
+            let metadata = Metadata {
+                size: Some(0),
+                modified: Some(UNIX_EPOCH + Duration::from_secs(seconds)),
+                attributes: Vec::new(),
+                entry_type: None,
+            };
+            writer
+                .add_entry(Path::new(OsStr::from_bytes(path)), &metadata)
+                .unwrap();
+        }
+        fs::write(&database, writer.finish().unwrap()).unwrap();
+        let portability = check_portability(&database).unwrap();
+        assert_eq!(
+            portability,
+            Portability {
+                entries: 3,
+                times_after_2038: 1,
+                non_utf8_paths: 1,
+            }
+        );
+        assert!(!portability.is_portable());
+    }
+}
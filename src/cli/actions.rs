@@ -0,0 +1,587 @@
+use crate::cli::CliError;
+use crate::clipboard::copy_to_clipboard;
+use crate::config::Config;
+use crate::event::{print_error, print_event};
+use crate::expand::{Expand, OpenRule, Selection};
+use crate::locate::confirm;
+use crate::storage::write_file;
+use crate::template::{has_file_placeholder, substitute, Values};
+use crate::tokenizer::Token;
+use crate::uri::UriMapping;
+use fsidx::Severity;
+use nix::errno::Errno;
+#[cfg(all(target_os = "linux", target_env = "gnu"))]
+use nix::fcntl::{renameat2, RenameFlags};
+use std::collections::{HashSet, VecDeque};
+#[cfg(target_os = "macos")]
+use std::ffi::CString;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Error as IOError, ErrorKind, Result as IOResult};
+use std::os::unix::ffi::OsStrExt;
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+// File operations of the shell applied to selected paths like \o opens them.
+// Copy, move and remove change files, write and copy to the clipboard reuse
+// the paths in other programs.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) enum FileAction {
+    Copy,
+    Move,
+    Remove,
+}
+
+impl FileAction {
+    fn verb(&self) -> &'static str {
+        match self {
+            FileAction::Copy => "Copy",
+            FileAction::Move => "Move",
+            FileAction::Remove => "Remove",
+        }
+    }
+
+    fn done(&self) -> &'static str {
+        match self {
+            FileAction::Copy => "Copied",
+            FileAction::Move => "Moved",
+            FileAction::Remove => "Removed",
+        }
+    }
+}
+
+// Copy and move take the destination directory as last token. The selected
+// paths are listed and nothing is changed without confirmation. Failures are
+// printed and the remaining paths are processed.
+pub(crate) fn file_action(
+    action: FileAction,
+    token: &[Token],
+    selection: &Option<Selection>,
+) -> Result<(), CliError> {
+    let Some(selection) = selection else {
+        print_error();
+        eprintln!("Run a query first.");
+        return Ok(());
+    };
+    let (rules, destination) = match action {
+        FileAction::Remove => (token, None),
+        FileAction::Copy | FileAction::Move => match token.split_last() {
+            Some((Token::Text(destination), rules)) => (rules, Some(PathBuf::from(destination))),
+            Some((Token::Option(option), _)) => {
+                return Err(CliError::InvalidShellArgument(option.clone()))
+            }
+            None => return Ok(()),
+        },
+    };
+    if let Some(destination) = &destination {
+        if !destination.is_dir() {
+            return Err(CliError::NotADirectory(destination.clone()));
+        }
+    }
+    let paths = selected_paths(rules, selection)?;
+    if paths.is_empty() {
+        println!("No paths selected.");
+        return Ok(());
+    }
+    for path in &paths {
+        println!("  {}", path.to_string_lossy());
+    }
+    let prompt = match &destination {
+        Some(destination) => format!(
+            "{} {} paths to '{}'? [y/N] ",
+            action.verb(),
+            paths.len(),
+            destination.to_string_lossy()
+        ),
+        None => format!("{} {} paths? [y/N] ", action.verb(), paths.len()),
+    };
+    if !confirm(&prompt)? {
+        println!("Nothing changed.");
+        return Ok(());
+    }
+    let mut done = 0;
+    for path in &paths {
+        match apply(action, path, destination.as_deref()) {
+            Ok(()) => done += 1,
+            Err(err) => print_event(Severity::Error, &err),
+        }
+    }
+    println!("{} {} of {} paths", action.done(), done, paths.len());
+    Ok(())
+}
+
+// Writes the paths of the selection into a file, one per line or separated
+// by NUL with --print0. With --uri the paths are mapped to URIs.
+pub(crate) fn write_selection(
+    config: &Config,
+    token: &[Token],
+    selection: &Option<Selection>,
+) -> Result<(), CliError> {
+    let mut file = None;
+    let mut print0 = false;
+    let mut uri = false;
+    for token in token {
+        match token {
+            Token::Option(option) if option == "print0" => print0 = true,
+            Token::Option(option) if option == "uri" => uri = true,
+            Token::Text(text) if file.is_none() => file = Some(PathBuf::from(text)),
+            Token::Option(text) | Token::Text(text) => {
+                return Err(CliError::InvalidShellArgument(text.clone()))
+            }
+        }
+    }
+    let Some(file) = file else {
+        return Ok(());
+    };
+    let Some(paths) = selected(selection) else {
+        return Ok(());
+    };
+    let mapping = uri.then(|| UriMapping::new(config));
+    let separator = if print0 { b"\0" } else { b"\n" };
+    write_file(&file, |writer| {
+        for path in paths {
+            match &mapping {
+                Some(mapping) => writer.write_all(mapping.uri(path).as_bytes())?,
+                None => writer.write_all(path.as_os_str().as_bytes())?,
+            }
+            writer.write_all(separator)?;
+        }
+        Ok(())
+    })
+    .map_err(|err| CliError::WritingListFailed(file.clone(), err))?;
+    println!(
+        "{} paths written to: {}",
+        paths.len(),
+        file.to_string_lossy()
+    );
+    Ok(())
+}
+
+// Copies the paths of the selection, one per line, into the clipboard. With
+// --uri the paths are mapped to URIs.
+pub(crate) fn copy_selection(
+    config: &Config,
+    token: &[Token],
+    selection: &Option<Selection>,
+) -> Result<(), CliError> {
+    let mut uri = false;
+    for token in token {
+        match token {
+            Token::Option(option) if option == "uri" => uri = true,
+            Token::Option(text) | Token::Text(text) => {
+                return Err(CliError::InvalidShellArgument(text.clone()))
+            }
+        }
+    }
+    let Some(paths) = selected(selection) else {
+        return Ok(());
+    };
+    let mapping = uri.then(|| UriMapping::new(config));
+    let lines: Vec<String> = paths
+        .iter()
+        .map(|path| match &mapping {
+            Some(mapping) => mapping.uri(path),
+            None => path.to_string_lossy().into_owned(),
+        })
+        .collect();
+    copy_to_clipboard(&lines.join("\n"))?;
+    println!("{} paths copied to the clipboard", paths.len());
+    Ok(())
+}
+
+fn selected(selection: &Option<Selection>) -> Option<&[PathBuf]> {
+    match selection {
+        Some(selection) => Some(&selection.paths),
+        None => {
+            print_error();
+            eprintln!("Run a query first.");
+            None
+        }
+    }
+}
+
+// Command line of \x with the number of parallel jobs, the open rules and
+// the words of the command template.
+#[derive(Debug, PartialEq)]
+struct Exec {
+    jobs: usize,
+    rules: Vec<Token>,
+    template: Vec<String>,
+}
+
+// Runs a command for every selected path like xargs. The words of the line
+// are not tokenized like queries, i.e. options after -- are passed to the
+// command. Without open rules the command runs for all results. The paths
+// are confirmed like by file_action. CTRL-C stops starting further commands.
+// The words of the command line are split by split_words with expanded
+// aliases, i.e. they start with \x.
+pub(crate) fn exec_selection(
+    config: &Config,
+    words: &[String],
+    selection: &Option<Selection>,
+    abort: &AtomicBool,
+) -> Result<(), CliError> {
+    let exec = parse_exec(&words[1..])?;
+    let Some(selection) = selection else {
+        print_error();
+        eprintln!("Run a query first.");
+        return Ok(());
+    };
+    let paths = if exec.rules.is_empty() {
+        selection.paths.clone()
+    } else {
+        selected_paths(&exec.rules, selection)?
+    };
+    if paths.is_empty() {
+        println!("No paths selected.");
+        return Ok(());
+    }
+    for path in &paths {
+        println!("  {}", path.to_string_lossy());
+    }
+    let prompt = format!(
+        "Run '{}' for {} paths? [y/N] ",
+        exec.template.join(" "),
+        paths.len()
+    );
+    if !confirm(&prompt)? {
+        println!("Nothing run.");
+        return Ok(());
+    }
+    let indices = selection.indices();
+    let mapping = UriMapping::new(config);
+    let mut running: VecDeque<(String, Child)> = VecDeque::new();
+    let mut started = 0;
+    let mut failed = 0;
+    for path in &paths {
+        if abort.load(Ordering::Relaxed) {
+            break;
+        }
+        let uri = mapping.uri(path);
+        let values = Values {
+            path: Some(path),
+            index: Some(indices.get(path.as_path()).copied().unwrap_or(0)),
+            uri: Some(&uri),
+            list: None,
+        };
+        let (mut command, text) = exec_command(&exec.template, &values)?;
+        // Parallel commands can't share the terminal for input.
+        if exec.jobs > 1 {
+            command.stdin(Stdio::null());
+        }
+        if running.len() == exec.jobs {
+            if let Some(job) = running.pop_front() {
+                failed += wait(job);
+            }
+        }
+        started += 1;
+        match command.spawn() {
+            Ok(child) => running.push_back((text, child)),
+            Err(err) => {
+                failed += 1;
+                print_event(Severity::Error, &CliError::CommandFailed(text, err));
+            }
+        }
+    }
+    while let Some(job) = running.pop_front() {
+        failed += wait(job);
+    }
+    println!("Ran {} commands, {} failed", started, failed);
+    Ok(())
+}
+
+fn parse_exec(words: &[String]) -> Result<Exec, CliError> {
+    let separator = words
+        .iter()
+        .position(|word| word == "--")
+        .ok_or(CliError::MissingCommand)?;
+    let template = words[separator + 1..].to_vec();
+    if template.is_empty() {
+        return Err(CliError::MissingCommand);
+    }
+    let mut jobs = 1;
+    let mut rules = Vec::new();
+    let mut args = words[..separator].iter();
+    while let Some(arg) = args.next() {
+        if arg == "-j" {
+            let value = args
+                .next()
+                .ok_or_else(|| CliError::MissingOptionValue(String::from("j")))?;
+            jobs = parse_jobs(value)?;
+        } else if let Some(value) = arg.strip_prefix("-j") {
+            jobs = parse_jobs(value)?;
+        } else {
+            rules.push(Token::Text(arg.clone()));
+        }
+    }
+    Ok(Exec {
+        jobs,
+        rules,
+        template,
+    })
+}
+
+fn parse_jobs(value: &str) -> Result<usize, CliError> {
+    value
+        .parse()
+        .ok()
+        .filter(|jobs| *jobs > 0)
+        .ok_or_else(|| CliError::InvalidShellArgument(value.to_string()))
+}
+
+// {} is replaced with the path like {path}. The path is appended, if the
+// template has no placeholder for a single file. Returns the command and its
+// text for messages.
+fn exec_command(template: &[String], values: &Values) -> Result<(Command, String), CliError> {
+    let mut words = template
+        .iter()
+        .map(|word| substitute(&word.replace("{}", "{path}"), values))
+        .collect::<Result<Vec<_>, _>>()?;
+    if !template
+        .iter()
+        .any(|word| word.contains("{}") || has_file_placeholder(word))
+    {
+        if let Some(path) = values.path {
+            words.push(path.as_os_str().to_owned());
+        }
+    }
+    let text = words
+        .iter()
+        .map(|word| word.to_string_lossy())
+        .collect::<Vec<_>>()
+        .join(" ");
+    let mut command = Command::new(&words[0]);
+    command.args(&words[1..]);
+    Ok((command, text))
+}
+
+// Returns 1, if the command failed.
+fn wait((text, mut child): (String, Child)) -> usize {
+    match child.wait() {
+        Ok(status) if status.success() => 0,
+        Ok(status) => {
+            print_event(Severity::Error, &CliError::CommandExitStatus(text, status));
+            1
+        }
+        Err(err) => {
+            print_event(Severity::Error, &CliError::CommandFailed(text, err));
+            1
+        }
+    }
+}
+
+// Paths of the open rules in the order of the rules without duplicates.
+fn selected_paths(rules: &[Token], selection: &Selection) -> Result<Vec<PathBuf>, CliError> {
+    let mut paths: Vec<PathBuf> = Vec::new();
+    let mut selected = HashSet::new();
+    for rule in rules {
+        match rule {
+            Token::Text(text) => {
+                let open_rule = text
+                    .parse::<OpenRule>()
+                    .map_err(|_| CliError::InvalidOpenRule(text.clone()))?;
+                Expand::new(open_rule, selection).foreach(|path| {
+                    if selected.insert(path.to_path_buf()) {
+                        paths.push(path.to_path_buf());
+                    }
+                    Ok(())
+                })?;
+            }
+            Token::Option(option) => return Err(CliError::InvalidShellArgument(option.clone())),
+        }
+    }
+    Ok(paths)
+}
+
+// Existing files are never replaced, even if they are created while the
+// action runs. Directories are moved, but not copied, and only removed when
+// they are empty. Moving to another file system falls back to copying and
+// removing files.
+fn apply(action: FileAction, path: &Path, destination: Option<&Path>) -> Result<(), CliError> {
+    let target = match (destination, path.file_name()) {
+        (Some(destination), Some(name)) => Some(destination.join(name)),
+        _ => None,
+    };
+    let is_dir = fs::symlink_metadata(path).is_ok_and(|metadata| metadata.is_dir());
+    let res = match (action, target) {
+        (FileAction::Copy, Some(_)) if is_dir => Err(IOError::other("Directories are not copied")),
+        (FileAction::Copy, Some(target)) => copy_new(path, &target),
+        (FileAction::Move, Some(target)) => match rename_new(path, &target) {
+            Err(err) if err.raw_os_error() == Some(Errno::EXDEV as i32) && !is_dir => {
+                copy_new(path, &target).and_then(|_| fs::remove_file(path))
+            }
+            res => res,
+        },
+        (FileAction::Remove, _) if is_dir => fs::remove_dir(path),
+        (FileAction::Remove, _) => fs::remove_file(path),
+        (FileAction::Copy | FileAction::Move, None) => Err(IOError::new(
+            ErrorKind::InvalidInput,
+            "Path without file name",
+        )),
+    };
+    res.map_err(|err| failed(action, path, err))
+}
+
+// Copies a file with its permissions. Fails, if the target exists. A
+// partially written target is removed.
+fn copy_new(path: &Path, target: &Path) -> IOResult<()> {
+    let mut source = File::open(path)?;
+    let permissions = source.metadata()?.permissions();
+    let mut file = OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(target)?;
+    let res = io::copy(&mut source, &mut file).and_then(|_| file.set_permissions(permissions));
+    if res.is_err() {
+        let _ = fs::remove_file(target);
+    }
+    res
+}
+
+// Renames a file or directory. Fails, if the target exists.
+#[cfg(all(target_os = "linux", target_env = "gnu"))]
+fn rename_new(path: &Path, target: &Path) -> IOResult<()> {
+    match renameat2(None, path, None, target, RenameFlags::RENAME_NOREPLACE) {
+        // The file system doesn't support the flag.
+        Err(Errno::EINVAL) => link_and_remove(path, target),
+        res => res.map_err(IOError::from),
+    }
+}
+
+#[cfg(target_os = "macos")]
+fn rename_new(path: &Path, target: &Path) -> IOResult<()> {
+    let path = CString::new(path.as_os_str().as_bytes())?;
+    let target = CString::new(target.as_os_str().as_bytes())?;
+    let res =
+        unsafe { nix::libc::renamex_np(path.as_ptr(), target.as_ptr(), nix::libc::RENAME_EXCL) };
+    Errno::result(res).map(drop).map_err(IOError::from)
+}
+
+#[cfg(not(any(all(target_os = "linux", target_env = "gnu"), target_os = "macos")))]
+fn rename_new(path: &Path, target: &Path) -> IOResult<()> {
+    link_and_remove(path, target)
+}
+
+// Linking fails, if the target exists. Directories aren't linked.
+#[cfg(not(target_os = "macos"))]
+fn link_and_remove(path: &Path, target: &Path) -> IOResult<()> {
+    fs::hard_link(path, target)?;
+    fs::remove_file(path)
+}
+
+fn failed(action: FileAction, path: &Path, err: IOError) -> CliError {
+    match action {
+        FileAction::Copy => CliError::CopyingFailed(path.to_owned(), err),
+        FileAction::Move => CliError::MovingFailed(path.to_owned(), err),
+        FileAction::Remove => CliError::RemovingFailed(path.to_owned(), err),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::temp_dir::TempDir;
+    use crate::tokenizer::split_words;
+
+    #[test]
+    fn exec_line() {
+        let words = |line: &str| split_words(line).unwrap();
+        assert_eq!(
+            parse_exec(&words(
+                "-j 4 1.-3. *.jpg -- convert {} -resize 50% {basename}"
+            ))
+            .unwrap(),
+            Exec {
+                jobs: 4,
+                rules: vec![
+                    Token::Text(String::from("1.-3.")),
+                    Token::Text(String::from("*.jpg")),
+                ],
+                template: words("convert {} -resize 50% {basename}"),
+            }
+        );
+        assert_eq!(parse_exec(&words("-j2 -- ls")).unwrap().jobs, 2);
+        assert!(parse_exec(&words("1. ls")).is_err());
+        assert!(parse_exec(&words("1. --")).is_err());
+        assert!(parse_exec(&words("-j 0 -- ls")).is_err());
+        let values = Values {
+            path: Some(Path::new("/a b/c.jpg")),
+            ..Values::default()
+        };
+        let (command, text) = exec_command(&words("ls -l"), &values).unwrap();
+        assert_eq!(
+            command.get_args().collect::<Vec<_>>(),
+            vec!["-l", "/a b/c.jpg"]
+        );
+        assert_eq!(text, "ls -l /a b/c.jpg");
+        let (command, _) = exec_command(&words("cp {} {dir}/copy"), &values).unwrap();
+        assert_eq!(
+            command.get_args().collect::<Vec<_>>(),
+            vec!["/a b/c.jpg", "/a b/copy"]
+        );
+    }
+
+    // Creates the file a.txt and an empty destination directory.
+    fn fixture(name: &str) -> (TempDir, PathBuf, PathBuf) {
+        let dir = TempDir::new(name);
+        let destination = dir.join("destination");
+        fs::create_dir_all(&destination).unwrap();
+        let file = dir.join("a.txt");
+        fs::write(&file, "a").unwrap();
+        (dir, file, destination)
+    }
+
+    #[test]
+    fn copy() {
+        let (_dir, file, destination) = fixture("copy");
+        assert!(apply(FileAction::Copy, &file, Some(&destination)).is_ok());
+        assert_eq!(fs::read_to_string(destination.join("a.txt")).unwrap(), "a");
+        assert!(file.exists());
+    }
+
+    #[test]
+    fn copy_existing() {
+        let (_dir, file, destination) = fixture("copy-existing");
+        apply(FileAction::Copy, &file, Some(&destination)).unwrap();
+        assert!(apply(FileAction::Copy, &file, Some(&destination)).is_err());
+    }
+
+    #[test]
+    fn copy_directory() {
+        let (dir, _, destination) = fixture("copy-directory");
+        assert!(apply(FileAction::Copy, &destination, Some(&dir)).is_err());
+    }
+
+    #[test]
+    fn move_file() {
+        let (_dir, file, destination) = fixture("move");
+        assert!(apply(FileAction::Move, &file, Some(&destination)).is_ok());
+        assert!(destination.join("a.txt").exists());
+        assert!(!file.exists());
+    }
+
+    #[test]
+    fn move_existing() {
+        let (_dir, file, destination) = fixture("move-existing");
+        fs::write(destination.join("a.txt"), "b").unwrap();
+        assert!(apply(FileAction::Move, &file, Some(&destination)).is_err());
+        // Neither file is changed.
+        assert_eq!(fs::read_to_string(destination.join("a.txt")).unwrap(), "b");
+        assert!(file.exists());
+    }
+
+    #[test]
+    fn remove() {
+        let (_dir, file, _) = fixture("remove");
+        assert!(apply(FileAction::Remove, &file, None).is_ok());
+        assert!(!file.exists());
+    }
+
+    #[test]
+    fn remove_directory() {
+        let (_dir, file, destination) = fixture("remove-directory");
+        apply(FileAction::Copy, &file, Some(&destination)).unwrap();
+        assert!(apply(FileAction::Remove, &destination, None).is_err());
+        assert!(destination.join("a.txt").exists());
+    }
+}
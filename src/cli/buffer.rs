@@ -0,0 +1,81 @@
+use crate::config::Config;
+use crate::event::{print_event, Message};
+use fsidx::Severity;
+use std::mem::size_of;
+use std::path::{Path, PathBuf};
+
+const MB: u64 = 1024 * 1024;
+
+// Memory budget for features buffering results, configured with
+// [output] max-buffer-mb. A feature reserves memory for each buffered result.
+// When the budget is exhausted, the feature continues without buffering, e.g.
+// results are only printed, and a warning is printed once.
+pub(crate) struct MemoryBudget {
+    limit: u64,
+    used: u64,
+    exceeded: bool,
+}
+
+impl MemoryBudget {
+    pub(crate) fn new(config: &Config) -> MemoryBudget {
+        MemoryBudget::with_limit(limit(config))
+    }
+
+    pub(crate) fn with_limit(limit: u64) -> MemoryBudget {
+        MemoryBudget {
+            limit,
+            used: 0,
+            exceeded: false,
+        }
+    }
+
+    // Returns false, if the budget is exhausted.
+    pub(crate) fn reserve(&mut self, bytes: usize) -> bool {
+        if self.exceeded || self.used + bytes as u64 > self.limit {
+            self.exceeded = true;
+            return false;
+        }
+        self.used += bytes as u64;
+        true
+    }
+
+    // Reserves memory for a buffered path.
+    pub(crate) fn reserve_path(&mut self, path: &Path) -> bool {
+        self.reserve(size_of::<PathBuf>() + path.as_os_str().len())
+    }
+
+    pub(crate) fn exceeded(&self) -> bool {
+        self.exceeded
+    }
+}
+
+// The memory budget in bytes.
+pub(crate) fn limit(config: &Config) -> u64 {
+    config.output.max_buffer_mb.saturating_mul(MB)
+}
+
+pub(crate) fn print_budget_warning(config: &Config, message: &str) {
+    let message = format!(
+        "{} The memory budget of {} MB is exceeded. See max-buffer-mb in the output table.",
+        message, config.output.max_buffer_mb
+    );
+    print_event(
+        Severity::Warning,
+        &Message::new("memory-budget-exceeded", message),
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn exhausted_budget() {
+        let mut budget = MemoryBudget::with_limit(100);
+        assert!(budget.reserve(60));
+        assert!(!budget.reserve(50));
+        assert!(budget.exceeded());
+        // Results are not buffered partially.
+        assert!(!budget.reserve(10));
+    }
+}
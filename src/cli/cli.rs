@@ -1,17 +1,30 @@
-use crate::config::{find_and_load, load_from_path, Config, ConfigError};
+use crate::config::{find_config_file, load_from_path, Config, ConfigError};
+use crate::configure::config_cli;
+use crate::event::{print_event, set_json_diagnostics, Diagnostic};
+use crate::explain::explain_syntax_cli;
+use crate::export::{export_cli, export_tree_cli};
 use crate::help::{help_cli_long, help_cli_short, help_toml, print_version, usage_cli};
+use crate::import::import_cli;
 use crate::locate::locate_cli;
+use crate::report::report_cli;
 use crate::shell::shell;
 use crate::tokenizer::{tokenize_arg, Token};
+#[cfg(feature = "tui")]
+use crate::tui::tui;
 use crate::update::update_cli;
-use crate::verbosity::{set_verbosity, verbosity};
+use crate::verbosity::{set_exit_summary, set_verbosity, verbosity};
+use crate::verify::verify_cli;
+use crate::warm::warm_cli;
+use fsidx::Severity;
 use std::env::{args, Args};
 use std::io::{stdout, Error, Write};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 #[derive(Default)]
 struct MainOptions {
     config_file: Option<PathBuf>,
+    json: bool,
+    summary: bool,
     help: u8,
     verbose: u8,
     version: bool,
@@ -29,16 +42,75 @@ pub(crate) enum CliError {
     CreatingSignalHandlerFailed(std::io::Error),
     StdoutWriteFailed(std::io::Error),
     InvalidLocateFilterOption(String),
+    InvalidMetaFilter(fsidx::MetaError),
+    InvalidSize(String),
+    InvalidNumber(String),
+    InvalidEntryType(String),
     InvalidShellArgument(String),
+    InvalidSessionSetting(String),
+    #[cfg(feature = "tui")]
+    InvalidTuiArgument(String),
     InvalidUpdateArgument(String),
+    MissingReportName,
+    InvalidExportArgument(String),
+    MissingVolume,
+    UnknownVolume(PathBuf),
+    InvalidReportArgument(String),
+    InvalidWarmArgument(String),
+    InvalidVerifyArgument(String),
+    InvalidExplainArgument(String),
+    MissingConfigCommand,
+    InvalidConfigArgument(String),
+    SerializingConfigFailed(String),
+    EditingConfigFailed(PathBuf, std::io::Error),
+    ConfigNotSaved(PathBuf),
+    EditorFailed(String, std::io::Error),
+    FolderAlreadyConfigured(PathBuf),
+    UnsupportedConfigLayout(String),
+    EditorExitStatus(String, std::process::ExitStatus),
+    CreatingSampleFailed(PathBuf, std::io::Error),
     InvalidOpenRule(String),
     MissingEscapedCharacter,
     MissingClosingQuote,
     InvalidEscape(char),
     GlobPatternError(String, globset::Error),
     InvalidOpenIndex(usize),
+    InvalidOpenPage(usize),
     NotImplementedForNonUtf8Path(PathBuf),
     ReadlineError(String),
+    RecursiveAlias(String),
+    UnknownAlias(String),
+    UpdateFailed,
+    VerificationFailed,
+    CreatingErrorLogFailed(PathBuf, std::io::Error),
+    HookFailed(String, std::io::Error),
+    HookExitStatus(String, std::process::ExitStatus),
+    Terminated(i32),
+    WritingSessionFailed(PathBuf, std::io::Error),
+    InvalidSortKey(String),
+    InvalidField(String),
+    InvalidResultFormat(String),
+    InvalidFormat(String),
+    SortFailed(PathBuf, std::io::Error),
+    InvalidTemplate(String),
+    InvalidPlaceholder(String),
+    WritingListFailed(PathBuf, std::io::Error),
+    ReadingQueriesFailed(PathBuf, std::io::Error),
+    ReadingHistoryFailed(PathBuf, std::io::Error),
+    WritingHistoryFailed(PathBuf, std::io::Error),
+    InvalidImportArgument(String),
+    MissingPathList,
+    ImportIntoSharedDatabase(PathBuf),
+    ReadingPathListFailed(PathBuf, std::io::Error),
+    WritingDatabaseFailed(PathBuf, std::io::Error),
+    NotADirectory(PathBuf),
+    CopyingFailed(PathBuf, std::io::Error),
+    MovingFailed(PathBuf, std::io::Error),
+    RemovingFailed(PathBuf, std::io::Error),
+    UnknownResultSet(String),
+    MissingCommand,
+    CommandFailed(String, std::io::Error),
+    CommandExitStatus(String, std::process::ExitStatus),
 }
 
 impl std::fmt::Display for CliError {
@@ -76,12 +148,89 @@ impl std::fmt::Display for CliError {
                 option_prefix(name.as_str()),
                 name
             )),
+            CliError::InvalidMetaFilter(err) => f.write_fmt(format_args!("{}", err)),
+            CliError::InvalidSize(size) => f.write_fmt(format_args!(
+                "Invalid size, expected a number with optional unit K, M, G or T: {}",
+                size
+            )),
+            CliError::InvalidNumber(number) => {
+                f.write_fmt(format_args!("Invalid number: {}", number))
+            }
+            CliError::InvalidEntryType(entry_type) => f.write_fmt(format_args!(
+                "Invalid type, expected f, d or l separated by commas: {}",
+                entry_type
+            )),
             CliError::InvalidShellArgument(arg) => {
                 f.write_fmt(format_args!("Invalid shell argument: {}", arg))
             }
+            CliError::InvalidSessionSetting(err) => {
+                f.write_fmt(format_args!("Invalid setting: {}", err))
+            }
+            #[cfg(feature = "tui")]
+            CliError::InvalidTuiArgument(arg) => {
+                f.write_fmt(format_args!("Invalid tui argument: {}", arg))
+            }
             CliError::InvalidUpdateArgument(arg) => {
                 f.write_fmt(format_args!("Invalid update argument: {}", arg))
             }
+            CliError::MissingReportName => f.write_str("Missing report name."),
+            CliError::InvalidReportArgument(arg) => {
+                f.write_fmt(format_args!("Invalid report argument: {}", arg))
+            }
+            CliError::InvalidWarmArgument(arg) => {
+                f.write_fmt(format_args!("Invalid warm argument: {}", arg))
+            }
+            CliError::InvalidVerifyArgument(arg) => {
+                f.write_fmt(format_args!("Invalid verify argument: {}", arg))
+            }
+            CliError::InvalidExplainArgument(arg) => {
+                f.write_fmt(format_args!("Invalid explain-syntax argument: {}", arg))
+            }
+            CliError::MissingConfigCommand => f.write_str(
+                "Missing config command (expected show, edit, add-folder or remove-folder).",
+            ),
+            CliError::InvalidConfigArgument(arg) => {
+                f.write_fmt(format_args!("Invalid config argument: {}", arg))
+            }
+            CliError::SerializingConfigFailed(err) => {
+                f.write_fmt(format_args!("Serializing configuration failed: {}", err))
+            }
+            CliError::EditingConfigFailed(path, err) => f.write_fmt(format_args!(
+                "Editing configuration '{}' failed: {}",
+                path.to_string_lossy(),
+                err
+            )),
+            CliError::ConfigNotSaved(path) => f.write_fmt(format_args!(
+                "Configuration '{}' is not changed.",
+                path.to_string_lossy()
+            )),
+            CliError::FolderAlreadyConfigured(folder) => f.write_fmt(format_args!(
+                "Folder '{}' is already configured.",
+                folder.to_string_lossy()
+            )),
+            CliError::UnsupportedConfigLayout(text) => f.write_fmt(format_args!(
+                "Cannot modify the configuration file: {}",
+                text
+            )),
+            CliError::EditorFailed(editor, err) => {
+                f.write_fmt(format_args!("Running editor '{}' failed: {}", editor, err))
+            }
+            CliError::EditorExitStatus(editor, status) => {
+                f.write_fmt(format_args!("Editor '{}' failed: {}", editor, status))
+            }
+            CliError::CreatingSampleFailed(path, err) => f.write_fmt(format_args!(
+                "Creating sample file '{}' failed: {}",
+                path.to_string_lossy(),
+                err
+            )),
+            CliError::InvalidExportArgument(arg) => {
+                f.write_fmt(format_args!("Invalid export argument: {}", arg))
+            }
+            CliError::MissingVolume => f.write_str("Missing volume."),
+            CliError::UnknownVolume(folder) => f.write_fmt(format_args!(
+                "Volume '{}' is not configured.",
+                folder.to_string_lossy()
+            )),
             CliError::InvalidOpenRule(rule) => {
                 f.write_fmt(format_args!("Invalid open rule: {}", rule))
             }
@@ -96,11 +245,252 @@ impl std::fmt::Display for CliError {
             CliError::InvalidOpenIndex(idx) => {
                 f.write_fmt(format_args!("Invalid open index: {}", idx))
             }
+            CliError::InvalidOpenPage(page) => f.write_fmt(format_args!("Invalid page: {}", page)),
             CliError::NotImplementedForNonUtf8Path(path) => f.write_fmt(format_args!(
                 "Not implemented for a non-UTF8 path: {}",
                 path.to_string_lossy()
             )),
             CliError::ReadlineError(err) => f.write_fmt(format_args!("Readline failed: {}", err)),
+            CliError::RecursiveAlias(name) => {
+                f.write_fmt(format_args!("Recursive alias: @{}", name))
+            }
+            CliError::UnknownAlias(name) => f.write_fmt(format_args!("Unknown alias: @{}", name)),
+            CliError::UpdateFailed => f.write_str("Update failed."),
+            CliError::VerificationFailed => f.write_str("Verification failed."),
+            CliError::CreatingErrorLogFailed(path, err) => f.write_fmt(format_args!(
+                "Creating error log '{}' failed: {}",
+                path.to_string_lossy(),
+                err
+            )),
+            CliError::HookFailed(command, err) => {
+                f.write_fmt(format_args!("Running hook '{}' failed: {}", command, err))
+            }
+            CliError::HookExitStatus(command, status) => {
+                f.write_fmt(format_args!("Hook '{}' failed: {}", command, status))
+            }
+            CliError::Terminated(signal) => {
+                f.write_fmt(format_args!("Terminated by signal {}", signal))
+            }
+            CliError::WritingSessionFailed(path, err) => f.write_fmt(format_args!(
+                "Writing session '{}' failed: {}",
+                path.to_string_lossy(),
+                err
+            )),
+            CliError::ReadingHistoryFailed(path, err) => f.write_fmt(format_args!(
+                "Reading history '{}' failed: {}",
+                path.to_string_lossy(),
+                err
+            )),
+            CliError::WritingHistoryFailed(path, err) => f.write_fmt(format_args!(
+                "Writing history '{}' failed: {}",
+                path.to_string_lossy(),
+                err
+            )),
+            CliError::InvalidSortKey(key) => f.write_fmt(format_args!(
+                "Invalid sort key: {} (expected path or size)",
+                key
+            )),
+            CliError::InvalidField(field) => f.write_fmt(format_args!(
+                "Invalid field: {} (expected path, size, mtime, volume or uri)",
+                field
+            )),
+            CliError::InvalidResultFormat(format) => f.write_fmt(format_args!(
+                "Invalid result format: {} (expected path or uri)",
+                format
+            )),
+            CliError::InvalidFormat(format) => f.write_fmt(format_args!(
+                "Invalid format: {} (expected text or json)",
+                format
+            )),
+            CliError::SortFailed(path, err) => f.write_fmt(format_args!(
+                "Sorting with temporary file '{}' failed: {}",
+                path.to_string_lossy(),
+                err
+            )),
+            CliError::InvalidTemplate(template) => {
+                f.write_fmt(format_args!("Invalid command template: {}", template))
+            }
+            CliError::InvalidPlaceholder(name) => {
+                f.write_fmt(format_args!("Invalid placeholder: {{{}}}", name))
+            }
+            CliError::WritingListFailed(path, err) => f.write_fmt(format_args!(
+                "Writing list '{}' failed: {}",
+                path.to_string_lossy(),
+                err
+            )),
+            CliError::ReadingQueriesFailed(path, err) => f.write_fmt(format_args!(
+                "Reading queries '{}' failed: {}",
+                path.to_string_lossy(),
+                err
+            )),
+            CliError::InvalidImportArgument(arg) => {
+                f.write_fmt(format_args!("Invalid import argument: {}", arg))
+            }
+            CliError::MissingPathList => f.write_str("Missing path list."),
+            CliError::ImportIntoSharedDatabase(folder) => f.write_fmt(format_args!(
+                "Folder '{}' shares its database with other folders, import is not supported.",
+                folder.to_string_lossy()
+            )),
+            CliError::ReadingPathListFailed(path, err) => f.write_fmt(format_args!(
+                "Reading path list '{}' failed: {}",
+                path.to_string_lossy(),
+                err
+            )),
+            CliError::WritingDatabaseFailed(path, err) => f.write_fmt(format_args!(
+                "Writing database '{}' failed: {}",
+                path.to_string_lossy(),
+                err
+            )),
+            CliError::NotADirectory(path) => f.write_fmt(format_args!(
+                "'{}' is not a directory.",
+                path.to_string_lossy()
+            )),
+            CliError::CopyingFailed(path, err) => f.write_fmt(format_args!(
+                "Copying '{}' failed: {}",
+                path.to_string_lossy(),
+                err
+            )),
+            CliError::MovingFailed(path, err) => f.write_fmt(format_args!(
+                "Moving '{}' failed: {}",
+                path.to_string_lossy(),
+                err
+            )),
+            CliError::RemovingFailed(path, err) => f.write_fmt(format_args!(
+                "Removing '{}' failed: {}",
+                path.to_string_lossy(),
+                err
+            )),
+            CliError::UnknownResultSet(name) => {
+                f.write_fmt(format_args!("Unknown named results: {}", name))
+            }
+            CliError::MissingCommand => f.write_str("Missing command after '--'."),
+            CliError::CommandFailed(command, err) => {
+                f.write_fmt(format_args!("Running '{}' failed: {}", command, err))
+            }
+            CliError::CommandExitStatus(command, status) => {
+                f.write_fmt(format_args!("Command '{}' failed: {}", command, status))
+            }
+        }
+    }
+}
+
+impl Diagnostic for CliError {
+    fn code(&self) -> &'static str {
+        match self {
+            CliError::MissingOptionValue(_) => "missing-option-value",
+            CliError::InvalidOption(_) => "invalid-option",
+            CliError::InvalidSubCommand(_) => "invalid-subcommand",
+            CliError::ConfigError(err) => err.code(),
+            CliError::LocateError(err) => err.code(),
+            CliError::NoDatabasePath => "no-database-path",
+            CliError::TtyConfigurationFailed(_) => "tty-configuration-failed",
+            CliError::CreatingSignalHandlerFailed(_) => "creating-signal-handler-failed",
+            CliError::StdoutWriteFailed(_) => "stdout-write-failed",
+            CliError::InvalidLocateFilterOption(_) => "invalid-locate-filter-option",
+            CliError::InvalidMetaFilter(_) => "invalid-meta-filter",
+            CliError::InvalidSize(_) => "invalid-size",
+            CliError::InvalidNumber(_) => "invalid-number",
+            CliError::InvalidEntryType(_) => "invalid-entry-type",
+            CliError::InvalidShellArgument(_) => "invalid-shell-argument",
+            CliError::InvalidSessionSetting(_) => "invalid-session-setting",
+            #[cfg(feature = "tui")]
+            CliError::InvalidTuiArgument(_) => "invalid-tui-argument",
+            CliError::InvalidUpdateArgument(_) => "invalid-update-argument",
+            CliError::MissingReportName => "missing-report-name",
+            CliError::InvalidExportArgument(_) => "invalid-export-argument",
+            CliError::MissingVolume => "missing-volume",
+            CliError::UnknownVolume(_) => "unknown-volume",
+            CliError::InvalidReportArgument(_) => "invalid-report-argument",
+            CliError::InvalidWarmArgument(_) => "invalid-warm-argument",
+            CliError::InvalidVerifyArgument(_) => "invalid-verify-argument",
+            CliError::InvalidExplainArgument(_) => "invalid-explain-argument",
+            CliError::MissingConfigCommand => "missing-config-command",
+            CliError::InvalidConfigArgument(_) => "invalid-config-argument",
+            CliError::SerializingConfigFailed(_) => "serializing-config-failed",
+            CliError::EditingConfigFailed(_, _) => "editing-config-failed",
+            CliError::ConfigNotSaved(_) => "config-not-saved",
+            CliError::EditorFailed(_, _) => "editor-failed",
+            CliError::FolderAlreadyConfigured(_) => "folder-already-configured",
+            CliError::UnsupportedConfigLayout(_) => "unsupported-config-layout",
+            CliError::EditorExitStatus(_, _) => "editor-exit-status",
+            CliError::CreatingSampleFailed(_, _) => "creating-sample-failed",
+            CliError::InvalidOpenRule(_) => "invalid-open-rule",
+            CliError::MissingEscapedCharacter => "missing-escaped-character",
+            CliError::MissingClosingQuote => "missing-closing-quote",
+            CliError::InvalidEscape(_) => "invalid-escape",
+            CliError::GlobPatternError(_, _) => "glob-pattern-error",
+            CliError::InvalidOpenIndex(_) => "invalid-open-index",
+            CliError::InvalidOpenPage(_) => "invalid-open-page",
+            CliError::NotImplementedForNonUtf8Path(_) => "non-utf8-path",
+            CliError::ReadlineError(_) => "readline-error",
+            CliError::RecursiveAlias(_) => "recursive-alias",
+            CliError::UnknownAlias(_) => "unknown-alias",
+            CliError::UpdateFailed => "update-failed",
+            CliError::VerificationFailed => "verification-failed",
+            CliError::CreatingErrorLogFailed(_, _) => "creating-error-log-failed",
+            CliError::HookFailed(_, _) => "hook-failed",
+            CliError::HookExitStatus(_, _) => "hook-exit-status",
+            CliError::Terminated(_) => "terminated",
+            CliError::WritingSessionFailed(_, _) => "writing-session-failed",
+            CliError::InvalidSortKey(_) => "invalid-sort-key",
+            CliError::InvalidField(_) => "invalid-field",
+            CliError::InvalidResultFormat(_) => "invalid-result-format",
+            CliError::InvalidFormat(_) => "invalid-format",
+            CliError::SortFailed(_, _) => "sort-failed",
+            CliError::InvalidTemplate(_) => "invalid-template",
+            CliError::InvalidPlaceholder(_) => "invalid-placeholder",
+            CliError::WritingListFailed(_, _) => "writing-list-failed",
+            CliError::ReadingQueriesFailed(_, _) => "reading-queries-failed",
+            CliError::ReadingHistoryFailed(_, _) => "reading-history-failed",
+            CliError::WritingHistoryFailed(_, _) => "writing-history-failed",
+            CliError::InvalidImportArgument(_) => "invalid-import-argument",
+            CliError::MissingPathList => "missing-path-list",
+            CliError::ImportIntoSharedDatabase(_) => "import-into-shared-database",
+            CliError::ReadingPathListFailed(_, _) => "reading-path-list-failed",
+            CliError::WritingDatabaseFailed(_, _) => "writing-database-failed",
+            CliError::NotADirectory(_) => "not-a-directory",
+            CliError::CopyingFailed(_, _) => "copying-failed",
+            CliError::MovingFailed(_, _) => "moving-failed",
+            CliError::RemovingFailed(_, _) => "removing-failed",
+            CliError::UnknownResultSet(_) => "unknown-result-set",
+            CliError::MissingCommand => "missing-command",
+            CliError::CommandFailed(_, _) => "command-failed",
+            CliError::CommandExitStatus(_, _) => "command-exit-status",
+        }
+    }
+
+    fn path(&self) -> Option<&Path> {
+        match self {
+            CliError::ConfigError(err) => err.path(),
+            CliError::LocateError(err) => err.path(),
+            CliError::CreatingSampleFailed(path, _)
+            | CliError::NotImplementedForNonUtf8Path(path)
+            | CliError::CreatingErrorLogFailed(path, _)
+            | CliError::WritingSessionFailed(path, _)
+            | CliError::SortFailed(path, _)
+            | CliError::WritingListFailed(path, _)
+            | CliError::ReadingQueriesFailed(path, _)
+            | CliError::ReadingHistoryFailed(path, _)
+            | CliError::WritingHistoryFailed(path, _)
+            | CliError::ReadingPathListFailed(path, _)
+            | CliError::WritingDatabaseFailed(path, _)
+            | CliError::EditingConfigFailed(path, _)
+            | CliError::NotADirectory(path)
+            | CliError::CopyingFailed(path, _)
+            | CliError::MovingFailed(path, _)
+            | CliError::RemovingFailed(path, _)
+            | CliError::ConfigNotSaved(path) => Some(path),
+            _ => None,
+        }
+    }
+
+    fn volume(&self) -> Option<&Path> {
+        match self {
+            CliError::ConfigError(err) => err.volume(),
+            CliError::UnknownVolume(folder)
+            | CliError::FolderAlreadyConfigured(folder)
+            | CliError::ImportIntoSharedDatabase(folder) => Some(folder),
+            _ => None,
         }
     }
 }
@@ -120,20 +510,28 @@ impl From<Error> for CliError {
 }
 
 pub fn main() -> i32 {
-    if let Err(err) = process_main_command() {
-        crate::shell::print_error();
-        eprintln!("{}", err);
-        1
-    } else {
-        0
+    match process_main_command() {
+        Ok(()) => 0,
+        Err(CliError::Terminated(signal)) => exit_code(signal),
+        Err(err) => {
+            print_event(Severity::Error, &err);
+            1
+        }
     }
 }
 
+// Conventional exit code of a process terminated by a signal.
+pub(crate) fn exit_code(signal: i32) -> i32 {
+    128 + signal
+}
+
 fn process_main_command() -> Result<(), CliError> {
     let mut args = args();
     let _ = args.next();
     let (main_options, sub_command) = parse_main_command(&mut args)?;
     set_verbosity(main_options.verbose);
+    set_json_diagnostics(main_options.json);
+    set_exit_summary(main_options.summary);
     if main_options.help != 0 {
         let _ = match main_options.help {
             1 => help_cli_short(),
@@ -147,7 +545,7 @@ fn process_main_command() -> Result<(), CliError> {
         print_version();
         return Ok(());
     }
-    let config: Config = if let Some(config_file) = main_options.config_file {
+    let config_file = if let Some(config_file) = main_options.config_file {
         if verbosity() {
             let _ = writeln!(
                 stdout().lock(),
@@ -155,22 +553,32 @@ fn process_main_command() -> Result<(), CliError> {
                 config_file.to_string_lossy()
             );
         }
-        match load_from_path(&config_file) {
-            Ok(config) => config,
-            Err(err) => return Err(CliError::ConfigError(err)),
-        }
+        config_file
     } else {
-        match find_and_load() {
-            Ok(config) => config,
-            Err(err) => return Err(CliError::ConfigError(err)),
-        }
+        find_config_file().map_err(CliError::ConfigError)?
+    };
+    if sub_command.as_deref() == Some("config") {
+        return config_cli(&config_file, &mut args);
+    }
+    let config: Config = match load_from_path(&config_file) {
+        Ok(config) => config,
+        Err(err) => return Err(CliError::ConfigError(err)),
     };
 
     if let Some(sub_command) = sub_command {
         match sub_command.as_str() {
-            "shell" => shell(config, &mut args),
+            "shell" => shell(config, config_file, &mut args),
             "locate" => locate_cli(&config, &mut args),
             "update" => update_cli(&config, &mut args),
+            "report" => report_cli(&config, &mut args),
+            "warm" => warm_cli(&config, &mut args),
+            "verify" => verify_cli(&config, &mut args),
+            "export" => export_cli(&config, &mut args),
+            "export-tree" => export_tree_cli(&config, &mut args),
+            "import" => import_cli(&config, &mut args),
+            "explain-syntax" => explain_syntax_cli(&mut args),
+            #[cfg(feature = "tui")]
+            "tui" => tui(&config, &mut args),
             "help" => help_cli_long(),
             _ => Err(CliError::InvalidSubCommand(sub_command)),
         }
@@ -210,12 +618,25 @@ impl MainOptions {
                         .ok_or_else(|| CliError::MissingOptionValue(option.to_string()))?,
                 );
             }
+            "format" => {
+                let format = args
+                    .next()
+                    .ok_or_else(|| CliError::MissingOptionValue(option.to_string()))?;
+                self.json = match format.as_str() {
+                    "text" => false,
+                    "json" => true,
+                    _ => return Err(CliError::InvalidFormat(format)),
+                };
+            }
             "h" => {
                 self.help += 1;
             }
             "help" => {
                 self.help = 2;
             }
+            "summary" => {
+                self.summary = true;
+            }
             "v" | "verbose" => {
                 self.verbose += 1;
             }
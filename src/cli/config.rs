@@ -1,5 +1,9 @@
-use fsidx::{LocateConfig, VolumeInfo};
+use crate::event::Diagnostic;
+use fsidx::{
+    LocateConfig, Mode, Order, ProviderRegistry, UpdateConfig, VolumeId, VolumeInfo, What,
+};
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use std::env;
 use std::fs;
 use std::path::{Path, PathBuf};
@@ -9,21 +13,350 @@ use std::path::{Path, PathBuf};
 pub struct Config {
     pub index: Index,
     pub locate: LocateConfig,
+    #[serde(default)]
+    pub update: UpdateConfig,
+    #[serde(default)]
+    pub shell: ShellConfig,
+    #[serde(default)]
+    pub output: OutputConfig,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub open: Option<OpenConfig>,
+    // Also accepted as [queries] table of named queries.
+    #[serde(default, alias = "queries", skip_serializing_if = "BTreeMap::is_empty")]
+    pub aliases: BTreeMap<String, String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hooks: Option<HooksConfig>,
 }
 
 #[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
 #[serde(deny_unknown_fields)]
 pub struct Index {
-    pub folder: Vec<PathBuf>,
+    pub folder: Vec<Folder>,
     pub db_path: Option<PathBuf>,
 }
 
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+#[serde(untagged)]
+pub enum Folder {
+    Path(PathBuf),
+    Table(Box<FolderConfig>),
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct FolderConfig {
+    pub path: PathBuf,
+    pub options: Option<LocateOptions>,
+    pub hooks: Option<HooksConfig>,
+    // Names of the enrichment providers run when scanning the folder.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub providers: Vec<String>,
+    // File system UUID or label of a removable volume containing the folder.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub uuid: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub label: Option<String>,
+    // Replaces the folder in URIs of results, e.g. smb://nas/share.
+    #[serde(
+        default,
+        rename = "uri-prefix",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub uri_prefix: Option<String>,
+    // Glob patterns of directories and files skipped when scanning.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub exclude: Vec<String>,
+    // Traverse symbolic links to directories.
+    #[serde(
+        default,
+        rename = "follow-symlinks",
+        skip_serializing_if = "std::ops::Not::not"
+    )]
+    pub follow_symlinks: bool,
+    // Folders of the same group share one database file.
+    #[serde(default, rename = "db-group", skip_serializing_if = "Option::is_none")]
+    pub db_group: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Default)]
+#[serde(deny_unknown_fields)]
+#[serde(rename_all = "kebab-case")]
+pub struct LocateOptions {
+    pub case_sensitive: Option<bool>,
+    pub order: Option<Order>,
+    pub what: Option<What>,
+    pub smart_spaces: Option<bool>,
+    pub word_boundaries: Option<bool>,
+    pub literal_separator: Option<bool>,
+    pub mode: Option<Mode>,
+    pub buffer_size: Option<usize>,
+    pub readahead: Option<bool>,
+}
+
+impl Folder {
+    pub fn path(&self) -> &Path {
+        match self {
+            Folder::Path(path) => path,
+            Folder::Table(table) => &table.path,
+        }
+    }
+
+    fn path_mut(&mut self) -> &mut PathBuf {
+        match self {
+            Folder::Path(path) => path,
+            Folder::Table(table) => &mut table.path,
+        }
+    }
+
+    pub fn options(&self) -> Option<&LocateOptions> {
+        match self {
+            Folder::Path(_) => None,
+            Folder::Table(table) => table.options.as_ref(),
+        }
+    }
+
+    pub fn hooks(&self) -> Option<&HooksConfig> {
+        match self {
+            Folder::Path(_) => None,
+            Folder::Table(table) => table.hooks.as_ref(),
+        }
+    }
+
+    pub fn providers(&self) -> &[String] {
+        match self {
+            Folder::Path(_) => &[],
+            Folder::Table(table) => &table.providers,
+        }
+    }
+
+    pub fn exclude(&self) -> &[String] {
+        match self {
+            Folder::Path(_) => &[],
+            Folder::Table(table) => &table.exclude,
+        }
+    }
+
+    pub fn follow_symlinks(&self) -> bool {
+        match self {
+            Folder::Path(_) => false,
+            Folder::Table(table) => table.follow_symlinks,
+        }
+    }
+
+    pub fn db_group(&self) -> Option<&str> {
+        match self {
+            Folder::Path(_) => None,
+            Folder::Table(table) => table.db_group.as_deref(),
+        }
+    }
+
+    pub fn uri_prefix(&self) -> Option<&str> {
+        match self {
+            Folder::Path(_) => None,
+            Folder::Table(table) => table.uri_prefix.as_deref(),
+        }
+    }
+
+    // The UUID is used, if both UUID and label are configured.
+    pub fn volume_id(&self) -> Option<VolumeId> {
+        match self {
+            Folder::Path(_) => None,
+            Folder::Table(table) => match (&table.uuid, &table.label) {
+                (Some(uuid), _) => Some(VolumeId::Uuid(uuid.clone())),
+                (None, Some(label)) => Some(VolumeId::Label(label.clone())),
+                (None, None) => None,
+            },
+        }
+    }
+}
+
+impl LocateOptions {
+    pub fn apply(&self, locate: &LocateConfig) -> LocateConfig {
+        let mut locate = locate.clone();
+        if let Some(case_sensitive) = self.case_sensitive {
+            locate.case_sensitive = case_sensitive;
+        }
+        if let Some(order) = &self.order {
+            locate.order = order.clone();
+        }
+        if let Some(what) = &self.what {
+            locate.what = what.clone();
+        }
+        if let Some(smart_spaces) = self.smart_spaces {
+            locate.smart_spaces = smart_spaces;
+        }
+        if let Some(word_boundaries) = self.word_boundaries {
+            locate.word_boundaries = word_boundaries;
+        }
+        if let Some(literal_separator) = self.literal_separator {
+            locate.literal_separator = literal_separator;
+        }
+        if let Some(mode) = self.mode {
+            locate.mode = mode;
+        }
+        if let Some(buffer_size) = self.buffer_size {
+            locate.buffer_size = buffer_size;
+        }
+        if let Some(readahead) = self.readahead {
+            locate.readahead = readahead;
+        }
+        locate
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+#[serde(deny_unknown_fields)]
+#[serde(rename_all = "kebab-case")]
+pub struct ShellConfig {
+    #[serde(default)]
+    pub auto_reload: bool,
+    #[serde(default)]
+    pub cache: bool,
+    #[serde(default)]
+    pub page_size: usize,
+    #[serde(default = "default_history")]
+    pub history: bool,
+    // Relative to the folder with the database files.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub history_file: Option<PathBuf>,
+    // Glob patterns of command lines, which are not stored in the history.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub history_ignore: Vec<String>,
+    // Threads searching folders in parallel. Zero for the available
+    // parallelism.
+    #[serde(default = "default_threads")]
+    pub threads: usize,
+    #[serde(default = "default_thread_idle_timeout_ms")]
+    pub thread_idle_timeout_ms: u64,
+}
+
+fn default_threads() -> usize {
+    1
+}
+
+fn default_thread_idle_timeout_ms() -> u64 {
+    60000
+}
+
+fn default_history() -> bool {
+    true
+}
+
+impl Default for ShellConfig {
+    fn default() -> Self {
+        ShellConfig {
+            auto_reload: false,
+            cache: false,
+            page_size: 0,
+            history: default_history(),
+            history_file: None,
+            history_ignore: Vec::new(),
+            threads: default_threads(),
+            thread_idle_timeout_ms: default_thread_idle_timeout_ms(),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+#[serde(deny_unknown_fields)]
+#[serde(rename_all = "kebab-case")]
+pub struct OutputConfig {
+    #[serde(default)]
+    pub truncate: bool,
+    #[serde(default)]
+    pub icons: bool,
+    #[serde(default = "default_max_buffer_mb")]
+    pub max_buffer_mb: u64,
+    // Writing query results to stdout fails, if it blocks longer.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub write_timeout_ms: Option<u64>,
+}
+
+fn default_max_buffer_mb() -> u64 {
+    1024
+}
+
+impl Default for OutputConfig {
+    fn default() -> Self {
+        OutputConfig {
+            truncate: false,
+            icons: false,
+            max_buffer_mb: default_max_buffer_mb(),
+            write_timeout_ms: None,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Default)]
+#[serde(deny_unknown_fields)]
+#[serde(rename_all = "kebab-case")]
+pub struct OpenConfig {
+    pub command: Option<String>,
+    // Application by file extension.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub apps: BTreeMap<String, String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Default)]
+#[serde(deny_unknown_fields)]
+#[serde(rename_all = "kebab-case")]
+pub struct HooksConfig {
+    pub pre_update: Option<String>,
+    pub post_update: Option<String>,
+    #[serde(default)]
+    pub on_failure: HookFailure,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Copy, Default)]
+#[serde(deny_unknown_fields)]
+#[serde(rename_all = "kebab-case")]
+pub enum HookFailure {
+    #[default]
+    Abort,
+    Continue,
+}
+
 #[derive(Debug)]
 pub enum ConfigError {
     FileReadError(PathBuf, std::io::Error),
     ParseError(PathBuf, toml::de::Error),
     TomlFileExpected(PathBuf),
     ConfigFileNotFound,
+    UnknownProvider(PathBuf, String, Vec<String>),
+    InvalidDbGroup(PathBuf, String),
+    DbGroupOnVolume(PathBuf, String),
+}
+
+impl Diagnostic for ConfigError {
+    fn code(&self) -> &'static str {
+        match self {
+            ConfigError::FileReadError(_, _) => "config-read-failed",
+            ConfigError::ParseError(_, _) => "config-parse-error",
+            ConfigError::TomlFileExpected(_) => "toml-file-expected",
+            ConfigError::ConfigFileNotFound => "config-file-not-found",
+            ConfigError::UnknownProvider(_, _, _) => "unknown-provider",
+            ConfigError::InvalidDbGroup(_, _) => "invalid-db-group",
+            ConfigError::DbGroupOnVolume(_, _) => "db-group-on-volume",
+        }
+    }
+
+    fn path(&self) -> Option<&Path> {
+        match self {
+            ConfigError::FileReadError(path, _)
+            | ConfigError::ParseError(path, _)
+            | ConfigError::TomlFileExpected(path) => Some(path),
+            _ => None,
+        }
+    }
+
+    fn volume(&self) -> Option<&Path> {
+        match self {
+            ConfigError::UnknownProvider(folder, _, _)
+            | ConfigError::InvalidDbGroup(folder, _)
+            | ConfigError::DbGroupOnVolume(folder, _) => Some(folder),
+            _ => None,
+        }
+    }
 }
 
 impl std::fmt::Display for ConfigError {
@@ -44,21 +377,37 @@ impl std::fmt::Display for ConfigError {
                 path.to_string_lossy()
             )),
             ConfigError::ConfigFileNotFound => f.write_str("Configuration file not found."),
+            ConfigError::UnknownProvider(folder, name, available) => f.write_fmt(format_args!(
+                "Unknown provider '{}' for folder '{}'. Available providers: {}",
+                name,
+                folder.to_string_lossy(),
+                available.join(", ")
+            )),
+            ConfigError::InvalidDbGroup(folder, group) => f.write_fmt(format_args!(
+                "Invalid db-group '{}' for folder '{}'. Names must not be empty, contain '/' or start with '_'.",
+                group,
+                folder.to_string_lossy()
+            )),
+            ConfigError::DbGroupOnVolume(folder, group) => f.write_fmt(format_args!(
+                "Folder '{}' on a removable volume can't be in db-group '{}'.",
+                folder.to_string_lossy(),
+                group
+            )),
         }
     }
 }
 
-pub fn find_and_load() -> Result<Config, ConfigError> {
+pub fn find_config_file() -> Result<PathBuf, ConfigError> {
     if let Ok(home) = env::var("HOME") {
         let path = Path::new(&home);
         let config_file_path = path.join(Path::new(".fsidx")).join(Path::new("fsidx.toml"));
         if config_file_path.exists() {
-            return load_from_path(&config_file_path);
+            return Ok(config_file_path);
         }
     }
     let config_file_path = Path::new("/etc/fsidx/fsidx.toml");
     if config_file_path.exists() {
-        return load_from_path(config_file_path);
+        return Ok(config_file_path.to_path_buf());
     }
     Err(ConfigError::ConfigFileNotFound)
 }
@@ -78,9 +427,55 @@ pub fn load_from_path(file_name: &Path) -> Result<Config, ConfigError> {
     let mut config = parse_content(&contents)
         .map_err(|err| ConfigError::ParseError(file_name.to_owned(), err))?;
     set_db_path(&mut config, file_name);
+    check_providers(&config, &ProviderRegistry::builtin())?;
+    check_db_groups(&config)?;
     Ok(config)
 }
 
+fn check_providers(config: &Config, registry: &ProviderRegistry) -> Result<(), ConfigError> {
+    for folder in &config.index.folder {
+        for name in folder.providers() {
+            if registry.get(name).is_none() {
+                let available = registry
+                    .providers()
+                    .iter()
+                    .map(|provider| provider.name().to_string())
+                    .collect();
+                return Err(ConfigError::UnknownProvider(
+                    folder.path().to_owned(),
+                    name.clone(),
+                    available,
+                ));
+            }
+        }
+    }
+    Ok(())
+}
+
+// Database files of other folders start with '_', i.e. the file name of a
+// group never clashes with them. Shared database files are written without
+// volume information. Thus folders on removable volumes can't be grouped.
+fn check_db_groups(config: &Config) -> Result<(), ConfigError> {
+    for folder in &config.index.folder {
+        let Some(group) = folder.db_group() else {
+            continue;
+        };
+        if group.is_empty() || group.contains('/') || group.starts_with('_') {
+            return Err(ConfigError::InvalidDbGroup(
+                folder.path().to_owned(),
+                group.to_string(),
+            ));
+        }
+        if folder.volume_id().is_some() {
+            return Err(ConfigError::DbGroupOnVolume(
+                folder.path().to_owned(),
+                group.to_string(),
+            ));
+        }
+    }
+    Ok(())
+}
+
 fn parse_content(contents: &str) -> Result<Config, toml::de::Error> {
     let mut config: Config = toml::from_str(contents)?;
     resolve_leading_tilde(&mut config);
@@ -91,11 +486,11 @@ fn resolve_leading_tilde(config: &mut Config) {
     let tilde = Path::new("~");
     if let Ok(home) = env::var("HOME") {
         let home = Path::new(&home);
-        for folder in &mut config.index.folder {
-            if folder.starts_with(tilde) {
-                if let Ok(path) = folder.strip_prefix(tilde) {
-                    *folder = home.join(path);
-                }
+        let history_file = config.shell.history_file.as_mut();
+        let folders = config.index.folder.iter_mut().map(Folder::path_mut);
+        for path in folders.chain(history_file) {
+            if let Ok(relative) = path.strip_prefix(tilde) {
+                *path = home.join(relative);
             }
         }
     }
@@ -113,14 +508,41 @@ pub fn get_volume_info(config: &Config) -> Option<Vec<VolumeInfo>> {
         .folder
         .iter()
         .filter_map(|folder| {
-            let database = get_db_file_path(config, folder)?;
-            let folder = folder.clone();
-            Some(VolumeInfo { folder, database })
+            let database = match folder.db_group() {
+                Some(group) => get_group_db_file_path(config, group)?,
+                None => get_db_file_path(config, folder.path())?,
+            };
+            let locate_config = folder
+                .options()
+                .map(|options| options.apply(&config.locate));
+            Some(
+                VolumeInfo::new(folder.path(), database)
+                    .with_locate_config(locate_config)
+                    .with_providers(folder.providers().to_vec())
+                    .with_volume_id(folder.volume_id())
+                    .with_exclude(folder.exclude().to_vec())
+                    .with_follow_symlinks(folder.follow_symlinks()),
+            )
         })
         .collect();
     Some(volume_info)
 }
 
+// Configured folders on identified volumes, which are not mounted.
+pub fn get_offline_folders(config: &Config) -> Vec<(PathBuf, VolumeId)> {
+    config
+        .index
+        .folder
+        .iter()
+        .filter_map(|folder| {
+            let id = folder.volume_id()?;
+            id.mount_point()
+                .is_none()
+                .then(|| (folder.path().to_path_buf(), id))
+        })
+        .collect()
+}
+
 pub fn get_db_file_path(config: &Config, folder: &Path) -> Option<PathBuf> {
     if let Some(db_path) = config.index.db_path.as_deref() {
         let s: &str = folder.to_str().unwrap();
@@ -132,10 +554,15 @@ pub fn get_db_file_path(config: &Config, folder: &Path) -> Option<PathBuf> {
     }
 }
 
+// Database file shared by the folders of a db-group.
+pub fn get_group_db_file_path(config: &Config, group: &str) -> Option<PathBuf> {
+    let db_path = config.index.db_path.as_deref()?;
+    Some(db_path.join(format!("{}.fsdb", group)))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
-    use fsidx::{Mode, Order, What};
     use indoc::indoc;
 
     #[test]
@@ -164,40 +591,72 @@ mod tests {
             Config {
                 index: Index {
                     folder: vec![
-                        PathBuf::from(format!("{}/Music", home)),
-                        PathBuf::from("/Volumes/Music")
+                        Folder::Path(PathBuf::from(format!("{}/Music", home))),
+                        Folder::Path(PathBuf::from("/Volumes/Music"))
                     ],
                     db_path: None
                 },
-                locate: LocateConfig {
-                    case_sensitive: false,
-                    order: Order::AnyOrder,
-                    what: What::WholePath,
-                    smart_spaces: true,
-                    word_boundaries: false,
-                    literal_separator: false,
-                    mode: Mode::Auto,
+                locate: LocateConfig::default(),
+                update: UpdateConfig::default(),
+                shell: ShellConfig {
+                    auto_reload: false,
+                    cache: false,
+                    page_size: 0,
+                    history: true,
+                    history_file: None,
+                    history_ignore: Vec::new(),
+                    threads: 1,
+                    thread_idle_timeout_ms: 60000,
                 },
+                output: OutputConfig {
+                    truncate: false,
+                    icons: false,
+                    max_buffer_mb: 1024,
+                    write_timeout_ms: None,
+                },
+                open: None,
+                aliases: BTreeMap::new(),
+                hooks: None,
             }
         );
     }
 
     #[test]
     fn encode_toml() {
+        let mut locate = LocateConfig::default();
+        locate.case_sensitive = true;
+        let mut update = UpdateConfig::default();
+        update.retries = 3;
+        update.retry_delay_ms = 50;
         let config = Config {
             index: Index {
-                folder: vec![PathBuf::from("~/Music"), PathBuf::from("/Volumes/Music")],
+                folder: vec![
+                    Folder::Path(PathBuf::from("~/Music")),
+                    Folder::Path(PathBuf::from("/Volumes/Music")),
+                ],
                 db_path: None,
             },
-            locate: LocateConfig {
-                case_sensitive: true,
-                order: Order::AnyOrder,
-                what: What::WholePath,
-                smart_spaces: true,
-                word_boundaries: false,
-                literal_separator: false,
-                mode: Mode::Auto,
+            locate,
+            update,
+            shell: ShellConfig {
+                auto_reload: true,
+                cache: false,
+                page_size: 40,
+                history: false,
+                history_file: Some(PathBuf::from("~/.fsidx_history")),
+                history_ignore: vec![String::from("*secret*")],
+                threads: 1,
+                thread_idle_timeout_ms: 60000,
+            },
+            output: OutputConfig {
+                truncate: true,
+                icons: false,
+                max_buffer_mb: 256,
+                write_timeout_ms: None,
             },
+            open: None,
+            aliases: BTreeMap::new(),
+            hooks: None,
         };
         let toml = toml::to_string(&config).unwrap();
         let expected = indoc! {
@@ -212,8 +671,205 @@ mod tests {
             word-boundaries = false
             literal-separator = false
             mode = "auto"
+            buffer-size = 8192
+            readahead = false
+            verify-checksums = false
+            ordered = false
+            broad-query = "warn"
+            update-wait-ms = 2000
+
+            [update]
+            retries = 3
+            retry-delay-ms = 50
+            modification-times = false
+            skip-exceeding = false
+            compress = false
+
+            [shell]
+            auto-reload = true
+            cache = false
+            page-size = 40
+            history = false
+            history-file = "~/.fsidx_history"
+            history-ignore = ["*secret*"]
+            threads = 1
+            thread-idle-timeout-ms = 60000
+
+            [output]
+            truncate = true
+            icons = false
+            max-buffer-mb = 256
             "#};
         assert_eq!(toml, expected);
         // println!("{}", toml);
     }
+
+    #[test]
+    fn folder_options() {
+        let data = indoc! {
+        r#"[index]
+            folder = [
+                "/Volumes/Music",
+                { path = "/Volumes/Code", options = { case-sensitive = true, what = "last-element", buffer-size = 65536, readahead = true } }
+            ]
+
+            [locate]
+            smart-spaces = false
+            "#};
+        let config: Config = parse_content(data).unwrap();
+        assert_eq!(config.index.folder[0].options(), None);
+        assert_eq!(config.index.folder[1].path(), Path::new("/Volumes/Code"));
+        let locate = config.index.folder[1]
+            .options()
+            .unwrap()
+            .apply(&config.locate);
+        let mut expected = LocateConfig::default();
+        expected.case_sensitive = true;
+        expected.what = What::LastElement;
+        expected.smart_spaces = false;
+        expected.buffer_size = 65536;
+        expected.readahead = true;
+        assert_eq!(locate, expected);
+    }
+
+    #[test]
+    fn hooks() {
+        let data = indoc! {
+        r#"[index]
+            folder = [
+                "/Volumes/Music",
+                { path = "/Volumes/Backup", hooks = { pre-update = "mount /Volumes/Backup" } }
+            ]
+
+            [locate]
+
+            [hooks]
+            post-update = "notify-send fsidx"
+            on-failure = "continue"
+            "#};
+        let config: Config = parse_content(data).unwrap();
+        assert_eq!(
+            config.hooks,
+            Some(HooksConfig {
+                pre_update: None,
+                post_update: Some(String::from("notify-send fsidx")),
+                on_failure: HookFailure::Continue,
+            })
+        );
+        assert_eq!(config.index.folder[0].hooks(), None);
+        assert_eq!(config.open, None);
+        assert_eq!(
+            config.index.folder[1].hooks(),
+            Some(&HooksConfig {
+                pre_update: Some(String::from("mount /Volumes/Backup")),
+                post_update: None,
+                on_failure: HookFailure::Abort,
+            })
+        );
+    }
+
+    #[test]
+    fn named_queries() {
+        let data = indoc! {
+        r#"[index]
+            folder = ["/Volumes/Music"]
+
+            [locate]
+
+            [queries]
+            flacs = "--glob *.flac --last-element"
+            "#};
+        let config: Config = parse_content(data).unwrap();
+        assert_eq!(
+            config.aliases.get("flacs").map(String::as_str),
+            Some("--glob *.flac --last-element")
+        );
+    }
+
+    #[test]
+    fn folder_providers() {
+        let data = indoc! {
+        r#"[index]
+            folder = [
+                "/Volumes/Music",
+                { path = "/Volumes/Photos", providers = ["content-hash"] }
+            ]
+
+            [locate]
+            "#};
+        let config: Config = parse_content(data).unwrap();
+        assert!(config.index.folder[0].providers().is_empty());
+        assert_eq!(config.index.folder[1].providers(), ["content-hash"]);
+        let registry = ProviderRegistry::builtin();
+        assert!(check_providers(&config, &registry).is_ok());
+        let data = data.replace("content-hash", "exif");
+        let config: Config = parse_content(&data).unwrap();
+        let err = check_providers(&config, &registry).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Unknown provider 'exif' for folder '/Volumes/Photos'. Available providers: content-hash"
+        );
+    }
+
+    #[test]
+    fn db_groups() {
+        let data = indoc! {
+        r#"[index]
+            folder = [
+                "/Music",
+                { path = "/etc/fsidx", db-group = "misc" },
+                { path = "/opt/scripts", db-group = "misc" }
+            ]
+
+            [locate]
+            "#};
+        let mut config: Config = parse_content(data).unwrap();
+        assert!(check_db_groups(&config).is_ok());
+        config.index.db_path = Some(PathBuf::from("/db"));
+        let databases: Vec<PathBuf> = get_volume_info(&config)
+            .unwrap()
+            .into_iter()
+            .map(|vi| vi.database)
+            .collect();
+        assert_eq!(
+            databases,
+            ["/db/_Music.fsdb", "/db/misc.fsdb", "/db/misc.fsdb"]
+                .iter()
+                .map(PathBuf::from)
+                .collect::<Vec<_>>()
+        );
+        let config: Config = parse_content(&data.replace("\"misc\" }\n", "\"a/b\" }\n")).unwrap();
+        assert_eq!(
+            check_db_groups(&config).unwrap_err().to_string(),
+            "Invalid db-group 'a/b' for folder '/opt/scripts'. Names must not be empty, contain '/' or start with '_'."
+        );
+        let config: Config =
+            parse_content(&data.replace("db-group", "uuid = \"1234\", db-group")).unwrap();
+        assert_eq!(
+            check_db_groups(&config).unwrap_err().code(),
+            "db-group-on-volume"
+        );
+    }
+
+    #[test]
+    fn open_apps() {
+        let data = indoc! {
+        r#"[index]
+            folder = ["/Volumes/Music"]
+
+            [locate]
+
+            [open]
+            command = "xdg-open"
+
+            [open.apps]
+            flac = "vlc"
+            jpg = "open -a Preview"
+            "#};
+        let config: Config = parse_content(data).unwrap();
+        let open = config.open.unwrap();
+        assert_eq!(open.command, Some(String::from("xdg-open")));
+        assert_eq!(open.apps.get("flac"), Some(&String::from("vlc")));
+        assert_eq!(open.apps.get("jpg"), Some(&String::from("open -a Preview")));
+    }
 }
@@ -0,0 +1,497 @@
+use crate::cli::CliError;
+use crate::config::{get_volume_info, load_from_path, Config};
+use crate::event::print_event;
+use crate::export::json_string;
+use crate::summary::ExitSummary;
+use crate::tokenizer::{tokenize_cli, Token};
+use crate::update::{update_shell, UpdateOptions};
+use fsidx::{LocateConfig, Severity};
+use serde::Serialize;
+use std::env::{self, Args};
+use std::fs;
+use std::io::{stdin, stdout, Write};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use toml::Value;
+use toml_edit::{DocumentMut, Item};
+
+// The config subcommand works with the configuration file itself. It is
+// dispatched before the configuration is loaded.
+pub(crate) fn config_cli(config_file: &Path, args: &mut Args) -> Result<(), CliError> {
+    let command = args.next().ok_or(CliError::MissingConfigCommand)?;
+    match command.as_str() {
+        "show" => show(config_file, args),
+        "edit" => edit(config_file, args),
+        "add-folder" => add_folder(config_file, args),
+        "remove-folder" => remove_folder(config_file, args),
+        "aliases" => aliases(config_file, args),
+        _ => Err(CliError::InvalidConfigArgument(command)),
+    }
+}
+
+fn show(config_file: &Path, args: &mut Args) -> Result<(), CliError> {
+    let mut json = false;
+    for token in tokenize_cli(args)? {
+        match token {
+            Token::Option(option) if option == "json" => json = true,
+            Token::Option(text) | Token::Text(text) => {
+                return Err(CliError::InvalidConfigArgument(text))
+            }
+        }
+    }
+    let config = load_from_path(config_file).map_err(CliError::ConfigError)?;
+    let config_file = config_file
+        .canonicalize()
+        .unwrap_or_else(|_| config_file.to_owned());
+    let effective = Effective::new(&config_file, &config)?;
+    let value = Value::try_from(&effective)
+        .map_err(|err| CliError::SerializingConfigFailed(err.to_string()))?;
+    let text = if json {
+        let mut text = String::new();
+        json_value(&mut text, &value, 0);
+        text.push('\n');
+        text
+    } else {
+        toml::to_string_pretty(&effective)
+            .map_err(|err| CliError::SerializingConfigFailed(err.to_string()))?
+    };
+    stdout().write_all(text.as_bytes())?;
+    Ok(())
+}
+
+fn aliases(config_file: &Path, args: &mut Args) -> Result<(), CliError> {
+    if let Some(arg) = args.next() {
+        return Err(CliError::InvalidConfigArgument(arg));
+    }
+    let config = load_from_path(config_file).map_err(CliError::ConfigError)?;
+    print_aliases(&config)
+}
+
+// Lists the aliases, i.e. the named queries, with their expansion.
+pub(crate) fn print_aliases(config: &Config) -> Result<(), CliError> {
+    let width = config.aliases.keys().map(|name| name.len() + 1).max();
+    let width = width.unwrap_or_default();
+    let mut stdout = stdout().lock();
+    for (name, value) in &config.aliases {
+        writeln!(stdout, "{:width$}  {}", format!("@{}", name), value)?;
+    }
+    Ok(())
+}
+
+// Edits a copy of the configuration file. The copy replaces the file only
+// when it is valid. The previous version is kept as backup.
+fn edit(config_file: &Path, args: &mut Args) -> Result<(), CliError> {
+    if let Some(arg) = args.next() {
+        return Err(CliError::InvalidConfigArgument(arg));
+    }
+    let map_err = |path: &Path| {
+        let path = path.to_owned();
+        move |err| CliError::EditingConfigFailed(path, err)
+    };
+    let original = fs::read_to_string(config_file).map_err(map_err(config_file))?;
+    // The copy is in the same folder, such that the default database folder
+    // is the same while validating.
+    let edited = config_file.with_extension("edit.toml");
+    fs::write(&edited, &original).map_err(map_err(&edited))?;
+    let res = edit_until_valid(config_file, &edited, &original);
+    let _ = fs::remove_file(&edited);
+    res
+}
+
+fn edit_until_valid(config_file: &Path, edited: &Path, original: &str) -> Result<(), CliError> {
+    loop {
+        run_editor(edited)?;
+        let content = fs::read_to_string(edited)
+            .map_err(|err| CliError::EditingConfigFailed(edited.to_owned(), err))?;
+        if content == original {
+            println!("No changes.");
+            return Ok(());
+        }
+        match load_from_path(edited) {
+            Ok(_) => {
+                let backup = save(config_file, &content)?;
+                println!(
+                    "Saved: {} (backup: {})",
+                    config_file.display(),
+                    backup.display()
+                );
+                return Ok(());
+            }
+            Err(err) => {
+                print_event(Severity::Error, &err);
+                if !confirm("Edit again? [Y/n] ")? {
+                    return Err(CliError::ConfigNotSaved(config_file.to_owned()));
+                }
+            }
+        }
+    }
+}
+
+// The editor is taken from VISUAL or EDITOR and may contain arguments.
+fn run_editor(path: &Path) -> Result<(), CliError> {
+    let editor = env::var("VISUAL")
+        .or_else(|_| env::var("EDITOR"))
+        .unwrap_or_else(|_| String::from("vi"));
+    let status = Command::new("sh")
+        .arg("-c")
+        .arg(format!("{} \"$1\"", editor))
+        .arg("sh")
+        .arg(path)
+        .status()
+        .map_err(|err| CliError::EditorFailed(editor.clone(), err))?;
+    if status.success() {
+        Ok(())
+    } else {
+        Err(CliError::EditorExitStatus(editor, status))
+    }
+}
+
+fn confirm(prompt: &str) -> Result<bool, CliError> {
+    print!("{}", prompt);
+    stdout().flush()?;
+    let mut answer = String::new();
+    if stdin().read_line(&mut answer)? == 0 {
+        println!();
+        return Ok(false);
+    }
+    Ok(matches!(answer.trim(), "" | "y" | "Y" | "yes"))
+}
+
+// Writes the new content and returns the backup of the previous version.
+fn save(config_file: &Path, content: &str) -> Result<PathBuf, CliError> {
+    let backup = config_file.with_extension("toml.bak");
+    fs::copy(config_file, &backup)
+        .map_err(|err| CliError::EditingConfigFailed(backup.clone(), err))?;
+    fs::write(config_file, content)
+        .map_err(|err| CliError::EditingConfigFailed(config_file.to_owned(), err))?;
+    Ok(backup)
+}
+
+fn add_folder(config_file: &Path, args: &mut Args) -> Result<(), CliError> {
+    let mut folder = None;
+    let mut update = false;
+    for token in tokenize_cli(args)? {
+        match token {
+            Token::Option(option) if option == "update" => update = true,
+            Token::Text(text) if folder.is_none() => folder = Some(absolute(Path::new(&text))?),
+            Token::Option(text) | Token::Text(text) => {
+                return Err(CliError::InvalidConfigArgument(text))
+            }
+        }
+    }
+    let folder = folder.ok_or(CliError::MissingVolume)?;
+    let config = load_from_path(config_file).map_err(CliError::ConfigError)?;
+    if config.index.folder.iter().any(|f| f.path() == folder) {
+        return Err(CliError::FolderAlreadyConfigured(folder));
+    }
+    let content = read_config(config_file)?;
+    let content = add_folder_to(&content, &folder)?;
+    let config = save_validated(config_file, &content)?;
+    println!("Added: {}", folder.display());
+    if update {
+        update_folder(config, &folder)?;
+    }
+    Ok(())
+}
+
+fn remove_folder(config_file: &Path, args: &mut Args) -> Result<(), CliError> {
+    let folder = args.next().ok_or(CliError::MissingVolume)?;
+    if let Some(arg) = args.next() {
+        return Err(CliError::InvalidConfigArgument(arg));
+    }
+    let folder = absolute(Path::new(&folder))?;
+    let config = load_from_path(config_file).map_err(CliError::ConfigError)?;
+    let index = config
+        .index
+        .folder
+        .iter()
+        .position(|f| f.path() == folder)
+        .ok_or_else(|| CliError::UnknownVolume(folder.clone()))?;
+    let content = read_config(config_file)?;
+    let content = remove_folder_from(&content, index)?;
+    save_validated(config_file, &content)?;
+    println!("Removed: {}", folder.display());
+    Ok(())
+}
+
+fn absolute(path: &Path) -> Result<PathBuf, CliError> {
+    Ok(env::current_dir()?.join(path))
+}
+
+fn read_config(config_file: &Path) -> Result<String, CliError> {
+    fs::read_to_string(config_file)
+        .map_err(|err| CliError::EditingConfigFailed(config_file.to_owned(), err))
+}
+
+// Validates the new content like config edit before it is saved.
+fn save_validated(config_file: &Path, content: &str) -> Result<Config, CliError> {
+    let edited = config_file.with_extension("edit.toml");
+    fs::write(&edited, content)
+        .map_err(|err| CliError::EditingConfigFailed(edited.clone(), err))?;
+    let res = load_from_path(&edited);
+    let _ = fs::remove_file(&edited);
+    let config = res.map_err(CliError::ConfigError)?;
+    save(config_file, content)?;
+    Ok(config)
+}
+
+// The folders are kept in the array of the index table. Comments and
+// formatting of the other entries are preserved.
+fn add_folder_to(content: &str, folder: &Path) -> Result<String, CliError> {
+    let mut document = parse_document(content)?;
+    let path = folder
+        .to_str()
+        .ok_or_else(|| CliError::NotImplementedForNonUtf8Path(folder.to_owned()))?;
+    let index = document
+        .entry("index")
+        .or_insert(toml_edit::table())
+        .as_table_like_mut()
+        .ok_or_else(|| CliError::UnsupportedConfigLayout(String::from("index")))?;
+    match index.entry("folder").or_insert(toml_edit::array()) {
+        Item::Value(toml_edit::Value::Array(array)) => {
+            let mut value = toml_edit::Value::from(path);
+            // New entries are indented like the last entry, if it is on an
+            // own line. A comment in front of the last entry is not copied.
+            let prefix = array
+                .iter()
+                .last()
+                .and_then(|last| last.decor().prefix())
+                .and_then(|prefix| prefix.as_str())
+                .and_then(|prefix| prefix.rfind('\n').map(|pos| &prefix[pos..]));
+            if let Some(prefix) = prefix {
+                value.decor_mut().set_prefix(prefix);
+            }
+            array.push_formatted(value);
+        }
+        Item::ArrayOfTables(tables) => {
+            let mut table = toml_edit::Table::new();
+            table.insert("path", toml_edit::value(path));
+            tables.push(table);
+        }
+        _ => {
+            return Err(CliError::UnsupportedConfigLayout(String::from(
+                "index.folder",
+            )))
+        }
+    }
+    Ok(document.to_string())
+}
+
+// Removes the folder with the index in the array of the index table.
+fn remove_folder_from(content: &str, index: usize) -> Result<String, CliError> {
+    let mut document = parse_document(content)?;
+    match document
+        .get_mut("index")
+        .and_then(|index| index.get_mut("folder"))
+    {
+        Some(Item::Value(toml_edit::Value::Array(array))) if index < array.len() => {
+            array.remove(index);
+        }
+        Some(Item::ArrayOfTables(tables)) if index < tables.len() => tables.remove(index),
+        _ => {
+            return Err(CliError::UnsupportedConfigLayout(String::from(
+                "index.folder",
+            )))
+        }
+    }
+    Ok(document.to_string())
+}
+
+fn parse_document(content: &str) -> Result<DocumentMut, CliError> {
+    content
+        .parse::<DocumentMut>()
+        .map_err(|err| CliError::UnsupportedConfigLayout(err.to_string()))
+}
+
+// Scans the new folder only.
+fn update_folder(mut config: Config, folder: &Path) -> Result<(), CliError> {
+    config.index.folder.retain(|f| f.path() == folder);
+    let mut exit_summary = ExitSummary::update();
+    if update_shell(&config, &UpdateOptions::default(), &mut exit_summary, None)? == Severity::Error
+    {
+        return Err(CliError::UpdateFailed);
+    }
+    Ok(())
+}
+
+// The loaded configuration with defaults applied, tilde expanded and the
+// database folder resolved, followed by the volumes derived from it.
+#[derive(Serialize)]
+#[serde(rename_all = "kebab-case")]
+struct Effective<'a> {
+    config_file: &'a Path,
+    #[serde(flatten)]
+    config: &'a Config,
+    volume: Vec<Volume>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "kebab-case")]
+struct Volume {
+    folder: PathBuf,
+    database: PathBuf,
+    providers: Vec<String>,
+    // Locate defaults with the folder options applied.
+    locate: LocateConfig,
+}
+
+impl<'a> Effective<'a> {
+    fn new(config_file: &'a Path, config: &'a Config) -> Result<Effective<'a>, CliError> {
+        let volume = get_volume_info(config)
+            .ok_or(CliError::NoDatabasePath)?
+            .into_iter()
+            .map(|vi| Volume {
+                locate: vi.locate_config.unwrap_or_else(|| config.locate.clone()),
+                folder: vi.folder,
+                database: vi.database,
+                providers: vi.providers,
+            })
+            .collect();
+        Ok(Effective {
+            config_file,
+            config,
+            volume,
+        })
+    }
+}
+
+fn json_value(text: &mut String, value: &Value, indent: usize) {
+    match value {
+        Value::String(string) => text.push_str(&json_string(string)),
+        Value::Integer(integer) => text.push_str(&integer.to_string()),
+        Value::Float(float) => text.push_str(&float.to_string()),
+        Value::Boolean(boolean) => text.push_str(&boolean.to_string()),
+        Value::Datetime(datetime) => text.push_str(&json_string(&datetime.to_string())),
+        Value::Array(array) => {
+            json_list(text, '[', ']', array.iter().map(|v| (None, v)), indent);
+        }
+        Value::Table(table) => {
+            let entries = table.iter().map(|(k, v)| (Some(k.as_str()), v));
+            json_list(text, '{', '}', entries, indent);
+        }
+    }
+}
+
+fn json_list<'a>(
+    text: &mut String,
+    open: char,
+    close: char,
+    entries: impl ExactSizeIterator<Item = (Option<&'a str>, &'a Value)>,
+    indent: usize,
+) {
+    text.push(open);
+    if entries.len() == 0 {
+        text.push(close);
+        return;
+    }
+    for (index, (key, value)) in entries.enumerate() {
+        if index > 0 {
+            text.push(',');
+        }
+        text.push('\n');
+        text.push_str(&"  ".repeat(indent + 1));
+        if let Some(key) = key {
+            text.push_str(&json_string(key));
+            text.push_str(": ");
+        }
+        json_value(text, value, indent + 1);
+    }
+    text.push('\n');
+    text.push_str(&"  ".repeat(indent));
+    text.push(close);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{Folder, Index};
+    use crate::temp_dir::TempDir;
+    use indoc::indoc;
+
+    #[test]
+    fn effective_config() {
+        let config = Config {
+            index: Index {
+                folder: vec![Folder::Path(PathBuf::from("/Volumes/Music"))],
+                db_path: Some(PathBuf::from("/db")),
+            },
+            locate: LocateConfig::default(),
+            update: Default::default(),
+            shell: Default::default(),
+            output: Default::default(),
+            open: None,
+            aliases: Default::default(),
+            hooks: None,
+        };
+        let config_file = Path::new("/etc/fsidx/fsidx.toml");
+        let effective = Effective::new(config_file, &config).unwrap();
+        let value = Value::try_from(&effective).unwrap();
+        assert_eq!(
+            value["volume"][0]["database"].as_str(),
+            Some("/db/_Volumes_Music.fsdb")
+        );
+        assert_eq!(value["output"]["max-buffer-mb"].as_integer(), Some(1024));
+        let text = toml::to_string_pretty(&effective).unwrap();
+        assert!(text.starts_with("config-file = \"/etc/fsidx/fsidx.toml\"\n"));
+    }
+
+    #[test]
+    fn save_with_backup() {
+        let dir = TempDir::new("edit");
+        let config_file = dir.join("fsidx.toml");
+        fs::write(&config_file, "old").unwrap();
+        let backup = save(&config_file, "new").unwrap();
+        let content = (
+            fs::read_to_string(&config_file).unwrap(),
+            fs::read_to_string(&backup).unwrap(),
+        );
+        assert_eq!(backup.file_name().unwrap(), "fsidx.toml.bak");
+        assert_eq!(content, (String::from("new"), String::from("old")));
+    }
+
+    #[test]
+    fn add_and_remove_folders() {
+        let content = indoc! {r#"
+            # Indexed folders
+            [index]
+            folder = [
+                "~/Music", # Local
+                { path = "/Volumes/Music" },
+            ]
+            "#};
+        let added = add_folder_to(content, Path::new("/Volumes/Photos")).unwrap();
+        assert_eq!(
+            added,
+            indoc! {r#"
+            # Indexed folders
+            [index]
+            folder = [
+                "~/Music", # Local
+                { path = "/Volumes/Music" },
+                "/Volumes/Photos",
+            ]
+            "#}
+        );
+        assert_eq!(remove_folder_from(&added, 2).unwrap(), content);
+        let removed = remove_folder_from(content, 0).unwrap();
+        assert!(removed.starts_with("# Indexed folders\n[index]\nfolder = ["));
+        assert!(!removed.contains("~/Music"));
+        let tables = "[[index.folder]]\npath = \"/a\"\n";
+        assert_eq!(
+            add_folder_to(tables, Path::new("/b")).unwrap(),
+            "[[index.folder]]\npath = \"/a\"\n\n[[index.folder]]\npath = \"/b\"\n"
+        );
+        assert!(remove_folder_from(tables, 1).is_err());
+    }
+
+    #[test]
+    fn json() {
+        let value: Value = toml::from_str("a = [1, \"x\"]\nb = {}\n[c]\nd = true\n").unwrap();
+        let mut text = String::new();
+        json_value(&mut text, &value, 0);
+        assert_eq!(
+            text,
+            "{\n  \"a\": [\n    1,\n    \"x\"\n  ],\n  \"b\": {},\n  \"c\": {\n    \"d\": true\n  }\n}"
+        );
+    }
+}
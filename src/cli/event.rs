@@ -0,0 +1,144 @@
+use crate::export::json_string;
+use fsidx::Severity;
+use std::fmt::Display;
+use std::io::{stdout, Write};
+use std::path::{Path, PathBuf};
+use termcolor::{Color, ColorChoice, ColorSpec, StandardStream, WriteColor};
+
+static mut JSON_DIAGNOSTICS: bool = false;
+
+// Selects whether warnings and errors are printed as JSON objects.
+pub fn set_json_diagnostics(json: bool) {
+    unsafe {
+        JSON_DIAGNOSTICS = json;
+    }
+}
+
+fn json_diagnostics() -> bool {
+    unsafe { JSON_DIAGNOSTICS }
+}
+
+// A message with a stable code and the paths it refers to. Tools wrapping
+// fsidx use the code instead of the localized text.
+pub(crate) trait Diagnostic: Display {
+    fn code(&self) -> &'static str;
+
+    fn path(&self) -> Option<&Path> {
+        None
+    }
+
+    // The configured folder the message refers to.
+    fn volume(&self) -> Option<&Path> {
+        None
+    }
+}
+
+// A diagnostic for messages without an own error type.
+pub(crate) struct Message {
+    code: &'static str,
+    text: String,
+    volume: Option<PathBuf>,
+}
+
+impl Message {
+    pub(crate) fn new(code: &'static str, text: String) -> Message {
+        Message {
+            code,
+            text,
+            volume: None,
+        }
+    }
+
+    pub(crate) fn with_volume(mut self, volume: &Path) -> Message {
+        self.volume = Some(volume.to_owned());
+        self
+    }
+}
+
+impl Display for Message {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.text)
+    }
+}
+
+impl Diagnostic for Message {
+    fn code(&self) -> &'static str {
+        self.code
+    }
+
+    fn volume(&self) -> Option<&Path> {
+        self.volume.as_deref()
+    }
+}
+
+pub(crate) fn print_event(severity: Severity, message: &dyn Diagnostic) {
+    match severity {
+        Severity::Info => {
+            let _ = writeln!(stdout(), "{}", message);
+        }
+        Severity::Warning | Severity::Error if json_diagnostics() => {
+            eprintln!("{}", json_diagnostic(severity, message));
+        }
+        Severity::Warning => {
+            print_warning();
+            eprintln!("{}", message);
+        }
+        Severity::Error => {
+            print_error();
+            eprintln!("{}", message);
+        }
+    }
+}
+
+pub(crate) fn json_diagnostic(severity: Severity, message: &dyn Diagnostic) -> String {
+    let severity = match severity {
+        Severity::Info => "info",
+        Severity::Warning => "warning",
+        Severity::Error => "error",
+    };
+    let optional = |path: Option<&Path>| match path {
+        Some(path) => json_string(&path.to_string_lossy()),
+        None => String::from("null"),
+    };
+    format!(
+        "{{\"severity\":\"{}\",\"code\":\"{}\",\"message\":{},\"path\":{},\"volume\":{}}}",
+        severity,
+        message.code(),
+        json_string(&message.to_string()),
+        optional(message.path()),
+        optional(message.volume()),
+    )
+}
+
+pub fn print_error() {
+    print_label("Error", Color::Red);
+}
+
+pub fn print_warning() {
+    print_label("Warning", Color::Yellow);
+}
+
+fn print_label(label: &str, color: Color) {
+    let mut stderr = StandardStream::stderr(ColorChoice::Auto);
+    let _ = stderr.set_color(ColorSpec::new().set_fg(Some(color)));
+    let _ = stderr.write_all(label.as_bytes());
+    let _ = stderr.set_color(&ColorSpec::new());
+    let _ = stderr.write_all(b": ");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn json() {
+        let message = Message::new("database-missing", String::from("Database for \"Music\""))
+            .with_volume(Path::new("/Volumes/Music"));
+        assert_eq!(
+            json_diagnostic(Severity::Warning, &message),
+            "{\"severity\":\"warning\",\"code\":\"database-missing\",\
+             \"message\":\"Database for \\\"Music\\\"\",\"path\":null,\
+             \"volume\":\"/Volumes/Music\"}"
+        );
+    }
+}
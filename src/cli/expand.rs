@@ -1,6 +1,7 @@
 use crate::cli::CliError;
 use globset::GlobBuilder;
 use nom::IResult;
+use std::collections::HashMap;
 use std::fmt::Debug;
 use std::path::{Path, PathBuf};
 use std::str::FromStr;
@@ -10,14 +11,60 @@ use std::str::FromStr;
 // idx.-idx.      -- Opens range of files from selection
 // glob           -- Opens all matching files from selection
 // idx./path/glob -- Opens all matching files from selection
+// ppage:rule     -- Index rule relative to a page of the selection
+
+// The last query result. It was printed in pages of page_size entries.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Selection {
+    pub paths: Vec<PathBuf>,
+    pub page_size: usize,
+    // The query was aborted, i.e. it has only the results found so far.
+    pub partial: bool,
+}
+
+impl Selection {
+    pub fn pages(&self) -> usize {
+        self.paths.len().div_ceil(self.page_size)
+    }
+
+    // Returns the index of a path as printed with the query results.
+    pub fn index(&self, path: &Path) -> usize {
+        self.paths
+            .iter()
+            .position(|p| p == path)
+            .map(|index| index + 1)
+            .unwrap_or(0)
+    }
+
+    // Returns the indices of all paths like index, but without searching
+    // the selection for each path.
+    pub fn indices(&self) -> HashMap<&Path, usize> {
+        let mut indices = HashMap::new();
+        for (index, path) in self.paths.iter().enumerate() {
+            indices.entry(path.as_path()).or_insert(index + 1);
+        }
+        indices
+    }
+
+    // Returns the index of the first entry and the entries of a page.
+    // Pages start with 1.
+    pub fn page(&self, page: usize) -> Option<(usize, &[PathBuf])> {
+        if page < 1 || page > self.pages() {
+            return None;
+        }
+        let start = (page - 1) * self.page_size;
+        let end = (start + self.page_size).min(self.paths.len());
+        Some((start + 1, &self.paths[start..end]))
+    }
+}
 
 pub struct Expand<'a> {
     open_rule: OpenRule,
-    selection: &'a Vec<PathBuf>,
+    selection: &'a Selection,
 }
 
 impl<'a> Expand<'a> {
-    pub fn new(open_rule: OpenRule, selection: &'a Vec<PathBuf>) -> Expand<'a> {
+    pub fn new(open_rule: OpenRule, selection: &'a Selection) -> Expand<'a> {
         Expand {
             open_rule,
             selection,
@@ -28,14 +75,19 @@ impl<'a> Expand<'a> {
         &self,
         mut f: F,
     ) -> Result<(), CliError> {
+        let paths = &self.selection.paths;
         match &self.open_rule {
-            OpenRule::Glob(glob) => expand_glob(glob, self.selection, &mut f),
-            OpenRule::Index(index) => expand_index(*index, self.selection, &mut f),
-            OpenRule::IndexRange(start, end) => {
-                expand_index_range(*start, *end, self.selection, &mut f)
-            }
-            OpenRule::IndexGlob(index, glob) => {
-                expand_index_with_glob(*index, glob, self.selection, &mut f)
+            OpenRule::Glob(glob) => expand_glob(glob, paths, &mut f),
+            OpenRule::Index(index) => expand_index(*index, paths, &mut f),
+            OpenRule::IndexRange(start, end) => expand_index_range(*start, *end, paths, &mut f),
+            OpenRule::IndexGlob(index, glob) => expand_index_with_glob(*index, glob, paths, &mut f),
+            OpenRule::Page(page, open_rule) => {
+                let (first, _) = self
+                    .selection
+                    .page(*page)
+                    .ok_or(CliError::InvalidOpenPage(*page))?;
+                let open_rule = open_rule.offset(first - 1);
+                Expand::new(open_rule, self.selection).foreach(f)
             }
         }
     }
@@ -98,7 +150,7 @@ fn expand_index_with_glob<F: FnMut(&Path) -> Result<(), CliError>>(
     if index < 1 {
         return Err(CliError::InvalidOpenIndex(index));
     }
-    let Some(path) = selection.get(index-1) else {
+    let Some(path) = selection.get(index - 1) else {
         return Err(CliError::InvalidOpenIndex(index));
     };
     let Some(path) = path.to_str() else {
@@ -112,12 +164,26 @@ fn expand_index_with_glob<F: FnMut(&Path) -> Result<(), CliError>>(
     Ok(())
 }
 
-#[derive(PartialEq)]
+#[derive(PartialEq, Clone)]
 pub enum OpenRule {
     Glob(String),
     Index(usize),
     IndexRange(usize, usize),
     IndexGlob(usize, String),
+    Page(usize, Box<OpenRule>),
+}
+
+impl OpenRule {
+    // Adds offset to the indexes of the rule.
+    fn offset(&self, offset: usize) -> OpenRule {
+        match self {
+            OpenRule::Glob(glob) => OpenRule::Glob(glob.clone()),
+            OpenRule::Index(index) => OpenRule::Index(index + offset),
+            OpenRule::IndexRange(start, end) => OpenRule::IndexRange(start + offset, end + offset),
+            OpenRule::IndexGlob(index, glob) => OpenRule::IndexGlob(index + offset, glob.clone()),
+            OpenRule::Page(page, open_rule) => OpenRule::Page(*page, open_rule.clone()),
+        }
+    }
 }
 
 #[derive(PartialEq)]
@@ -152,6 +218,9 @@ impl Debug for OpenRule {
             Self::IndexGlob(index, glob) => {
                 f.debug_tuple("IndexGlob").field(index).field(glob).finish()
             }
+            Self::Page(page, open_rule) => {
+                f.debug_tuple("Page").field(page).field(open_rule).finish()
+            }
         }
     }
 }
@@ -172,6 +241,22 @@ fn parse_open_rule(input: &str) -> IResult<&str, OpenRule> {
     use nom::combinator::{all_consuming, map, rest};
     use nom::sequence::tuple;
     all_consuming(alt((
+        map(
+            tuple((tag("p"), u64, tag(":"), parse_index_rule)),
+            |(_, page, _, open_rule)| OpenRule::Page(page as usize, Box::new(open_rule)),
+        ),
+        parse_index_rule,
+        map(rest::<&str, _>, |glob| OpenRule::Glob(glob.to_string())),
+    )))(input)
+}
+
+fn parse_index_rule(input: &str) -> IResult<&str, OpenRule> {
+    use nom::branch::alt;
+    use nom::bytes::complete::tag;
+    use nom::character::complete::u64;
+    use nom::combinator::{map, rest};
+    use nom::sequence::tuple;
+    alt((
         map(
             tuple((u64::<&str, _>, tag("./"), rest)),
             |(idx, _, glob)| OpenRule::IndexGlob(idx as usize, glob.to_string()),
@@ -183,8 +268,7 @@ fn parse_open_rule(input: &str) -> IResult<&str, OpenRule> {
         map(tuple((u64, tag("."))), |(idx, _)| {
             OpenRule::Index(idx as usize)
         }),
-        map(rest::<&str, _>, |glob| OpenRule::Glob(glob.to_string())),
-    )))(input)
+    ))(input)
 }
 
 fn normalize(mut glob: String) -> String {
@@ -233,6 +317,63 @@ mod tests {
         );
     }
 
+    #[test]
+    fn page() {
+        assert_eq!(
+            "p2:3.".parse(),
+            Ok(OpenRule::Page(2, Box::new(OpenRule::Index(3))))
+        );
+        assert_eq!(
+            "p2:3.-5.".parse(),
+            Ok(OpenRule::Page(2, Box::new(OpenRule::IndexRange(3, 5))))
+        );
+        assert_eq!(
+            "p2:*.jpg".parse::<OpenRule>(),
+            Ok(OpenRule::Glob("p2:*.jpg".to_string()))
+        );
+    }
+
+    #[test]
+    fn expand_page() {
+        let selection = Selection {
+            paths: (1..=5).map(|i| PathBuf::from(format!("/{}", i))).collect(),
+            page_size: 2,
+            partial: false,
+        };
+        assert_eq!(selection.pages(), 3);
+        assert_eq!(selection.page(3), Some((5, &selection.paths[4..])));
+        assert_eq!(selection.page(4), None);
+        let mut paths = Vec::new();
+        let expand = Expand::new("p2:1.-2.".parse().unwrap(), &selection);
+        expand
+            .foreach(|path| {
+                paths.push(path.to_path_buf());
+                Ok(())
+            })
+            .unwrap();
+        assert_eq!(paths, vec![PathBuf::from("/3"), PathBuf::from("/4")]);
+        let expand = Expand::new("p3:2.".parse().unwrap(), &selection);
+        assert!(expand.foreach(|_| Ok(())).is_err());
+    }
+
+    #[test]
+    fn indices() {
+        let selection = Selection {
+            paths: ["/a", "/b", "/a"].iter().map(PathBuf::from).collect(),
+            page_size: 2,
+            partial: false,
+        };
+        let indices = selection.indices();
+        assert_eq!(indices.len(), 2);
+        for path in ["/a", "/b", "/c"] {
+            let path = Path::new(path);
+            assert_eq!(
+                indices.get(path).copied().unwrap_or(0),
+                selection.index(path)
+            );
+        }
+    }
+
     #[test]
     fn test_normalize() {
         let path = String::from("/abc/../foo/bar/baz/../../*.jpg");
@@ -0,0 +1,217 @@
+use crate::cli::CliError;
+use crate::locate::{locate_filter, LOCATE_OPTIONS};
+use crate::output::Output;
+use crate::storage::private_temp_dir;
+use crate::tokenizer::tokenize_shell;
+use fsidx::{LocateConfig, LocateEvent, Settings, UpdateConfig, VolumeInfo};
+use std::env::{self, Args};
+use std::fs::{self, File};
+use std::io::{Result as IOResult, Write};
+use std::os::unix::prelude::OsStrExt;
+use std::path::{Path, PathBuf};
+use termcolor::{Color, ColorSpec, WriteColor};
+
+// Files of the sample volume the examples are run against.
+const SAMPLE: &[&str] = &[
+    "Music/The Beatles/Help!/01 Help!.flac",
+    "Music/The Beatles/Abbey Road/01 Come Together.flac",
+    "Music/Pink Floyd/The Wall/05 Another Brick in the Wall.mp3",
+    "Photos/2023/beach_party.jpg",
+    "Photos/2023/Beach-Party.JPG",
+    "Documents/Reviews/beatles.txt",
+];
+
+// Example queries with a short explanation.
+const EXAMPLES: &[(&str, &str)] = &[
+    ("beatles help", "Plain text matches in any order"),
+    (
+        "-o help beatles",
+        "Plain text must appear in the same order",
+    ),
+    ("-l beatles", "Only the last element of a path is searched"),
+    ("-c Beach", "Case-sensitive matching"),
+    (
+        "\"beach party\"",
+        "Space, dash and underscore match each other",
+    ),
+    ("-S \"beach party\"", "Spaces only match with spaces"),
+    ("*.flac", "Arguments with wildcards are glob patterns"),
+    ("**/Photos/*.jpg", "Asterisk matches any character"),
+    ("--ls **/Photos/*.jpg", "Asterisk does not match a slash"),
+    ("-1 help!", "Plain text without glob detection"),
+];
+
+pub(crate) fn explain_syntax_cli(args: &mut Args) -> Result<(), CliError> {
+    if let Some(arg) = args.next() {
+        return Err(CliError::InvalidExplainArgument(arg));
+    }
+    let mut stdout = Output::stdout();
+    print_options(&mut stdout)?;
+    let sample = Sample::create()?;
+    print_heading(&mut stdout, "Examples:")?;
+    for (query, explanation) in EXAMPLES {
+        stdout.set_color(ColorSpec::new().set_fg(Some(Color::Green)))?;
+        write!(stdout, "    fsidx locate {}", query)?;
+        stdout.set_color(&ColorSpec::new())?;
+        write!(stdout, "  # {}", explanation)?;
+        stdout.end_line()?;
+        let results = sample.locate(query)?;
+        if results.is_empty() {
+            write!(stdout, "        (no results)")?;
+            stdout.end_line()?;
+        }
+        for path in results {
+            stdout.write_all(b"        ")?;
+            stdout.write_all(path.as_os_str().as_bytes())?;
+            stdout.end_line()?;
+        }
+    }
+    Ok(())
+}
+
+fn print_options(stdout: &mut Output) -> IOResult<()> {
+    let width = LOCATE_OPTIONS
+        .iter()
+        .map(|option| option_names(option.short, option.long).len())
+        .max()
+        .unwrap_or(0);
+    let mut group = "";
+    for option in LOCATE_OPTIONS {
+        if option.group != group {
+            if !group.is_empty() {
+                stdout.end_line()?;
+            }
+            group = option.group;
+            print_heading(stdout, &format!("{}:", group))?;
+        }
+        stdout.set_color(ColorSpec::new().set_fg(Some(Color::Green)))?;
+        write!(
+            stdout,
+            "    {:width$}",
+            option_names(option.short, option.long),
+            width = width
+        )?;
+        stdout.set_color(&ColorSpec::new())?;
+        write!(stdout, "  {}", option.description)?;
+        stdout.end_line()?;
+    }
+    stdout.end_line()
+}
+
+fn option_names(short: &str, long: &str) -> String {
+    let prefix = if short.len() == 1 { "-" } else { "--" };
+    format!("{}{} | --{}", prefix, short, long)
+}
+
+fn print_heading(stdout: &mut Output, heading: &str) -> IOResult<()> {
+    stdout.set_color(ColorSpec::new().set_fg(Some(Color::Yellow)))?;
+    stdout.write_all(heading.as_bytes())?;
+    stdout.set_color(&ColorSpec::new())?;
+    stdout.end_line()
+}
+
+// Sample volume with an index in a temporary folder. The index is created by
+// the same update function as any other index. The folder is removed on drop.
+struct Sample {
+    dir: PathBuf,
+    volume_info: VolumeInfo,
+}
+
+impl Sample {
+    fn create() -> Result<Sample, CliError> {
+        let dir = private_temp_dir("fsidx-explain")
+            .map_err(|err| CliError::CreatingSampleFailed(env::temp_dir(), err))?;
+        let sample = Sample {
+            volume_info: VolumeInfo::new(dir.join("sample"), dir.join("sample.fsdb")),
+            dir,
+        };
+        for file in SAMPLE {
+            let path = sample.volume_info.folder.join(file);
+            let map_err = |err| CliError::CreatingSampleFailed(path.clone(), err);
+            if let Some(parent) = path.parent() {
+                fs::create_dir_all(parent).map_err(map_err)?;
+            }
+            File::create(&path).map_err(map_err)?;
+        }
+        let mut failed = false;
+        fsidx::update(
+            vec![sample.volume_info.clone()],
+            Settings::WithFileSizes,
+            &UpdateConfig::default(),
+            None,
+            |event| {
+                failed |= event.severity() == fsidx::Severity::Error;
+                Ok(())
+            },
+        );
+        if failed {
+            return Err(CliError::UpdateFailed);
+        }
+        Ok(sample)
+    }
+
+    // Returns the matching files relative to the sample folder.
+    fn locate(&self, query: &str) -> Result<Vec<PathBuf>, CliError> {
+        let filter = locate_filter(tokenize_shell(query)?)?;
+        let mut results = Vec::new();
+        fsidx::locate(
+            vec![self.volume_info.clone()],
+            filter,
+            &LocateConfig::default(),
+            None,
+            |event| {
+                if let LocateEvent::Entry(path, _) = event {
+                    results.push(relative(&self.volume_info.folder, path).to_owned());
+                }
+                Ok(())
+            },
+        )
+        .map_err(CliError::LocateError)?;
+        Ok(results)
+    }
+}
+
+impl Drop for Sample {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.dir);
+    }
+}
+
+fn relative<'a>(folder: &Path, path: &'a Path) -> &'a Path {
+    path.strip_prefix(folder).unwrap_or(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tokenizer::Token;
+    use std::collections::HashSet;
+
+    #[test]
+    fn option_table() {
+        let mut names = HashSet::new();
+        for option in LOCATE_OPTIONS {
+            assert!(names.insert(option.long));
+            assert!(names.insert(option.short));
+            for name in [option.long, option.short] {
+                let filter = locate_filter(vec![Token::Option(name.to_string())]).unwrap();
+                assert_eq!(filter, vec![(option.token)()]);
+            }
+        }
+        assert_eq!(option_names("c", "case-sensitive"), "-c | --case-sensitive");
+        assert_eq!(
+            option_names("ls", "literal-separator"),
+            "--ls | --literal-separator"
+        );
+    }
+
+    #[test]
+    fn examples() {
+        let sample = Sample::create().unwrap();
+        let results: Vec<usize> = EXAMPLES
+            .iter()
+            .map(|(query, _)| sample.locate(query).unwrap().len())
+            .collect();
+        assert_eq!(results, vec![2, 0, 2, 1, 2, 0, 2, 1, 0, 2]);
+    }
+}
@@ -0,0 +1,246 @@
+use crate::cli::CliError;
+use crate::config::{get_volume_info, Config};
+use crate::locate::{check_databases, searchable_volumes};
+use crate::tokenizer::{tokenize_cli, Token};
+use fsidx::{ExportFormat, TreeEvent};
+use std::env::Args;
+use std::io::{stdout, BufWriter, Result as IOResult, Write};
+use std::os::unix::prelude::OsStrExt;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+enum Format {
+    // Output of du -ab: size in bytes and path, directories after their content.
+    Du,
+    // JSON export format of ncdu.
+    NcduJson,
+}
+
+// Exports the entries of all folders to stdout, e.g. for locate -d.
+pub(crate) fn export_cli(config: &Config, args: &mut Args) -> Result<(), CliError> {
+    let mut format = ExportFormat::Text;
+    let mut token = tokenize_cli(args)?.into_iter();
+    while let Some(tk) = token.next() {
+        match tk {
+            Token::Option(option) if option == "format" => {
+                format = match token.next() {
+                    Some(Token::Text(text)) if text == "txt" => ExportFormat::Text,
+                    Some(Token::Text(text)) if text == "locatedb" => ExportFormat::Locatedb,
+                    Some(Token::Text(text)) => return Err(CliError::InvalidExportArgument(text)),
+                    _ => return Err(CliError::MissingOptionValue(option)),
+                }
+            }
+            Token::Option(text) | Token::Text(text) => {
+                return Err(CliError::InvalidExportArgument(text))
+            }
+        }
+    }
+    let checks = check_databases(config)?;
+    let volume_info = searchable_volumes(config, &checks)?;
+    let mut stdout = BufWriter::new(stdout().lock());
+    let res = fsidx::export(volume_info, format, &mut stdout);
+    finish(&mut stdout, res)
+}
+
+pub(crate) fn export_tree_cli(config: &Config, args: &mut Args) -> Result<(), CliError> {
+    let mut format = Format::Du;
+    let mut folder = None;
+    let mut token = tokenize_cli(args)?.into_iter();
+    while let Some(tk) = token.next() {
+        match tk {
+            Token::Option(option) if option == "format" => {
+                format = match token.next() {
+                    Some(Token::Text(text)) if text == "du" => Format::Du,
+                    Some(Token::Text(text)) if text == "ncdu-json" => Format::NcduJson,
+                    Some(Token::Text(text)) => return Err(CliError::InvalidExportArgument(text)),
+                    _ => return Err(CliError::MissingOptionValue(option)),
+                }
+            }
+            Token::Text(text) if folder.is_none() => folder = Some(PathBuf::from(text)),
+            Token::Option(text) | Token::Text(text) => {
+                return Err(CliError::InvalidExportArgument(text))
+            }
+        }
+    }
+    let folder = folder.ok_or(CliError::MissingVolume)?;
+    let volume_info = get_volume_info(config)
+        .ok_or(CliError::NoDatabasePath)?
+        .into_iter()
+        .find(|vi| vi.folder == folder)
+        .ok_or(CliError::UnknownVolume(folder))?;
+    let mut stdout = BufWriter::new(stdout().lock());
+    let res = match format {
+        Format::Du => fsidx::tree(&volume_info.database, |event| du(&mut stdout, event)),
+        Format::NcduJson => {
+            let mut ncdu = NcduJson::new();
+            ncdu.begin(&mut stdout)?;
+            fsidx::tree(&volume_info.database, |event| {
+                ncdu.event(&mut stdout, event)
+            })
+            .and_then(|_| {
+                ncdu.end(&mut stdout)
+                    .map_err(fsidx::LocateError::WritingResultFailed)
+            })
+        }
+    };
+    finish(&mut stdout, res)
+}
+
+// A broken pipe isn't an error, e.g. for: fsidx export | head
+fn finish(stdout: &mut dyn Write, res: Result<(), fsidx::LocateError>) -> Result<(), CliError> {
+    match res.and_then(|_| {
+        stdout
+            .flush()
+            .map_err(fsidx::LocateError::WritingResultFailed)
+    }) {
+        Ok(()) | Err(fsidx::LocateError::BrokenPipe) => Ok(()),
+        Err(fsidx::LocateError::WritingResultFailed(err))
+            if err.kind() == std::io::ErrorKind::BrokenPipe =>
+        {
+            Ok(())
+        }
+        Err(err) => Err(CliError::LocateError(err)),
+    }
+}
+
+fn du(stdout: &mut dyn Write, event: TreeEvent) -> IOResult<()> {
+    let (path, size) = match event {
+        TreeEvent::EnterDirectory(_, _) => return Ok(()),
+        TreeEvent::LeaveDirectory(path, size) => (path, size),
+        TreeEvent::File(path, size) => (path, size.unwrap_or(0)),
+        _ => return Ok(()),
+    };
+    write!(stdout, "{}\t", size)?;
+    stdout.write_all(path.as_os_str().as_bytes())?;
+    stdout.write_all(b"\n")
+}
+
+// Writes the ncdu JSON export format: [1, 0, {metadata}, directory], where a
+// directory is an array with an object for the directory itself followed by
+// objects for files and arrays for subdirectories.
+struct NcduJson {
+    // Whether the next item in the current directory needs a separator.
+    separator: bool,
+    depth: usize,
+}
+
+impl NcduJson {
+    fn new() -> NcduJson {
+        NcduJson {
+            separator: false,
+            depth: 0,
+        }
+    }
+
+    fn begin(&mut self, stdout: &mut dyn Write) -> IOResult<()> {
+        let timestamp = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .map(|duration| duration.as_secs())
+            .unwrap_or(0);
+        write!(
+            stdout,
+            "[1,0,{{\"progname\":\"fsidx\",\"progver\":\"{}\",\"timestamp\":{}}}",
+            env!("CARGO_PKG_VERSION"),
+            timestamp
+        )?;
+        self.separator = true;
+        Ok(())
+    }
+
+    fn event(&mut self, stdout: &mut dyn Write, event: TreeEvent) -> IOResult<()> {
+        match event {
+            TreeEvent::EnterDirectory(path, size) => {
+                self.item(stdout)?;
+                stdout.write_all(b"[")?;
+                self.info(stdout, path, size)?;
+                self.depth += 1;
+            }
+            TreeEvent::LeaveDirectory(_, _) => {
+                stdout.write_all(b"]")?;
+                self.depth -= 1;
+            }
+            TreeEvent::File(path, size) => {
+                self.item(stdout)?;
+                self.info(stdout, path, size)?;
+            }
+            _ => return Ok(()),
+        }
+        self.separator = true;
+        Ok(())
+    }
+
+    fn end(&mut self, stdout: &mut dyn Write) -> IOResult<()> {
+        stdout.write_all(b"]\n")
+    }
+
+    fn item(&mut self, stdout: &mut dyn Write) -> IOResult<()> {
+        if self.separator {
+            stdout.write_all(b",\n")?;
+        }
+        Ok(())
+    }
+
+    // The top level directory is named with its complete path.
+    fn info(&self, stdout: &mut dyn Write, path: &Path, size: Option<u64>) -> IOResult<()> {
+        let name = match path.file_name() {
+            Some(name) if self.depth > 0 => name.to_string_lossy(),
+            _ => path.to_string_lossy(),
+        };
+        write!(stdout, "{{\"name\":{}", json_string(&name))?;
+        if let Some(size) = size {
+            write!(stdout, ",\"asize\":{}", size)?;
+        }
+        stdout.write_all(b"}")
+    }
+}
+
+pub(crate) fn json_string(text: &str) -> String {
+    let mut json = String::with_capacity(text.len() + 2);
+    json.push('"');
+    for ch in text.chars() {
+        match ch {
+            '"' => json.push_str("\\\""),
+            '\\' => json.push_str("\\\\"),
+            '\n' => json.push_str("\\n"),
+            '\r' => json.push_str("\\r"),
+            '\t' => json.push_str("\\t"),
+            ch if ch.is_control() => json.push_str(&format!("\\u{:04x}", ch as u32)),
+            ch => json.push(ch),
+        }
+    }
+    json.push('"');
+    json
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ncdu_json() {
+        let mut output = Vec::new();
+        let mut ncdu = NcduJson::new();
+        let events = [
+            TreeEvent::EnterDirectory(Path::new("/Music"), Some(4096)),
+            TreeEvent::File(Path::new("/Music/\"Hits\".flac"), Some(10)),
+            TreeEvent::EnterDirectory(Path::new("/Music/Live"), None),
+            TreeEvent::File(Path::new("/Music/Live/A.flac"), Some(20)),
+            TreeEvent::LeaveDirectory(Path::new("/Music/Live"), 20),
+            TreeEvent::LeaveDirectory(Path::new("/Music"), 4126),
+        ];
+        for event in events {
+            ncdu.event(&mut output, event).unwrap();
+        }
+        ncdu.end(&mut output).unwrap();
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            concat!(
+                "[{\"name\":\"/Music\",\"asize\":4096},\n",
+                "{\"name\":\"\\\"Hits\\\".flac\",\"asize\":10},\n",
+                "[{\"name\":\"Live\"},\n",
+                "{\"name\":\"A.flac\",\"asize\":20}]]]\n",
+            )
+        );
+        assert_eq!(json_string("a\u{1}b"), "\"a\\u0001b\"");
+    }
+}
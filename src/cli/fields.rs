@@ -0,0 +1,230 @@
+use crate::cli::CliError;
+use crate::locate::format_size;
+use crate::output::Output;
+use crate::uri::UriMapping;
+use std::fs;
+use std::io::{Result as IOResult, Write};
+use std::os::unix::prelude::OsStrExt;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+// Column of the locate output selected with --fields.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) enum Field {
+    Path,
+    // File size in bytes.
+    Size,
+    // Modification time. Read from the file system, if the database file
+    // doesn't store it.
+    Mtime,
+    // Configured folder containing the result.
+    Volume,
+    // Path as URI, using the uri-prefix of the folder.
+    Uri,
+}
+
+impl FromStr for Field {
+    type Err = CliError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "path" => Ok(Field::Path),
+            "size" => Ok(Field::Size),
+            "mtime" => Ok(Field::Mtime),
+            "volume" => Ok(Field::Volume),
+            "uri" => Ok(Field::Uri),
+            _ => Err(CliError::InvalidField(s.to_string())),
+        }
+    }
+}
+
+pub(crate) fn parse_fields(text: &str) -> Result<Vec<Field>, CliError> {
+    text.split(',').map(str::parse).collect()
+}
+
+// Width of aligned columns. Paths are not aligned.
+const SIZE_WIDTH: usize = 17;
+const MTIME_WIDTH: usize = 20;
+
+// Prints the selected fields of results separated by tabs. Aligned columns
+// are separated by two spaces instead.
+#[derive(Clone)]
+pub(crate) struct Columns {
+    fields: Vec<Field>,
+    align: bool,
+    volumes: Vec<PathBuf>,
+    uris: UriMapping,
+}
+
+impl Columns {
+    pub(crate) fn new(
+        fields: Vec<Field>,
+        align: bool,
+        volumes: Vec<PathBuf>,
+        uris: UriMapping,
+    ) -> Columns {
+        Columns {
+            fields,
+            align,
+            volumes,
+            uris,
+        }
+    }
+
+    pub(crate) fn print(
+        &self,
+        stdout: &mut Output,
+        path: &Path,
+        size: Option<u64>,
+        modified: Option<SystemTime>,
+    ) -> IOResult<()> {
+        for (index, field) in self.fields.iter().enumerate() {
+            if index > 0 {
+                stdout.write_all(if self.align { b"  " } else { b"\t" })?;
+            }
+            let last = index + 1 == self.fields.len();
+            match field {
+                Field::Path => stdout.write_all(path.as_os_str().as_bytes())?,
+                Field::Uri => stdout.write_all(self.uris.uri(path).as_bytes())?,
+                Field::Size => {
+                    let text = match size {
+                        Some(size) if self.align => format_size(size),
+                        Some(size) => size.to_string(),
+                        None => String::from("-"),
+                    };
+                    self.write(stdout, &text, SIZE_WIDTH, true, last)?;
+                }
+                Field::Mtime => {
+                    let text = mtime(path, modified).unwrap_or_else(|| String::from("-"));
+                    self.write(stdout, &text, MTIME_WIDTH, false, last)?;
+                }
+                Field::Volume => {
+                    let volume = self.volume(path);
+                    let width = self
+                        .volumes
+                        .iter()
+                        .map(|volume| volume.as_os_str().len())
+                        .max()
+                        .unwrap_or(0);
+                    match volume {
+                        Some(volume) => {
+                            stdout.write_all(volume.as_os_str().as_bytes())?;
+                            self.write(stdout, "", width - volume.as_os_str().len(), false, last)?;
+                        }
+                        None => self.write(stdout, "-", width, false, last)?,
+                    }
+                }
+            }
+        }
+        stdout.end_line()
+    }
+
+    fn write(
+        &self,
+        stdout: &mut Output,
+        text: &str,
+        width: usize,
+        right: bool,
+        last: bool,
+    ) -> IOResult<()> {
+        match (self.align, right) {
+            (true, true) => write!(stdout, "{:>width$}", text, width = width),
+            (true, false) if !last => write!(stdout, "{:width$}", text, width = width),
+            _ => stdout.write_all(text.as_bytes()),
+        }
+    }
+
+    // The volume with the longest folder containing the path.
+    fn volume(&self, path: &Path) -> Option<&Path> {
+        self.volumes
+            .iter()
+            .filter(|volume| path.starts_with(volume))
+            .max_by_key(|volume| volume.as_os_str().len())
+            .map(|volume| volume.as_path())
+    }
+}
+
+// The modification time stored in the database file or read from the file
+// system.
+fn mtime(path: &Path, modified: Option<SystemTime>) -> Option<String> {
+    let modified = match modified {
+        Some(modified) => modified,
+        None => fs::symlink_metadata(path).ok()?.modified().ok()?,
+    };
+    let seconds = modified.duration_since(UNIX_EPOCH).ok()?.as_secs();
+    Some(format_timestamp(seconds))
+}
+
+// Formats seconds since the epoch as ISO 8601 date and time in UTC.
+pub(crate) fn format_timestamp(seconds: u64) -> String {
+    let days = (seconds / 86400) as i64;
+    let time = seconds % 86400;
+    let (year, month, day) = civil_from_days(days);
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+        year,
+        month,
+        day,
+        time / 3600,
+        time / 60 % 60,
+        time % 60
+    )
+}
+
+// Converts days since 1970-01-01 into year, month and day of the proleptic
+// Gregorian calendar.
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z.rem_euclid(146097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fields() {
+        assert_eq!(
+            parse_fields("path,size,mtime,volume,uri").unwrap(),
+            vec![
+                Field::Path,
+                Field::Size,
+                Field::Mtime,
+                Field::Volume,
+                Field::Uri
+            ]
+        );
+        assert!(matches!(
+            parse_fields("path,owner"),
+            Err(CliError::InvalidField(_))
+        ));
+        let columns = Columns::new(
+            vec![Field::Volume, Field::Path],
+            false,
+            vec![PathBuf::from("/Volumes"), PathBuf::from("/Volumes/Music")],
+            UriMapping::default(),
+        );
+        assert_eq!(
+            columns.volume(Path::new("/Volumes/Music/Help.flac")),
+            Some(Path::new("/Volumes/Music"))
+        );
+        assert_eq!(columns.volume(Path::new("/Music")), None);
+    }
+
+    #[test]
+    fn timestamps() {
+        assert_eq!(format_timestamp(0), "1970-01-01T00:00:00Z");
+        assert_eq!(format_timestamp(951782400), "2000-02-29T00:00:00Z");
+        assert_eq!(format_timestamp(1625054400), "2021-06-30T12:00:00Z");
+        assert_eq!(format_timestamp(1704067199), "2023-12-31T23:59:59Z");
+    }
+}
@@ -8,10 +8,24 @@ pub(crate) fn print_version() {}
 pub(crate) fn usage_cli() -> Result<(), CliError> {
     let usage = concat!(
         "Usage: fsidx [-h | -hh | -hhh | --help] [-v | --verbose] [-V | --version]\n",
-        "             [-c <path> | --config-file <path>] <command> [<args>]\n",
-        "       fsidx [<options>] update\n",
+        "             [-c <path> | --config-file <path>] [--format text|json] [--summary]\n",
+        "             <command> [<args>]\n",
+        "       fsidx [<options>] update [--error-log] [--json] [--reproducible]\n",
         "       fsidx [<options>] locate [<args>]\n",
-        "       fsidx [<options>] shell\n",
+        "       fsidx [<options>] report <report>\n",
+        "       fsidx [<options>] export [--format txt|locatedb]\n",
+        "       fsidx [<options>] export-tree [--format du|ncdu-json] <volume>\n",
+        "       fsidx [<options>] import <file> <folder>\n",
+        "       fsidx [<options>] warm\n",
+        "       fsidx [<options>] verify [--fast] [--portable]\n",
+        "       fsidx [<options>] explain-syntax\n",
+        "       fsidx [<options>] config show [--json]\n",
+        "       fsidx [<options>] config edit\n",
+        "       fsidx [<options>] config add-folder [--update] <folder>\n",
+        "       fsidx [<options>] config remove-folder <folder>\n",
+        "       fsidx [<options>] config aliases\n",
+        "       fsidx [<options>] shell [--private]\n",
+        "       fsidx [<options>] tui\n",
         "       fsidx [<options>] help\n",
     );
     pretty_print_usage(usage)
@@ -64,7 +78,30 @@ pub(crate) fn help_shell_long() -> Result<(), CliError> {
         "    \\o nnn.-mmm.        Open query result\n",
         "    \\o *.jpg            Open matching query results\n",
         "    \\o nnn./path/*.jpg  Open matching quey results\n",
+        "    \\cp rules dir       Copy selected query results into dir\n",
+        "    \\mv rules dir       Move selected query results into dir\n",
+        "    \\rm rules           Remove selected query results\n",
+        "    \\x rules -- cmd {}  Run cmd for each selected query result\n",
+        "    \\w file [--print0]  Write the query results into file\n",
+        "    \\y [--uri]          Copy the query results into the clipboard\n",
         "    \\u                  Scan folders and update database\n",
+        "    \\reload             Reload the configuration file\n",
+        "    \\warm               Read the database files into memory\n",
+        "    \\t                  Toggle truncation of long paths\n",
+        "    \\c [key value|reset] Print or change locate defaults for the session\n",
+        "    \\@name [terms]      Run the named query with additional terms\n",
+        "    \\@                  List the named queries\n",
+        "    \\hist               List recent queries with their result counts\n",
+        "    \\r [n]              Run the nth previous query again (default 1)\n",
+        "    \\again [terms]      Run the last query again with additional terms\n",
+        "    \\f terms            Filter the results of the last query with terms\n",
+        "    \\save-session file  Write queries and opened files into file\n",
+        "    \\undo               Restore the previous selection\n",
+        "    \\name [name]        Name the selection or list the named selections\n",
+        "    \\and|or|minus a b   Combine named selections without searching\n",
+        "    \\private on|off     Don't record queries and opened files\n",
+        "    \\page n             Print page n of the last query result\n",
+        "    \\o pnn:mmm.         Open result mmm. of page nn\n",
         "\n",
         "Options:\n",
         "    -c | --case-sensitive    Case-sensitive matching\n",
@@ -72,6 +109,11 @@ pub(crate) fn help_shell_long() -> Result<(), CliError> {
         "    -0 | --auto              Argument type is autodetected\n",
         "    -1 | --plain             Arguments are plain text\n",
         "    -2 | --glob              Arguments are glob pattern\n",
+        "    --min-size <size>        Files of at least size bytes, e.g. 10M\n",
+        "    --max-size <size>        Files of at most size bytes, e.g. 2G\n",
+        "    --type f|d|l             Files, directories or symbolic links\n",
+        "    --not <term>             Entries must not match the term\n",
+        "    --or                     Entries may match the terms before or after\n",
         "\n",
         "Options for plain text:\n",
         "    -a | --any-order         Plain text may match in any order (default)\n",
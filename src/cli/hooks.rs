@@ -0,0 +1,92 @@
+use crate::cli::CliError;
+use crate::config::{HookFailure, HooksConfig};
+use crate::event::{print_event, Message};
+use crate::verbosity::verbosity;
+use fsidx::Severity;
+use std::ffi::OsStr;
+use std::process::{Command, Stdio};
+
+#[derive(Clone, Copy)]
+pub(crate) enum Stage {
+    PreUpdate,
+    PostUpdate,
+}
+
+// Runs the hook command of the stage, if configured. Folder, database and
+// status are passed as environment variables and never substituted into the
+// command line. A failure is reported with the severity of the failure policy.
+// Returns false, if the update or the folder must be skipped.
+pub(crate) fn run_hook(
+    hooks: &HooksConfig,
+    stage: Stage,
+    env: &[(&str, &OsStr)],
+    severity: &mut Severity,
+) -> bool {
+    let command = match stage {
+        Stage::PreUpdate => &hooks.pre_update,
+        Stage::PostUpdate => &hooks.post_update,
+    };
+    let Some(command) = command else {
+        return true;
+    };
+    if verbosity() {
+        let message = format!("Running hook: {}", command);
+        print_event(Severity::Info, &Message::new("running-hook", message));
+    }
+    match execute(command, env) {
+        Ok(()) => true,
+        Err(err) => {
+            let (failure_severity, proceed) = match hooks.on_failure {
+                HookFailure::Abort => (Severity::Error, false),
+                HookFailure::Continue => (Severity::Warning, true),
+            };
+            *severity = (*severity).max(failure_severity);
+            print_event(failure_severity, &err);
+            proceed
+        }
+    }
+}
+
+fn execute(command: &str, env: &[(&str, &OsStr)]) -> Result<(), CliError> {
+    let status = Command::new("sh")
+        .arg("-c")
+        .arg(command)
+        .envs(env.iter().copied())
+        .stdin(Stdio::null())
+        .status()
+        .map_err(|err| CliError::HookFailed(command.to_string(), err))?;
+    if status.success() {
+        Ok(())
+    } else {
+        Err(CliError::HookExitStatus(command.to_string(), status))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hooks(command: &str, on_failure: HookFailure) -> HooksConfig {
+        HooksConfig {
+            pre_update: Some(command.to_string()),
+            post_update: None,
+            on_failure,
+        }
+    }
+
+    #[test]
+    fn hook_failure_policy() {
+        let mut severity = Severity::Info;
+        let env = [("FSIDX_FOLDER", OsStr::new("/tmp"))];
+        let ok = hooks("test \"$FSIDX_FOLDER\" = /tmp", HookFailure::Abort);
+        assert!(run_hook(&ok, Stage::PreUpdate, &env, &mut severity));
+        assert!(run_hook(&ok, Stage::PostUpdate, &env, &mut severity));
+        assert_eq!(severity, Severity::Info);
+        let failing = hooks("exit 3", HookFailure::Continue);
+        assert!(run_hook(&failing, Stage::PreUpdate, &env, &mut severity));
+        assert_eq!(severity, Severity::Warning);
+        let failing = hooks("exit 3", HookFailure::Abort);
+        assert!(!run_hook(&failing, Stage::PreUpdate, &env, &mut severity));
+        assert_eq!(severity, Severity::Error);
+    }
+}
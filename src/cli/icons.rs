@@ -0,0 +1,47 @@
+use std::path::Path;
+
+const AUDIO: &[&str] = &[
+    "aac", "aif", "aiff", "alac", "ape", "dsf", "flac", "m4a", "mp3", "ogg", "opus", "wav", "wma",
+];
+const VIDEO: &[&str] = &[
+    "avi", "flv", "m2ts", "m4v", "mkv", "mov", "mp4", "mpeg", "mpg", "ts", "webm", "wmv",
+];
+const IMAGE: &[&str] = &[
+    "bmp", "cr2", "dng", "gif", "heic", "jpeg", "jpg", "nef", "png", "raw", "svg", "tif", "tiff",
+    "webp",
+];
+const ARCHIVE: &[&str] = &[
+    "7z", "bz2", "dmg", "gz", "iso", "rar", "tar", "tgz", "xz", "zip", "zst",
+];
+
+// Returns a glyph for the file type derived from the file extension. Paths
+// without a known extension get the folder glyph, if they are a directory on
+// a mounted volume.
+pub(crate) fn icon(path: &Path) -> Option<&'static str> {
+    let extension = path
+        .extension()
+        .map(|extension| extension.to_string_lossy().to_lowercase());
+    match extension.as_deref() {
+        Some(ext) if AUDIO.contains(&ext) => Some("🎵"),
+        Some(ext) if VIDEO.contains(&ext) => Some("🎬"),
+        Some(ext) if IMAGE.contains(&ext) => Some("📷"),
+        Some(ext) if ARCHIVE.contains(&ext) => Some("📦"),
+        _ if path.is_dir() => Some("📁"),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn icon_by_extension() {
+        assert_eq!(icon(Path::new("/Music/Song.FLAC")), Some("🎵"));
+        assert_eq!(icon(Path::new("/Movies/Film.mkv")), Some("🎬"));
+        assert_eq!(icon(Path::new("/Photos/IMG_0001.jpg")), Some("📷"));
+        assert_eq!(icon(Path::new("/Backup/home.tar")), Some("📦"));
+        assert_eq!(icon(Path::new("/")), Some("📁"));
+        assert_eq!(icon(Path::new("/nonexistent/README")), None);
+    }
+}
@@ -0,0 +1,132 @@
+use crate::cli::CliError;
+use crate::config::{get_volume_info, Config};
+use crate::event::{print_event, Message};
+use crate::storage::write_file;
+use fsidx::{DbWriter, Metadata, Settings, Severity};
+use std::env::Args;
+use std::ffi::OsStr;
+use std::fs;
+use std::io::{stdin, Read};
+use std::os::unix::ffi::OsStrExt;
+use std::path::{Component, Path, PathBuf};
+
+// Replaces the database of a configured folder with a list of paths, e.g.
+// from find on a host without fsidx. The list is read from stdin for "-".
+pub(crate) fn import_cli(config: &Config, args: &mut Args) -> Result<(), CliError> {
+    // Arguments aren't tokenized, since "-" is a file.
+    let file = PathBuf::from(args.next().ok_or(CliError::MissingPathList)?);
+    let folder = PathBuf::from(args.next().ok_or(CliError::MissingVolume)?);
+    if let Some(arg) = args.next() {
+        return Err(CliError::InvalidImportArgument(arg));
+    }
+    let volume_info = get_volume_info(config).ok_or(CliError::NoDatabasePath)?;
+    let vi = volume_info
+        .iter()
+        .find(|vi| vi.folder == folder)
+        .ok_or_else(|| CliError::UnknownVolume(folder.clone()))?;
+    // Importing into a shared database would remove the other folders.
+    if volume_info
+        .iter()
+        .any(|other| other.database == vi.database && other.folder != vi.folder)
+    {
+        return Err(CliError::ImportIntoSharedDatabase(folder));
+    }
+    let content =
+        read_path_list(&file).map_err(|err| CliError::ReadingPathListFailed(file.clone(), err))?;
+    let (paths, outside) = parse_path_list(&content, &folder);
+    if outside > 0 {
+        let message = format!(
+            "Skipped {} paths outside of '{}'",
+            outside,
+            folder.display()
+        );
+        print_event(
+            Severity::Warning,
+            &Message::new("path-outside-folder", message).with_volume(&folder),
+        );
+    }
+    if let Some(parent) = vi.database.parent() {
+        fs::create_dir_all(parent)
+            .map_err(|err| CliError::WritingDatabaseFailed(vi.database.clone(), err))?;
+    }
+    // Sizes and types are unknown.
+    let metadata = Metadata::default();
+    write_file(&vi.database, |writer| {
+        let mut writer = DbWriter::new(writer, Settings::FileNamesOnly)?;
+        for path in &paths {
+            writer.add_entry(path, &metadata)?;
+        }
+        writer.finish()?;
+        Ok(())
+    })
+    .map_err(|err| CliError::WritingDatabaseFailed(vi.database.clone(), err))?;
+    let message = format!("Imported {} paths into '{}'", paths.len(), folder.display());
+    print_event(
+        Severity::Info,
+        &Message::new("imported", message).with_volume(&folder),
+    );
+    Ok(())
+}
+
+fn read_path_list(file: &Path) -> std::io::Result<Vec<u8>> {
+    if file == Path::new("-") {
+        let mut content = Vec::new();
+        stdin().lock().read_to_end(&mut content)?;
+        Ok(content)
+    } else {
+        fs::read(file)
+    }
+}
+
+// Paths are separated by NUL, e.g. from find -print0, or else by newlines.
+// Relative paths are relative to the folder, e.g. from find . in the folder.
+// Returns the paths in database order and the number of skipped paths
+// outside of the folder.
+fn parse_path_list(content: &[u8], folder: &Path) -> (Vec<PathBuf>, usize) {
+    let separator = if content.contains(&0) { 0 } else { b'\n' };
+    let mut outside = 0;
+    let mut paths = Vec::new();
+    for line in content.split(|byte| *byte == separator) {
+        if line.is_empty() {
+            continue;
+        }
+        // Components of the joined path drop "." and trailing slashes.
+        let path: PathBuf = folder.join(OsStr::from_bytes(line)).components().collect();
+        let parent_dir = path.components().any(|c| c == Component::ParentDir);
+        if path.starts_with(folder) && !parent_dir {
+            paths.push(path);
+        } else {
+            outside += 1;
+        }
+    }
+    paths.sort_by(|a, b| fsidx::compare_paths(a, b));
+    paths.dedup();
+    (paths, outside)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn path_list() {
+        let folder = Path::new("/data");
+        let (paths, outside) = parse_path_list(b".\0./b\0./a10\0/data/a2/\0/other\0../x\0", folder);
+        assert_eq!(
+            paths,
+            vec![
+                PathBuf::from("/data"),
+                PathBuf::from("/data/a2"),
+                PathBuf::from("/data/a10"),
+                PathBuf::from("/data/b"),
+            ]
+        );
+        assert_eq!(outside, 2);
+        let (paths, outside) = parse_path_list(b"/data\n/data/x y\n\n", folder);
+        assert_eq!(
+            paths,
+            vec![PathBuf::from("/data"), PathBuf::from("/data/x y")]
+        );
+        assert_eq!(outside, 0);
+    }
+}
@@ -1,144 +1,1263 @@
+use crate::buffer::{self, print_budget_warning, MemoryBudget};
 use crate::cli::CliError;
-use crate::config::{get_volume_info, Config};
-use crate::tokenizer::{tokenize_cli, tokenize_shell, Token};
+use crate::config::{get_offline_folders, get_volume_info, Config};
+use crate::event::{print_event, Message};
+use crate::export::json_string;
+use crate::fields::{parse_fields, Columns, Field};
+use crate::icons::icon;
+use crate::output::{Output, PipeWatcher};
+use crate::sort::{ExternalSort, SortKey};
+use crate::stats::ResourceUsage;
+use crate::summary::{ExitSummary, Summary};
+use crate::tokenizer::{expand_aliases, tokenize_cli, tokenize_shell, Token};
+use crate::tty::terminal_width;
+use crate::uri::UriMapping;
 use crate::verbosity::verbosity;
-use fsidx::{FilterToken, LocateEvent, Metadata};
-use std::env::Args;
-use std::io::{Result as IOResult, Write};
+use fsidx::{
+    BroadQuery, BroadQueryAction, CompiledFilter, DatabaseCache, EntryType, FilterToken,
+    HeaderCheck, LocateEvent, LocatePool, MetaPredicate, Metadata, ProviderRegistry, Severity,
+    VolumeInfo,
+};
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::env::{self, Args};
+use std::fs;
+use std::io::{self, Error as IOError, ErrorKind, Result as IOResult, Write};
 use std::os::unix::prelude::OsStrExt;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
 use std::sync::atomic::AtomicBool;
 use std::sync::Arc;
-use termcolor::{Color, ColorChoice, ColorSpec, StandardStream, WriteColor};
+use termcolor::{Color, ColorSpec, WriteColor};
 
 pub(crate) fn locate_cli(config: &Config, args: &mut Args) -> Result<(), CliError> {
-    let mut stdout = StandardStream::stdout(ColorChoice::Auto);
-    let token = tokenize_cli(args)?;
-    let filter_token = locate_filter(token)?;
-    locate_impl(config, filter_token, None, |res| {
-        print_locate_result(&mut stdout, &res)
-    })?;
-    Ok(())
+    let usage = ResourceUsage::start();
+    let mut exit_summary = ExitSummary::locate();
+    let abort = Arc::new(AtomicBool::new(false));
+    let watcher = PipeWatcher::watch(abort.clone());
+    let res = match locate_run(config, args, abort, &mut exit_summary) {
+        // No error for: fsidx | head -n 5
+        Err(CliError::LocateError(fsidx::LocateError::Aborted)) if watcher.closed() => Ok(()),
+        res => res,
+    };
+    if res.is_err() {
+        exit_summary.add_errors(1);
+    }
+    exit_summary.print();
+    ResourceUsage::print(usage, "Query");
+    res
+}
+
+fn locate_run(
+    config: &Config,
+    args: &mut Args,
+    abort: Arc<AtomicBool>,
+    exit_summary: &mut ExitSummary,
+) -> Result<(), CliError> {
+    let mut stdout = Output::stdout().with_write_timeout(config.output.write_timeout_ms);
+    let token = expand_aliases(tokenize_cli(args)?, &config.aliases)?;
+    let (token, options) = locate_options(token)?;
+    let config = &with_query_options(config, &options);
+    let filter_token = locate_filter(token.clone())?;
+    if options.queries_file.is_none() {
+        check_broad_query(config, &filter_token, false)?;
+    }
+    let checks = check_databases(config)?;
+    exit_summary.add_errors(checks.count());
+    let mut format = Format::new(config);
+    if options.json {
+        // JSON objects replace the other output formats.
+        if options.fields.is_some() || options.format.is_some() {
+            return Err(CliError::InvalidLocateFilterOption(String::from("json")));
+        }
+        format.json = true;
+    }
+    if options.print0 {
+        // Only the path is printed.
+        if options.fields.is_some() || options.format.is_some() || options.json {
+            return Err(CliError::InvalidLocateFilterOption(String::from("print0")));
+        }
+        format.print0 = true;
+    }
+    let fields = match options.format {
+        Some(ResultFormat::Uri) => Some(vec![Field::Uri]),
+        _ => options.fields,
+    };
+    if let Some(fields) = fields {
+        let volumes = get_volume_info(config)
+            .ok_or(CliError::NoDatabasePath)?
+            .into_iter()
+            .map(|vi| vi.folder)
+            .collect();
+        format.columns = Some(Columns::new(
+            fields,
+            options.align,
+            volumes,
+            UriMapping::new(config),
+        ));
+    }
+    let mut summary = options.summary.then(|| Summary::new(config));
+    if let Some(queries_file) = &options.queries_file {
+        if options.sort.is_some() {
+            return Err(CliError::InvalidLocateFilterOption(String::from("sort")));
+        }
+        let (lines, queries) = read_queries(config, queries_file, &token)?;
+        locate_batch_impl(
+            &with_window(config, options.offset, options.limit),
+            &checks,
+            queries,
+            Some(abort),
+            |res| {
+                exit_summary.add_locate_event(&res);
+                let LocateEvent::BatchEntry(index, path, metadata) = res else {
+                    return print_locate_result(&mut stdout, &res, &format);
+                };
+                if let Some(summary) = summary.as_mut() {
+                    summary.add(path, metadata.size);
+                }
+                if format.json {
+                    return print_json_entry(
+                        &mut stdout,
+                        path,
+                        metadata,
+                        Some(lines[index]),
+                        &format,
+                    );
+                }
+                // Results are tagged with the line number of the query.
+                let tag = format!("{}\t", lines[index]);
+                stdout.write_all(tag.as_bytes())?;
+                let format = format.clone().shrink(tag.len());
+                print_locate_result(&mut stdout, &LocateEvent::Entry(path, metadata), &format)
+            },
+        )?;
+        return print_summary(config, summary);
+    }
+    let Some(sort_key) = options.sort else {
+        let config = &with_window(config, options.offset, options.limit);
+        locate_impl(
+            config,
+            &checks,
+            filter_token,
+            Some(abort),
+            None,
+            None,
+            |res| {
+                exit_summary.add_locate_event(&res);
+                if let (Some(summary), LocateEvent::Entry(path, metadata)) =
+                    (summary.as_mut(), &res)
+                {
+                    summary.add(path, metadata.size);
+                }
+                print_locate_result(&mut stdout, &res, &format)
+            },
+        )?;
+        return print_summary(config, summary);
+    };
+    // Temporary files are written into the folder with the database files.
+    let dir = config.index.db_path.clone().unwrap_or_else(env::temp_dir);
+    let mut sort = ExternalSort::new(sort_key, &dir, buffer::limit(config));
+    let mut sort_error = None;
+    let res = locate_impl(
+        config,
+        &checks,
+        filter_token,
+        Some(abort),
+        None,
+        None,
+        |res| {
+            exit_summary.add_locate_event(&res);
+            if let LocateEvent::Entry(path, metadata) = res {
+                if let Some(summary) = summary.as_mut() {
+                    summary.add(path, metadata.size);
+                }
+                if let Err(err) = sort.push(path, metadata.size) {
+                    sort_error = Some(err);
+                    return Err(IOError::from(ErrorKind::Other));
+                }
+                return Ok(());
+            }
+            print_locate_result(&mut stdout, &res, &format)
+        },
+    );
+    if let Some(err) = sort_error {
+        return Err(err);
+    }
+    res?;
+    // Offset and limit apply to the sorted results.
+    let mut skip = options.offset;
+    let mut remaining = options.limit.unwrap_or(u64::MAX);
+    let res = sort.finish(|path, size| {
+        if skip > 0 {
+            skip -= 1;
+            return Ok(());
+        }
+        if remaining == 0 {
+            return Ok(());
+        }
+        remaining -= 1;
+        let mut metadata = Metadata::default();
+        metadata.size = size;
+        print_locate_result(&mut stdout, &LocateEvent::Entry(path, &metadata), &format)
+    });
+    match res {
+        Err(CliError::StdoutWriteFailed(err)) if err.kind() == ErrorKind::BrokenPipe => Ok(()),
+        res => res.and_then(|_| print_summary(config, summary)),
+    }
+}
+
+// Options controlling the output of a query instead of filtering.
+#[derive(Default)]
+struct LocateOptions {
+    sort: Option<SortKey>,
+    summary: bool,
+    fields: Option<Vec<Field>>,
+    align: bool,
+    format: Option<ResultFormat>,
+    queries_file: Option<PathBuf>,
+    json: bool,
+    print0: bool,
+    offset: u64,
+    limit: Option<u64>,
+    max_entries_scanned: Option<u64>,
+    ordered: bool,
+}
+
+// Selected with --format. Uri is the same as --fields uri.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum ResultFormat {
+    Path,
+    Uri,
+}
+
+impl FromStr for ResultFormat {
+    type Err = CliError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "path" => Ok(ResultFormat::Path),
+            "uri" => Ok(ResultFormat::Uri),
+            _ => Err(CliError::InvalidResultFormat(s.to_string())),
+        }
+    }
+}
+
+// Removes the output options from a query.
+fn locate_options(token: Vec<Token>) -> Result<(Vec<Token>, LocateOptions), CliError> {
+    let mut options = LocateOptions::default();
+    let mut query = Vec::new();
+    let mut token = token.into_iter();
+    while let Some(tk) = token.next() {
+        match tk {
+            Token::Option(option) if option == "sort" => match token.next() {
+                Some(Token::Text(key)) => options.sort = Some(key.parse()?),
+                _ => return Err(CliError::MissingOptionValue(option)),
+            },
+            Token::Option(option) if option == "summary" => options.summary = true,
+            Token::Option(option) if option == "fields" => match token.next() {
+                Some(Token::Text(fields)) => options.fields = Some(parse_fields(&fields)?),
+                _ => return Err(CliError::MissingOptionValue(option)),
+            },
+            Token::Option(option) if option == "align" => options.align = true,
+            Token::Option(option) if option == "queries-file" => match token.next() {
+                Some(Token::Text(path)) => options.queries_file = Some(PathBuf::from(path)),
+                _ => return Err(CliError::MissingOptionValue(option)),
+            },
+            Token::Option(option) if option == "format" => match token.next() {
+                Some(Token::Text(format)) => options.format = Some(format.parse()?),
+                _ => return Err(CliError::MissingOptionValue(option)),
+            },
+            Token::Option(option) if option == "json" => options.json = true,
+            Token::Option(option) if option == "print0" => options.print0 = true,
+            Token::Option(option) if option == "offset" => match token.next() {
+                Some(Token::Text(offset)) => options.offset = parse_count(&offset)?,
+                _ => return Err(CliError::MissingOptionValue(option)),
+            },
+            Token::Option(option) if option == "limit" => match token.next() {
+                Some(Token::Text(limit)) => options.limit = Some(parse_count(&limit)?),
+                _ => return Err(CliError::MissingOptionValue(option)),
+            },
+            Token::Option(option) if option == "max-entries-scanned" => match token.next() {
+                Some(Token::Text(max)) => options.max_entries_scanned = Some(parse_count(&max)?),
+                _ => return Err(CliError::MissingOptionValue(option)),
+            },
+            Token::Option(option) if option == "ordered" => options.ordered = true,
+            tk => query.push(tk),
+        }
+    }
+    Ok((query, options))
+}
+
+fn parse_count(text: &str) -> Result<u64, CliError> {
+    text.parse()
+        .map_err(|_| CliError::InvalidNumber(text.to_string()))
+}
+
+// Returns the configuration with offset and limit of the query. The search
+// stops when the limit is reached.
+fn with_window(config: &Config, offset: u64, limit: Option<u64>) -> Config {
+    let mut config = config.clone();
+    config.locate.offset = offset;
+    config.locate.limit = limit;
+    config
+}
+
+// Warns about a query matching all or most entries. Returns whether the query
+// is run. Only the shell asks for confirmation.
+fn check_broad_query(
+    config: &Config,
+    filter_token: &[FilterToken],
+    interactive: bool,
+) -> Result<bool, CliError> {
+    let action = config.locate.broad_query;
+    if action == BroadQueryAction::Allow {
+        return Ok(true);
+    }
+    let Some(broad) = fsidx::broad_query(filter_token, &config.locate) else {
+        return Ok(true);
+    };
+    if broad == BroadQuery::OptionsOnly {
+        let message = format!("{}. Nothing is searched.", broad);
+        print_event(Severity::Warning, &Message::new("broad-query", message));
+        return Ok(true);
+    }
+    if interactive && action == BroadQueryAction::Confirm {
+        return confirm(&format!("{}. Run it anyway? [y/N] ", broad));
+    }
+    let message = format!("{}.", broad);
+    print_event(Severity::Warning, &Message::new("broad-query", message));
+    Ok(true)
+}
+
+// The shell disables ECHO, i.e. the answer is printed after reading it.
+pub(crate) fn confirm(prompt: &str) -> Result<bool, CliError> {
+    print!("{}", prompt);
+    io::stdout().flush()?;
+    let mut answer = String::new();
+    io::stdin().read_line(&mut answer)?;
+    println!("{}", answer.trim());
+    Ok(matches!(answer.trim(), "y" | "Y" | "yes"))
+}
+
+// --max-entries-scanned and --ordered override the defaults of the
+// configuration file.
+fn with_query_options(config: &Config, options: &LocateOptions) -> Config {
+    let mut config = config.clone();
+    if options.max_entries_scanned.is_some() {
+        config.locate.max_entries_scanned = options.max_entries_scanned;
+    }
+    config.locate.ordered |= options.ordered;
+    config
+}
+
+// Reads one query per line. Empty lines and lines starting with # are
+// skipped. The tokens of the command line are prepended to every query.
+// Returns the line numbers and the queries.
+fn read_queries(
+    config: &Config,
+    path: &Path,
+    common: &[Token],
+) -> Result<(Vec<usize>, Vec<Vec<FilterToken>>), CliError> {
+    let content = fs::read_to_string(path)
+        .map_err(|err| CliError::ReadingQueriesFailed(path.to_owned(), err))?;
+    let mut lines = Vec::new();
+    let mut queries = Vec::new();
+    for (number, line) in content.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let mut token = common.to_vec();
+        token.extend(expand_aliases(tokenize_shell(line)?, &config.aliases)?);
+        lines.push(number + 1);
+        queries.push(locate_filter(token)?);
+    }
+    Ok((lines, queries))
+}
+
+fn print_summary(config: &Config, summary: Option<Summary>) -> Result<(), CliError> {
+    match summary.map(|summary| summary.print(config)) {
+        Some(Err(err)) if err.kind() != ErrorKind::BrokenPipe => Err(err.into()),
+        _ => Ok(()),
+    }
+}
+
+// Header checks of the database files by path. The shell keeps them until
+// the configuration is reloaded or the folders are updated, i.e. queries don't
+// read the headers again.
+#[derive(Default)]
+pub(crate) struct HeaderChecks {
+    checks: HashMap<PathBuf, HeaderCheck>,
+    skipped: u64,
+}
+
+impl HeaderChecks {
+    // Returns the check of a database file, which is missing or invalid.
+    // Database files written by the first update are searched, i.e. queries
+    // wait for them. Database files which weren't checked are searched.
+    pub(crate) fn skipped(&self, database: &Path) -> Option<&HeaderCheck> {
+        self.checks
+            .get(database)
+            .filter(|check| matches!(check, HeaderCheck::Missing | HeaderCheck::Invalid(_)))
+    }
+
+    // Number of skipped folders.
+    pub(crate) fn count(&self) -> u64 {
+        self.skipped
+    }
+}
+
+// Prints a warning for each folder, which isn't searched or has results from
+// an unmounted volume.
+pub(crate) fn check_databases(config: &Config) -> Result<HeaderChecks, CliError> {
+    let volume_info = get_volume_info(config).ok_or(CliError::NoDatabasePath)?;
+    let mut checks = HeaderChecks::default();
+    for vi in volume_info {
+        // Folders of a group share the database file.
+        let check = checks
+            .checks
+            .entry(vi.database.clone())
+            .or_insert_with(|| fsidx::check_header(&vi.database));
+        match check {
+            HeaderCheck::Ok(_) => {
+                // Results of an unmounted volume are still reported.
+                if let Some(id) = vi.volume_id.filter(|id| id.mount_point().is_none()) {
+                    let message = format!(
+                        "Volume of '{}' ({}) is not mounted. Results are from the last update.",
+                        vi.folder.display(),
+                        id
+                    );
+                    print_event(
+                        Severity::Warning,
+                        &Message::new("volume-not-mounted", message).with_volume(&vi.folder),
+                    );
+                }
+            }
+            HeaderCheck::Missing => {
+                let message = format!(
+                    "Database for '{}' is missing. Run update first.",
+                    vi.folder.display()
+                );
+                print_event(
+                    Severity::Warning,
+                    &Message::new("database-missing", message).with_volume(&vi.folder),
+                );
+                checks.skipped += 1;
+            }
+            HeaderCheck::Updating => {
+                let message = format!(
+                    "Database for '{}' is written by the first update. Queries wait for it.",
+                    vi.folder.display()
+                );
+                print_event(
+                    Severity::Warning,
+                    &Message::new("database-updating", message).with_volume(&vi.folder),
+                );
+            }
+            HeaderCheck::Invalid(err) => {
+                let message = format!("Database for '{}' is skipped: {}", vi.folder.display(), err);
+                print_event(
+                    Severity::Warning,
+                    &Message::new("database-skipped", message).with_volume(&vi.folder),
+                );
+                checks.skipped += 1;
+            }
+            // Searched like a valid database file.
+            _ => {}
+        }
+    }
+    Ok(checks)
 }
 
 pub(crate) fn locate_shell(
     config: &Config,
-    line: &str,
+    checks: &HeaderChecks,
+    token: Vec<Token>,
     abort: Option<Arc<AtomicBool>>,
-) -> Result<Vec<PathBuf>, CliError> {
-    let mut stdout = StandardStream::stdout(ColorChoice::Auto);
+    cache: Option<&mut DatabaseCache>,
+    pool: Option<&LocatePool>,
+) -> Result<(Vec<PathBuf>, bool), CliError> {
+    let mut stdout = Output::stdout();
     let mut selection = Vec::new();
-    let token = tokenize_shell(line)?;
+    let mut budget = MemoryBudget::new(config);
+    let (token, options) = locate_options(token)?;
+    let config = &with_query_options(config, &options);
+    if options.sort.is_some() {
+        return Err(CliError::InvalidLocateFilterOption(String::from("sort")));
+    }
+    if options.fields.is_some() || options.align {
+        return Err(CliError::InvalidLocateFilterOption(String::from("fields")));
+    }
+    if options.format.is_some() {
+        return Err(CliError::InvalidLocateFilterOption(String::from("format")));
+    }
+    if options.json {
+        return Err(CliError::InvalidLocateFilterOption(String::from("json")));
+    }
+    if options.print0 {
+        return Err(CliError::InvalidLocateFilterOption(String::from("print0")));
+    }
+    if options.queries_file.is_some() {
+        return Err(CliError::InvalidLocateFilterOption(String::from(
+            "queries-file",
+        )));
+    }
+    let mut summary = options.summary.then(|| Summary::new(config));
     let filter_token = locate_filter(token)?;
-    locate_impl(config, filter_token, abort, |res| {
-        if let LocateEvent::Entry(path, _) = res {
-            let pb = path.to_path_buf();
-            selection.push(pb);
-            let index = selection.len();
-            stdout.set_color(ColorSpec::new().set_fg(Some(Color::Green)))?;
-            stdout.write_fmt(format_args!("{}. ", index))?;
-            stdout.set_color(&ColorSpec::new())?;
+    if !check_broad_query(config, &filter_token, true)? {
+        return Ok((selection, false));
+    }
+    let format = Format::new(config);
+    let res = locate_impl(
+        &with_window(config, options.offset, options.limit),
+        checks,
+        filter_token,
+        abort,
+        cache,
+        pool,
+        |res| {
+            let mut format = format.clone();
+            // Results exceeding the memory budget are printed without index.
+            if let LocateEvent::Entry(path, metadata) = res {
+                if let Some(summary) = summary.as_mut() {
+                    summary.add(path, metadata.size);
+                }
+                if budget.reserve_path(path) {
+                    selection.push(path.to_path_buf());
+                    let index = format!("{}. ", selection.len());
+                    format = format.shrink(index.len());
+                    stdout.set_color(ColorSpec::new().set_fg(Some(Color::Green)))?;
+                    stdout.write_all(index.as_bytes())?;
+                    stdout.set_color(&ColorSpec::new())?;
+                }
+            }
+            print_locate_result(&mut stdout, &res, &format)
+        },
+    );
+    if budget.exceeded() {
+        let message = format!("Only the first {} results can be opened.", selection.len());
+        print_budget_warning(config, &message);
+    }
+    // The results found before an abort are kept as partial selection.
+    let partial = match res {
+        Err(CliError::LocateError(fsidx::LocateError::Aborted)) => true,
+        res => {
+            res?;
+            false
         }
-        print_locate_result(&mut stdout, &res)
-    })?;
-    Ok(selection)
+    };
+    print_summary(config, summary)?;
+    Ok((selection, partial))
+}
+
+// Applies a query to the paths of the selection instead of the database
+// files. The selection keeps no metadata, i.e. sizes and types are read from
+// the file system for size and type filters. Paths of volumes, which are not
+// mounted, don't match them.
+pub(crate) fn filter_selection(
+    config: &Config,
+    token: Vec<Token>,
+    paths: &[PathBuf],
+) -> Result<Vec<PathBuf>, CliError> {
+    let filter_token = locate_filter(token)?;
+    if filter_token.iter().any(is_meta_filter) {
+        return Err(CliError::InvalidLocateFilterOption(String::from("meta")));
+    }
+    let filter =
+        CompiledFilter::compile(&filter_token, &config.locate).map_err(CliError::LocateError)?;
+    let with_metadata = filter_token.iter().any(is_file_filter);
+    let paths: Vec<PathBuf> = paths
+        .iter()
+        .filter(|path| {
+            let metadata = if with_metadata {
+                file_metadata(path)
+            } else {
+                Metadata::default()
+            };
+            filter.apply(path, &metadata)
+        })
+        .cloned()
+        .collect();
+    print_selection(config, &paths, 1)?;
+    Ok(paths)
+}
+
+// Metadata of enrichment providers is only stored in the database files.
+fn is_meta_filter(token: &FilterToken) -> bool {
+    match token {
+        FilterToken::Meta(_) => true,
+        FilterToken::Not(token) => is_meta_filter(token),
+        _ => false,
+    }
+}
+
+fn is_file_filter(token: &FilterToken) -> bool {
+    match token {
+        FilterToken::MinSize(_) | FilterToken::MaxSize(_) | FilterToken::EntryType(_) => true,
+        FilterToken::Not(token) => is_file_filter(token),
+        _ => false,
+    }
+}
+
+fn file_metadata(path: &Path) -> Metadata {
+    match fs::symlink_metadata(path) {
+        Ok(metadata) => {
+            let file_type = metadata.file_type();
+            let entry_type = if file_type.is_file() {
+                EntryType::File
+            } else if file_type.is_dir() {
+                EntryType::Directory
+            } else if file_type.is_symlink() {
+                EntryType::Symlink
+            } else {
+                EntryType::Other
+            };
+            let mut result = Metadata::default();
+            result.size = Some(metadata.len());
+            result.modified = metadata.modified().ok();
+            result.entry_type = Some(entry_type);
+            result
+        }
+        Err(_) => Metadata::default(),
+    }
+}
+
+// Prints the paths of a selection with their index. The first path has the
+// index first.
+pub(crate) fn print_selection(
+    config: &Config,
+    selection: &[PathBuf],
+    first: usize,
+) -> Result<(), CliError> {
+    let mut stdout = Output::stdout();
+    let format = Format::new(config);
+    for (index, path) in selection.iter().enumerate() {
+        let index = format!("{}. ", first + index);
+        stdout.set_color(ColorSpec::new().set_fg(Some(Color::Green)))?;
+        stdout.write_all(index.as_bytes())?;
+        stdout.set_color(&ColorSpec::new())?;
+        let format = format.clone().shrink(index.len());
+        print_path_annotated(&mut stdout, path, &format)?;
+        stdout.end_line()?;
+    }
+    Ok(())
+}
+
+#[cfg(feature = "tui")]
+pub(crate) type Results = Vec<(PathBuf, Option<u64>)>;
+
+// Collects up to limit results of a query. Returns true as second value, if
+// the query was stopped at the limit.
+#[cfg(feature = "tui")]
+pub(crate) fn locate_collect(
+    config: &Config,
+    checks: &HeaderChecks,
+    token: Vec<Token>,
+    limit: usize,
+) -> Result<(Results, bool), CliError> {
+    let abort = Arc::new(AtomicBool::new(false));
+    let mut results = Vec::new();
+    let mut budget = MemoryBudget::new(config);
+    let filter_token = locate_filter(token)?;
+    let res = locate_impl(
+        config,
+        checks,
+        filter_token,
+        Some(abort.clone()),
+        None,
+        None,
+        |res| {
+            if let LocateEvent::Entry(path, metadata) = res {
+                if !budget.reserve_path(path) {
+                    abort.store(true, std::sync::atomic::Ordering::Relaxed);
+                    return Ok(());
+                }
+                results.push((path.to_path_buf(), metadata.size));
+                if results.len() >= limit {
+                    abort.store(true, std::sync::atomic::Ordering::Relaxed);
+                }
+            }
+            Ok(())
+        },
+    );
+    match res {
+        Ok(()) => Ok((results, false)),
+        Err(CliError::LocateError(fsidx::LocateError::Aborted)) => Ok((results, true)),
+        Err(err) => Err(err),
+    }
 }
 
 fn locate_impl<F: FnMut(LocateEvent) -> IOResult<()>>(
     config: &Config,
+    checks: &HeaderChecks,
     filter_token: Vec<FilterToken>,
     abort: Option<Arc<AtomicBool>>,
-    f: F,
+    cache: Option<&mut DatabaseCache>,
+    pool: Option<&LocatePool>,
+    mut f: F,
 ) -> Result<(), CliError> {
-    let volume_info = get_volume_info(config).ok_or(CliError::NoDatabasePath)?;
-    match fsidx::locate(volume_info, filter_token, &config.locate, abort, f) {
+    let volume_info = match report_skipped(config, checks, &mut f) {
+        Err(CliError::LocateError(fsidx::LocateError::BrokenPipe)) => return Ok(()),
+        res => res?,
+    };
+    // Cached database files are searched serially.
+    let res = match (cache, pool) {
+        (Some(cache), _) => {
+            fsidx::locate_cached(cache, volume_info, filter_token, &config.locate, abort, f)
+        }
+        (None, Some(pool)) => {
+            fsidx::locate_pooled(pool, volume_info, filter_token, &config.locate, abort, f)
+        }
+        (None, None) => fsidx::locate(volume_info, filter_token, &config.locate, abort, f),
+    };
+    locate_result(res)
+}
+
+fn locate_batch_impl<F: FnMut(LocateEvent) -> IOResult<()>>(
+    config: &Config,
+    checks: &HeaderChecks,
+    queries: Vec<Vec<FilterToken>>,
+    abort: Option<Arc<AtomicBool>>,
+    mut f: F,
+) -> Result<(), CliError> {
+    let volume_info = match report_skipped(config, checks, &mut f) {
+        Err(CliError::LocateError(fsidx::LocateError::BrokenPipe)) => return Ok(()),
+        res => res?,
+    };
+    locate_result(fsidx::locate_batch(
+        volume_info,
+        queries,
+        &config.locate,
+        abort,
+        f,
+    ))
+}
+
+fn locate_result(res: Result<(), fsidx::LocateError>) -> Result<(), CliError> {
+    match res {
         Ok(_) => Ok(()),
         Err(fsidx::LocateError::BrokenPipe) => Ok(()), // No error for: fsidx | head -n 5
         Err(err) => Err(CliError::LocateError(err)),
     }
 }
 
-fn locate_filter(token: Vec<Token>) -> Result<Vec<FilterToken>, CliError> {
+// Databases failing the header check are reported by check_databases.
+pub(crate) fn searchable_volumes(
+    config: &Config,
+    checks: &HeaderChecks,
+) -> Result<Vec<VolumeInfo>, CliError> {
+    Ok(get_volume_info(config)
+        .ok_or(CliError::NoDatabasePath)?
+        .into_iter()
+        .filter(|vi| checks.skipped(&vi.database).is_none())
+        .collect())
+}
+
+// Reports the skipped folders to the callback and returns the searched ones.
+fn report_skipped<F: FnMut(LocateEvent) -> IOResult<()>>(
+    config: &Config,
+    checks: &HeaderChecks,
+    f: &mut F,
+) -> Result<Vec<VolumeInfo>, CliError> {
+    let mut volume_info = Vec::new();
+    for vi in get_volume_info(config).ok_or(CliError::NoDatabasePath)? {
+        match checks.skipped(&vi.database) {
+            Some(check) => f(LocateEvent::Skipped(&vi.folder, check)).map_err(|err| {
+                CliError::LocateError(match err.kind() {
+                    ErrorKind::BrokenPipe => fsidx::LocateError::BrokenPipe,
+                    _ => fsidx::LocateError::WritingResultFailed(err),
+                })
+            })?,
+            None => volume_info.push(vi),
+        }
+    }
+    Ok(volume_info)
+}
+
+// Declaration of a locate filter option. The table below is used for
+// parsing options and for printing the reference of explain-syntax.
+pub(crate) struct LocateOption {
+    pub(crate) long: &'static str,
+    pub(crate) short: &'static str,
+    pub(crate) token: fn() -> FilterToken,
+    // Heading of the group of options in the reference.
+    pub(crate) group: &'static str,
+    pub(crate) description: &'static str,
+}
+
+const GENERAL: &str = "Options";
+const PLAIN_TEXT: &str = "Options for plain text";
+const GLOB: &str = "Options for glob patterns";
+
+pub(crate) const LOCATE_OPTIONS: &[LocateOption] = &[
+    LocateOption {
+        long: "case-sensitive",
+        short: "c",
+        token: || FilterToken::CaseSensitive,
+        group: GENERAL,
+        description: "Case-sensitive matching",
+    },
+    LocateOption {
+        long: "case-insensitive",
+        short: "i",
+        token: || FilterToken::CaseInSensitive,
+        group: GENERAL,
+        description: "Case-insensitive matching (default)",
+    },
+    LocateOption {
+        long: "auto",
+        short: "0",
+        token: || FilterToken::Auto,
+        group: GENERAL,
+        description: "Argument type is autodetected (default)",
+    },
+    LocateOption {
+        long: "plain",
+        short: "1",
+        token: || FilterToken::Plain,
+        group: GENERAL,
+        description: "Arguments are plain text",
+    },
+    LocateOption {
+        long: "glob",
+        short: "2",
+        token: || FilterToken::Glob,
+        group: GENERAL,
+        description: "Arguments are glob patterns",
+    },
+    LocateOption {
+        long: "any-order",
+        short: "a",
+        token: || FilterToken::AnyOrder,
+        group: PLAIN_TEXT,
+        description: "Plain text may match in any order (default)",
+    },
+    LocateOption {
+        long: "same-order",
+        short: "o",
+        token: || FilterToken::SameOrder,
+        group: PLAIN_TEXT,
+        description: "Plain text must appear in same order",
+    },
+    LocateOption {
+        long: "whole-path",
+        short: "w",
+        token: || FilterToken::WholePath,
+        group: PLAIN_TEXT,
+        description: "Pattern is applied on whole path (default)",
+    },
+    LocateOption {
+        long: "last-element",
+        short: "l",
+        token: || FilterToken::LastElement,
+        group: PLAIN_TEXT,
+        description: "Pattern is applied on last element",
+    },
+    LocateOption {
+        long: "smart-spaces",
+        short: "s",
+        token: || FilterToken::SmartSpaces(true),
+        group: PLAIN_TEXT,
+        description: "Space, dash and underscore match each other (default)",
+    },
+    LocateOption {
+        long: "no-smart-spaces",
+        short: "S",
+        token: || FilterToken::SmartSpaces(false),
+        group: PLAIN_TEXT,
+        description: "Spaces only match with spaces",
+    },
+    LocateOption {
+        long: "word-boundary",
+        short: "b",
+        token: || FilterToken::WordBoundary(true),
+        group: PLAIN_TEXT,
+        description: "Plain text must start and end at a word boundary",
+    },
+    LocateOption {
+        long: "no-word-boundary",
+        short: "B",
+        token: || FilterToken::WordBoundary(false),
+        group: PLAIN_TEXT,
+        description: "Plain text may match within words (default)",
+    },
+    LocateOption {
+        long: "literal-separator",
+        short: "ls",
+        token: || FilterToken::LiteralSeparator(true),
+        group: GLOB,
+        description: "Asterisk does not match a slash",
+    },
+    LocateOption {
+        long: "no-literal-separator",
+        short: "nls",
+        token: || FilterToken::LiteralSeparator(false),
+        group: GLOB,
+        description: "Asterisk matches any character (default)",
+    },
+];
+
+pub(crate) fn locate_filter(token: Vec<Token>) -> Result<Vec<FilterToken>, CliError> {
     let mut filter: Vec<FilterToken> = Vec::new();
-    for token in token {
-        let filter_token = match token {
-            Token::Text(text) => FilterToken::Text(text),
-            Token::Option(text) => match text.as_str() {
-                "case-sensitive" | "c" => FilterToken::CaseSensitive,
-                "case-insensitive" | "i" => FilterToken::CaseInSensitive,
-                "any-order" | "a" => FilterToken::AnyOrder,
-                "same-order" | "o" => FilterToken::SameOrder,
-                "whole-path" | "w" => FilterToken::WholePath,
-                "last-element" | "l" => FilterToken::LastElement,
-                "smart-spaces" | "s" => FilterToken::SmartSpaces(true),
-                "no-smart-spaces" | "S" => FilterToken::SmartSpaces(false),
-                "literal-separator" | "ls" => FilterToken::LiteralSeparator(true),
-                "no-literal-separator" | "nls" => FilterToken::LiteralSeparator(false),
-                "word-boundary" | "b" => FilterToken::WordBoundary(true),
-                "no-word-boundary" | "B" => FilterToken::WordBoundary(false),
-                "auto" | "-0" => FilterToken::Auto,
-                "plain" | "-1" => FilterToken::Plain,
-                "glob" | "-2" => FilterToken::Glob,
-                _ => {
-                    return Err(CliError::InvalidLocateFilterOption(text));
-                }
-            },
-        };
-        filter.push(filter_token);
+    let mut token = token.into_iter();
+    while let Some(tk) = token.next() {
+        filter.push(filter_token(tk, &mut token)?);
     }
     Ok(filter)
 }
 
-fn print_size(stdout: &mut StandardStream, size: u64) -> IOResult<()> {
+// Options with a value take the value from the remaining tokens.
+fn filter_token(
+    tk: Token,
+    token: &mut impl Iterator<Item = Token>,
+) -> Result<FilterToken, CliError> {
+    Ok(match tk {
+        Token::Text(text) => FilterToken::Text(text),
+        // Metadata keys are the names of the builtin enrichment providers.
+        Token::Option(text) if text == "meta" => match token.next() {
+            Some(Token::Text(expression)) => FilterToken::Meta(
+                MetaPredicate::parse(&expression, &ProviderRegistry::builtin())
+                    .map_err(CliError::InvalidMetaFilter)?,
+            ),
+            _ => return Err(CliError::MissingOptionValue(text)),
+        },
+        Token::Option(text) if text == "min-size" || text == "max-size" => {
+            let size = match token.next() {
+                Some(Token::Text(size)) => parse_size(&size)?,
+                _ => return Err(CliError::MissingOptionValue(text)),
+            };
+            if text == "min-size" {
+                FilterToken::MinSize(size)
+            } else {
+                FilterToken::MaxSize(size)
+            }
+        }
+        Token::Option(text) if text == "type" => match token.next() {
+            Some(Token::Text(types)) => FilterToken::EntryType(parse_entry_types(&types)?),
+            _ => return Err(CliError::MissingOptionValue(text)),
+        },
+        // The term is the next plain text, glob pattern or option.
+        Token::Option(text) if text == "not" => match token.next() {
+            Some(term) => FilterToken::Not(Box::new(filter_token(term, token)?)),
+            None => return Err(CliError::MissingOptionValue(text)),
+        },
+        Token::Option(text) if text == "or" => FilterToken::Or,
+        Token::Option(text) => match LOCATE_OPTIONS
+            .iter()
+            .find(|option| option.long == text || option.short == text)
+        {
+            Some(option) => (option.token)(),
+            None => return Err(CliError::InvalidLocateFilterOption(text)),
+        },
+    })
+}
+
+// Parses entry types separated by commas like find -type, e.g. f,l.
+fn parse_entry_types(text: &str) -> Result<Vec<EntryType>, CliError> {
+    text.split(',')
+        .map(|entry_type| match entry_type {
+            "f" => Ok(EntryType::File),
+            "d" => Ok(EntryType::Directory),
+            "l" => Ok(EntryType::Symlink),
+            _ => Err(CliError::InvalidEntryType(text.to_string())),
+        })
+        .collect()
+}
+
+// Parses a number of bytes with an optional binary unit, e.g. 10M or 2G.
+fn parse_size(text: &str) -> Result<u64, CliError> {
+    let invalid = || CliError::InvalidSize(text.to_string());
+    let pos = text
+        .find(|ch: char| !ch.is_ascii_digit())
+        .unwrap_or(text.len());
+    let shift = match text[pos..].to_ascii_uppercase().as_str() {
+        "" | "B" => 0,
+        "K" => 10,
+        "M" => 20,
+        "G" => 30,
+        "T" => 40,
+        _ => return Err(invalid()),
+    };
+    let number: u64 = text[..pos].parse().map_err(|_| invalid())?;
+    number.checked_mul(1 << shift).ok_or_else(invalid)
+}
+
+// How results are printed.
+#[derive(Clone)]
+struct Format {
+    // Width available for printing a result, if long paths are truncated.
+    width: Option<usize>,
+    icons: bool,
+    // Selected fields replace the default format.
+    columns: Option<Columns>,
+    // Results in these folders are annotated as offline.
+    offline: Arc<[PathBuf]>,
+    // Results are printed as JSON objects, one per line.
+    json: bool,
+    // Paths are printed without decoration and terminated by a NUL byte.
+    print0: bool,
+}
+
+impl Format {
+    fn new(config: &Config) -> Format {
+        let width = if config.output.truncate {
+            terminal_width()
+        } else {
+            None
+        };
+        Format {
+            width,
+            icons: config.output.icons,
+            columns: None,
+            json: false,
+            print0: false,
+            offline: get_offline_folders(config)
+                .into_iter()
+                .map(|(folder, _)| folder)
+                .collect(),
+        }
+    }
+
+    fn is_offline(&self, path: &Path) -> bool {
+        self.offline.iter().any(|folder| path.starts_with(folder))
+    }
+
+    fn shrink(self, len: usize) -> Format {
+        Format {
+            width: self.width.map(|width| width.saturating_sub(len)),
+            ..self
+        }
+    }
+}
+
+// Replaces the middle of the text with an ellipsis to fit into max characters.
+fn truncate_middle(text: &str, max: usize) -> Cow<'_, str> {
+    let len = text.chars().count();
+    if len <= max {
+        return Cow::Borrowed(text);
+    }
+    if max == 0 {
+        return Cow::Borrowed("");
+    }
+    let head = (max - 1) / 2;
+    let tail = max - 1 - head;
+    let mut truncated: String = text.chars().take(head).collect();
+    truncated.push('…');
+    truncated.extend(text.chars().skip(len - tail));
+    Cow::Owned(truncated)
+}
+
+fn print_path(stdout: &mut Output, path: &Path, format: &Format) -> IOResult<()> {
+    let mut width = format.width;
+    if format.icons {
+        // Icons are two columns wide.
+        let icon = icon(path).unwrap_or("  ");
+        stdout.write_all(icon.as_bytes())?;
+        stdout.write_all(b" ")?;
+        width = width.map(|width| width.saturating_sub(3));
+    }
+    if let Some(width) = width {
+        let text = path.to_string_lossy();
+        if text.chars().count() > width {
+            return stdout.write_all(truncate_middle(&text, width).as_bytes());
+        }
+    }
+    stdout.write_all(path.as_os_str().as_bytes())
+}
+
+const OFFLINE: &str = " [offline]";
+
+// Paths on volumes, which are not mounted, are followed by a dimmed tag.
+fn print_path_annotated(stdout: &mut Output, path: &Path, format: &Format) -> IOResult<()> {
+    if !format.is_offline(path) {
+        return print_path(stdout, path, format);
+    }
+    let format = format.clone().shrink(OFFLINE.len());
+    print_path(stdout, path, &format)?;
+    stdout.set_color(ColorSpec::new().set_dimmed(true))?;
+    stdout.write_all(OFFLINE.as_bytes())?;
+    stdout.set_color(&ColorSpec::new())
+}
+
+// Number of characters printed by print_size.
+fn size_len(size: u64) -> usize {
+    let digits = size.to_string().len();
+    digits + (digits - 1) / 3
+}
+
+pub(crate) fn format_size(size: u64) -> String {
     let text = size.to_string();
-    let bytes = text.bytes();
-    let len = bytes.len();
-    for (i, ch) in bytes.into_iter().enumerate() {
+    let len = text.len();
+    let mut formatted = String::with_capacity(size_len(size));
+    for (i, ch) in text.chars().enumerate() {
         if i > 0 && (len - i) % 3 == 0 {
-            stdout.write_all(b".")?;
+            formatted.push('.');
         }
-        stdout.write_all(&[ch])?;
+        formatted.push(ch);
     }
-    Ok(())
+    formatted
 }
 
-fn print_locate_result(stdout: &mut StandardStream, res: &LocateEvent) -> IOResult<()> {
+fn print_size(stdout: &mut Output, size: u64) -> IOResult<()> {
+    stdout.write_all(format_size(size).as_bytes())
+}
+
+fn print_locate_result(stdout: &mut Output, res: &LocateEvent, format: &Format) -> IOResult<()> {
+    // Printed to stderr in all formats.
     match *res {
-        LocateEvent::Entry(path, Metadata { size: Some(size) }) => {
+        LocateEvent::UpdateInProgress(folder) => {
+            let message = format!(
+                "Database for '{}' is being updated. Searching the previous version.",
+                folder.display()
+            );
+            print_event(
+                Severity::Warning,
+                &Message::new("update-in-progress", message).with_volume(folder),
+            );
+            return Ok(());
+        }
+        LocateEvent::WaitingForUpdate(folder) => {
+            let message = format!("Waiting for the first update of '{}'.", folder.display());
+            print_event(
+                Severity::Warning,
+                &Message::new("waiting-for-update", message).with_volume(folder),
+            );
+            return Ok(());
+        }
+        _ => {}
+    }
+    if format.json {
+        return print_json_result(stdout, res, format);
+    }
+    if format.print0 {
+        // Other events would corrupt the list of paths.
+        if let LocateEvent::Entry(path, _) = *res {
             stdout.write_all(path.as_os_str().as_bytes())?;
+            stdout.end_with(b"\0")?;
+        }
+        return Ok(());
+    }
+    match *res {
+        LocateEvent::Entry(path, metadata) if format.columns.is_some() => {
+            if let Some(columns) = &format.columns {
+                columns.print(stdout, path, metadata.size, metadata.modified)?;
+            }
+        }
+        LocateEvent::Entry(
+            path,
+            Metadata {
+                size: Some(size), ..
+            },
+        ) => {
+            let format = format.clone().shrink(size_len(*size) + 3);
+            print_path_annotated(stdout, path, &format)?;
             stdout.set_color(ColorSpec::new().set_fg(Some(Color::Green)))?;
             stdout.write_all(b" (")?;
             print_size(stdout, *size)?;
             stdout.write_all(b")")?;
             stdout.set_color(&ColorSpec::new())?;
-            stdout.write_all(b"\n")?;
+            stdout.end_line()?;
+        }
+        LocateEvent::Entry(path, Metadata { size: None, .. }) => {
+            print_path_annotated(stdout, path, format)?;
+            stdout.end_line()?;
         }
-        LocateEvent::Entry(path, Metadata { size: None }) => {
+        // Batch results are tagged by the caller and printed as Entry.
+        // Matches are only reported by locate_with_context.
+        LocateEvent::Finished
+        | LocateEvent::Statistics(_, _)
+        | LocateEvent::BatchEntry(_, _, _)
+        | LocateEvent::Match(_)
+        | LocateEvent::UpdateInProgress(_)
+        | LocateEvent::WaitingForUpdate(_) => {}
+        // Skipped folders are reported by check_databases, i.e. once per
+        // configuration in the shell.
+        LocateEvent::Skipped(folder, _) if verbosity() => {
+            stdout.write_all(b"Skipped:   ")?;
+            stdout.write_all(folder.as_os_str().as_bytes())?;
+            stdout.end_line()?;
+        }
+        LocateEvent::Searching(path) if verbosity() => {
+            stdout.write_all(b"Searching: ")?;
             stdout.write_all(path.as_os_str().as_bytes())?;
-            stdout.write_all(b"\n")?;
-        }
-        LocateEvent::Finished => {}
-        LocateEvent::Searching(path) => {
-            if verbosity() {
-                stdout.write_all(b"Searching: ")?;
-                stdout.write_all(path.as_os_str().as_bytes())?;
-                stdout.write_all(b"\n")?;
-            }
+            stdout.end_line()?;
         }
-        LocateEvent::SearchingFinished(path) => {
-            if verbosity() {
-                stdout.write_all(b"Searching  ")?;
-                stdout.write_all(path.as_os_str().as_bytes())?;
-                stdout.write_all(b" finished\n")?;
-            }
+        LocateEvent::SearchingFinished(path) if verbosity() => {
+            stdout.write_all(b"Searching  ")?;
+            stdout.write_all(path.as_os_str().as_bytes())?;
+            stdout.write_all(b" finished")?;
+            stdout.end_line()?;
         }
+        _ => {}
     }
     Ok(())
 }
 
+fn print_json_result(stdout: &mut Output, res: &LocateEvent, format: &Format) -> IOResult<()> {
+    let (event, path) = match *res {
+        LocateEvent::Entry(path, metadata) => {
+            return print_json_entry(stdout, path, metadata, None, format)
+        }
+        LocateEvent::Searching(path) if verbosity() => ("searching", path),
+        LocateEvent::SearchingFinished(path) if verbosity() => ("searching-finished", path),
+        LocateEvent::Skipped(folder, _) if verbosity() => ("skipped", folder),
+        _ => return Ok(()),
+    };
+    let mut line = format!("{{\"event\":\"{}\",", event);
+    json_path(&mut line, path);
+    line.push('}');
+    stdout.write_all(line.as_bytes())?;
+    stdout.end_line()
+}
+
+// Prints a result as JSON object. Results of a queries file contain the line
+// number of the query.
+fn print_json_entry(
+    stdout: &mut Output,
+    path: &Path,
+    metadata: &Metadata,
+    query: Option<usize>,
+    format: &Format,
+) -> IOResult<()> {
+    let mut line = String::from("{\"event\":\"entry\",");
+    json_path(&mut line, path);
+    match metadata.size {
+        Some(size) => line.push_str(&format!(",\"size\":{}", size)),
+        None => line.push_str(",\"size\":null"),
+    }
+    if format.is_offline(path) {
+        line.push_str(",\"offline\":true");
+    }
+    if let Some(query) = query {
+        line.push_str(&format!(",\"query\":{}", query));
+    }
+    line.push('}');
+    stdout.write_all(line.as_bytes())?;
+    stdout.end_line()
+}
+
+// JSON strings can't represent paths, which aren't valid UTF-8. These paths
+// are additionally given as array of bytes.
+fn json_path(line: &mut String, path: &Path) {
+    line.push_str("\"path\":");
+    line.push_str(&json_string(&path.to_string_lossy()));
+    if path.to_str().is_none() {
+        let bytes: Vec<String> = path
+            .as_os_str()
+            .as_bytes()
+            .iter()
+            .map(|byte| byte.to_string())
+            .collect();
+        line.push_str(&format!(",\"path_bytes\":[{}]", bytes.join(",")));
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::temp_dir::TempDir;
+    use crate::tokenizer::tokenize_shell;
+    use std::ffi::OsStr;
 
     #[test]
     fn glob_case() {
@@ -153,4 +1272,230 @@ mod tests {
             ]
         );
     }
+
+    #[test]
+    fn size_filter() {
+        let token = tokenize_shell("--min-size 10M --max-size 2g").unwrap();
+        let filter = locate_filter(token).unwrap();
+        assert!(matches!(
+            filter[..],
+            [
+                FilterToken::MinSize(10485760),
+                FilterToken::MaxSize(2147483648)
+            ]
+        ));
+        assert_eq!(parse_size("100").unwrap(), 100);
+        assert!(matches!(parse_size("1.5G"), Err(CliError::InvalidSize(_))));
+        assert!(matches!(parse_size("M"), Err(CliError::InvalidSize(_))));
+        let token = tokenize_shell("--min-size").unwrap();
+        assert!(matches!(
+            locate_filter(token),
+            Err(CliError::MissingOptionValue(_))
+        ));
+    }
+
+    #[test]
+    fn type_filter() {
+        let token = tokenize_shell("--type d,l").unwrap();
+        assert_eq!(
+            locate_filter(token).unwrap(),
+            vec![FilterToken::EntryType(vec![
+                EntryType::Directory,
+                EntryType::Symlink
+            ])]
+        );
+        assert!(matches!(
+            parse_entry_types("f,x"),
+            Err(CliError::InvalidEntryType(_))
+        ));
+    }
+
+    #[test]
+    fn negation() {
+        let token = tokenize_shell("flac --not live --not --min-size 1G").unwrap();
+        assert_eq!(
+            locate_filter(token).unwrap(),
+            vec![
+                FilterToken::Text(String::from("flac")),
+                FilterToken::Not(Box::new(FilterToken::Text(String::from("live")))),
+                FilterToken::Not(Box::new(FilterToken::MinSize(1073741824))),
+            ]
+        );
+        let token = tokenize_shell("flac --not").unwrap();
+        assert!(matches!(
+            locate_filter(token),
+            Err(CliError::MissingOptionValue(_))
+        ));
+        let token = tokenize_shell("mp3 --or flac").unwrap();
+        assert_eq!(
+            locate_filter(token).unwrap(),
+            vec![
+                FilterToken::Text(String::from("mp3")),
+                FilterToken::Or,
+                FilterToken::Text(String::from("flac")),
+            ]
+        );
+    }
+
+    #[test]
+    fn json_paths() {
+        let mut line = String::new();
+        json_path(&mut line, Path::new("/Music/\"Help\".flac"));
+        assert_eq!(line, "\"path\":\"/Music/\\\"Help\\\".flac\"");
+        let mut line = String::new();
+        json_path(&mut line, Path::new(OsStr::from_bytes(b"/a\xff")));
+        assert_eq!(line, "\"path\":\"/a\u{FFFD}\",\"path_bytes\":[47,97,255]");
+    }
+
+    #[test]
+    fn offline() {
+        let format = Format {
+            width: None,
+            icons: false,
+            columns: None,
+            offline: Arc::from(vec![PathBuf::from("/media/usb")]),
+            json: false,
+            print0: false,
+        };
+        assert!(format.is_offline(Path::new("/media/usb")));
+        assert!(format.is_offline(Path::new("/media/usb/Photos/a.jpg")));
+        assert!(!format.is_offline(Path::new("/media/usb2/a.jpg")));
+    }
+
+    #[test]
+    fn meta_filter() {
+        let token = tokenize_shell("--meta content-hash=af63dc4c8601ec8c").unwrap();
+        let filter = locate_filter(token).unwrap();
+        assert!(matches!(filter[..], [FilterToken::Meta(_)]));
+        let token = tokenize_shell("Beatles --meta").unwrap();
+        assert!(matches!(
+            locate_filter(token),
+            Err(CliError::MissingOptionValue(_))
+        ));
+        let token = tokenize_shell("--meta artist=Beatles").unwrap();
+        assert!(matches!(
+            locate_filter(token),
+            Err(CliError::InvalidMetaFilter(fsidx::MetaError::UnknownKey(_)))
+        ));
+    }
+
+    #[test]
+    fn selection_filter() {
+        let dir = TempDir::new("filter");
+        fs::create_dir_all(dir.join("a.flac")).unwrap();
+        fs::write(dir.join("b.flac"), b"xyz").unwrap();
+        let paths = vec![
+            dir.join("a.flac"),
+            dir.join("b.flac"),
+            dir.join("c.mp3"),
+            PathBuf::from("/offline/d.flac"),
+        ];
+        let config: Config = toml::from_str("[index]\nfolder = []\n[locate]\n").unwrap();
+        let filter = |query: &str| {
+            filter_selection(&config, tokenize_shell(query).unwrap(), &paths).unwrap()
+        };
+        let flac = filter("-l *.flac");
+        let files = filter("flac --type f");
+        let large = filter("--min-size 2");
+        let meta = filter_selection(
+            &config,
+            tokenize_shell("--meta content-hash=af63dc4c8601ec8c").unwrap(),
+            &paths,
+        );
+        assert_eq!(flac, [&paths[0], &paths[1], &paths[3]].map(PathBuf::clone));
+        assert_eq!(files, [paths[1].clone()]);
+        assert!(large.contains(&paths[1]) && !large.contains(&paths[3]));
+        assert!(meta.is_err());
+    }
+
+    #[test]
+    fn skipped_databases() {
+        let dir = TempDir::new("skipped");
+        let config: Config = toml::from_str(&format!(
+            "[index]\nfolder = [\"/a\", \"/b\"]\ndb_path = {:?}\n[locate]\n",
+            &*dir
+        ))
+        .unwrap();
+        fs::write(dir.join("_a.fsdb"), b"fsix\x00\x00\x05/a/f1").unwrap();
+        let query = |checks: &HeaderChecks| {
+            let mut events = Vec::new();
+            let filter = vec![FilterToken::Text(String::from("f1"))];
+            locate_impl(&config, checks, filter, None, None, None, |event| {
+                match event {
+                    LocateEvent::Entry(path, _) => events.push(path.display().to_string()),
+                    LocateEvent::Skipped(folder, HeaderCheck::Missing) => {
+                        events.push(format!("skipped {}", folder.display()))
+                    }
+                    _ => {}
+                }
+                Ok(())
+            })
+            .unwrap();
+            events
+        };
+        let checks = check_databases(&config).unwrap();
+        assert_eq!(checks.count(), 1);
+        assert_eq!(query(&checks), ["skipped /b", "/a/f1"]);
+        // The folder is searched after checking the headers again.
+        fs::write(dir.join("_b.fsdb"), b"fsix\x00\x00\x05/b/f1").unwrap();
+        assert_eq!(query(&checks), ["skipped /b", "/a/f1"]);
+        let checks = check_databases(&config).unwrap();
+        let events = query(&checks);
+        assert_eq!(checks.count(), 0);
+        assert_eq!(events, ["/a/f1", "/b/f1"]);
+    }
+
+    #[test]
+    fn sort_key() {
+        let token = tokenize_shell("--sort size Beatles").unwrap();
+        let (token, options) = locate_options(token).unwrap();
+        assert_eq!(token, vec![Token::Text(String::from("Beatles"))]);
+        assert_eq!(options.sort, Some(SortKey::Size));
+        assert!(!options.summary);
+        let token = tokenize_shell("Beatles --summary").unwrap();
+        assert!(locate_options(token).unwrap().1.summary);
+        let token = tokenize_shell("Beatles --sort").unwrap();
+        assert!(matches!(
+            locate_options(token),
+            Err(CliError::MissingOptionValue(_))
+        ));
+        let token = tokenize_shell("--fields size,path --align Beatles").unwrap();
+        let (token, options) = locate_options(token).unwrap();
+        assert_eq!(token, vec![Token::Text(String::from("Beatles"))]);
+        assert_eq!(options.fields, Some(vec![Field::Size, Field::Path]));
+        assert!(options.align);
+        // -0 selects the argument type and is kept in the query.
+        let token = tokenize_shell("-0 Beatles --print0").unwrap();
+        let (token, options) = locate_options(token).unwrap();
+        assert_eq!(token.len(), 2);
+        assert!(options.print0);
+        let token = tokenize_shell("--offset 20 --limit 10 Beatles").unwrap();
+        let (_, options) = locate_options(token).unwrap();
+        assert_eq!((options.offset, options.limit), (20, Some(10)));
+        let token = tokenize_shell("--limit ten").unwrap();
+        assert!(matches!(
+            locate_options(token),
+            Err(CliError::InvalidNumber(_))
+        ));
+        let token = tokenize_shell("--sort date").unwrap();
+        assert!(matches!(
+            locate_options(token),
+            Err(CliError::InvalidSortKey(_))
+        ));
+    }
+
+    #[test]
+    fn truncate() {
+        assert_eq!(truncate_middle("/Music/Artist", 13), "/Music/Artist");
+        assert_eq!(truncate_middle("/Music/Artist", 7), "/Mu…ist");
+        assert_eq!(truncate_middle("/Müsik/Ärtist", 6), "/M…ist");
+        assert_eq!(truncate_middle("/Music", 1), "…");
+        assert_eq!(truncate_middle("/Music", 0), "");
+        assert_eq!(size_len(0), 1);
+        assert_eq!(size_len(999), 3);
+        assert_eq!(size_len(1000), 5);
+        assert_eq!(size_len(1234567), 9);
+        assert_eq!(format_size(1234567), "1.234.567");
+        assert_eq!(format_size(999), "999");
+    }
 }
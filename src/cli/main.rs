@@ -1,13 +1,43 @@
+mod actions;
+mod buffer;
 mod cli;
+mod clipboard;
 mod config;
+mod configure;
+mod event;
 mod expand;
+mod explain;
+mod export;
+mod fields;
 mod help;
+mod hooks;
+mod icons;
+mod import;
 mod locate;
+mod open;
+mod output;
+mod report;
+mod session;
+mod sets;
 mod shell;
+mod sort;
+mod stats;
+mod storage;
+mod summary;
+#[cfg(test)]
+#[path = "../temp_dir.rs"]
+mod temp_dir;
+mod template;
 mod tokenizer;
 mod tty;
+#[cfg(feature = "tui")]
+mod tui;
+mod undo;
 mod update;
+mod uri;
 mod verbosity;
+mod verify;
+mod warm;
 
 fn main() {
     let exit_code = cli::main();
@@ -0,0 +1,258 @@
+use crate::cli::CliError;
+use crate::config::OpenConfig;
+use crate::storage::private_temp_dir;
+use crate::template::{self, has_file_placeholder, has_list_placeholder, Values};
+use crate::uri::UriMapping;
+use globset::GlobBuilder;
+use std::env;
+use std::fs::{self, OpenOptions};
+use std::io::{BufWriter, Write};
+use std::os::unix::fs::OpenOptionsExt;
+use std::os::unix::prelude::OsStrExt;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+// Program used to open files, if no application is configured.
+#[cfg(target_os = "macos")]
+const DEFAULT_APPLICATION: &str = "open";
+#[cfg(not(target_os = "macos"))]
+const DEFAULT_APPLICATION: &str = "xdg-open";
+
+// Returns the application configured for the longest glob pattern matching
+// path or for its file extension, or the configured open command.
+pub(crate) fn application<'a>(
+    open: Option<&'a OpenConfig>,
+    path: &Path,
+) -> Result<&'a str, CliError> {
+    let Some(open) = open else {
+        return Ok(DEFAULT_APPLICATION);
+    };
+    let mut longest: Option<(&str, &str)> = None;
+    for (pattern, application) in open.apps.iter().filter(|(key, _)| is_glob(key)) {
+        if longest.is_some_and(|(longest, _)| longest.len() >= pattern.len()) {
+            continue;
+        }
+        if glob_matches(pattern, path)? {
+            longest = Some((pattern, application));
+        }
+    }
+    if let Some((_, application)) = longest {
+        return Ok(application);
+    }
+    let extension = path
+        .extension()
+        .map(|extension| extension.to_string_lossy());
+    let application = extension
+        .and_then(|extension| {
+            open.apps
+                .iter()
+                .find(|(key, _)| key.eq_ignore_ascii_case(&extension))
+        })
+        .map(|(_, application)| application)
+        .or(open.command.as_ref());
+    Ok(application.map_or(DEFAULT_APPLICATION, String::as_str))
+}
+
+// Keys of [open.apps] are file extensions or glob patterns.
+fn is_glob(key: &str) -> bool {
+    key.contains(['*', '?', '[', '{'])
+}
+
+// Patterns with a slash match the whole path, other patterns the file name.
+// Matching is case-insensitive like for file extensions.
+fn glob_matches(pattern: &str, path: &Path) -> Result<bool, CliError> {
+    let glob = GlobBuilder::new(pattern)
+        .case_insensitive(true)
+        .literal_separator(true)
+        .build()
+        .map_err(|err| CliError::GlobPatternError(pattern.to_string(), err))?
+        .compile_matcher();
+    if pattern.contains('/') {
+        Ok(glob.is_match(path))
+    } else {
+        Ok(path.file_name().is_some_and(|name| glob.is_match(name)))
+    }
+}
+
+// A command opening files. The file with the paths for the {list}
+// placeholder and its private folder are removed, when the command is
+// dropped.
+pub(crate) struct OpenCommand {
+    pub(crate) command: Command,
+    list: Option<PathBuf>,
+}
+
+impl Drop for OpenCommand {
+    fn drop(&mut self) {
+        if let Some(list) = &self.list {
+            let _ = fs::remove_file(list);
+            if let Some(dir) = list.parent() {
+                let _ = fs::remove_dir(dir);
+            }
+        }
+    }
+}
+
+// Creates the commands to open files, which are passed with their index in
+// the query results. Files are grouped by application, so that an application
+// opens all its files at once. Applications using a placeholder for a single
+// file are run once for each file.
+pub(crate) fn open_commands(
+    open: Option<&OpenConfig>,
+    uris: &UriMapping,
+    files: &[(usize, &Path)],
+) -> Result<Vec<OpenCommand>, CliError> {
+    let mut groups: Vec<(&str, Vec<(usize, &Path)>)> = Vec::new();
+    for &(index, path) in files {
+        let application = application(open, path)?;
+        match groups.iter_mut().find(|(name, _)| *name == application) {
+            Some((_, group)) => group.push((index, path)),
+            None => groups.push((application, vec![(index, path)])),
+        }
+    }
+    let mut commands = Vec::new();
+    for (application, group) in groups {
+        if has_file_placeholder(application) {
+            for (index, path) in group {
+                let uri = uris.uri(path);
+                let values = Values {
+                    path: Some(path),
+                    index: Some(index),
+                    uri: Some(&uri),
+                    list: None,
+                };
+                let command = template::command(application, &values)?;
+                commands.push(OpenCommand {
+                    command,
+                    list: None,
+                });
+            }
+        } else if has_list_placeholder(application) {
+            // Other users can't replace the list in a private folder.
+            let dir = private_temp_dir("fsidx-list")
+                .map_err(|err| CliError::WritingListFailed(env::temp_dir(), err))?;
+            let list = dir.join("list.txt");
+            let values = Values {
+                list: Some(&list),
+                ..Values::default()
+            };
+            let command = match template::command(application, &values) {
+                Ok(command) => command,
+                Err(err) => {
+                    let _ = fs::remove_dir(&dir);
+                    return Err(err);
+                }
+            };
+            let command = OpenCommand {
+                command,
+                list: Some(list),
+            };
+            if let Some(list) = &command.list {
+                write_list(list, &group)
+                    .map_err(|err| CliError::WritingListFailed(list.clone(), err))?;
+            }
+            commands.push(command);
+        } else {
+            let mut command = template::command(application, &Values::default())?;
+            command.args(group.iter().map(|(_, path)| path));
+            commands.push(OpenCommand {
+                command,
+                list: None,
+            });
+        }
+    }
+    Ok(commands)
+}
+
+fn write_list(list: &Path, files: &[(usize, &Path)]) -> std::io::Result<()> {
+    let file = OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .mode(0o600)
+        .open(list)?;
+    let mut writer = BufWriter::new(file);
+    for (_, path) in files {
+        writer.write_all(path.as_os_str().as_bytes())?;
+        writer.write_all(b"\n")?;
+    }
+    writer.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeMap;
+
+    #[test]
+    fn application_by_extension() {
+        let open = OpenConfig {
+            command: Some(String::from("xdg-open")),
+            apps: BTreeMap::from([
+                (String::from("flac"), String::from("vlc")),
+                (String::from("jpg"), String::from("open -a Preview")),
+            ]),
+        };
+        let app = |open, path| application(open, Path::new(path)).unwrap();
+        assert_eq!(app(Some(&open), "/a/b.flac"), "vlc");
+        assert_eq!(app(Some(&open), "/a/b.JPG"), "open -a Preview");
+        assert_eq!(app(Some(&open), "/a/b.pdf"), "xdg-open");
+        assert_eq!(app(Some(&open), "/a/b"), "xdg-open");
+        assert_eq!(app(None, "/a/b.flac"), DEFAULT_APPLICATION);
+    }
+
+    #[test]
+    fn application_by_glob() {
+        let open = OpenConfig {
+            command: None,
+            apps: BTreeMap::from([
+                (String::from("flac"), String::from("vlc")),
+                (String::from("*.FLAC"), String::from("audacious")),
+                (String::from("live-*.flac"), String::from("mpv")),
+                (String::from("/photos/**"), String::from("gimp")),
+                (String::from("/photos/*/raw/*"), String::from("darktable")),
+            ]),
+        };
+        let app = |path| application(Some(&open), Path::new(path)).unwrap();
+        assert_eq!(app("/a/b.flac"), "audacious");
+        assert_eq!(app("/a/live-b.flac"), "mpv");
+        assert_eq!(app("/photos/2024/a.jpg"), "gimp");
+        assert_eq!(app("/photos/2024/raw/a.cr2"), "darktable");
+        assert_eq!(app("/a/photos/a.jpg"), DEFAULT_APPLICATION);
+        let invalid = OpenConfig {
+            command: None,
+            apps: BTreeMap::from([(String::from("*.{a"), String::from("vlc"))]),
+        };
+        assert!(application(Some(&invalid), Path::new("/a.a")).is_err());
+    }
+
+    #[test]
+    fn grouped_commands() {
+        let open = OpenConfig {
+            command: Some(String::from("code --goto {path}")),
+            apps: BTreeMap::from([
+                (String::from("flac"), String::from("vlc")),
+                (String::from("mkv"), String::from("mpv --playlist={list}")),
+            ]),
+        };
+        let files = [
+            (1, Path::new("/a/1.flac")),
+            (2, Path::new("/a/1.txt")),
+            (3, Path::new("/a/2.flac")),
+            (4, Path::new("/a/2.txt")),
+            (5, Path::new("/a/1.mkv")),
+        ];
+        let commands = open_commands(Some(&open), &UriMapping::default(), &files).unwrap();
+        let args: Vec<Vec<&std::ffi::OsStr>> = commands
+            .iter()
+            .map(|command| command.command.get_args().collect())
+            .collect();
+        assert_eq!(args[0], vec!["/a/1.flac", "/a/2.flac"]);
+        assert_eq!(args[1], vec!["--goto", "/a/1.txt"]);
+        assert_eq!(args[2], vec!["--goto", "/a/2.txt"]);
+        let list = commands[3].list.clone().unwrap();
+        assert_eq!(fs::read_to_string(&list).unwrap(), "/a/1.mkv\n");
+        drop(commands);
+        assert!(!list.exists());
+        assert!(!list.parent().unwrap().exists());
+    }
+}
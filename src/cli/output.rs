@@ -0,0 +1,169 @@
+use nix::errno::Errno;
+use nix::poll::{poll, PollFd, PollFlags, PollTimeout};
+use nix::sys::stat::{fstat, SFlag};
+use std::io::{stdout, Error as IOError, ErrorKind, Result as IOResult, Write};
+use std::os::unix::io::{AsFd, AsRawFd};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+use termcolor::{Buffer, BufferWriter, ColorChoice, ColorSpec, WriteColor};
+
+// Serialized output for results. Printers write a complete line including
+// its color sequences into a buffer, which is written to stdout with a single
+// locked write by end_line. Lines of printers running in parallel threads
+// therefore never interleave and color sequences are never split.
+pub(crate) struct Output {
+    writer: BufferWriter,
+    buffer: Buffer,
+    write_timeout: Option<Duration>,
+}
+
+impl Output {
+    pub(crate) fn stdout() -> Output {
+        let writer = BufferWriter::stdout(ColorChoice::Auto);
+        let buffer = writer.buffer();
+        Output {
+            writer,
+            buffer,
+            write_timeout: None,
+        }
+    }
+
+    // Writing fails, if stdout doesn't accept data within the timeout, e.g.
+    // a pager waiting for input. See [output] write-timeout-ms.
+    pub(crate) fn with_write_timeout(mut self, timeout_ms: Option<u64>) -> Output {
+        self.write_timeout = timeout_ms.map(Duration::from_millis);
+        self
+    }
+
+    // Terminates the current line and writes it atomically.
+    pub(crate) fn end_line(&mut self) -> IOResult<()> {
+        self.end_with(b"\n")
+    }
+
+    // Like end_line, but with another terminator, e.g. a NUL byte.
+    pub(crate) fn end_with(&mut self, terminator: &[u8]) -> IOResult<()> {
+        self.buffer.write_all(terminator)?;
+        self.print()
+    }
+
+    fn print(&mut self) -> IOResult<()> {
+        let res = match self.write_timeout {
+            Some(timeout) if !self.buffer.is_empty() => wait_writable(timeout),
+            _ => Ok(()),
+        };
+        let res = res.and_then(|_| self.writer.print(&self.buffer));
+        self.buffer.clear();
+        res
+    }
+}
+
+// A pipe is writable, if its buffer has space for at least one page. A
+// longer line may still block, but only until the reader continues.
+fn wait_writable(timeout: Duration) -> IOResult<()> {
+    let stdout = stdout();
+    let mut fds = [PollFd::new(stdout.as_fd(), PollFlags::POLLOUT)];
+    let timeout = PollTimeout::try_from(timeout).unwrap_or(PollTimeout::MAX);
+    match poll(&mut fds, timeout) {
+        Ok(0) => Err(IOError::new(
+            ErrorKind::TimedOut,
+            "stdout is not accepting data",
+        )),
+        Ok(_) if is_closed(&fds[0]) => Err(IOError::from(ErrorKind::BrokenPipe)),
+        Ok(_) | Err(Errno::EINTR) => Ok(()),
+        Err(err) => Err(err.into()),
+    }
+}
+
+fn is_closed(fd: &PollFd) -> bool {
+    fd.revents()
+        .is_some_and(|revents| revents.intersects(PollFlags::POLLERR | PollFlags::POLLHUP))
+}
+
+// Stdout is a pipe, e.g. to head, whose reader may close it long before the
+// next result is written. The watcher polls the pipe in a thread and sets the
+// abort flag when the reader is gone, so that a query stops without waiting
+// for a write to fail with a broken pipe.
+pub(crate) struct PipeWatcher {
+    closed: Arc<AtomicBool>,
+    stop: Arc<AtomicBool>,
+}
+
+const WATCH_INTERVAL: u16 = 100;
+
+impl PipeWatcher {
+    pub(crate) fn watch(abort: Arc<AtomicBool>) -> PipeWatcher {
+        let closed = Arc::new(AtomicBool::new(false));
+        let stop = Arc::new(AtomicBool::new(false));
+        if is_pipe() {
+            let closed = closed.clone();
+            let stop = stop.clone();
+            thread::spawn(move || {
+                let stdout = stdout();
+                while !stop.load(Ordering::Relaxed) {
+                    // POLLERR is reported without being requested.
+                    let mut fds = [PollFd::new(stdout.as_fd(), PollFlags::empty())];
+                    match poll(&mut fds, WATCH_INTERVAL) {
+                        Ok(0) | Err(Errno::EINTR) => {}
+                        Ok(_) if is_closed(&fds[0]) => {
+                            closed.store(true, Ordering::Relaxed);
+                            abort.store(true, Ordering::Relaxed);
+                            break;
+                        }
+                        _ => break,
+                    }
+                }
+            });
+        }
+        PipeWatcher { closed, stop }
+    }
+
+    // The reader of the pipe is gone, i.e. an abort is no error.
+    pub(crate) fn closed(&self) -> bool {
+        self.closed.load(Ordering::Relaxed)
+    }
+}
+
+impl Drop for PipeWatcher {
+    // The thread isn't joined. It ends with the next poll interval.
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+    }
+}
+
+fn is_pipe() -> bool {
+    fstat(stdout().as_raw_fd())
+        .is_ok_and(|stat| SFlag::from_bits_truncate(stat.st_mode) & SFlag::S_IFMT == SFlag::S_IFIFO)
+}
+
+impl Write for Output {
+    fn write(&mut self, buf: &[u8]) -> IOResult<usize> {
+        self.buffer.write(buf)
+    }
+
+    // Writes an incomplete line.
+    fn flush(&mut self) -> IOResult<()> {
+        self.print()
+    }
+}
+
+impl WriteColor for Output {
+    fn supports_color(&self) -> bool {
+        self.buffer.supports_color()
+    }
+
+    fn set_color(&mut self, spec: &ColorSpec) -> IOResult<()> {
+        self.buffer.set_color(spec)
+    }
+
+    fn reset(&mut self) -> IOResult<()> {
+        self.buffer.reset()
+    }
+}
+
+impl Drop for Output {
+    fn drop(&mut self) {
+        let _ = self.print();
+    }
+}
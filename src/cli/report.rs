@@ -0,0 +1,56 @@
+use crate::cli::CliError;
+use crate::config::Config;
+use crate::locate::{check_databases, searchable_volumes};
+use crate::output::Output;
+use crate::verbosity::verbosity;
+use fsidx::{Aggregator, BadNames, CaseCollisions, EmptyDirectories, ReportEvent, ZeroByteFiles};
+use std::env::Args;
+use std::io::Write;
+use std::os::unix::prelude::OsStrExt;
+use termcolor::{Color, ColorSpec, WriteColor};
+
+pub(crate) fn report_cli(config: &Config, args: &mut Args) -> Result<(), CliError> {
+    let name = args.next().ok_or(CliError::MissingReportName)?;
+    if let Some(arg) = args.next() {
+        return Err(CliError::InvalidReportArgument(arg));
+    }
+    let mut aggregator = aggregator(&name)?;
+    let checks = check_databases(config)?;
+    let volume_info = searchable_volumes(config, &checks)?;
+    let mut stdout = Output::stdout();
+    let res = fsidx::report(volume_info, aggregator.as_mut(), None, |event| {
+        match event {
+            ReportEvent::Searching(path) => {
+                if !verbosity() {
+                    return Ok(());
+                }
+                stdout.write_all(b"Searching: ")?;
+                stdout.write_all(path.as_os_str().as_bytes())?;
+            }
+            ReportEvent::Finding(finding) => {
+                stdout.write_all(finding.path.as_os_str().as_bytes())?;
+                stdout.set_color(ColorSpec::new().set_fg(Some(Color::Yellow)))?;
+                stdout.write_all(b" (")?;
+                stdout.write_all(finding.description.as_bytes())?;
+                stdout.write_all(b")")?;
+                stdout.set_color(&ColorSpec::new())?;
+            }
+            _ => return Ok(()),
+        }
+        stdout.end_line()
+    });
+    match res {
+        Ok(()) | Err(fsidx::LocateError::BrokenPipe) => Ok(()),
+        Err(err) => Err(CliError::LocateError(err)),
+    }
+}
+
+fn aggregator(name: &str) -> Result<Box<dyn Aggregator>, CliError> {
+    match name {
+        "case-collisions" => Ok(Box::new(CaseCollisions::new())),
+        "badnames" => Ok(Box::new(BadNames::new())),
+        "empty-dirs" => Ok(Box::new(EmptyDirectories::new())),
+        "zero-byte" => Ok(Box::new(ZeroByteFiles::new())),
+        _ => Err(CliError::InvalidReportArgument(name.to_string())),
+    }
+}
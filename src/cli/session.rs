@@ -0,0 +1,136 @@
+use crate::cli::CliError;
+use crate::storage::write_file;
+use crate::tokenizer::Token;
+use std::io::Write;
+use std::os::unix::prelude::OsStrExt;
+use std::path::{Path, PathBuf};
+
+// Queries and opened files of a shell session in chronological order.
+#[derive(Default)]
+pub(crate) struct Session {
+    entries: Vec<SessionEntry>,
+    // Nothing is recorded in private mode.
+    private: bool,
+    // The line and the alias expanded tokens of the last successful query
+    // for \\again. It is kept in private mode as well, but never saved.
+    last_query: Option<(String, Vec<Token>)>,
+}
+
+enum SessionEntry {
+    Query(String, usize),
+    Opened(PathBuf),
+}
+
+impl Session {
+    pub(crate) fn new() -> Session {
+        Session::default()
+    }
+
+    pub(crate) fn set_private(&mut self, private: bool) {
+        self.private = private;
+    }
+
+    pub(crate) fn query(&mut self, line: &str, results: usize) {
+        if self.private {
+            return;
+        }
+        self.entries
+            .push(SessionEntry::Query(line.trim().to_string(), results));
+    }
+
+    pub(crate) fn opened(&mut self, paths: Vec<PathBuf>) {
+        if self.private {
+            return;
+        }
+        self.entries
+            .extend(paths.into_iter().map(SessionEntry::Opened));
+    }
+
+    pub(crate) fn set_last_query(&mut self, line: &str, token: Vec<Token>) {
+        self.last_query = Some((line.trim().to_string(), token));
+    }
+
+    pub(crate) fn last_query(&self) -> Option<(&str, &[Token])> {
+        self.last_query
+            .as_ref()
+            .map(|(line, token)| (line.as_str(), token.as_slice()))
+    }
+
+    // Recorded queries with their number of results, the last one first.
+    pub(crate) fn queries(&self) -> impl Iterator<Item = (&str, usize)> {
+        self.entries.iter().rev().filter_map(|entry| match entry {
+            SessionEntry::Query(line, results) => Some((line.as_str(), *results)),
+            SessionEntry::Opened(_) => None,
+        })
+    }
+
+    // The nth previous query, starting with 1 for the last one.
+    pub(crate) fn previous_query(&self, n: usize) -> Option<&str> {
+        self.queries().nth(n.checked_sub(1)?).map(|(line, _)| line)
+    }
+
+    pub(crate) fn save(&self, file_name: &Path) -> Result<(), CliError> {
+        write_file(file_name, |writer| self.write(writer))
+            .map_err(|err| CliError::WritingSessionFailed(file_name.to_owned(), err))
+    }
+
+    fn write(&self, writer: &mut dyn Write) -> std::io::Result<()> {
+        for entry in &self.entries {
+            match entry {
+                SessionEntry::Query(line, results) => {
+                    writeln!(writer, "> {}", line)?;
+                    writeln!(writer, "  {} results", results)?;
+                }
+                SessionEntry::Opened(path) => {
+                    writer.write_all(b"  opened: ")?;
+                    writer.write_all(path.as_os_str().as_bytes())?;
+                    writer.write_all(b"\n")?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn session_transcript() {
+        let mut session = Session::new();
+        session.query("Beatles *.flac ", 2);
+        session.opened(vec![PathBuf::from("/Music/Beatles/Help.flac")]);
+        session.query("Stones", 0);
+        session.set_private(true);
+        session.query("secret", 1);
+        session.opened(vec![PathBuf::from("/secret.txt")]);
+        session.set_private(false);
+        let mut transcript = Vec::new();
+        session.write(&mut transcript).unwrap();
+        assert_eq!(
+            String::from_utf8(transcript).unwrap(),
+            concat!(
+                "> Beatles *.flac\n",
+                "  2 results\n",
+                "  opened: /Music/Beatles/Help.flac\n",
+                "> Stones\n",
+                "  0 results\n",
+            )
+        );
+        assert_eq!(
+            session.queries().collect::<Vec<_>>(),
+            vec![("Stones", 0), ("Beatles *.flac", 2)]
+        );
+        assert_eq!(session.previous_query(2), Some("Beatles *.flac"));
+        assert_eq!(session.previous_query(0), None);
+        assert_eq!(session.previous_query(3), None);
+        assert_eq!(session.last_query(), None);
+        session.set_private(true);
+        session.set_last_query("secret ", vec![Token::Text("secret".to_string())]);
+        assert_eq!(
+            session.last_query(),
+            Some(("secret", &[Token::Text("secret".to_string())][..]))
+        );
+    }
+}
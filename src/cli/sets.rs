@@ -0,0 +1,127 @@
+use crate::cli::CliError;
+use std::collections::{BTreeMap, HashSet};
+use std::path::PathBuf;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) enum SetOperation {
+    And,
+    Or,
+    Minus,
+}
+
+// Selections named with \name for the set operations \and, \or and \minus.
+// Combining them doesn't search the database files again.
+#[derive(Default)]
+pub(crate) struct ResultSets {
+    sets: BTreeMap<String, Vec<PathBuf>>,
+}
+
+impl ResultSets {
+    pub(crate) fn new() -> ResultSets {
+        ResultSets::default()
+    }
+
+    pub(crate) fn insert(&mut self, name: &str, paths: Vec<PathBuf>) {
+        self.sets.insert(name.to_string(), paths);
+    }
+
+    fn get(&self, name: &str) -> Result<&[PathBuf], CliError> {
+        self.sets
+            .get(name)
+            .map(Vec::as_slice)
+            .ok_or_else(|| CliError::UnknownResultSet(name.to_string()))
+    }
+
+    // The paths keep the order of the first set. The union appends the paths
+    // of the other sets, which are new. The difference removes the paths of
+    // all other sets.
+    pub(crate) fn combine(
+        &self,
+        operation: SetOperation,
+        names: &[String],
+    ) -> Result<Vec<PathBuf>, CliError> {
+        let sets = names
+            .iter()
+            .map(|name| self.get(name))
+            .collect::<Result<Vec<_>, _>>()?;
+        let Some((first, others)) = sets.split_first() else {
+            return Ok(Vec::new());
+        };
+        let paths = match operation {
+            SetOperation::And => {
+                let others: Vec<HashSet<&PathBuf>> =
+                    others.iter().map(|set| set.iter().collect()).collect();
+                first
+                    .iter()
+                    .filter(|path| others.iter().all(|set| set.contains(path)))
+                    .cloned()
+                    .collect()
+            }
+            SetOperation::Or => {
+                let mut seen = HashSet::new();
+                sets.iter()
+                    .flat_map(|set| set.iter())
+                    .filter(|path| seen.insert(*path))
+                    .cloned()
+                    .collect()
+            }
+            SetOperation::Minus => {
+                let others: HashSet<&PathBuf> = others.iter().flat_map(|set| set.iter()).collect();
+                first
+                    .iter()
+                    .filter(|path| !others.contains(path))
+                    .cloned()
+                    .collect()
+            }
+        };
+        Ok(paths)
+    }
+
+    pub(crate) fn print(&self) {
+        if self.sets.is_empty() {
+            println!("No named results.");
+        }
+        for (name, paths) in &self.sets {
+            println!("{}: {} results", name, paths.len());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn paths(names: &[&str]) -> Vec<PathBuf> {
+        names.iter().map(PathBuf::from).collect()
+    }
+
+    #[test]
+    fn set_operations() {
+        let mut sets = ResultSets::new();
+        sets.insert("a", paths(&["/c", "/a", "/b"]));
+        sets.insert("b", paths(&["/b", "/d", "/c"]));
+        sets.insert("c", paths(&["/c"]));
+        let names =
+            |names: &[&str]| -> Vec<String> { names.iter().map(|name| name.to_string()).collect() };
+        assert_eq!(
+            sets.combine(SetOperation::And, &names(&["a", "b"]))
+                .unwrap(),
+            paths(&["/c", "/b"])
+        );
+        assert_eq!(
+            sets.combine(SetOperation::Or, &names(&["a", "b"])).unwrap(),
+            paths(&["/c", "/a", "/b", "/d"])
+        );
+        assert_eq!(
+            sets.combine(SetOperation::Minus, &names(&["a", "b"]))
+                .unwrap(),
+            paths(&["/a"])
+        );
+        assert_eq!(
+            sets.combine(SetOperation::And, &names(&["a", "b", "c"]))
+                .unwrap(),
+            paths(&["/c"])
+        );
+        assert!(sets.combine(SetOperation::Or, &names(&["a", "x"])).is_err());
+    }
+}
@@ -1,13 +1,34 @@
-use crate::cli::CliError;
-use crate::config::Config;
-use crate::expand::{Expand, OpenRule};
+use crate::actions::{copy_selection, exec_selection, file_action, write_selection, FileAction};
+use crate::buffer::{self, print_budget_warning};
+use crate::cli::{exit_code, CliError};
+use crate::config::{get_offline_folders, get_volume_info, load_from_path, Config, LocateOptions};
+use crate::configure::print_aliases;
+use crate::event::{print_error, print_event};
+use crate::expand::{Expand, OpenRule, Selection};
 use crate::help::{help_shell_long, help_shell_short};
-use crate::locate::locate_shell;
-use crate::tokenizer::{tokenize_shell, Token};
-use crate::tty::set_tty;
-use crate::update::update_shell;
+use crate::locate::{
+    check_databases, filter_selection, locate_shell, print_selection, searchable_volumes,
+    HeaderChecks,
+};
+use crate::open::open_commands;
+use crate::session::Session;
+use crate::sets::{ResultSets, SetOperation};
+use crate::stats::ResourceUsage;
+use crate::storage::{DeferredHistory, History};
+use crate::summary::ExitSummary;
+use crate::tokenizer::{
+    expand_alias_words, expand_aliases, join_tokens, quote, split_words, tokenize_shell, Token,
+};
+use crate::tty::{restore_tty, set_tty, terminal_height};
+use crate::undo::{Operation, UndoStack};
+use crate::update::{update_shell, UpdateOptions};
+use crate::uri::UriMapping;
 use crate::verbosity::verbosity;
-use fsidx::LocateError;
+use crate::warm::warm_databases;
+use fsidx::{
+    DatabaseCache, FilterToken, LocateConfig, LocateError, LocateEvent, LocatePool, Severity,
+    VolumeInfo,
+};
 use rustyline::completion::Completer;
 use rustyline::config::Config as RlConfig;
 use rustyline::error::ReadlineError;
@@ -15,17 +36,20 @@ use rustyline::highlight::Highlighter;
 use rustyline::hint::Hinter;
 use rustyline::Editor;
 use rustyline::{Helper, Validator};
-use signal_hook::consts::signal::SIGINT;
+use signal_hook::consts::signal::{SIGHUP, SIGINT, SIGTERM};
 use signal_hook::iterator::Signals;
 use std::borrow::Cow;
+use std::collections::BTreeSet;
 use std::env::Args;
+use std::fs;
 use std::io::{stderr, stdout, Result as IOResult, Write};
 use std::os::unix::prelude::OsStrExt;
 use std::path::{Path, PathBuf};
 use std::process::Command;
 use std::sync::atomic::{AtomicBool, Ordering};
-use std::sync::Arc;
-use termcolor::{Color, ColorChoice, ColorSpec, StandardStream, WriteColor};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime};
+use toml::{Table, Value};
 
 impl From<ReadlineError> for CliError {
     fn from(err: ReadlineError) -> Self {
@@ -34,23 +58,45 @@ impl From<ReadlineError> for CliError {
     }
 }
 
-pub(crate) fn shell(config: Config, args: &mut Args) -> Result<(), CliError> {
-    if let Some(arg) = args.next() {
-        return Err(CliError::InvalidShellArgument(arg));
+pub(crate) fn shell(
+    mut config: Config,
+    config_file: PathBuf,
+    args: &mut Args,
+) -> Result<(), CliError> {
+    let mut private = false;
+    for arg in args {
+        match arg.as_str() {
+            "--private" => private = true,
+            _ => return Err(CliError::InvalidShellArgument(arg)),
+        }
     }
-    set_tty().map_err(CliError::TtyConfigurationFailed)?;
+    let _tty_guard = set_tty().map_err(CliError::TtyConfigurationFailed)?;
     let abort = Arc::new(AtomicBool::new(false));
-    let mut signals = Signals::new([SIGINT]) // Ctrl-C
+    let termination = Arc::new(Mutex::new(Termination::default()));
+    let mut signals = Signals::new([SIGINT, SIGTERM, SIGHUP]) // Ctrl-C, kill, hangup
         .map_err(CliError::CreatingSignalHandlerFailed)?;
     let abort_for_signal_handler = abort.clone();
+    let termination_for_signal_handler = termination.clone();
     std::thread::spawn(move || {
         let abort = abort_for_signal_handler;
+        let termination = termination_for_signal_handler;
         for sig in signals.forever() {
             if verbosity() {
                 println!("Received signal {}", sig);
             }
-            if sig == SIGINT {
-                abort.store(true, Ordering::Relaxed);
+            abort.store(true, Ordering::Relaxed);
+            if sig == SIGTERM || sig == SIGHUP {
+                let mut termination = termination.lock().unwrap();
+                termination.signal = Some(sig);
+                if !termination.busy {
+                    // Waiting for input. Only deferred lines of the history
+                    // are not saved yet.
+                    restore_tty();
+                    if let Some(deferred) = termination.deferred.take() {
+                        deferred.save();
+                    }
+                    std::process::exit(exit_code(sig));
+                }
             }
         }
     });
@@ -61,43 +107,151 @@ pub(crate) fn shell(config: Config, args: &mut Args) -> Result<(), CliError> {
         .completion_type(rustyline::CompletionType::List)
         .completion_prompt_limit(20)
         .edit_mode(rustyline::EditMode::Emacs)
-        .auto_add_history(true)
+        .auto_add_history(false)
         .bell_style(rustyline::config::BellStyle::None)
         .color_mode(rustyline::ColorMode::Enabled)
         .build();
-    let helper = ShellHelper {};
+    let mut checks = check_databases(&config)?;
+    let helper = ShellHelper::new(&config, &checks);
     let mut rl = Editor::<ShellHelper, _>::with_config(rl_config)?;
     rl.set_helper(Some(helper));
-    let history = if let Some(db_path) = &config.index.db_path {
-        let history = Path::new(&db_path).join("history.txt");
-        if let Err(err) = rl.load_history(&history) {
-            if matches!(err, ReadlineError::Errno(nix::errno::Errno::ENOENT)) {
-                print_error();
-                eprintln!("Reading '{}' failed: {}", history.display(), err);
-            }
-        }
-        Some(history)
-    } else {
-        None
-    };
+    let mut history = History::new(&config)?;
+    if let Some(history) = &history {
+        history.load(&mut rl);
+    }
     let _ = help_shell_short();
-    let mut selection: Option<Vec<PathBuf>> = None;
+    let mut selection: Option<Selection> = None;
+    let mut config_modified = modified(&config_file);
+    let mut cache = DatabaseCache::with_limit(buffer::limit(&config));
+    if config.shell.cache {
+        check_cache_size(&config);
+    }
+    let mut session = Session::new();
+    session.set_private(private);
+    let mut undo = UndoStack::new();
+    let mut overrides = Overrides::new(&config.locate);
+    let mut pool = Pool::new(&config);
+    let mut result_sets = ResultSets::new();
     loop {
-        let readline = rl.readline("> ");
+        let readline = rl.readline(if private { "(private) > " } else { "> " });
+        termination.lock().unwrap().busy = true;
         match readline {
             Ok(line) => {
-                rl.add_history_entry(line.as_str())?;
+                if !private
+                    && !history
+                        .as_ref()
+                        .is_some_and(|history| history.ignores(&line))
+                {
+                    rl.add_history_entry(line.as_str())?;
+                }
                 abort.store(false, Ordering::Relaxed);
-                match process_shell_line(&config, &line, abort.clone(), &selection) {
-                    Ok(ShellAction::Found(s)) => {
+                if config.shell.auto_reload && modified(&config_file) != config_modified {
+                    config_modified = modified(&config_file);
+                    if reload_config(&config_file, &mut config, &mut checks) {
+                        overrides.reloaded(&mut config.locate);
+                    }
+                }
+                if !config.shell.cache || private {
+                    cache.clear();
+                }
+                pool.reconfigure(&config);
+                let usage = ResourceUsage::start();
+                // Database files are not kept in memory in private mode.
+                let databases = Databases {
+                    checks: &mut checks,
+                    cache: (config.shell.cache && !private).then_some(&mut cache),
+                    pool: pool.get(),
+                };
+                let res = process_shell_line(
+                    &config,
+                    &line,
+                    abort.clone(),
+                    &selection,
+                    databases,
+                    &mut session,
+                );
+                ResourceUsage::print(usage, "Command");
+                // Combined results replace the selection like query results.
+                let res = match res {
+                    Ok(ShellAction::Combine(operation, names)) => {
+                        result_sets.combine(operation, &names).and_then(|paths| {
+                            print_selection(&config, &paths, 1)?;
+                            Ok(ShellAction::Found(paths, false))
+                        })
+                    }
+                    res => res,
+                };
+                match res {
+                    Ok(ShellAction::Found(s, partial)) => {
+                        if partial {
+                            println!("CTRL-C: {} results, partial (aborted)", s.len());
+                        }
                         if !s.is_empty() {
-                            selection = Some(s);
+                            let s = Selection {
+                                paths: s,
+                                page_size: page_size(&config),
+                                partial,
+                            };
+                            undo.push(Operation::Selection(selection.replace(s)));
                         }
                     }
+                    Ok(ShellAction::Undo) => match undo.pop() {
+                        Some(Operation::Selection(previous)) => {
+                            selection = previous;
+                            match &selection {
+                                Some(selection) => print_selection(&config, &selection.paths, 1)?,
+                                None => println!("No selection."),
+                            }
+                        }
+                        None => println!("Nothing to undo."),
+                    },
                     Ok(ShellAction::Quit) => {
                         // Don't store \q in history.
+                        if let Some(history) = &mut history {
+                            history.quit(&mut rl, &line);
+                        }
                         break;
                     }
+                    Ok(ShellAction::Reload) => {
+                        config_modified = modified(&config_file);
+                        if reload_config(&config_file, &mut config, &mut checks) {
+                            overrides.reloaded(&mut config.locate);
+                        }
+                    }
+                    Ok(ShellAction::Name(Some(name))) => match &selection {
+                        Some(selection) => {
+                            result_sets.insert(&name, selection.paths.clone());
+                            println!("{}: {} results", name, selection.paths.len());
+                        }
+                        None => {
+                            print_error();
+                            eprintln!("Run a query first.");
+                        }
+                    },
+                    Ok(ShellAction::Name(None)) => result_sets.print(),
+                    Ok(ShellAction::Combine(_, _)) => {}
+                    Ok(ShellAction::Config(args)) => {
+                        if let Err(err) = overrides.command(&mut config.locate, &args) {
+                            print_event(Severity::Error, &err);
+                        }
+                    }
+                    Ok(ShellAction::Private(on)) => {
+                        private = on;
+                        session.set_private(on);
+                        if on {
+                            println!("Private mode: queries are not recorded");
+                        } else {
+                            println!("Private mode off");
+                        }
+                    }
+                    Ok(ShellAction::ToggleTruncate) => {
+                        config.output.truncate = !config.output.truncate;
+                        if config.output.truncate {
+                            println!("Truncating long paths");
+                        } else {
+                            println!("Printing full paths");
+                        }
+                    }
                     Ok(ShellAction::None) => {}
                     Err(CliError::LocateError(LocateError::Aborted)) => {
                         println!("CTRL-C");
@@ -105,10 +259,7 @@ pub(crate) fn shell(config: Config, args: &mut Args) -> Result<(), CliError> {
                     Err(CliError::LocateError(LocateError::BrokenPipe)) => {
                         println!("EOF");
                     }
-                    Err(err) => {
-                        print_error();
-                        eprintln!("{}", err);
-                    }
+                    Err(err) => print_event(Severity::Error, &err),
                 };
             }
             Err(ReadlineError::Interrupted) => {
@@ -124,15 +275,53 @@ pub(crate) fn shell(config: Config, args: &mut Args) -> Result<(), CliError> {
                 break;
             }
         }
-        if let Some(history) = &history {
-            rl.save_history(history).unwrap();
+        if let Some(helper) = rl.helper_mut() {
+            helper.refresh(&config, &checks, &selection);
         }
+        let mut termination = termination.lock().unwrap();
+        if let Some(history) = &mut history {
+            if termination.signal.is_some() {
+                history.flush(&mut rl);
+            } else {
+                termination.deferred = history.save(&mut rl);
+            }
+        }
+        if let Some(sig) = termination.signal {
+            // Still busy, i.e. the signal handler does not exit concurrently.
+            return Err(CliError::Terminated(sig));
+        }
+        termination.busy = false;
+    }
+    if let Some(history) = &mut history {
+        history.flush(&mut rl);
     }
     Ok(())
 }
 
+// SIGTERM and SIGHUP terminate the shell. While a command line is processed
+// termination is deferred until the command is aborted and the history is
+// saved.
+#[derive(Default)]
+struct Termination {
+    busy: bool,
+    signal: Option<i32>,
+    deferred: Option<DeferredHistory>,
+}
+
+// The helper completes with the current selection and the databases of the
+// configuration. Both are updated after each command line.
 #[derive(Helper, Validator)]
-struct ShellHelper {}
+struct ShellHelper {
+    config: Config,
+    volume_info: Vec<VolumeInfo>,
+    selection: Vec<PathBuf>,
+}
+
+// Database entries scanned to find path elements for completion. Larger
+// databases are sampled from the beginning.
+const COMPLETION_SAMPLE: u64 = 200_000;
+// Maximum number of completion candidates.
+const COMPLETION_LIMIT: usize = 100;
 
 const LONG_OPTIONS: [&str; 15] = [
     "--case-sensitive ",
@@ -174,8 +363,67 @@ impl Hinter for ShellHelper {
     }
 }
 
+impl ShellHelper {
+    fn new(config: &Config, checks: &HeaderChecks) -> ShellHelper {
+        let mut helper = ShellHelper {
+            config: config.clone(),
+            volume_info: Vec::new(),
+            selection: Vec::new(),
+        };
+        helper.refresh(config, checks, &None);
+        helper
+    }
+
+    fn refresh(&mut self, config: &Config, checks: &HeaderChecks, selection: &Option<Selection>) {
+        self.config = config.clone();
+        // Completion neither waits for updates nor reads whole databases.
+        self.config.locate.update_wait_ms = 0;
+        self.config.locate.max_entries_scanned = Some(COMPLETION_SAMPLE);
+        self.volume_info = searchable_volumes(&self.config, checks).unwrap_or_default();
+        self.selection = selection
+            .as_ref()
+            .map(|selection| selection.paths.clone())
+            .unwrap_or_default();
+    }
+
+    // Path elements of database entries starting with the partial text,
+    // ignoring case like queries do by default.
+    fn complete_from_databases(&self, partial: &str) -> Vec<String> {
+        let partial = partial.to_lowercase();
+        let filter = vec![FilterToken::Plain, FilterToken::Text(partial.clone())];
+        let enough = Arc::new(AtomicBool::new(false));
+        let mut elements = BTreeSet::new();
+        // Aborted after enough candidates and too broad after the sample,
+        // i.e. the candidates found so far are used in any case.
+        let _ = fsidx::locate(
+            self.volume_info.clone(),
+            filter,
+            &self.config.locate,
+            Some(enough.clone()),
+            |event| {
+                if let LocateEvent::Entry(path, _) = event {
+                    for element in path.iter().filter_map(|element| element.to_str()) {
+                        if element.to_lowercase().starts_with(&partial) {
+                            elements.insert(element.to_string());
+                        }
+                    }
+                    if elements.len() >= COMPLETION_LIMIT {
+                        enough.store(true, Ordering::Relaxed);
+                    }
+                }
+                Ok(())
+            },
+        );
+        elements
+            .into_iter()
+            .take(COMPLETION_LIMIT)
+            .map(|element| quote(&element))
+            .collect()
+    }
+}
+
 impl Completer for ShellHelper {
-    type Candidate = &'static str;
+    type Candidate = String;
 
     fn complete(
         &self,
@@ -185,14 +433,22 @@ impl Completer for ShellHelper {
     ) -> rustyline::Result<(usize, Vec<Self::Candidate>)> {
         let start = start_position(line, pos);
         let partial = &line[start..pos];
+        let command = line[..start].split_whitespace().next();
         if partial.is_empty() {
             Ok((0, Vec::with_capacity(0)))
-        } else {
+        } else if partial.starts_with('-') {
             let candidates = LONG_OPTIONS
                 .into_iter()
                 .filter(|cand| cand.starts_with(partial))
+                .map(String::from)
                 .collect();
             Ok((start, candidates))
+        } else if command == Some("\\o") {
+            Ok((start, complete_from_selection(&self.selection, partial)))
+        } else if partial.starts_with('\\') || command.is_some_and(|cmd| cmd.starts_with('\\')) {
+            Ok((start, Vec::with_capacity(0)))
+        } else {
+            Ok((start, self.complete_from_databases(partial)))
         }
     }
 
@@ -217,6 +473,38 @@ impl Highlighter for ShellHelper {
     }
 }
 
+// Paths of the selection starting with the partial path or with a file name
+// starting with the partial text. The paths are used as globs by \\o, i.e.
+// glob characters are escaped.
+fn complete_from_selection(selection: &[PathBuf], partial: &str) -> Vec<String> {
+    let partial_name = partial.to_lowercase();
+    selection
+        .iter()
+        .filter_map(|path| path.to_str())
+        .filter(|path| {
+            if partial.starts_with('/') {
+                path.starts_with(partial)
+            } else {
+                Path::new(path)
+                    .file_name()
+                    .and_then(|name| name.to_str())
+                    .is_some_and(|name| name.to_lowercase().starts_with(&partial_name))
+            }
+        })
+        .take(COMPLETION_LIMIT)
+        .map(|path| {
+            let mut glob = String::new();
+            for ch in path.chars() {
+                if "*?[]{}\\".contains(ch) {
+                    glob.push('\\');
+                }
+                glob.push(ch);
+            }
+            quote(&glob)
+        })
+        .collect()
+}
+
 fn start_position(line: &str, pos: usize) -> usize {
     if let Some(pos_last_whitespace) = line[0..pos].rfind(|ch: char| ch.is_whitespace()) {
         pos_last_whitespace
@@ -231,18 +519,37 @@ fn start_position(line: &str, pos: usize) -> usize {
 }
 
 enum ShellAction {
-    Found(Vec<PathBuf>),
+    // Results of a query and whether it was aborted.
+    Found(Vec<PathBuf>, bool),
     None,
     Quit,
+    Reload,
+    Private(bool),
+    ToggleTruncate,
+    Undo,
+    Config(Vec<String>),
+    // Names the selection or lists the named selections.
+    Name(Option<String>),
+    Combine(SetOperation, Vec<String>),
+}
+
+// Header checks, cached database files and the threads searching them, which
+// are kept between command lines.
+struct Databases<'a> {
+    checks: &'a mut HeaderChecks,
+    cache: Option<&'a mut DatabaseCache>,
+    pool: Option<&'a LocatePool>,
 }
 
 fn process_shell_line(
     config: &Config,
     line: &str,
     abort: Arc<AtomicBool>,
-    selection: &Option<Vec<PathBuf>>,
+    selection: &Option<Selection>,
+    databases: Databases,
+    session: &mut Session,
 ) -> Result<ShellAction, CliError> {
-    let token = tokenize_shell(line)?;
+    let token = expand_aliases(tokenize_shell(line)?, &config.aliases)?;
     if let Some(Token::Text(command)) = token.first() {
         // Backslash commands:
         if command.starts_with('\\') {
@@ -251,14 +558,172 @@ fn process_shell_line(
                     return Ok(ShellAction::Quit);
                 }
                 "\\o" => {
-                    open_command(config, &token[1..], selection)?;
+                    session.opened(open_command(config, &token[1..], selection)?);
+                }
+                "\\cp" if token.len() >= 3 => {
+                    file_action(FileAction::Copy, &token[1..], selection)?;
+                }
+                "\\mv" if token.len() >= 3 => {
+                    file_action(FileAction::Move, &token[1..], selection)?;
+                }
+                "\\rm" if token.len() >= 2 => {
+                    file_action(FileAction::Remove, &token[1..], selection)?;
+                }
+                "\\x" if token.len() >= 2 => {
+                    let words = expand_alias_words(split_words(line)?, &config.aliases)?;
+                    exec_selection(config, &words, selection, &abort)?;
+                }
+                "\\w" if token.len() >= 2 => {
+                    write_selection(config, &token[1..], selection)?;
+                }
+                "\\y" => {
+                    copy_selection(config, &token[1..], selection)?;
                 }
                 "\\u" if token.len() == 1 => {
-                    update_shell(config)?;
+                    let res = update_shell(
+                        config,
+                        &UpdateOptions::default(),
+                        &mut ExitSummary::update(),
+                        Some(abort.clone()),
+                    );
+                    // Also folders updated before an error or abort are searched.
+                    *databases.checks = check_databases(config)?;
+                    res?;
+                    if abort.load(Ordering::Relaxed) {
+                        return Err(CliError::LocateError(LocateError::Aborted));
+                    }
+                }
+                "\\warm" if token.len() == 1 => {
+                    warm_databases(config, databases.checks, databases.cache)?;
+                }
+                "\\reload" if token.len() == 1 => {
+                    return Ok(ShellAction::Reload);
+                }
+                "\\t" if token.len() == 1 => {
+                    return Ok(ShellAction::ToggleTruncate);
+                }
+                "\\undo" if token.len() == 1 => {
+                    return Ok(ShellAction::Undo);
+                }
+                "\\name" if token.len() <= 2 => {
+                    return match token.get(1) {
+                        Some(Token::Text(name)) => Ok(ShellAction::Name(Some(name.clone()))),
+                        Some(Token::Option(name)) => {
+                            Err(CliError::InvalidShellArgument(name.clone()))
+                        }
+                        None => Ok(ShellAction::Name(None)),
+                    };
+                }
+                "\\and" | "\\or" | "\\minus" if token.len() >= 3 => {
+                    let operation = match command.as_str() {
+                        "\\and" => SetOperation::And,
+                        "\\or" => SetOperation::Or,
+                        _ => SetOperation::Minus,
+                    };
+                    let names = token[1..]
+                        .iter()
+                        .map(|token| match token {
+                            Token::Text(name) => Ok(name.clone()),
+                            Token::Option(name) => {
+                                Err(CliError::InvalidShellArgument(name.clone()))
+                            }
+                        })
+                        .collect::<Result<_, _>>()?;
+                    return Ok(ShellAction::Combine(operation, names));
+                }
+                "\\c" if token.len() <= 3 => {
+                    let args = token[1..].iter().map(|token| match token {
+                        Token::Text(text) | Token::Option(text) => text.clone(),
+                    });
+                    return Ok(ShellAction::Config(args.collect()));
+                }
+                "\\private" if token.len() == 2 => match &token[1] {
+                    Token::Text(on) if on == "on" => return Ok(ShellAction::Private(true)),
+                    Token::Text(off) if off == "off" => return Ok(ShellAction::Private(false)),
+                    _ => {
+                        let _ = help_shell_short();
+                    }
+                },
+                "\\page" if token.len() == 2 => {
+                    if let Token::Text(page) = &token[1] {
+                        page_command(config, page, selection)?;
+                    }
+                }
+                "\\hist" if token.len() == 1 => {
+                    print_history(session);
+                }
+                "\\r" if token.len() <= 2 => {
+                    let n = match token.get(1) {
+                        Some(Token::Text(n)) => n
+                            .parse()
+                            .map_err(|_| CliError::InvalidShellArgument(n.clone()))?,
+                        Some(Token::Option(n)) => {
+                            return Err(CliError::InvalidShellArgument(n.clone()))
+                        }
+                        None => 1,
+                    };
+                    let Some(query) = session.previous_query(n).map(String::from) else {
+                        print_error();
+                        eprintln!("No query {} in the history.", n);
+                        return Ok(ShellAction::None);
+                    };
+                    println!("> {}", query);
+                    return process_shell_line(
+                        config, &query, abort, selection, databases, session,
+                    );
+                }
+                "\\again" => {
+                    let Some((last, last_token)) = session.last_query() else {
+                        print_error();
+                        eprintln!("No previous query.");
+                        return Ok(ShellAction::None);
+                    };
+                    // The extra tokens are appended to the line, also when \again
+                    // is the expansion of an alias.
+                    let query = if token.len() == 1 {
+                        last.to_string()
+                    } else {
+                        format!("{} {}", last, join_tokens(&token[1..]))
+                    };
+                    let mut query_token = last_token.to_vec();
+                    query_token.extend_from_slice(&token[1..]);
+                    println!("> {}", query);
+                    return locate_query(config, &query, query_token, abort, databases, session);
+                }
+                "\\f" if token.len() >= 2 => {
+                    // Refines the selection without reading the database files.
+                    let Some(selection) = selection else {
+                        print_error();
+                        eprintln!("Run a query first.");
+                        return Ok(ShellAction::None);
+                    };
+                    let paths = filter_selection(config, token[1..].to_vec(), &selection.paths)?;
+                    session.query(line, paths.len());
+                    return Ok(ShellAction::Found(paths, false));
+                }
+                "\\save-session" if token.len() == 2 => {
+                    if let Token::Text(file_name) = &token[1] {
+                        session.save(Path::new(file_name))?;
+                        println!("Session written to: {}", file_name);
+                    }
                 }
                 "\\h" => {
                     let _ = help_shell_long();
                 }
+                "\\@" if token.len() == 1 => {
+                    print_aliases(config)?;
+                }
+                _ if command.starts_with("\\@") => {
+                    // Runs the named query with the additional terms.
+                    let name = &command[2..];
+                    if !config.aliases.contains_key(name) {
+                        return Err(CliError::UnknownAlias(name.to_string()));
+                    }
+                    let mut query = vec![Token::Text(format!("@{}", name))];
+                    query.extend_from_slice(&token[1..]);
+                    let query = expand_aliases(query, &config.aliases)?;
+                    return locate_query(config, line, query, abort, databases, session);
+                }
                 _ => {
                     let _ = help_shell_short();
                 }
@@ -268,34 +733,258 @@ fn process_shell_line(
         // Open commands:
         if matches!(
             command.parse::<OpenRule>(),
-            Ok(OpenRule::Index(_)) | Ok(OpenRule::IndexRange(_, _)) | Ok(OpenRule::IndexGlob(_, _))
+            Ok(OpenRule::Index(_))
+                | Ok(OpenRule::IndexRange(_, _))
+                | Ok(OpenRule::IndexGlob(_, _))
+                | Ok(OpenRule::Page(_, _))
         ) {
-            open_command(config, &token, selection)?;
+            session.opened(open_command(config, &token, selection)?);
             return Ok(ShellAction::None);
         }
     }
     // Locate query:
-    match locate_shell(config, line, Some(abort)) {
-        Ok(paths) => Ok(ShellAction::Found(paths)),
+    locate_query(config, line, token, abort, databases, session)
+}
+
+fn locate_query(
+    config: &Config,
+    line: &str,
+    token: Vec<Token>,
+    abort: Arc<AtomicBool>,
+    databases: Databases,
+    session: &mut Session,
+) -> Result<ShellAction, CliError> {
+    let Databases {
+        checks,
+        cache,
+        pool,
+    } = databases;
+    match locate_shell(config, checks, token.clone(), Some(abort), cache, pool) {
+        Ok((paths, partial)) => {
+            session.query(line, paths.len());
+            session.set_last_query(line, token);
+            Ok(ShellAction::Found(paths, partial))
+        }
         Err(err) => Err(err),
     }
 }
 
+// Lists the recent queries numbered like for \r, the last one at the bottom.
+fn print_history(session: &Session) {
+    let queries: Vec<(&str, usize)> = session.queries().take(HISTORY_SIZE).collect();
+    let width = queries.len().to_string().len();
+    for (n, (line, results)) in queries.iter().enumerate().rev() {
+        println!("{:>width$}  {}  ({} results)", n + 1, line, results);
+    }
+}
+
+const HISTORY_SIZE: usize = 20;
+
+// Database files exceeding the memory budget are searched without caching.
+fn check_cache_size(config: &Config) {
+    let size: u64 = get_volume_info(config)
+        .unwrap_or_default()
+        .iter()
+        .filter_map(|vi| fs::metadata(&vi.database).ok())
+        .map(|metadata| metadata.len())
+        .sum();
+    if size > buffer::limit(config) {
+        print_budget_warning(config, "Not all database files can be cached.");
+    }
+}
+
+fn modified(config_file: &Path) -> Option<SystemTime> {
+    fs::metadata(config_file)
+        .and_then(|metadata| metadata.modified())
+        .ok()
+}
+
+// Threads searching folders in parallel are kept between queries. The pool
+// is replaced when its settings are changed by reloading the configuration.
+struct Pool {
+    settings: (usize, u64),
+    pool: Option<LocatePool>,
+}
+
+impl Pool {
+    fn new(config: &Config) -> Pool {
+        let settings = (config.shell.threads, config.shell.thread_idle_timeout_ms);
+        let pool = (settings.0 != 1)
+            .then(|| LocatePool::new(settings.0, Duration::from_millis(settings.1)));
+        Pool { settings, pool }
+    }
+
+    fn reconfigure(&mut self, config: &Config) {
+        if self.settings != (config.shell.threads, config.shell.thread_idle_timeout_ms) {
+            *self = Pool::new(config);
+        }
+    }
+
+    fn get(&self) -> Option<&LocatePool> {
+        self.pool.as_ref()
+    }
+}
+
+// Returns false if the previous configuration stays active.
+fn reload_config(config_file: &Path, config: &mut Config, checks: &mut HeaderChecks) -> bool {
+    match load_from_path(config_file) {
+        Ok(new_config) => {
+            *config = new_config;
+            println!("Reloaded: {}", config_file.display());
+            match check_databases(config) {
+                Ok(new_checks) => *checks = new_checks,
+                Err(err) => {
+                    // Queries fail like the check without database path.
+                    *checks = HeaderChecks::default();
+                    print_event(Severity::Error, &err);
+                }
+            }
+            true
+        }
+        Err(err) => {
+            print_event(Severity::Error, &err);
+            false
+        }
+    }
+}
+
+// Locate defaults changed with \c for the rest of the session. They are
+// applied on top of the [locate] table of the configuration file and kept
+// when it is reloaded.
+struct Overrides {
+    file: LocateConfig,
+    // Keys of LocateOptions, i.e. the per-folder overrides.
+    values: Table,
+}
+
+impl Overrides {
+    fn new(locate: &LocateConfig) -> Overrides {
+        Overrides {
+            file: locate.clone(),
+            values: Table::new(),
+        }
+    }
+
+    fn reloaded(&mut self, locate: &mut LocateConfig) {
+        self.file = locate.clone();
+        *locate = self.options().apply(&self.file);
+    }
+
+    // \c prints the active defaults, \c key value sets one, \c key default
+    // removes the override of a key and \c reset removes all of them.
+    fn command(&mut self, locate: &mut LocateConfig, args: &[String]) -> Result<(), CliError> {
+        match args {
+            [] => return self.print(locate),
+            [reset] if reset == "reset" => self.values.clear(),
+            [key, value] => self.set(key, value)?,
+            _ => return Err(CliError::InvalidSessionSetting(args.join(" "))),
+        }
+        *locate = self.options().apply(&self.file);
+        Ok(())
+    }
+
+    fn set(&mut self, key: &str, value: &str) -> Result<(), CliError> {
+        // Underscores are accepted like in the field names.
+        let key = key.replace('_', "-");
+        let mut values = self.values.clone();
+        if value == "default" {
+            values.remove(&key);
+        } else {
+            values.insert(key.clone(), parse_value(value));
+        }
+        Value::Table(values.clone())
+            .try_into::<LocateOptions>()
+            .map_err(|err| {
+                CliError::InvalidSessionSetting(format!("{} {}: {}", key, value, err.message()))
+            })?;
+        self.values = values;
+        Ok(())
+    }
+
+    fn options(&self) -> LocateOptions {
+        // The values are validated when they are set.
+        Value::Table(self.values.clone())
+            .try_into()
+            .unwrap_or_default()
+    }
+
+    fn print(&self, locate: &LocateConfig) -> Result<(), CliError> {
+        let text = toml::to_string(locate)
+            .map_err(|err| CliError::SerializingConfigFailed(err.to_string()))?;
+        for line in text.lines() {
+            let key = line.split(" = ").next().unwrap_or_default();
+            if self.values.contains_key(key) {
+                println!("{}  # session", line);
+            } else {
+                println!("{}", line);
+            }
+        }
+        Ok(())
+    }
+}
+
+fn parse_value(value: &str) -> Value {
+    match value {
+        "on" | "true" => Value::Boolean(true),
+        "off" | "false" => Value::Boolean(false),
+        _ => match value.parse() {
+            Ok(number) => Value::Integer(number),
+            Err(_) => Value::String(value.to_string()),
+        },
+    }
+}
+
+// Number of results printed on a page. Results of a query are printed without
+// pause, but open commands may refer to an index relative to a page.
+fn page_size(config: &Config) -> usize {
+    if config.shell.page_size > 0 {
+        config.shell.page_size
+    } else {
+        // The prompt line scrolls the first result out of view.
+        terminal_height().map_or(DEFAULT_PAGE_SIZE, |height| height.saturating_sub(1).max(1))
+    }
+}
+
+const DEFAULT_PAGE_SIZE: usize = 20;
+
+fn page_command(
+    config: &Config,
+    page: &str,
+    selection: &Option<Selection>,
+) -> Result<(), CliError> {
+    let Some(selection) = selection else {
+        print_error();
+        eprintln!("Run a query first.");
+        return Ok(());
+    };
+    let page: usize = page
+        .parse()
+        .map_err(|_| CliError::InvalidShellArgument(page.to_string()))?;
+    let (first, paths) = selection
+        .page(page)
+        .ok_or(CliError::InvalidOpenPage(page))?;
+    if selection.partial {
+        println!("Page {} of {}, partial (aborted)", page, selection.pages());
+    } else {
+        println!("Page {} of {}", page, selection.pages());
+    }
+    print_selection(config, paths, first)
+}
+
+// Returns the opened paths.
 fn open_command(
     config: &Config,
     token: &[Token],
-    selection: &Option<Vec<PathBuf>>,
-) -> Result<(), CliError> {
+    selection: &Option<Selection>,
+) -> Result<Vec<PathBuf>, CliError> {
+    let mut opened = Vec::new();
     if let Some(selection) = selection {
-        let mut command = Command::new("open");
-        let mut found = false;
         for token in token {
             match token {
                 crate::tokenizer::Token::Text(text) => {
                     if let Ok(open_rule) = text.parse::<OpenRule>() {
                         let expand = Expand::new(open_rule, selection);
-                        expand
-                            .foreach(|path| open_append(&mut command, path, &mut found, config))?;
+                        expand.foreach(|path| open_append(path, &mut opened, config))?;
                     } else {
                         return Err(CliError::InvalidOpenRule(text.clone()));
                     }
@@ -303,25 +992,23 @@ fn open_command(
                 crate::tokenizer::Token::Option(_) => {} // TODO: Implement options to configure glob expansion.
             };
         }
-        if found {
-            open_spawn(&mut command)?;
+        let files: Vec<(usize, &Path)> = opened
+            .iter()
+            .map(|path| (selection.index(path), path.as_path()))
+            .collect();
+        for mut open in open_commands(config.open.as_ref(), &UriMapping::new(config), &files)? {
+            open_spawn(&mut open.command)?;
         }
     } else {
         print_error();
         eprintln!("Run a query first.");
     }
-    Ok(())
+    Ok(opened)
 }
 
-fn open_append(
-    command: &mut Command,
-    path: &Path,
-    found: &mut bool,
-    config: &Config,
-) -> Result<(), CliError> {
+fn open_append(path: &Path, opened: &mut Vec<PathBuf>, config: &Config) -> Result<(), CliError> {
     if path.exists() {
-        command.arg(path);
-        *found = true;
+        opened.push(path.to_path_buf());
         stdout().write_all(b"Opening: '")?;
         stdout().write_all(path.as_os_str().as_bytes())?;
         stdout().write_all(b"'\n")?;
@@ -330,7 +1017,19 @@ fn open_append(
         stderr().write_all(b"'")?;
         stderr().write_all(path.as_os_str().as_bytes())?;
         stderr().write_all(b"' not exists.")?;
+        let offline = get_offline_folders(config);
+        if let Some((_, id)) = offline.iter().find(|(folder, _)| path.starts_with(folder)) {
+            stderr().write_all(
+                format!(
+                    " Volume with {} is not mounted. Connect the device to open it.\n",
+                    id
+                )
+                .as_bytes(),
+            )?;
+            return Ok(());
+        }
         for base in &config.index.folder {
+            let base = base.path();
             if path.starts_with(base) && !base.exists() {
                 stderr().write_all(b" Device not mounted.")?;
                 break;
@@ -351,10 +1050,114 @@ fn open_spawn(command: &mut Command) -> IOResult<()> {
     Ok(())
 }
 
-pub fn print_error() {
-    let mut stderr = StandardStream::stderr(ColorChoice::Auto);
-    let _ = stderr.set_color(ColorSpec::new().set_fg(Some(Color::Red)));
-    let _ = stderr.write_all(b"Error");
-    let _ = stderr.set_color(&ColorSpec::new());
-    let _ = stderr.write_all(b": ");
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::temp_dir::TempDir;
+    use fsidx::Order;
+
+    // Configuration with the folder /m and the entries /m/song.flac and
+    // /m/song.mp3.
+    fn music(dir: &TempDir, aliases: &str) -> Config {
+        fs::write(
+            dir.join("_m.fsdb"),
+            b"fsix\x00\x00\x0c/m/song.flac\x04\x03mp3",
+        )
+        .unwrap();
+        let config = format!(
+            "[index]\nfolder = [\"/m\"]\ndb_path = {:?}\n[locate]\n[aliases]\n{}",
+            &**dir, aliases
+        );
+        toml::from_str(&config).unwrap()
+    }
+
+    fn run(config: &Config, session: &mut Session, line: &str) -> Result<ShellAction, CliError> {
+        let mut checks = check_databases(config).unwrap();
+        let databases = Databases {
+            checks: &mut checks,
+            cache: None,
+            pool: None,
+        };
+        let abort = Arc::new(AtomicBool::new(false));
+        process_shell_line(config, line, abort, &None, databases, session)
+    }
+
+    fn command(overrides: &mut Overrides, locate: &mut LocateConfig, line: &str) -> bool {
+        let args: Vec<String> = line.split(' ').map(String::from).collect();
+        overrides.command(locate, &args).is_ok()
+    }
+
+    #[test]
+    fn completion_candidates() {
+        let selection = vec![
+            PathBuf::from("/music/Song [live].flac"),
+            PathBuf::from("/music/song.mp3"),
+            PathBuf::from("/photos/song.jpg"),
+        ];
+        assert_eq!(
+            complete_from_selection(&selection, "SONG"),
+            vec![
+                String::from("\"/music/Song \\\\[live\\\\].flac\""),
+                String::from("/music/song.mp3"),
+                String::from("/photos/song.jpg"),
+            ]
+        );
+        assert_eq!(
+            complete_from_selection(&selection, "/photos"),
+            vec![String::from("/photos/song.jpg")]
+        );
+        assert_eq!(
+            tokenize_shell(&complete_from_selection(&selection, "/music/Song")[0]).unwrap(),
+            vec![Token::Text(String::from("/music/Song \\[live\\].flac"))]
+        );
+        assert_eq!(quote("a\"b"), "\"a\\\"b\"");
+    }
+
+    #[test]
+    fn again_through_alias() {
+        let dir = TempDir::new("again");
+        let config = music(&dir, "a = '\\again'\n");
+        let found = |res: Result<ShellAction, CliError>| match res {
+            Ok(ShellAction::Found(paths, false)) => paths,
+            _ => panic!("no results"),
+        };
+        let mut session = Session::new();
+        found(run(&config, &mut session, "song"));
+        // The alias is shorter than \again.
+        assert_eq!(found(run(&config, &mut session, "@a")).len(), 2);
+        assert_eq!(session.last_query().unwrap().0, "song");
+        let paths = found(run(&config, &mut session, "@a flac"));
+        assert_eq!(paths, [PathBuf::from("/m/song.flac")]);
+        assert_eq!(session.last_query().unwrap().0, "song flac");
+    }
+
+    #[test]
+    fn session_overrides() {
+        let mut locate = LocateConfig::default();
+        let mut overrides = Overrides::new(&locate);
+        assert!(command(&mut overrides, &mut locate, "case_sensitive on"));
+        assert!(command(&mut overrides, &mut locate, "order same-order"));
+        assert!(locate.case_sensitive);
+        assert_eq!(locate.order, Order::SameOrder);
+        // Invalid settings don't change anything.
+        assert!(!command(&mut overrides, &mut locate, "order random"));
+        assert!(!command(&mut overrides, &mut locate, "limit 10"));
+        assert!(!command(&mut overrides, &mut locate, "smart-spaces"));
+        assert_eq!(locate.order, Order::SameOrder);
+        // Overrides are kept when the configuration file is reloaded.
+        let mut reloaded = LocateConfig::default();
+        reloaded.word_boundaries = true;
+        overrides.reloaded(&mut reloaded);
+        assert!(reloaded.case_sensitive && reloaded.word_boundaries);
+        assert!(command(
+            &mut overrides,
+            &mut reloaded,
+            "case-sensitive default"
+        ));
+        assert!(!reloaded.case_sensitive);
+        assert_eq!(reloaded.order, Order::SameOrder);
+        assert!(command(&mut overrides, &mut reloaded, "reset"));
+        assert_eq!(reloaded.order, Order::AnyOrder);
+        assert!(reloaded.word_boundaries);
+    }
 }
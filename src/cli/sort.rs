@@ -0,0 +1,223 @@
+use crate::buffer::MemoryBudget;
+use crate::cli::CliError;
+use std::cmp::Ordering;
+use std::fs::{self, File};
+use std::io::{BufReader, BufWriter, ErrorKind, Read, Result as IOResult, Write};
+use std::mem::size_of;
+use std::os::unix::prelude::{OsStrExt, OsStringExt};
+use std::path::{Path, PathBuf};
+use std::process;
+use std::str::FromStr;
+
+type Entry = (PathBuf, Option<u64>);
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) enum SortKey {
+    // Path names in ascending order.
+    Path,
+    // Largest files first. Entries without size are last.
+    Size,
+}
+
+impl FromStr for SortKey {
+    type Err = CliError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "path" => Ok(SortKey::Path),
+            "size" => Ok(SortKey::Size),
+            _ => Err(CliError::InvalidSortKey(s.to_string())),
+        }
+    }
+}
+
+fn compare(key: SortKey, a: &Entry, b: &Entry) -> Ordering {
+    match key {
+        SortKey::Path => a.0.cmp(&b.0),
+        SortKey::Size => match (a.1, b.1) {
+            (Some(a_size), Some(b_size)) => b_size.cmp(&a_size),
+            (Some(_), None) => Ordering::Less,
+            (None, Some(_)) => Ordering::Greater,
+            (None, None) => Ordering::Equal,
+        }
+        .then_with(|| a.0.cmp(&b.0)),
+    }
+}
+
+// Sorts query results with bounded memory. Results are collected until the
+// memory budget is exhausted. Then the collected results are sorted and
+// spilled as a run into a temporary file. Finally, all runs are merged.
+pub(crate) struct ExternalSort {
+    key: SortKey,
+    dir: PathBuf,
+    limit: u64,
+    budget: MemoryBudget,
+    entries: Vec<Entry>,
+    runs: Vec<PathBuf>,
+}
+
+impl ExternalSort {
+    // Runs are written into dir.
+    pub(crate) fn new(key: SortKey, dir: &Path, limit: u64) -> ExternalSort {
+        ExternalSort {
+            key,
+            dir: dir.to_owned(),
+            limit,
+            budget: MemoryBudget::with_limit(limit),
+            entries: Vec::new(),
+            runs: Vec::new(),
+        }
+    }
+
+    pub(crate) fn push(&mut self, path: &Path, size: Option<u64>) -> Result<(), CliError> {
+        let bytes = size_of::<Entry>() + path.as_os_str().len();
+        if !self.budget.reserve(bytes) && !self.entries.is_empty() {
+            self.spill()?;
+            self.budget = MemoryBudget::with_limit(self.limit);
+            self.budget.reserve(bytes);
+        }
+        self.entries.push((path.to_owned(), size));
+        Ok(())
+    }
+
+    fn sort(&mut self) {
+        let key = self.key;
+        self.entries.sort_unstable_by(|a, b| compare(key, a, b));
+    }
+
+    fn spill(&mut self) -> Result<(), CliError> {
+        self.sort();
+        let run = self.dir.join(format!(
+            "fsidx-sort-{}-{}.tmp",
+            process::id(),
+            self.runs.len()
+        ));
+        // Registered before creating the file, so that it is always removed.
+        self.runs.push(run.clone());
+        let map_err = |err| CliError::SortFailed(run.clone(), err);
+        let file = File::create(&run).map_err(map_err)?;
+        let mut writer = BufWriter::new(file);
+        for (path, size) in self.entries.drain(..) {
+            write_entry(&mut writer, &path, size).map_err(map_err)?;
+        }
+        writer.flush().map_err(map_err)
+    }
+
+    // Reports all results in sorted order.
+    pub(crate) fn finish<F: FnMut(&Path, Option<u64>) -> IOResult<()>>(
+        mut self,
+        mut f: F,
+    ) -> Result<(), CliError> {
+        if self.runs.is_empty() {
+            self.sort();
+            for (path, size) in &self.entries {
+                f(path, *size)?;
+            }
+            return Ok(());
+        }
+        self.spill()?;
+        let mut readers = Vec::new();
+        let mut heads = Vec::new();
+        for run in &self.runs {
+            let map_err = |err| CliError::SortFailed(run.clone(), err);
+            let mut reader = BufReader::new(File::open(run).map_err(map_err)?);
+            heads.push(read_entry(&mut reader).map_err(map_err)?);
+            readers.push(reader);
+        }
+        loop {
+            let next = heads
+                .iter()
+                .enumerate()
+                .filter_map(|(index, head)| head.as_ref().map(|head| (index, head)))
+                .min_by(|(_, a), (_, b)| compare(self.key, a, b))
+                .map(|(index, _)| index);
+            let Some(index) = next else {
+                return Ok(());
+            };
+            if let Some((path, size)) = heads[index].take() {
+                f(&path, size)?;
+            }
+            heads[index] = read_entry(&mut readers[index])
+                .map_err(|err| CliError::SortFailed(self.runs[index].clone(), err))?;
+        }
+    }
+}
+
+impl Drop for ExternalSort {
+    fn drop(&mut self) {
+        for run in &self.runs {
+            let _ = fs::remove_file(run);
+        }
+    }
+}
+
+// Entry format in a run: path length, path, size plus one or zero
+fn write_entry(writer: &mut dyn Write, path: &Path, size: Option<u64>) -> IOResult<()> {
+    let bytes = path.as_os_str().as_bytes();
+    writer.write_all(&(bytes.len() as u64).to_le_bytes())?;
+    writer.write_all(bytes)?;
+    writer.write_all(&size.map(|size| size + 1).unwrap_or(0).to_le_bytes())
+}
+
+fn read_entry(reader: &mut dyn Read) -> IOResult<Option<Entry>> {
+    let mut len = [0u8; 8];
+    match reader.read_exact(&mut len) {
+        Ok(()) => {}
+        Err(err) if err.kind() == ErrorKind::UnexpectedEof => return Ok(None),
+        Err(err) => return Err(err),
+    }
+    let mut bytes = vec![0u8; u64::from_le_bytes(len) as usize];
+    reader.read_exact(&mut bytes)?;
+    let mut size = [0u8; 8];
+    reader.read_exact(&mut size)?;
+    let size = u64::from_le_bytes(size).checked_sub(1);
+    let path = PathBuf::from(std::ffi::OsString::from_vec(bytes));
+    Ok(Some((path, size)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sorted(key: SortKey, limit: u64) -> (Vec<Entry>, usize) {
+        let entries: Vec<Entry> = vec![
+            (PathBuf::from("/b/2.flac"), Some(200)),
+            (PathBuf::from("/a/1.flac"), Some(100)),
+            (PathBuf::from("/c"), None),
+            (PathBuf::from("/a/3.flac"), Some(300)),
+            (PathBuf::from("/b/1.flac"), Some(100)),
+        ];
+        let mut sort = ExternalSort::new(key, &std::env::temp_dir(), limit);
+        for (path, size) in &entries {
+            sort.push(path, *size).unwrap();
+        }
+        let runs = sort.runs.len();
+        let mut result = Vec::new();
+        sort.finish(|path, size| {
+            result.push((path.to_owned(), size));
+            Ok(())
+        })
+        .unwrap();
+        (result, runs)
+    }
+
+    #[test]
+    fn sort_in_memory_and_spilled() {
+        let entry_size = size_of::<Entry>() as u64 + 9;
+        for limit in [1 << 20, 2 * entry_size, 1] {
+            let (result, runs) = sorted(SortKey::Path, limit);
+            let paths: Vec<&str> = result.iter().map(|e| e.0.to_str().unwrap()).collect();
+            assert_eq!(
+                paths,
+                vec!["/a/1.flac", "/a/3.flac", "/b/1.flac", "/b/2.flac", "/c"]
+            );
+            assert_eq!(runs > 0, limit != 1 << 20);
+            let (result, _) = sorted(SortKey::Size, limit);
+            let paths: Vec<&str> = result.iter().map(|e| e.0.to_str().unwrap()).collect();
+            assert_eq!(
+                paths,
+                vec!["/a/3.flac", "/b/2.flac", "/a/1.flac", "/b/1.flac", "/c"]
+            );
+        }
+    }
+}
@@ -0,0 +1,111 @@
+use crate::event::{print_event, Message};
+use crate::locate::format_size;
+use crate::verbosity::verbosity_level;
+use fsidx::Severity;
+use std::fs;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+// Resource usage of a query or an update, printed with -vv when the stats
+// feature is enabled. The feature installs an allocator counting all
+// allocations of the process.
+pub(crate) struct ResourceUsage {
+    allocations: u64,
+    allocated: u64,
+    bytes_read: Option<u64>,
+}
+
+static ALLOCATIONS: AtomicU64 = AtomicU64::new(0);
+static ALLOCATED: AtomicU64 = AtomicU64::new(0);
+
+#[cfg(feature = "stats")]
+mod allocator {
+    use super::{ALLOCATED, ALLOCATIONS};
+    use std::alloc::{GlobalAlloc, Layout, System};
+    use std::sync::atomic::Ordering;
+
+    struct CountingAllocator;
+
+    unsafe impl GlobalAlloc for CountingAllocator {
+        unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+            ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+            ALLOCATED.fetch_add(layout.size() as u64, Ordering::Relaxed);
+            System.alloc(layout)
+        }
+
+        unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+            System.dealloc(ptr, layout)
+        }
+
+        unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+            ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+            ALLOCATED.fetch_add(new_size as u64, Ordering::Relaxed);
+            System.realloc(ptr, layout, new_size)
+        }
+    }
+
+    #[global_allocator]
+    static ALLOCATOR: CountingAllocator = CountingAllocator;
+}
+
+impl ResourceUsage {
+    // Returns None, unless the usage is printed.
+    pub(crate) fn start() -> Option<ResourceUsage> {
+        if !cfg!(feature = "stats") || verbosity_level() < 2 {
+            return None;
+        }
+        Some(ResourceUsage::now())
+    }
+
+    fn now() -> ResourceUsage {
+        ResourceUsage {
+            allocations: ALLOCATIONS.load(Ordering::Relaxed),
+            allocated: ALLOCATED.load(Ordering::Relaxed),
+            bytes_read: bytes_read(),
+        }
+    }
+
+    pub(crate) fn print(start: Option<ResourceUsage>, what: &str) {
+        let Some(start) = start else {
+            return;
+        };
+        let end = ResourceUsage::now();
+        let mut message = format!(
+            "{}: {} allocations with {} bytes",
+            what,
+            format_size(end.allocations - start.allocations),
+            format_size(end.allocated - start.allocated)
+        );
+        if let (Some(start), Some(end)) = (start.bytes_read, end.bytes_read) {
+            message.push_str(&format!(", {} bytes read", format_size(end - start)));
+        }
+        if let Some(peak) = peak_rss() {
+            message.push_str(&format!(", peak RSS {} bytes", format_size(peak)));
+        }
+        print_event(Severity::Info, &Message::new("resource-usage", message));
+    }
+}
+
+// Bytes read by the process including the page cache. Only available on
+// Linux.
+fn bytes_read() -> Option<u64> {
+    let io = fs::read_to_string("/proc/self/io").ok()?;
+    io.lines()
+        .find_map(|line| line.strip_prefix("rchar:"))
+        .and_then(|value| value.trim().parse().ok())
+}
+
+// Maximum resident set size of the process in bytes.
+fn peak_rss() -> Option<u64> {
+    let mut usage = std::mem::MaybeUninit::<nix::libc::rusage>::uninit();
+    let res = unsafe { nix::libc::getrusage(nix::libc::RUSAGE_SELF, usage.as_mut_ptr()) };
+    if res != 0 {
+        return None;
+    }
+    let max_rss = unsafe { usage.assume_init() }.ru_maxrss as u64;
+    // Linux reports kilobytes, macOS bytes.
+    if cfg!(target_os = "macos") {
+        Some(max_rss)
+    } else {
+        Some(max_rss * 1024)
+    }
+}
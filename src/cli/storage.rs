@@ -0,0 +1,274 @@
+use crate::cli::CliError;
+use crate::config::Config;
+use crate::event::print_event;
+use fsidx::Severity;
+use globset::{Glob, GlobSet, GlobSetBuilder};
+use nix::unistd::mkdtemp;
+use rustyline::error::ReadlineError;
+use rustyline::history::{FileHistory, History as _};
+use rustyline::{Editor, Helper};
+use std::env;
+use std::ffi::OsString;
+use std::fs::{self, File, OpenOptions};
+use std::io::{BufWriter, ErrorKind, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+// Files written by the shell are first written to a temporary file next to
+// the target and then renamed. A crash or a full disk never leaves a
+// truncated file behind. The file is left unchanged, if the temporary file
+// isn't created, e.g. by rustyline without new history entries.
+pub(crate) fn replace_file(
+    path: &Path,
+    write: impl FnOnce(&Path) -> std::io::Result<()>,
+) -> std::io::Result<()> {
+    let mut temp = OsString::from(path.as_os_str());
+    temp.push(".tmp");
+    let temp = PathBuf::from(temp);
+    // A stale temporary file or a planted symlink is removed, not followed.
+    match fs::remove_file(&temp) {
+        Err(err) if err.kind() != ErrorKind::NotFound => return Err(err),
+        _ => {}
+    }
+    let res = write(&temp).and_then(|_| {
+        let file = match File::open(&temp) {
+            Err(err) if err.kind() == ErrorKind::NotFound => return Ok(()),
+            res => res?,
+        };
+        file.sync_all()?;
+        fs::rename(&temp, path)
+    });
+    if res.is_err() {
+        let _ = fs::remove_file(&temp);
+    }
+    res
+}
+
+// Creates a directory only accessible by the user with a random name in the
+// temporary folder, e.g. for files passed to other programs. An existing
+// directory is never reused.
+pub(crate) fn private_temp_dir(prefix: &str) -> std::io::Result<PathBuf> {
+    let template = env::temp_dir().join(format!("{}-XXXXXX", prefix));
+    Ok(mkdtemp(&template)?)
+}
+
+pub(crate) fn write_file(
+    path: &Path,
+    write: impl FnOnce(&mut dyn Write) -> std::io::Result<()>,
+) -> std::io::Result<()> {
+    replace_file(path, |temp| {
+        let file = OpenOptions::new().write(true).create_new(true).open(temp)?;
+        let mut writer = BufWriter::new(file);
+        write(&mut writer)?;
+        writer.flush()
+    })
+}
+
+// Saving the history is deferred, while lines are entered in quick
+// succession. Deferred lines are saved with the next line or when the shell
+// exits.
+const SAVE_INTERVAL: Duration = Duration::from_secs(1);
+
+// The history of the shell stored in the history-file of the shell table,
+// by default history.txt in the db_path folder.
+pub(crate) struct History {
+    path: PathBuf,
+    // Command lines matching these patterns are not stored.
+    ignore: GlobSet,
+    saved: Option<Instant>,
+    pending: bool,
+    // Saving is disabled after the first failure to report it only once.
+    failed: bool,
+}
+
+impl History {
+    // Returns None, if the history is disabled.
+    pub(crate) fn new(config: &Config) -> Result<Option<History>, CliError> {
+        let shell = &config.shell;
+        if !shell.history {
+            return Ok(None);
+        }
+        let path = match (&shell.history_file, &config.index.db_path) {
+            (Some(file), Some(db_path)) => db_path.join(file),
+            (Some(file), None) => file.clone(),
+            (None, Some(db_path)) => db_path.join("history.txt"),
+            (None, None) => return Ok(None),
+        };
+        let mut ignore = GlobSetBuilder::new();
+        for pattern in &shell.history_ignore {
+            let glob = Glob::new(pattern)
+                .map_err(|err| CliError::GlobPatternError(pattern.clone(), err))?;
+            ignore.add(glob);
+        }
+        let ignore = ignore
+            .build()
+            .map_err(|err| CliError::GlobPatternError(shell.history_ignore.join(", "), err))?;
+        Ok(Some(History {
+            path,
+            ignore,
+            saved: None,
+            pending: false,
+            failed: false,
+        }))
+    }
+
+    // Leading and trailing white space is ignored.
+    pub(crate) fn ignores(&self, line: &str) -> bool {
+        self.ignore.is_match(line.trim())
+    }
+
+    pub(crate) fn load<H: Helper>(&self, rl: &mut Editor<H, FileHistory>) {
+        match rl.load_history(&self.path) {
+            Ok(()) => {}
+            Err(ReadlineError::Io(err)) if err.kind() == ErrorKind::NotFound => {}
+            Err(err) => print_event(
+                Severity::Warning,
+                &CliError::ReadingHistoryFailed(self.path.clone(), io_error(err)),
+            ),
+        }
+    }
+
+    // Returns the lines to save, if the save is deferred.
+    pub(crate) fn save<H: Helper>(
+        &mut self,
+        rl: &mut Editor<H, FileHistory>,
+    ) -> Option<DeferredHistory> {
+        self.pending = true;
+        if self
+            .saved
+            .is_some_and(|saved| saved.elapsed() < SAVE_INTERVAL)
+        {
+            if self.failed {
+                return None;
+            }
+            let mut lines = FileHistory::new();
+            for line in rl.history().iter() {
+                let _ = lines.add(line);
+            }
+            return Some(DeferredHistory {
+                path: self.path.clone(),
+                lines,
+            });
+        }
+        self.flush(rl);
+        None
+    }
+
+    // Saves deferred lines, but not the line quitting the shell.
+    pub(crate) fn quit<H: Helper>(&mut self, rl: &mut Editor<H, FileHistory>, line: &str) {
+        let mut entries: Vec<String> = rl.history().iter().cloned().collect();
+        if entries.last().map(String::as_str) == Some(line) {
+            entries.pop();
+            let _ = rl.clear_history();
+            for entry in entries {
+                let _ = rl.add_history_entry(entry);
+            }
+        }
+        self.flush(rl);
+    }
+
+    pub(crate) fn flush<H: Helper>(&mut self, rl: &mut Editor<H, FileHistory>) {
+        if !self.pending || self.failed {
+            return;
+        }
+        let res = replace_file(&self.path, |temp| rl.save_history(temp).map_err(io_error));
+        self.pending = false;
+        self.saved = Some(Instant::now());
+        if let Err(err) = res {
+            self.failed = true;
+            print_event(
+                Severity::Warning,
+                &CliError::WritingHistoryFailed(self.path.clone(), err),
+            );
+        }
+    }
+}
+
+// A copy of the history with deferred lines. The signal handler saves it, if
+// the shell is terminated while waiting for input, i.e. while the editor is
+// not accessible.
+pub(crate) struct DeferredHistory {
+    path: PathBuf,
+    lines: FileHistory,
+}
+
+impl DeferredHistory {
+    pub(crate) fn save(mut self) {
+        let res = replace_file(&self.path, |temp| self.lines.save(temp).map_err(io_error));
+        if let Err(err) = res {
+            print_event(
+                Severity::Warning,
+                &CliError::WritingHistoryFailed(self.path, err),
+            );
+        }
+    }
+}
+
+fn io_error(err: ReadlineError) -> std::io::Error {
+    match err {
+        ReadlineError::Io(err) => err,
+        err => std::io::Error::other(err.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::temp_dir::TempDir;
+
+    #[test]
+    fn replace() {
+        let dir = TempDir::new("storage");
+        let path = dir.join("storage.txt");
+        write_file(&path, |writer| writer.write_all(b"first\n")).unwrap();
+        let res = write_file(&path, |writer| {
+            writer.write_all(b"second\n")?;
+            Err(std::io::Error::other("failed"))
+        });
+        assert!(res.is_err());
+        // The file is unchanged and the temporary file removed.
+        assert_eq!(fs::read(&path).unwrap(), b"first\n");
+        assert!(!Path::new(&format!("{}.tmp", path.display())).exists());
+    }
+
+    #[test]
+    fn ignore() {
+        let mut ignore = GlobSetBuilder::new();
+        ignore.add(Glob::new("*secret*").unwrap());
+        ignore.add(Glob::new("\\\\q").unwrap());
+        let history = History {
+            path: PathBuf::from("history.txt"),
+            ignore: ignore.build().unwrap(),
+            saved: None,
+            pending: false,
+            failed: false,
+        };
+        assert!(history.ignores("my secret files "));
+        assert!(history.ignores(" \\q"));
+        assert!(!history.ignores("\\quit"));
+        assert!(!history.ignores("flac"));
+    }
+
+    #[test]
+    fn deferred() {
+        let dir = TempDir::new("deferred");
+        let path = dir.join("history.txt");
+        let mut history = History {
+            path: path.clone(),
+            ignore: GlobSetBuilder::new().build().unwrap(),
+            saved: None,
+            pending: false,
+            failed: false,
+        };
+        let mut rl = Editor::<(), FileHistory>::new().unwrap();
+        rl.add_history_entry("flac").unwrap();
+        assert!(history.save(&mut rl).is_none());
+        rl.add_history_entry("mp3").unwrap();
+        let deferred = history.save(&mut rl).unwrap();
+        let saved = fs::read_to_string(&path).unwrap();
+        assert!(saved.contains("flac") && !saved.contains("mp3"));
+        deferred.save();
+        let saved = fs::read_to_string(&path).unwrap();
+        assert!(saved.contains("flac") && saved.contains("mp3"));
+    }
+}
@@ -0,0 +1,362 @@
+use crate::buffer::{print_budget_warning, MemoryBudget};
+use crate::config::Config;
+use crate::locate::format_size;
+use crate::output::Output;
+use crate::verbosity::exit_summary;
+use fsidx::{LocateEvent, Severity, UpdateEvent};
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap};
+use std::fmt::Display;
+use std::io::{Result as IOResult, Write};
+use std::mem::size_of;
+use std::os::unix::prelude::OsStrExt;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+use termcolor::{Color, ColorSpec, WriteColor};
+
+// Number of directories and files listed in a summary.
+const TOP: usize = 10;
+
+#[derive(Default)]
+struct DirectoryStatistics {
+    results: usize,
+    size: u64,
+}
+
+// Summary of the results of a query, printed after the results.
+pub(crate) struct Summary {
+    budget: MemoryBudget,
+    results: usize,
+    size: u64,
+    directories: HashMap<PathBuf, DirectoryStatistics>,
+    largest: BinaryHeap<Reverse<(u64, PathBuf)>>,
+}
+
+impl Summary {
+    pub(crate) fn new(config: &Config) -> Summary {
+        Summary {
+            budget: MemoryBudget::new(config),
+            results: 0,
+            size: 0,
+            directories: HashMap::new(),
+            largest: BinaryHeap::new(),
+        }
+    }
+
+    pub(crate) fn add(&mut self, path: &Path, size: Option<u64>) {
+        self.results += 1;
+        self.size += size.unwrap_or(0);
+        if let Some(size) = size {
+            if self.largest.len() < TOP {
+                self.largest.push(Reverse((size, path.to_owned())));
+            } else if self.largest.peek().is_some_and(|min| min.0 .0 < size) {
+                self.largest.pop();
+                self.largest.push(Reverse((size, path.to_owned())));
+            }
+        }
+        let Some(parent) = path.parent() else {
+            return;
+        };
+        // Directories exceeding the memory budget are not counted.
+        let statistics = match self.directories.get_mut(parent) {
+            Some(statistics) => statistics,
+            None if self.budget.reserve(
+                size_of::<(PathBuf, DirectoryStatistics)>() + parent.as_os_str().len(),
+            ) =>
+            {
+                self.directories.entry(parent.to_owned()).or_default()
+            }
+            None => return,
+        };
+        statistics.results += 1;
+        statistics.size += size.unwrap_or(0);
+    }
+
+    // Directories with most results.
+    fn top_directories(&self) -> Vec<(&Path, &DirectoryStatistics)> {
+        let mut directories: Vec<(&Path, &DirectoryStatistics)> = self
+            .directories
+            .iter()
+            .map(|(path, statistics)| (path.as_path(), statistics))
+            .collect();
+        directories.sort_by(|a, b| b.1.results.cmp(&a.1.results).then(a.0.cmp(b.0)));
+        directories.truncate(TOP);
+        directories
+    }
+
+    fn largest_files(&self) -> Vec<(u64, &Path)> {
+        let mut largest: Vec<(u64, &Path)> = self
+            .largest
+            .iter()
+            .map(|Reverse((size, path))| (*size, path.as_path()))
+            .collect();
+        largest.sort_by(|a, b| b.0.cmp(&a.0).then(a.1.cmp(b.1)));
+        largest
+    }
+
+    pub(crate) fn print(&self, config: &Config) -> IOResult<()> {
+        if self.budget.exceeded() {
+            print_budget_warning(config, "Top directories are incomplete.");
+        }
+        let mut stdout = Output::stdout();
+        stdout.end_line()?;
+        print_heading(&mut stdout, "Summary:")?;
+        write!(
+            stdout,
+            "    {} results, {} bytes",
+            self.results,
+            format_size(self.size)
+        )?;
+        stdout.end_line()?;
+        let directories = self.top_directories();
+        if !directories.is_empty() {
+            print_heading(&mut stdout, "Top directories:")?;
+            for (path, statistics) in directories {
+                write!(
+                    stdout,
+                    "    {:>8} {:>17}  ",
+                    statistics.results,
+                    format_size(statistics.size)
+                )?;
+                stdout.write_all(path.as_os_str().as_bytes())?;
+                stdout.end_line()?;
+            }
+        }
+        let largest = self.largest_files();
+        if !largest.is_empty() {
+            print_heading(&mut stdout, "Largest files:")?;
+            for (size, path) in largest {
+                write!(stdout, "    {:>17}  ", format_size(size))?;
+                stdout.write_all(path.as_os_str().as_bytes())?;
+                stdout.end_line()?;
+            }
+        }
+        Ok(())
+    }
+}
+
+// Counts and timing of a locate or update run, printed to stderr when the
+// run exits.
+pub(crate) struct ExitSummary {
+    start: Instant,
+    volumes: u64,
+    entries: u64,
+    // Not counted for updates.
+    matches: Option<u64>,
+    errors: u64,
+    // Updated folders, not collected for queries.
+    scans: Vec<fsidx::ScanSummary>,
+}
+
+impl ExitSummary {
+    pub(crate) fn locate() -> ExitSummary {
+        ExitSummary {
+            matches: Some(0),
+            ..ExitSummary::update()
+        }
+    }
+
+    pub(crate) fn update() -> ExitSummary {
+        ExitSummary {
+            start: Instant::now(),
+            volumes: 0,
+            entries: 0,
+            matches: None,
+            errors: 0,
+            scans: Vec::new(),
+        }
+    }
+
+    pub(crate) fn add_locate_event(&mut self, event: &LocateEvent) {
+        if let LocateEvent::Statistics(_, statistics) = event {
+            self.volumes += 1;
+            self.entries += statistics.entries;
+            if let Some(matches) = self.matches.as_mut() {
+                *matches += statistics.matches;
+            }
+        }
+    }
+
+    pub(crate) fn add_update_event(&mut self, event: &UpdateEvent) {
+        if event.severity() > Severity::Info {
+            self.errors += 1;
+        }
+        match event {
+            UpdateEvent::Statistics(_, statistics) => {
+                self.volumes += 1;
+                self.entries += statistics.entries;
+            }
+            UpdateEvent::ScanSummary(summary) => self.scans.push(summary.clone()),
+            _ => {}
+        }
+    }
+
+    pub(crate) fn add_errors(&mut self, errors: u64) {
+        self.errors += errors;
+    }
+
+    // Printed with -v or --summary.
+    pub(crate) fn print(&self) {
+        if exit_summary() {
+            eprintln!("{}", self);
+        }
+    }
+
+    // Table of the updated folders with entries, scan time and size of the
+    // database file. Shared database files are counted once in the total.
+    pub(crate) fn print_scans(&self) -> IOResult<()> {
+        if self.scans.is_empty() {
+            return Ok(());
+        }
+        let mut stdout = Output::stdout();
+        print_heading(&mut stdout, "Updated folders:")?;
+        write!(
+            stdout,
+            "    {:>13} {:>9} {:>17}  folder",
+            "entries", "seconds", "database bytes"
+        )?;
+        stdout.end_line()?;
+        let mut entries = 0;
+        let mut duration = Duration::ZERO;
+        let mut db_bytes = 0;
+        let mut databases = Vec::new();
+        for scan in &self.scans {
+            print_scan_row(
+                &mut stdout,
+                scan.entries,
+                scan.duration,
+                scan.db_bytes,
+                &scan.folder,
+            )?;
+            entries += scan.entries;
+            duration += scan.duration;
+            if !databases.contains(&&scan.database) {
+                databases.push(&scan.database);
+                db_bytes += scan.db_bytes;
+            }
+        }
+        if self.scans.len() > 1 {
+            print_scan_row(&mut stdout, entries, duration, db_bytes, Path::new("total"))?;
+        }
+        Ok(())
+    }
+}
+
+fn print_scan_row(
+    stdout: &mut Output,
+    entries: u64,
+    duration: Duration,
+    db_bytes: u64,
+    folder: &Path,
+) -> IOResult<()> {
+    write!(
+        stdout,
+        "    {:>13} {:>9.1} {:>17}  ",
+        format_size(entries),
+        duration.as_secs_f64(),
+        format_size(db_bytes)
+    )?;
+    stdout.write_all(folder.as_os_str().as_bytes())?;
+    stdout.end_line()
+}
+
+impl Display for ExitSummary {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_fmt(format_args!(
+            "Summary: {} volumes, {} entries",
+            self.volumes, self.entries
+        ))?;
+        if let Some(matches) = self.matches {
+            f.write_fmt(format_args!(", {} matches", matches))?;
+        }
+        f.write_fmt(format_args!(
+            ", {} errors, {:.2} s",
+            self.errors,
+            self.start.elapsed().as_secs_f64()
+        ))
+    }
+}
+
+fn print_heading(stdout: &mut Output, heading: &str) -> IOResult<()> {
+    stdout.set_color(ColorSpec::new().set_fg(Some(Color::Yellow)))?;
+    stdout.write_all(heading.as_bytes())?;
+    stdout.set_color(&ColorSpec::new())?;
+    stdout.end_line()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn top_directories_and_largest_files() {
+        let mut summary = Summary {
+            budget: MemoryBudget::with_limit(1 << 20),
+            results: 0,
+            size: 0,
+            directories: HashMap::new(),
+            largest: BinaryHeap::new(),
+        };
+        summary.add(Path::new("/a/1.flac"), Some(100));
+        summary.add(Path::new("/b/1.flac"), Some(50));
+        summary.add(Path::new("/b/2.flac"), Some(70));
+        summary.add(Path::new("/c"), None);
+        for size in 0..TOP as u64 {
+            summary.add(Path::new("/d/small"), Some(size));
+        }
+        assert_eq!(summary.results, 4 + TOP);
+        let directories: Vec<(&Path, usize)> = summary
+            .top_directories()
+            .into_iter()
+            .map(|(path, statistics)| (path, statistics.results))
+            .collect();
+        assert_eq!(
+            directories,
+            vec![
+                (Path::new("/d"), TOP),
+                (Path::new("/b"), 2),
+                (Path::new("/"), 1),
+                (Path::new("/a"), 1)
+            ]
+        );
+        let largest = summary.largest_files();
+        assert_eq!(largest.len(), TOP);
+        assert_eq!(largest[0], (100, Path::new("/a/1.flac")));
+        assert_eq!(largest[1], (70, Path::new("/b/2.flac")));
+        assert_eq!(largest[TOP - 1], (3, Path::new("/d/small")));
+    }
+
+    #[test]
+    fn exit_summary() {
+        let mut exit_summary = ExitSummary::locate();
+        let statistics = fsidx::LocateStatistics {
+            entries: 1000,
+            matches: 3,
+            ..Default::default()
+        };
+        exit_summary.add_locate_event(&LocateEvent::Statistics(Path::new("/a"), &statistics));
+        exit_summary.add_locate_event(&LocateEvent::Statistics(Path::new("/b"), &statistics));
+        exit_summary.add_errors(1);
+        let text = exit_summary.to_string();
+        assert!(
+            text.starts_with("Summary: 2 volumes, 2000 entries, 6 matches, 1 errors, "),
+            "{}",
+            text
+        );
+        let mut exit_summary = ExitSummary::update();
+        exit_summary.add_update_event(&UpdateEvent::Statistics(
+            PathBuf::from("/a"),
+            fsidx::ScanStatistics {
+                entries: 10,
+                ..Default::default()
+            },
+        ));
+        exit_summary.add_update_event(&UpdateEvent::ScanningFailed(PathBuf::from("/b")));
+        let text = exit_summary.to_string();
+        assert!(
+            text.starts_with("Summary: 1 volumes, 10 entries, 1 errors, "),
+            "{}",
+            text
+        );
+    }
+}
@@ -0,0 +1,122 @@
+use crate::cli::CliError;
+use std::ffi::OsString;
+use std::path::Path;
+use std::process::Command;
+
+// Placeholders which are replaced with a property of a single file.
+const FILE_PLACEHOLDERS: &[&str] = &["path", "dir", "basename", "ext", "index", "uri"];
+
+// Values for the placeholders of a command template.
+#[derive(Default)]
+pub(crate) struct Values<'a> {
+    // File for {path}, {dir}, {basename} and {ext}.
+    pub(crate) path: Option<&'a Path>,
+    // Index of the file in the query results for {index}.
+    pub(crate) index: Option<usize>,
+    // URI of the file for {uri}.
+    pub(crate) uri: Option<&'a str>,
+    // File with a list of paths for {list}.
+    pub(crate) list: Option<&'a Path>,
+}
+
+// Returns true, if the template must be run once for every file.
+pub(crate) fn has_file_placeholder(template: &str) -> bool {
+    FILE_PLACEHOLDERS
+        .iter()
+        .any(|name| template.contains(&format!("{{{}}}", name)))
+}
+
+pub(crate) fn has_list_placeholder(template: &str) -> bool {
+    template.contains("{list}")
+}
+
+// Creates a command from a template. Arguments are separated by white space.
+// Placeholders are substituted within each argument, so that substituted
+// paths containing white space are passed as a single argument.
+pub(crate) fn command(template: &str, values: &Values) -> Result<Command, CliError> {
+    let mut words = template.split_whitespace();
+    let program = words
+        .next()
+        .ok_or_else(|| CliError::InvalidTemplate(template.to_string()))?;
+    let mut command = Command::new(substitute(program, values)?);
+    for word in words {
+        command.arg(substitute(word, values)?);
+    }
+    Ok(command)
+}
+
+pub(crate) fn substitute(text: &str, values: &Values) -> Result<OsString, CliError> {
+    let mut result = OsString::new();
+    let mut remainder = text;
+    while let Some(start) = remainder.find('{') {
+        result.push(&remainder[..start]);
+        let end = remainder[start..]
+            .find('}')
+            .ok_or_else(|| CliError::InvalidTemplate(text.to_string()))?;
+        let name = &remainder[start + 1..start + end];
+        result.push(
+            value(name, values).ok_or_else(|| CliError::InvalidPlaceholder(name.to_string()))?,
+        );
+        remainder = &remainder[start + end + 1..];
+    }
+    result.push(remainder);
+    Ok(result)
+}
+
+fn value(name: &str, values: &Values) -> Option<OsString> {
+    let path = values.path;
+    match name {
+        "path" => path.map(|path| path.as_os_str().to_owned()),
+        "dir" => path
+            .and_then(|path| path.parent())
+            .map(|dir| dir.as_os_str().to_owned()),
+        "basename" => path
+            .and_then(|path| path.file_name())
+            .map(|name| name.to_owned()),
+        "ext" => path.map(|path| path.extension().unwrap_or_default().to_owned()),
+        "index" => values.index.map(|index| OsString::from(index.to_string())),
+        "uri" => values.uri.map(OsString::from),
+        "list" => values.list.map(|list| list.as_os_str().to_owned()),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn placeholders() {
+        let values = Values {
+            path: Some(Path::new("/Music/The Beatles/Help.flac")),
+            index: Some(7),
+            uri: Some("smb://nas/music/The%20Beatles/Help.flac"),
+            list: None,
+        };
+        let command = command("code --goto {path}", &values).unwrap();
+        assert_eq!(command.get_program(), "code");
+        assert_eq!(
+            command.get_args().collect::<Vec<_>>(),
+            vec!["--goto", "/Music/The Beatles/Help.flac"]
+        );
+        assert_eq!(
+            substitute("{dir}|{basename}|{ext}|{index}", &values).unwrap(),
+            "/Music/The Beatles|Help.flac|flac|7"
+        );
+        assert_eq!(
+            substitute("{uri}", &values).unwrap(),
+            "smb://nas/music/The%20Beatles/Help.flac"
+        );
+        assert!(matches!(
+            substitute("--playlist={list}", &values),
+            Err(CliError::InvalidPlaceholder(_))
+        ));
+        assert!(matches!(
+            substitute("{path", &values),
+            Err(CliError::InvalidTemplate(_))
+        ));
+        assert!(has_file_placeholder("mpv {basename}"));
+        assert!(!has_file_placeholder("mpv --playlist={list}"));
+        assert!(has_list_placeholder("mpv --playlist={list}"));
+    }
+}
@@ -1,4 +1,5 @@
 use crate::cli::CliError;
+use std::collections::BTreeMap;
 use std::env::Args;
 
 #[derive(Debug, Clone, PartialEq)]
@@ -47,16 +48,7 @@ pub(crate) fn tokenize_shell(line: &str) -> Result<Vec<Token>, CliError> {
         if quoted {
             if escaped {
                 escaped = false;
-                match ch {
-                    '"' => item.push('"'),
-                    't' => item.push('\t'),
-                    'n' => item.push('\n'),
-                    'r' => item.push('\r'),
-                    '\\' => item.push('\\'),
-                    ch => {
-                        return Err(CliError::InvalidEscape(ch));
-                    }
-                };
+                item.push(unescape(ch)?);
             } else {
                 match ch {
                     '\\' => {
@@ -135,6 +127,155 @@ pub(crate) fn tokenize_shell(line: &str) -> Result<Vec<Token>, CliError> {
     Ok(token)
 }
 
+// Splits a line into words like tokenize_shell, but without detecting
+// options, e.g. for the command line of an external program.
+pub(crate) fn split_words(line: &str) -> Result<Vec<String>, CliError> {
+    let mut words = Vec::new();
+    let mut item = String::new();
+    // Quotes may create an empty word.
+    let mut word = false;
+    let mut quoted = false;
+    let mut escaped = false;
+    for ch in line.chars() {
+        if escaped {
+            escaped = false;
+            item.push(unescape(ch)?);
+        } else if quoted {
+            match ch {
+                '\\' => escaped = true,
+                '"' => quoted = false,
+                ch => item.push(ch),
+            }
+        } else {
+            match ch {
+                ' ' | '\t' | '\n' | '\r' => {
+                    if word {
+                        words.push(swap(&mut item));
+                        word = false;
+                    }
+                }
+                '"' => {
+                    quoted = true;
+                    word = true;
+                }
+                ch => {
+                    item.push(ch);
+                    word = true;
+                }
+            }
+        }
+    }
+    if escaped {
+        return Err(CliError::MissingEscapedCharacter);
+    } else if quoted {
+        return Err(CliError::MissingClosingQuote);
+    } else if word {
+        words.push(item);
+    }
+    Ok(words)
+}
+
+// Escape sequences within quotes.
+fn unescape(ch: char) -> Result<char, CliError> {
+    match ch {
+        '"' => Ok('"'),
+        't' => Ok('\t'),
+        'n' => Ok('\n'),
+        'r' => Ok('\r'),
+        '\\' => Ok('\\'),
+        ch => Err(CliError::InvalidEscape(ch)),
+    }
+}
+
+pub(crate) fn expand_aliases(
+    token: Vec<Token>,
+    aliases: &BTreeMap<String, String>,
+) -> Result<Vec<Token>, CliError> {
+    let alias_name: fn(&Token) -> Option<&str> = |tk| match tk {
+        Token::Text(text) => text.strip_prefix('@'),
+        Token::Option(_) => None,
+    };
+    let mut active = Vec::new();
+    expand_aliases_impl(token, aliases, &mut active, alias_name, tokenize_shell)
+}
+
+// Expands aliases in words of split_words, e.g. for \x, which passes the
+// words to an external program instead of reading options.
+pub(crate) fn expand_alias_words(
+    words: Vec<String>,
+    aliases: &BTreeMap<String, String>,
+) -> Result<Vec<String>, CliError> {
+    let alias_name: fn(&String) -> Option<&str> = |word| word.strip_prefix('@');
+    let mut active = Vec::new();
+    expand_aliases_impl(words, aliases, &mut active, alias_name, split_words)
+}
+
+fn expand_aliases_impl<T>(
+    items: Vec<T>,
+    aliases: &BTreeMap<String, String>,
+    active: &mut Vec<String>,
+    alias_name: fn(&T) -> Option<&str>,
+    split: fn(&str) -> Result<Vec<T>, CliError>,
+) -> Result<Vec<T>, CliError> {
+    let mut expanded = Vec::new();
+    for item in items {
+        // Text starting with @ and without a matching alias is kept as plain text.
+        let alias = alias_name(&item).and_then(|name| aliases.get_key_value(name));
+        if let Some((name, value)) = alias {
+            if active.contains(name) {
+                return Err(CliError::RecursiveAlias(name.clone()));
+            }
+            active.push(name.clone());
+            let mut items = expand_aliases_impl(split(value)?, aliases, active, alias_name, split)?;
+            active.pop();
+            expanded.append(&mut items);
+        } else {
+            expanded.push(item);
+        }
+    }
+    Ok(expanded)
+}
+
+// Text with white space or quotes is quoted for tokenize_shell, e.g. for
+// completion candidates.
+pub(crate) fn quote(text: &str) -> String {
+    if !text.contains(|ch: char| ch.is_whitespace() || ch == '"') {
+        return text.to_string();
+    }
+    quoted(text)
+}
+
+fn quoted(text: &str) -> String {
+    let mut quoted = String::from("\"");
+    for ch in text.chars() {
+        match ch {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\t' => quoted.push_str("\\t"),
+            '\n' => quoted.push_str("\\n"),
+            '\r' => quoted.push_str("\\r"),
+            ch => quoted.push(ch),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+// Inverse of tokenize_shell. Text, which would be read as option, is quoted
+// as well.
+pub(crate) fn join_tokens(token: &[Token]) -> String {
+    token
+        .iter()
+        .map(|tk| match tk {
+            Token::Option(option) if option.chars().count() == 1 => format!("-{}", option),
+            Token::Option(option) => format!("--{}", option),
+            Token::Text(text) if text.starts_with('-') => quoted(text),
+            Token::Text(text) => quote(text),
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
 fn swap(value: &mut String) -> String {
     let mut other = String::new();
     std::mem::swap(value, &mut other);
@@ -368,4 +509,100 @@ mod tests {
             )
         );
     }
+
+    fn aliases() -> BTreeMap<String, String> {
+        BTreeMap::from([
+            ("flacs".to_string(), "--last-element *.flac".to_string()),
+            ("beatles".to_string(), "@flacs Beatles".to_string()),
+            ("loop1".to_string(), "@loop2".to_string()),
+            ("loop2".to_string(), "foo @loop1".to_string()),
+        ])
+    }
+
+    #[test]
+    fn alias_expansion() {
+        assert_eq!(
+            expand_aliases(tokenize_shell("@flacs live").unwrap(), &aliases()).unwrap(),
+            vec!(
+                Token::Option("last-element".to_string()),
+                Token::Text("*.flac".to_string()),
+                Token::Text("live".to_string()),
+            )
+        );
+    }
+
+    #[test]
+    fn nested_alias_expansion() {
+        assert_eq!(
+            expand_aliases(tokenize_shell("-c @beatles").unwrap(), &aliases()).unwrap(),
+            vec!(
+                Token::Option("c".to_string()),
+                Token::Option("last-element".to_string()),
+                Token::Text("*.flac".to_string()),
+                Token::Text("Beatles".to_string()),
+            )
+        );
+    }
+
+    #[test]
+    fn unknown_alias_is_text() {
+        assert_eq!(
+            expand_aliases(tokenize_shell("@eaDir @").unwrap(), &aliases()).unwrap(),
+            vec!(
+                Token::Text("@eaDir".to_string()),
+                Token::Text("@".to_string()),
+            )
+        );
+    }
+
+    #[test]
+    fn words() {
+        assert_eq!(
+            split_words(r#"\x 1. -- ls -la "{} x" "" a"\""b"#).unwrap(),
+            vec!["\\x", "1.", "--", "ls", "-la", "{} x", "", "a\"b"]
+        );
+        assert!(matches!(
+            split_words("echo \"a").unwrap_err(),
+            CliError::MissingClosingQuote
+        ));
+    }
+
+    #[test]
+    fn joined_tokens() {
+        let token = tokenize_shell(r#"-c --last-element "a b" "-x" "q\"" @x"#).unwrap();
+        assert_eq!(
+            join_tokens(&token),
+            r#"-c --last-element "a b" "-x" "q\"" @x"#
+        );
+        assert_eq!(tokenize_shell(&join_tokens(&token)).unwrap(), token);
+    }
+
+    #[test]
+    fn alias_words() {
+        let commands = BTreeMap::from([
+            (
+                "conv".to_string(),
+                r#"\x -j2 -- convert "{}" @size"#.to_string(),
+            ),
+            ("size".to_string(), "-resize 50%".to_string()),
+        ]);
+        let words = split_words("@conv {.}.png").unwrap();
+        assert_eq!(
+            expand_alias_words(words, &commands).unwrap(),
+            ["\\x", "-j2", "--", "convert", "{}", "-resize", "50%", "{.}.png"]
+        );
+        let words = split_words("@loop1").unwrap();
+        assert!(matches!(
+            expand_alias_words(words, &aliases()).unwrap_err(),
+            CliError::RecursiveAlias(name) if name == "loop1"
+        ));
+    }
+
+    #[test]
+    fn recursive_alias() {
+        assert!(matches!(
+            expand_aliases(tokenize_shell("@loop1").unwrap(), &aliases()).unwrap_err(),
+            CliError::RecursiveAlias(name) if name == "loop1"
+        ));
+    }
 }
@@ -1,7 +1,24 @@
-use nix::sys::termios::{self, LocalFlags, SetArg};
+use nix::libc;
+use nix::sys::termios::{self, LocalFlags, SetArg, Termios};
 use std::io::Result;
+use std::panic;
+use std::sync::{Mutex, Once};
 
-pub fn set_tty() -> Result<()> {
+// Terminal mode before set_tty was called. Stored as libc::termios, because
+// Termios is not Sync.
+static ORIGINAL_MODE: Mutex<Option<libc::termios>> = Mutex::new(None);
+static PANIC_HOOK: Once = Once::new();
+
+// Restores the original terminal mode when dropped.
+pub struct TtyGuard {}
+
+impl Drop for TtyGuard {
+    fn drop(&mut self) {
+        restore_tty();
+    }
+}
+
+pub fn set_tty() -> Result<TtyGuard> {
     let original_mode = termios::tcgetattr(std::io::stdin())?;
     let mut raw = original_mode.clone();
 
@@ -14,7 +31,56 @@ pub fn set_tty() -> Result<()> {
     // I.e. some characters/lines are missing and output is corrupted.
     raw.local_flags |= LocalFlags::NOFLSH;
 
+    *ORIGINAL_MODE.lock().unwrap() = Some(original_mode.into());
+    PANIC_HOOK.call_once(|| {
+        let default_hook = panic::take_hook();
+        panic::set_hook(Box::new(move |info| {
+            restore_tty();
+            default_hook(info);
+        }));
+    });
+
     termios::tcsetattr(std::io::stdin(), SetArg::TCSADRAIN, &raw)?;
 
-    Ok(())
+    Ok(TtyGuard {})
+}
+
+// Returns the number of columns, if stdout is a terminal.
+pub fn terminal_width() -> Option<usize> {
+    winsize().map(|winsize| winsize.ws_col as usize)
+}
+
+// Returns the number of rows, if stdout is a terminal.
+pub fn terminal_height() -> Option<usize> {
+    winsize().map(|winsize| winsize.ws_row as usize)
+}
+
+fn winsize() -> Option<libc::winsize> {
+    let mut winsize = libc::winsize {
+        ws_row: 0,
+        ws_col: 0,
+        ws_xpixel: 0,
+        ws_ypixel: 0,
+    };
+    // SAFETY: TIOCGWINSZ writes a winsize struct into the passed pointer.
+    let res = unsafe { libc::ioctl(libc::STDOUT_FILENO, libc::TIOCGWINSZ, &mut winsize) };
+    if res == 0 && winsize.ws_col > 0 && winsize.ws_row > 0 {
+        Some(winsize)
+    } else {
+        None
+    }
+}
+
+// Restores the terminal mode changed by set_tty. Must be called before the
+// process exits without unwinding, e.g. with std::process::exit.
+pub fn restore_tty() {
+    // A poisoned lock must not prevent restoring the terminal in a panic.
+    let original_mode = match ORIGINAL_MODE.lock() {
+        Ok(mut guard) => guard.take(),
+        Err(poisoned) => poisoned.into_inner().take(),
+    };
+    if let Some(original_mode) = original_mode {
+        let original_mode = Termios::from(original_mode);
+        let _ = termios::tcsetattr(std::io::stdin(), SetArg::TCSADRAIN, &original_mode);
+    }
 }
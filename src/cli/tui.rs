@@ -0,0 +1,250 @@
+use crate::cli::CliError;
+use crate::clipboard::copy_to_clipboard;
+use crate::config::Config;
+use crate::locate::{check_databases, format_size, locate_collect, HeaderChecks, Results};
+use crate::open::open_commands;
+use crate::tokenizer::{expand_aliases, tokenize_shell};
+use crate::uri::UriMapping;
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use ratatui::layout::{Constraint, Layout, Position};
+use ratatui::style::{Style, Stylize};
+use ratatui::text::Line;
+use ratatui::widgets::{Block, List, ListItem, ListState, Paragraph, Wrap};
+use ratatui::{DefaultTerminal, Frame};
+use std::env::Args;
+use std::fs;
+use std::path::Path;
+use std::process::Stdio;
+
+// Queries are stopped after this number of results to keep the list responsive.
+const MAX_RESULTS: usize = 10_000;
+const MAX_PREVIEW_ENTRIES: usize = 100;
+
+pub(crate) fn tui(config: &Config, args: &mut Args) -> Result<(), CliError> {
+    if let Some(arg) = args.next() {
+        return Err(CliError::InvalidTuiArgument(arg));
+    }
+    // Warnings are printed before the terminal is switched to the TUI.
+    let checks = check_databases(config)?;
+    // Restores the terminal also after a panic.
+    let mut terminal = ratatui::init();
+    let result = Tui::new(config, checks).run(&mut terminal);
+    ratatui::restore();
+    result
+}
+
+struct Tui<'a> {
+    config: &'a Config,
+    checks: HeaderChecks,
+    query: String,
+    results: Results,
+    truncated: bool,
+    list_state: ListState,
+    status: String,
+}
+
+impl Tui<'_> {
+    fn new(config: &Config, checks: HeaderChecks) -> Tui<'_> {
+        Tui {
+            config,
+            checks,
+            query: String::new(),
+            results: Vec::new(),
+            truncated: false,
+            list_state: ListState::default(),
+            status: String::new(),
+        }
+    }
+
+    fn run(&mut self, terminal: &mut DefaultTerminal) -> Result<(), CliError> {
+        loop {
+            terminal.draw(|frame| self.draw(frame))?;
+            if let Event::Key(key) = event::read()? {
+                if key.kind == KeyEventKind::Press && !self.handle_key(key) {
+                    return Ok(());
+                }
+            }
+        }
+    }
+
+    // Returns false to quit.
+    fn handle_key(&mut self, key: KeyEvent) -> bool {
+        let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+        match key.code {
+            KeyCode::Esc => return false,
+            KeyCode::Char('c') | KeyCode::Char('d') if ctrl => return false,
+            KeyCode::Char('y') if ctrl => self.copy_selected(),
+            KeyCode::Char('u') if ctrl => {
+                self.query.clear();
+                self.run_query();
+            }
+            KeyCode::Char(ch) if !ctrl => {
+                self.query.push(ch);
+                self.run_query();
+            }
+            KeyCode::Backspace => {
+                self.query.pop();
+                self.run_query();
+            }
+            KeyCode::Enter => self.open_selected(),
+            KeyCode::Down => self.list_state.select_next(),
+            KeyCode::Up => self.list_state.select_previous(),
+            KeyCode::PageDown => self.list_state.scroll_down_by(20),
+            KeyCode::PageUp => self.list_state.scroll_up_by(20),
+            KeyCode::Home => self.list_state.select_first(),
+            KeyCode::End => self.list_state.select_last(),
+            _ => {}
+        }
+        true
+    }
+
+    fn run_query(&mut self) {
+        let result = tokenize_shell(&self.query)
+            .and_then(|token| expand_aliases(token, &self.config.aliases))
+            .and_then(|token| locate_collect(self.config, &self.checks, token, MAX_RESULTS));
+        match result {
+            Ok((results, truncated)) => {
+                self.results = results;
+                self.truncated = truncated;
+                self.status.clear();
+            }
+            Err(err) => {
+                // Incomplete input, e.g. a missing closing quote, is expected
+                // while typing. The previous results are kept.
+                self.status = err.to_string();
+            }
+        }
+        let selected = if self.results.is_empty() {
+            None
+        } else {
+            Some(0)
+        };
+        self.list_state.select(selected);
+    }
+
+    fn selected(&self) -> Option<&Path> {
+        self.list_state
+            .selected()
+            .and_then(|index| self.results.get(index))
+            .map(|(path, _)| path.as_path())
+    }
+
+    fn open_selected(&mut self) {
+        let (Some(index), Some(path)) = (self.list_state.selected(), self.selected()) else {
+            return;
+        };
+        self.status = match open(self.config, index + 1, path) {
+            Ok(()) => format!("Opened: {}", path.to_string_lossy()),
+            Err(err) => format!("Open failed: {}", err),
+        };
+    }
+
+    fn copy_selected(&mut self) {
+        let Some(path) = self.selected() else {
+            return;
+        };
+        let text = path.to_string_lossy();
+        self.status = match copy_to_clipboard(&text) {
+            Ok(()) => format!("Copied: {}", text),
+            Err(err) => format!("Copy failed: {}", err),
+        };
+    }
+
+    fn draw(&mut self, frame: &mut Frame) {
+        let [query_area, main_area, status_area] = Layout::vertical([
+            Constraint::Length(3),
+            Constraint::Min(1),
+            Constraint::Length(1),
+        ])
+        .areas(frame.area());
+        let [list_area, preview_area] =
+            Layout::horizontal([Constraint::Percentage(60), Constraint::Percentage(40)])
+                .areas(main_area);
+
+        let query = Paragraph::new(self.query.as_str()).block(Block::bordered().title("Query"));
+        frame.render_widget(query, query_area);
+        frame.set_cursor_position(Position::new(
+            query_area.x + 1 + self.query.chars().count() as u16,
+            query_area.y + 1,
+        ));
+
+        let title = if self.truncated {
+            format!("Results (first {})", self.results.len())
+        } else {
+            format!("Results ({})", self.results.len())
+        };
+        let items: Vec<ListItem> = self
+            .results
+            .iter()
+            .enumerate()
+            .map(|(index, (path, _))| {
+                ListItem::new(format!("{}. {}", index + 1, path.to_string_lossy()))
+            })
+            .collect();
+        let list = List::new(items)
+            .block(Block::bordered().title(title))
+            .highlight_style(Style::new().reversed());
+        frame.render_stateful_widget(list, list_area, &mut self.list_state);
+
+        let preview = Paragraph::new(self.preview())
+            .block(Block::bordered().title("Preview"))
+            .wrap(Wrap { trim: false });
+        frame.render_widget(preview, preview_area);
+
+        let status = if self.status.is_empty() {
+            "Enter: open  Ctrl-Y: copy  Ctrl-U: clear  Esc: quit"
+        } else {
+            self.status.as_str()
+        };
+        frame.render_widget(Line::from(status).dim(), status_area);
+    }
+
+    fn preview(&self) -> Vec<Line<'_>> {
+        let Some((path, size)) = self
+            .list_state
+            .selected()
+            .and_then(|index| self.results.get(index))
+        else {
+            return Vec::new();
+        };
+        let mut lines = vec![Line::from(path.to_string_lossy().into_owned()).bold()];
+        lines.push(Line::from(match size {
+            Some(size) => format!("Size: {}", format_size(*size)),
+            None => String::from("Size: unknown"),
+        }));
+        if !path.exists() {
+            lines.push(Line::from("Not available. Device not mounted?"));
+        } else if let Ok(dir) = fs::read_dir(path) {
+            let mut names: Vec<String> = dir
+                .filter_map(|entry| entry.ok())
+                .map(|entry| entry.file_name().to_string_lossy().into_owned())
+                .collect();
+            names.sort();
+            lines.push(Line::from(""));
+            lines.extend(names.into_iter().take(MAX_PREVIEW_ENTRIES).map(Line::from));
+        }
+        lines
+    }
+}
+
+fn open(config: &Config, index: usize, path: &Path) -> Result<(), String> {
+    let commands = open_commands(
+        config.open.as_ref(),
+        &UriMapping::new(config),
+        &[(index, path)],
+    )
+    .map_err(|err| err.to_string())?;
+    for mut open in commands {
+        let status = open
+            .command
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status()
+            .map_err(|err| err.to_string())?;
+        if !status.success() {
+            return Err(status.to_string());
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,67 @@
+use crate::expand::Selection;
+
+// Number of operations which can be undone.
+const MAX_OPERATIONS: usize = 20;
+
+// Journal entry with the state needed to undo an operation.
+pub(crate) enum Operation {
+    // A query replaced this selection.
+    Selection(Option<Selection>),
+}
+
+#[derive(Default)]
+pub(crate) struct UndoStack {
+    operations: Vec<Operation>,
+}
+
+impl UndoStack {
+    pub(crate) fn new() -> UndoStack {
+        UndoStack::default()
+    }
+
+    pub(crate) fn push(&mut self, operation: Operation) {
+        if self.operations.len() == MAX_OPERATIONS {
+            self.operations.remove(0);
+        }
+        self.operations.push(operation);
+    }
+
+    pub(crate) fn pop(&mut self) -> Option<Operation> {
+        self.operations.pop()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    fn selection(operation: Option<Operation>) -> Option<Selection> {
+        match operation {
+            Some(Operation::Selection(selection)) => selection,
+            None => panic!("empty undo stack"),
+        }
+    }
+
+    #[test]
+    fn undo_limit() {
+        let mut undo = UndoStack::new();
+        undo.push(Operation::Selection(None));
+        for i in 0..MAX_OPERATIONS {
+            undo.push(Operation::Selection(Some(Selection {
+                paths: vec![PathBuf::from(i.to_string())],
+                page_size: 10,
+                partial: false,
+            })));
+        }
+        assert_eq!(
+            selection(undo.pop()).unwrap().paths,
+            vec![PathBuf::from((MAX_OPERATIONS - 1).to_string())]
+        );
+        for _ in 1..MAX_OPERATIONS {
+            assert!(selection(undo.pop()).is_some());
+        }
+        // The oldest operation was dropped.
+        assert!(undo.pop().is_none());
+    }
+}
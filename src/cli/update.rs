@@ -1,81 +1,347 @@
-use crate::cli::CliError;
-use crate::config::{get_volume_info, Config};
-use fsidx::Settings;
+use crate::cli::{exit_code, CliError};
+use crate::config::{get_volume_info, Config, HooksConfig};
+use crate::event::{json_diagnostic, print_event, Diagnostic, Message};
+use crate::hooks::{run_hook, Stage};
+use crate::stats::ResourceUsage;
+use crate::summary::ExitSummary;
+use crate::tokenizer::{tokenize_cli, Token};
+use crate::verbosity::verbosity;
+use fsidx::{ProviderRegistry, Settings, Severity, UpdateEvent};
+use signal_hook::consts::signal::{SIGHUP, SIGINT, SIGTERM};
+use signal_hook::iterator::Signals;
+use std::collections::BTreeMap;
 use std::env::Args;
-use std::io::{stderr, stdout, Write};
-use std::os::unix::prelude::OsStrExt;
+use std::ffi::OsStr;
+use std::fs::{self, File};
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::atomic::AtomicBool;
+use std::sync::Arc;
+use std::thread;
+
+#[derive(Default)]
+pub(crate) struct UpdateOptions {
+    error_log: bool,
+    // Events are printed to stdout as JSON objects, one per line.
+    json: bool,
+    reproducible: bool,
+}
 
 pub(crate) fn update_cli(config: &Config, args: &mut Args) -> Result<(), CliError> {
-    if let Some(arg) = args.next() {
-        return Err(CliError::InvalidUpdateArgument(arg));
+    let mut options = UpdateOptions::default();
+    for token in tokenize_cli(args)? {
+        match token {
+            Token::Option(option) if option == "error-log" => options.error_log = true,
+            Token::Option(option) if option == "json" => options.json = true,
+            Token::Option(option) if option == "reproducible" => options.reproducible = true,
+            Token::Option(option) => return Err(CliError::InvalidUpdateArgument(option)),
+            Token::Text(text) => return Err(CliError::InvalidUpdateArgument(text)),
+        }
+    }
+    remove_temporary_files_on_signal(config)?;
+    let usage = ResourceUsage::start();
+    let mut exit_summary = ExitSummary::update();
+    let res = update_shell(config, &options, &mut exit_summary, None);
+    if res.is_err() {
+        exit_summary.add_errors(1);
+    }
+    if !options.json {
+        exit_summary.print_scans()?;
     }
-    update_shell(config)
+    exit_summary.print();
+    ResourceUsage::print(usage, "Update");
+    if res? == Severity::Error {
+        return Err(CliError::UpdateFailed);
+    }
+    Ok(())
 }
 
-pub(crate) fn update_shell(config: &Config) -> Result<(), CliError> {
-    let volume_info = get_volume_info(config).ok_or(CliError::NoDatabasePath)?;
-    fsidx::update(volume_info, Settings::WithFileSizes, |event| {
-        match event {
-            fsidx::UpdateEvent::Scanning(path) => {
-                stdout().write_all(b"Scanning: ")?;
-                stdout().write_all(path.as_os_str().as_bytes())?;
-                stdout().write_all(b"\n")?;
-            }
-            fsidx::UpdateEvent::ScanningFinished(path) => {
-                stdout().write_all(b"Finished: ")?;
-                stdout().write_all(path.as_os_str().as_bytes())?;
-                stdout().write_all(b"\n")?;
-            }
-            fsidx::UpdateEvent::ScanningFailed(path) => {
-                stderr().write_all(b"Error: Scanning failed: ")?;
-                stderr().write_all(path.as_os_str().as_bytes())?;
-                stderr().write_all(b"\n")?;
-            }
-            fsidx::UpdateEvent::DbWriteError(path, error) => {
-                stderr().write_all(b"Error: Writing database \'")?;
-                stderr().write_all(path.as_os_str().as_bytes())?;
-                stderr().write_fmt(format_args!("\' failed: {}\n", error))?;
-            }
-            fsidx::UpdateEvent::ReplacingDatabaseFailed(tmp_path, path, error) => {
-                stderr().write_all(b"Error: Replacing database \'")?;
-                stderr().write_all(path.as_os_str().as_bytes())?;
-                stderr().write_fmt(format_args!("\' with \'"))?;
-                stderr().write_all(tmp_path.as_os_str().as_bytes())?;
-                stderr().write_fmt(format_args!("\' failed: {}\n", error))?;
-            }
-            fsidx::UpdateEvent::RemovingTemporaryFileFailed(path, error) => {
-                stderr().write_all(b"Error: Removing temporary file \'")?;
-                stderr().write_all(path.as_os_str().as_bytes())?;
-                stderr().write_fmt(format_args!("\' failed: {}\n", error))?;
-            }
-            fsidx::UpdateEvent::CreatingTemporaryFileFailed(path, error) => {
-                stderr().write_all(b"Error: Creating temporary file \'")?;
-                stderr().write_all(path.as_os_str().as_bytes())?;
-                stderr().write_fmt(format_args!("\' failed: {}\n", error))?;
-            }
-            fsidx::UpdateEvent::ScanError(path, walk_dir_error) => {
-                let depth = walk_dir_error.depth();
-                stderr().write_all(b"Error: Scanning directory failed \'")?;
-                stderr().write_all(path.as_os_str().as_bytes())?;
-                stderr().write_fmt(format_args!("\' failed at depth {}", depth))?;
-                if let Some(io_error) = walk_dir_error.io_error() {
-                    stderr().write_fmt(format_args!("\': {}\n", io_error))?;
-                } else {
-                    stderr().write_all(b"\'.\n")?;
+pub(crate) fn update_shell(
+    config: &Config,
+    options: &UpdateOptions,
+    exit_summary: &mut ExitSummary,
+    abort: Option<Arc<AtomicBool>>,
+) -> Result<Severity, CliError> {
+    let mut volume_info = get_volume_info(config).ok_or(CliError::NoDatabasePath)?;
+    let db_path = config
+        .index
+        .db_path
+        .as_ref()
+        .ok_or(CliError::NoDatabasePath)?;
+    let mut scan_errors = ScanErrors::new(config, options)?;
+    let mut severity = Severity::Info;
+    if let Some(hooks) = &config.hooks {
+        let env = [("FSIDX_DB_PATH", db_path.as_os_str())];
+        if !run_hook(hooks, Stage::PreUpdate, &env, &mut severity) {
+            return Ok(severity);
+        }
+    }
+    volume_info.retain(|volume| match folder_hooks(config, &volume.folder) {
+        Some(hooks) => {
+            let env = [
+                ("FSIDX_FOLDER", volume.folder.as_os_str()),
+                ("FSIDX_DATABASE", volume.database.as_os_str()),
+            ];
+            run_hook(hooks, Stage::PreUpdate, &env, &mut severity)
+        }
+        None => true,
+    });
+    let databases: BTreeMap<PathBuf, PathBuf> = volume_info
+        .iter()
+        .map(|volume| (volume.folder.clone(), volume.database.clone()))
+        .collect();
+    let mut update_config = config.update.clone();
+    update_config.reproducible = options.reproducible;
+    fsidx::update_with_providers(
+        volume_info,
+        Settings::WithFileSizes,
+        &update_config,
+        &ProviderRegistry::builtin(),
+        abort,
+        |event| {
+            severity = severity.max(event.severity());
+            exit_summary.add_update_event(&event);
+            match &event {
+                UpdateEvent::ScanError(folder, error) => {
+                    scan_errors.add(folder, error, &event);
+                    if verbosity() {
+                        print_update_event(options, &event);
+                    }
+                }
+                UpdateEvent::Progress(_, _) => {
+                    if verbosity() {
+                        print_update_event(options, &event);
+                    }
                 }
-                if let Some(associated_path) = walk_dir_error.path() {
-                    stderr().write_all(b"       Associated path: \'")?;
-                    stderr().write_all(associated_path.as_os_str().as_bytes())?;
-                    stderr().write_all(b"\'\n")?;
+                // Printed as table by update_cli.
+                UpdateEvent::ScanSummary(_) if !options.json => {
+                    if verbosity() {
+                        print_update_event(options, &event);
+                    }
                 }
-                if let Some(cycle_path) = walk_dir_error.loop_ancestor() {
-                    stderr().write_all(b"       Cycle detected at path: \'")?;
-                    stderr().write_all(cycle_path.as_os_str().as_bytes())?;
-                    stderr().write_all(b"\'\n")?;
+                UpdateEvent::ScanningFinished(folder) | UpdateEvent::ScanningFailed(folder) => {
+                    print_update_event(options, &event);
+                    scan_errors.print_summary(folder);
+                    if let (Some(hooks), Some(database)) =
+                        (folder_hooks(config, folder), databases.get(folder))
+                    {
+                        let status = status(event.severity());
+                        let env = [
+                            ("FSIDX_FOLDER", folder.as_os_str()),
+                            ("FSIDX_DATABASE", database.as_os_str()),
+                            ("FSIDX_STATUS", OsStr::new(status)),
+                        ];
+                        run_hook(hooks, Stage::PostUpdate, &env, &mut severity);
+                    }
                 }
+                _ => print_update_event(options, &event),
             }
-        };
-        Ok(())
+            Ok(())
+        },
+    );
+    scan_errors.finish();
+    if let Some(hooks) = &config.hooks {
+        let env = [
+            ("FSIDX_DB_PATH", db_path.as_os_str()),
+            ("FSIDX_STATUS", OsStr::new(status(severity))),
+        ];
+        run_hook(hooks, Stage::PostUpdate, &env, &mut severity);
+    }
+    Ok(severity)
+}
+
+// JSON objects of all severities are printed to stdout to be piped into
+// other tools.
+fn print_update_event(options: &UpdateOptions, event: &UpdateEvent) {
+    if options.json {
+        println!("{}", json_diagnostic(event.severity(), event));
+    } else {
+        print_event(event.severity(), event);
+    }
+}
+
+// The update is terminated by SIGINT, SIGTERM and SIGHUP. The existing
+// database files are kept, but temporary files of an unfinished scan are
+// removed.
+fn remove_temporary_files_on_signal(config: &Config) -> Result<(), CliError> {
+    let volume_info = get_volume_info(config).ok_or(CliError::NoDatabasePath)?;
+    let mut signals =
+        Signals::new([SIGINT, SIGTERM, SIGHUP]).map_err(CliError::CreatingSignalHandlerFailed)?;
+    thread::spawn(move || {
+        if let Some(sig) = signals.forever().next() {
+            for volume in volume_info {
+                let _ = fs::remove_file(volume.database.with_extension("~"));
+            }
+            process::exit(exit_code(sig));
+        }
     });
     Ok(())
 }
+
+fn folder_hooks<'a>(config: &'a Config, folder: &Path) -> Option<&'a HooksConfig> {
+    config
+        .index
+        .folder
+        .iter()
+        .find(|item| item.path() == folder)
+        .and_then(|item| item.hooks())
+}
+
+impl Diagnostic for UpdateEvent {
+    fn code(&self) -> &'static str {
+        match self {
+            UpdateEvent::Scanning(_) => "scanning",
+            UpdateEvent::ScanningFinished(_) => "scanning-finished",
+            UpdateEvent::ScanningFailed(_) => "scanning-failed",
+            UpdateEvent::DbWriteError(_, _) => "db-write-error",
+            UpdateEvent::ReplacingDatabaseFailed(_, _, _) => "replacing-database-failed",
+            UpdateEvent::RemovingTemporaryFileFailed(_, _) => "removing-temporary-file-failed",
+            UpdateEvent::CreatingTemporaryFileFailed(_, _) => "creating-temporary-file-failed",
+            UpdateEvent::DatabaseLocked(_) => "database-locked",
+            UpdateEvent::ScanError(_, _) => "scan-error",
+            UpdateEvent::Retrying(_, _, _) => "retrying",
+            UpdateEvent::Progress(_, _) => "progress",
+            UpdateEvent::Statistics(_, _) => "statistics",
+            UpdateEvent::Changes(_, _) => "changes",
+            UpdateEvent::ScanSummary(_) => "scan-summary",
+            UpdateEvent::EnrichmentFailed(_, _, _) => "enrichment-failed",
+            UpdateEvent::VolumeNotMounted(_, _) => "volume-not-mounted",
+            UpdateEvent::VolumeMismatch(_, _) => "volume-mismatch",
+            UpdateEvent::InvalidExcludePattern(_, _, _) => "invalid-exclude-pattern",
+            UpdateEvent::SymlinkLoop(_, _, _) => "symlink-loop",
+            UpdateEvent::PathTooLong(_, _, _) => "path-too-long",
+            UpdateEvent::PathTooDeep(_, _, _) => "path-too-deep",
+            UpdateEvent::Aborted(_) => "aborted",
+            _ => "update-event",
+        }
+    }
+
+    fn path(&self) -> Option<&Path> {
+        match self {
+            UpdateEvent::DbWriteError(path, _)
+            | UpdateEvent::ReplacingDatabaseFailed(_, path, _)
+            | UpdateEvent::RemovingTemporaryFileFailed(path, _)
+            | UpdateEvent::CreatingTemporaryFileFailed(path, _)
+            | UpdateEvent::DatabaseLocked(path)
+            | UpdateEvent::Retrying(path, _, _)
+            | UpdateEvent::EnrichmentFailed(path, _, _)
+            | UpdateEvent::SymlinkLoop(_, path, _)
+            | UpdateEvent::PathTooLong(_, path, _)
+            | UpdateEvent::PathTooDeep(_, path, _) => Some(path),
+            UpdateEvent::ScanError(_, err) => err.path(),
+            _ => None,
+        }
+    }
+
+    fn volume(&self) -> Option<&Path> {
+        match self {
+            UpdateEvent::Scanning(folder)
+            | UpdateEvent::ScanningFinished(folder)
+            | UpdateEvent::ScanningFailed(folder)
+            | UpdateEvent::ScanError(folder, _)
+            | UpdateEvent::Progress(folder, _)
+            | UpdateEvent::Statistics(folder, _)
+            | UpdateEvent::Changes(folder, _)
+            | UpdateEvent::VolumeNotMounted(folder, _)
+            | UpdateEvent::VolumeMismatch(folder, _)
+            | UpdateEvent::InvalidExcludePattern(folder, _, _)
+            | UpdateEvent::SymlinkLoop(folder, _, _)
+            | UpdateEvent::PathTooLong(folder, _, _)
+            | UpdateEvent::PathTooDeep(folder, _, _)
+            | UpdateEvent::Aborted(folder) => Some(folder),
+            UpdateEvent::ScanSummary(summary) => Some(&summary.folder),
+            _ => None,
+        }
+    }
+}
+
+fn status(severity: Severity) -> &'static str {
+    if severity == Severity::Error {
+        "failed"
+    } else {
+        "finished"
+    }
+}
+
+// Scan errors are counted per volume and error kind. Only a summary is
+// printed, unless verbose mode is enabled. The complete list of scan errors
+// is optionally written into a log file.
+struct ScanErrors {
+    counts: BTreeMap<PathBuf, BTreeMap<String, usize>>,
+    log: Option<(PathBuf, BufWriter<File>)>,
+}
+
+impl ScanErrors {
+    fn new(config: &Config, options: &UpdateOptions) -> Result<ScanErrors, CliError> {
+        let log = if options.error_log {
+            let db_path = config
+                .index
+                .db_path
+                .as_ref()
+                .ok_or(CliError::NoDatabasePath)?;
+            let path = db_path.join("scan-errors.log");
+            let file = File::create(&path)
+                .map_err(|err| CliError::CreatingErrorLogFailed(path.clone(), err))?;
+            Some((path, BufWriter::new(file)))
+        } else {
+            None
+        };
+        Ok(ScanErrors {
+            counts: BTreeMap::new(),
+            log,
+        })
+    }
+
+    fn add(&mut self, folder: &Path, error: &walkdir::Error, event: &UpdateEvent) {
+        let kind = if let Some(io_error) = error.io_error() {
+            io_error.kind().to_string()
+        } else if error.loop_ancestor().is_some() {
+            String::from("filesystem loop")
+        } else {
+            String::from("other error")
+        };
+        *self
+            .counts
+            .entry(folder.to_path_buf())
+            .or_default()
+            .entry(kind)
+            .or_default() += 1;
+        if let Some((_, log)) = &mut self.log {
+            let _ = writeln!(log, "{}", event);
+        }
+    }
+
+    fn print_summary(&self, folder: &Path) {
+        if verbosity() {
+            return;
+        }
+        if let Some(counts) = self.counts.get(folder) {
+            let total: usize = counts.values().sum();
+            let kinds: Vec<String> = counts
+                .iter()
+                .map(|(kind, count)| format!("{} {}", count, kind))
+                .collect();
+            let message = format!(
+                "{} scan errors in '{}': {}",
+                total,
+                folder.to_string_lossy(),
+                kinds.join(", ")
+            );
+            print_event(
+                Severity::Warning,
+                &Message::new("scan-errors", message).with_volume(folder),
+            );
+        }
+    }
+
+    fn finish(self) {
+        if let Some((path, mut log)) = self.log {
+            let _ = log.flush();
+            if !self.counts.is_empty() {
+                let message = format!("Scan errors written to: {}", path.to_string_lossy());
+                print_event(Severity::Info, &Message::new("scan-error-log", message));
+            }
+        }
+    }
+}
@@ -0,0 +1,94 @@
+use crate::config::Config;
+use std::os::unix::prelude::OsStrExt;
+use std::path::{Path, PathBuf};
+
+// Maps paths to URIs. Paths in a folder with a configured uri-prefix are
+// mapped to the prefix, e.g. /Volumes/NAS/a.txt to smb://nas/share/a.txt.
+// Other paths are mapped to file URIs.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct UriMapping {
+    prefixes: Vec<(PathBuf, String)>,
+}
+
+impl UriMapping {
+    pub(crate) fn new(config: &Config) -> UriMapping {
+        let prefixes = config
+            .index
+            .folder
+            .iter()
+            .filter_map(|folder| {
+                let prefix = folder.uri_prefix()?;
+                Some((folder.path().to_path_buf(), prefix.to_string()))
+            })
+            .collect();
+        UriMapping { prefixes }
+    }
+
+    pub(crate) fn uri(&self, path: &Path) -> String {
+        // The longest folder containing the path is used.
+        let mapped = self
+            .prefixes
+            .iter()
+            .filter(|(folder, _)| path.starts_with(folder))
+            .max_by_key(|(folder, _)| folder.as_os_str().len());
+        let Some((folder, prefix)) = mapped else {
+            return format!("file://{}", encode(path.as_os_str().as_bytes()));
+        };
+        let relative = path.strip_prefix(folder).unwrap_or(path);
+        if relative.as_os_str().is_empty() {
+            return prefix.clone();
+        }
+        format!(
+            "{}/{}",
+            prefix.trim_end_matches('/'),
+            encode(relative.as_os_str().as_bytes())
+        )
+    }
+}
+
+// Percent-encodes all bytes except unreserved characters and slashes.
+fn encode(bytes: &[u8]) -> String {
+    let mut encoded = String::with_capacity(bytes.len());
+    for byte in bytes {
+        if byte.is_ascii_alphanumeric() || b"-._~/".contains(byte) {
+            encoded.push(*byte as char);
+        } else {
+            encoded.push_str(&format!("%{:02X}", byte));
+        }
+    }
+    encoded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn uris() {
+        let mapping = UriMapping {
+            prefixes: vec![
+                (
+                    PathBuf::from("/Volumes/NAS"),
+                    String::from("smb://nas/share/"),
+                ),
+                (
+                    PathBuf::from("/Volumes/NAS/Music"),
+                    String::from("smb://nas/music"),
+                ),
+            ],
+        };
+        assert_eq!(
+            mapping.uri(Path::new("/Volumes/NAS/Photos/Beach 1.jpg")),
+            "smb://nas/share/Photos/Beach%201.jpg"
+        );
+        assert_eq!(
+            mapping.uri(Path::new("/Volumes/NAS/Music/Help.flac")),
+            "smb://nas/music/Help.flac"
+        );
+        assert_eq!(mapping.uri(Path::new("/Volumes/NAS")), "smb://nas/share/");
+        assert_eq!(
+            mapping.uri(Path::new("/Users/me/Müller#1.txt")),
+            "file:///Users/me/M%C3%BCller%231.txt"
+        );
+    }
+}
@@ -5,8 +5,27 @@ pub fn verbosity() -> bool {
     v > 0
 }
 
+// Number of -v options.
+pub fn verbosity_level() -> u8 {
+    unsafe { VERBOSITY_LEVEL }
+}
+
 pub fn set_verbosity(v: u8) {
     unsafe {
         VERBOSITY_LEVEL = v;
     }
 }
+
+static mut EXIT_SUMMARY: bool = false;
+
+// The exit summary is printed with --summary or in verbose mode.
+pub fn exit_summary() -> bool {
+    let v = unsafe { EXIT_SUMMARY };
+    v || verbosity()
+}
+
+pub fn set_exit_summary(v: bool) {
+    unsafe {
+        EXIT_SUMMARY = v;
+    }
+}
@@ -0,0 +1,134 @@
+use crate::cli::CliError;
+use crate::config::{get_volume_info, Config};
+use crate::event::{print_event, Message};
+use crate::locate::format_size;
+use crate::tokenizer::{tokenize_cli, Token};
+use fsidx::{HeaderCheck, Portability, Severity, Verification};
+use std::env::Args;
+use std::path::{Path, PathBuf};
+
+// Checks the database files of all folders. Folders sharing a database file
+// are verified once. With --fast only the checksum in the footer is compared
+// with the content, otherwise all entries are decoded, too. With --portable
+// entries read differently on other platforms are reported.
+pub(crate) fn verify_cli(config: &Config, args: &mut Args) -> Result<(), CliError> {
+    let mut fast = false;
+    let mut portable = false;
+    for token in tokenize_cli(args)? {
+        match token {
+            Token::Option(option) if option == "fast" => fast = true,
+            Token::Option(option) if option == "portable" => portable = true,
+            Token::Option(text) | Token::Text(text) => {
+                return Err(CliError::InvalidVerifyArgument(text))
+            }
+        }
+    }
+    let volume_info = get_volume_info(config).ok_or(CliError::NoDatabasePath)?;
+    let mut verified: Vec<PathBuf> = Vec::new();
+    let mut failed = false;
+    for vi in volume_info {
+        if verified.contains(&vi.database) {
+            continue;
+        }
+        verified.push(vi.database.clone());
+        let missing = match fsidx::check_header(&vi.database) {
+            HeaderCheck::Missing => Some(("database-missing", "is missing. Run update first.")),
+            HeaderCheck::Updating => Some(("database-updating", "is written by the first update.")),
+            _ => None,
+        };
+        if let Some((code, message)) = missing {
+            let message = format!("Database for '{}' {}", vi.folder.display(), message);
+            print_event(
+                Severity::Warning,
+                &Message::new(code, message).with_volume(&vi.folder),
+            );
+            continue;
+        }
+        let (code, severity, message) = match fsidx::verify(&vi.database, fast) {
+            Ok(Verification::Valid(entries)) => (
+                "verified",
+                Severity::Info,
+                format!("Verified '{}'{}", vi.folder.display(), count(entries)),
+            ),
+            Ok(Verification::NoChecksum(_)) => (
+                "no-checksum",
+                Severity::Warning,
+                format!(
+                    "Database for '{}' has no checksum. Run update to add it.",
+                    vi.folder.display()
+                ),
+            ),
+            Ok(verification) => (
+                "unknown-verification",
+                Severity::Warning,
+                format!(
+                    "Database for '{}' has an unknown verification result: {:?}",
+                    vi.folder.display(),
+                    verification
+                ),
+            ),
+            Err(err) => {
+                failed = true;
+                print_event(Severity::Error, &CliError::LocateError(err));
+                continue;
+            }
+        };
+        print_event(
+            severity,
+            &Message::new(code, message).with_volume(&vi.folder),
+        );
+        if portable {
+            match fsidx::check_portability(&vi.database) {
+                Ok(portability) => print_portability(&vi.folder, &portability),
+                Err(err) => {
+                    failed = true;
+                    print_event(Severity::Error, &CliError::LocateError(err));
+                }
+            }
+        }
+    }
+    if failed {
+        return Err(CliError::VerificationFailed);
+    }
+    Ok(())
+}
+
+fn print_portability(folder: &Path, portability: &Portability) {
+    if portability.is_portable() {
+        let message = format!("Database for '{}' is portable", folder.display());
+        print_event(
+            Severity::Info,
+            &Message::new("portable", message).with_volume(folder),
+        );
+        return;
+    }
+    let mut issues = Vec::new();
+    if portability.times_after_2038 > 0 {
+        issues.push(format!(
+            "{} modification times after 2038 are unknown on platforms with a 32-bit time_t",
+            portability.times_after_2038
+        ));
+    }
+    if portability.non_utf8_paths > 0 {
+        issues.push(format!(
+            "{} paths are not valid UTF-8 and can't exist on macOS",
+            portability.non_utf8_paths
+        ));
+    }
+    let message = format!(
+        "Database for '{}' is not portable: {}",
+        folder.display(),
+        issues.join(", ")
+    );
+    print_event(
+        Severity::Warning,
+        &Message::new("not-portable", message).with_volume(folder),
+    );
+}
+
+fn count(entries: Option<u64>) -> String {
+    match entries {
+        Some(entries) => format!(": {} entries", format_size(entries)),
+        None => String::new(),
+    }
+}
@@ -0,0 +1,62 @@
+use crate::cli::CliError;
+use crate::config::Config;
+use crate::event::{print_event, Message};
+use crate::locate::{check_databases, format_size, searchable_volumes, HeaderChecks};
+use fsidx::{DatabaseCache, LocateError, Severity};
+use std::env::Args;
+use std::fs;
+use std::time::Instant;
+
+pub(crate) fn warm_cli(config: &Config, args: &mut Args) -> Result<(), CliError> {
+    if let Some(arg) = args.next() {
+        return Err(CliError::InvalidWarmArgument(arg));
+    }
+    let checks = check_databases(config)?;
+    warm_databases(config, &checks, None)
+}
+
+// Reads the database files into the in-memory cache of the shell, if given.
+// Database files, which don't fit into the cache, and all database files
+// without cache are read into the page cache of the operating system.
+pub(crate) fn warm_databases(
+    config: &Config,
+    checks: &HeaderChecks,
+    mut cache: Option<&mut DatabaseCache>,
+) -> Result<(), CliError> {
+    for vi in searchable_volumes(config, checks)? {
+        let start = Instant::now();
+        let cached = match cache.as_deref_mut() {
+            Some(cache) => cache.load(&vi.database),
+            None => Ok(false),
+        };
+        let res = cached.and_then(|cached| {
+            let size = if cached {
+                fs::metadata(&vi.database)
+                    .map(|metadata| metadata.len())
+                    .map_err(|err| LocateError::ReadingFileFailed(vi.database.clone(), err))?
+            } else {
+                fsidx::warm(&vi.database)?
+            };
+            Ok((cached, size))
+        });
+        let (cached, size) = match res {
+            Ok(res) => res,
+            Err(err) => {
+                print_event(Severity::Warning, &CliError::LocateError(err));
+                continue;
+            }
+        };
+        let message = format!(
+            "{} '{}': {} bytes in {:.3} s",
+            if cached { "Cached" } else { "Warmed" },
+            vi.folder.display(),
+            format_size(size),
+            start.elapsed().as_secs_f64()
+        );
+        print_event(
+            Severity::Info,
+            &Message::new("warmed", message).with_volume(&vi.folder),
+        );
+    }
+    Ok(())
+}
@@ -0,0 +1,104 @@
+#[cfg(feature = "compression")]
+use std::io::BufReader;
+#[cfg(feature = "update")]
+use std::io::Write;
+#[cfg(not(feature = "compression"))]
+use std::io::{Error, ErrorKind};
+use std::io::{Read, Result as IOResult};
+#[cfg(feature = "compression")]
+use zstd::stream::read::Decoder;
+#[cfg(all(feature = "update", feature = "compression"))]
+use zstd::stream::write::Encoder;
+
+// Compression level of zstd. Higher levels compress delta encoded paths only
+// slightly better, but are much slower.
+#[cfg(all(feature = "update", feature = "compression"))]
+const LEVEL: i32 = 3;
+
+// The entries of a compressed database file and the end marker are stored as
+// a single zstd frame after the header. The header stays uncompressed, i.e.
+// it is checked without decompression. The checksum covers the stored bytes.
+#[cfg(feature = "update")]
+pub(crate) enum PayloadWriter<W: Write> {
+    Plain(W),
+    #[cfg(feature = "compression")]
+    Zstd(Encoder<'static, W>),
+}
+
+#[cfg(feature = "update")]
+impl<W: Write> PayloadWriter<W> {
+    pub(crate) fn new(writer: W, compress: bool) -> IOResult<PayloadWriter<W>> {
+        match compress {
+            #[cfg(feature = "compression")]
+            true => Ok(PayloadWriter::Zstd(Encoder::new(writer, LEVEL)?)),
+            #[cfg(not(feature = "compression"))]
+            true => Err(unsupported()),
+            false => Ok(PayloadWriter::Plain(writer)),
+        }
+    }
+
+    // Completes the zstd frame.
+    pub(crate) fn finish(self) -> IOResult<W> {
+        match self {
+            PayloadWriter::Plain(writer) => Ok(writer),
+            #[cfg(feature = "compression")]
+            PayloadWriter::Zstd(encoder) => encoder.finish(),
+        }
+    }
+}
+
+#[cfg(feature = "update")]
+impl<W: Write> Write for PayloadWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> IOResult<usize> {
+        match self {
+            PayloadWriter::Plain(writer) => writer.write(buf),
+            #[cfg(feature = "compression")]
+            PayloadWriter::Zstd(encoder) => encoder.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> IOResult<()> {
+        match self {
+            PayloadWriter::Plain(writer) => writer.flush(),
+            #[cfg(feature = "compression")]
+            PayloadWriter::Zstd(encoder) => encoder.flush(),
+        }
+    }
+}
+
+pub(crate) enum PayloadReader<R: Read> {
+    Plain(R),
+    #[cfg(feature = "compression")]
+    Zstd(Decoder<'static, BufReader<R>>),
+}
+
+impl<R: Read> PayloadReader<R> {
+    // The checksum after the frame is not read.
+    pub(crate) fn new(reader: R, compressed: bool) -> IOResult<PayloadReader<R>> {
+        match compressed {
+            #[cfg(feature = "compression")]
+            true => Ok(PayloadReader::Zstd(Decoder::new(reader)?.single_frame())),
+            #[cfg(not(feature = "compression"))]
+            true => Err(unsupported()),
+            false => Ok(PayloadReader::Plain(reader)),
+        }
+    }
+}
+
+impl<R: Read> Read for PayloadReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> IOResult<usize> {
+        match self {
+            PayloadReader::Plain(reader) => reader.read(buf),
+            #[cfg(feature = "compression")]
+            PayloadReader::Zstd(decoder) => decoder.read(buf),
+        }
+    }
+}
+
+#[cfg(not(feature = "compression"))]
+fn unsupported() -> Error {
+    Error::new(
+        ErrorKind::Unsupported,
+        "zstd compression requires the compression feature",
+    )
+}
@@ -1,14 +1,97 @@
+use crate::volume::VolumeId;
 use num_enum::TryFromPrimitive;
+#[cfg(feature = "serde")]
 use serde::{Deserialize, Serialize};
 use std::path::PathBuf;
 
 /// VolumeInfo holds the root folder of a scanned directory tree and the location of the corresponding database file.
+///
+/// Fields may be added in any release. Use [VolumeInfo::new] and the `with_`
+/// methods to create it.
 #[derive(Debug, Clone)]
+#[non_exhaustive]
 pub struct VolumeInfo {
     /// Root folder of a scanned directory tree.
     pub folder: PathBuf,
-    /// Location of the corresponding database file.
+    /// Location of the corresponding database file. Folders with the same
+    /// database file are stored together in this file. The entries of
+    /// [locate] are reported with the folder containing them. Shared
+    /// database files are written without volume information.
+    ///
+    /// [locate]: crate::locate
     pub database: PathBuf,
+    /// Locate defaults used for this volume instead of the defaults passed
+    /// to [locate](crate::locate()).
+    pub locate_config: Option<LocateConfig>,
+    /// Names of the enrichment providers enabled for this volume. See
+    /// [update_with_providers](crate::update_with_providers()).
+    pub providers: Vec<String>,
+    /// Identifies the file system of a removable volume. The folder is
+    /// scanned and searched at its current location, when the file system is
+    /// mounted at a different path.
+    pub volume_id: Option<VolumeId>,
+    /// Glob patterns of directories and files skipped when scanning the
+    /// folder. Patterns are matched against paths relative to the folder,
+    /// e.g. `**/.git` or `*.tmp`. The contents of excluded directories are
+    /// skipped, too.
+    pub exclude: Vec<String>,
+    /// Traverse symbolic links to directories when scanning the folder.
+    /// Otherwise the links are stored as entries without their contents.
+    pub follow_symlinks: bool,
+}
+
+impl VolumeInfo {
+    /// Creates the volume information of a folder and its database file
+    /// without per-volume settings.
+    pub fn new(folder: impl Into<PathBuf>, database: impl Into<PathBuf>) -> VolumeInfo {
+        VolumeInfo {
+            folder: folder.into(),
+            database: database.into(),
+            locate_config: None,
+            providers: Vec::new(),
+            volume_id: None,
+            exclude: Vec::new(),
+            follow_symlinks: false,
+        }
+    }
+
+    /// Sets [VolumeInfo::locate_config].
+    pub fn with_locate_config(mut self, locate_config: Option<LocateConfig>) -> VolumeInfo {
+        self.locate_config = locate_config;
+        self
+    }
+
+    /// Sets [VolumeInfo::providers].
+    pub fn with_providers(mut self, providers: Vec<String>) -> VolumeInfo {
+        self.providers = providers;
+        self
+    }
+
+    /// Sets [VolumeInfo::volume_id].
+    pub fn with_volume_id(mut self, volume_id: Option<VolumeId>) -> VolumeInfo {
+        self.volume_id = volume_id;
+        self
+    }
+
+    /// Sets [VolumeInfo::exclude].
+    pub fn with_exclude(mut self, exclude: Vec<String>) -> VolumeInfo {
+        self.exclude = exclude;
+        self
+    }
+
+    /// Sets [VolumeInfo::follow_symlinks].
+    pub fn with_follow_symlinks(mut self, follow_symlinks: bool) -> VolumeInfo {
+        self.follow_symlinks = follow_symlinks;
+        self
+    }
+}
+
+// Folder dir/name of a test with the database file dir/name.fsdb.
+#[cfg(all(test, feature = "update"))]
+impl VolumeInfo {
+    pub(crate) fn in_dir(dir: &std::path::Path, name: &str) -> VolumeInfo {
+        VolumeInfo::new(dir.join(name), dir.join(format!("{}.fsdb", name)))
+    }
 }
 
 /// Settings about what information will be stored in the database.
@@ -19,34 +102,178 @@ pub enum Settings {
     FileNamesOnly = 0,
     /// Store file names and sizes.
     WithFileSizes = 1,
+    /// Store file names, sizes and the metadata contributed by enrichment
+    /// providers.
+    WithMetadata = 2,
+}
+
+impl Settings {
+    pub(crate) fn has_sizes(&self) -> bool {
+        *self != Settings::FileNamesOnly
+    }
+
+    pub(crate) fn has_metadata(&self) -> bool {
+        *self == Settings::WithMetadata
+    }
 }
 
 /// Default configuration for locate queries.
-#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
-#[serde(deny_unknown_fields)]
-#[serde(rename_all = "kebab-case")]
+///
+/// Fields may be added in any release. Start with [LocateConfig::default].
+#[derive(Debug, PartialEq, Clone)]
+#[non_exhaustive]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(deny_unknown_fields, rename_all = "kebab-case")
+)]
 pub struct LocateConfig {
     /// Case-sensitivity.
-    #[serde(default = "default_case_sensitive")]
+    #[cfg_attr(feature = "serde", serde(default = "default_case_sensitive"))]
     pub case_sensitive: bool,
     /// In which order plain text must appear.
-    #[serde(default)]
+    #[cfg_attr(feature = "serde", serde(default))]
     pub order: Order,
     /// What parts of the pathname are searched.
-    #[serde(default)]
+    #[cfg_attr(feature = "serde", serde(default))]
     pub what: What,
     /// If space, minus and underscore in plain text match each other and no character.
-    #[serde(default = "default_smart_spaces")]
+    #[cfg_attr(feature = "serde", serde(default = "default_smart_spaces"))]
     pub smart_spaces: bool,
     /// If start and end of plain text must match on a word boundary.
-    #[serde(default = "default_word_boundaries")]
+    #[cfg_attr(feature = "serde", serde(default = "default_word_boundaries"))]
     pub word_boundaries: bool,
     /// If an asterisk (*) in a glob expression is not matching a path separator (/).
-    #[serde(default = "default_literal_separator")]
+    #[cfg_attr(feature = "serde", serde(default = "default_literal_separator"))]
     pub literal_separator: bool,
     /// Distinguish between glob patterns and plain text.
-    #[serde(default)]
+    #[cfg_attr(feature = "serde", serde(default))]
     pub mode: Mode,
+    /// Capacity of the buffer for reading database files in bytes.
+    #[cfg_attr(feature = "serde", serde(default = "default_buffer_size"))]
+    pub buffer_size: usize,
+    /// Advise the operating system that database files are read
+    /// sequentially. Increases the read-ahead on Linux and is ignored on
+    /// other systems.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub readahead: bool,
+    /// Verify the checksum of database files before searching them. The
+    /// file is read twice. Database files in a
+    /// [DatabaseCache](crate::DatabaseCache) are verified once. Database
+    /// files without checksum, written by earlier versions, are searched
+    /// without verification.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub verify_checksums: bool,
+    /// Number of matching entries skipped before entries are reported.
+    /// Not read from configuration files.
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub offset: u64,
+    /// Maximum number of reported entries. Database files are not read any
+    /// further when the limit is reached. Not read from configuration files.
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub limit: Option<u64>,
+    /// Maximum number of database entries scanned by a query over all
+    /// folders. A query exceeding it fails with
+    /// [LocateError::QueryTooBroad](crate::LocateError::QueryTooBroad).
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    pub max_entries_scanned: Option<u64>,
+    /// Report the results of [locate_parallel](crate::locate_parallel) and
+    /// [locate_pooled](crate::locate_pooled) in the order of the folders.
+    /// Results of later folders are buffered in memory.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub ordered: bool,
+    /// How a frontend handles queries detected by
+    /// [broad_query](crate::broad_query).
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub broad_query: BroadQueryAction,
+    /// Maximum time in milliseconds a query waits for an update writing the
+    /// first database file of a folder. While an update replaces an
+    /// existing database file, the previous one is searched without
+    /// waiting.
+    #[cfg_attr(feature = "serde", serde(default = "default_update_wait_ms"))]
+    pub update_wait_ms: u64,
+}
+
+/// Configuration for updating the database files.
+///
+/// Fields may be added in any release. Start with [UpdateConfig::default].
+#[derive(Debug, PartialEq, Clone)]
+#[non_exhaustive]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(deny_unknown_fields, rename_all = "kebab-case")
+)]
+pub struct UpdateConfig {
+    /// How often reading a directory or file metadata is retried after a
+    /// transient error.
+    #[cfg_attr(feature = "serde", serde(default = "default_retries"))]
+    pub retries: u32,
+    /// Delay in milliseconds before the first retry. The delay is doubled
+    /// for each further retry.
+    #[cfg_attr(feature = "serde", serde(default = "default_retry_delay_ms"))]
+    pub retry_delay_ms: u64,
+    /// Store the modification times of entries in addition to the
+    /// information selected by the [Settings].
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub modification_times: bool,
+    /// Maximum length of stored paths in bytes. Longer paths are reported
+    /// with [UpdateEvent::PathTooLong](crate::UpdateEvent::PathTooLong).
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub max_path_length: Option<usize>,
+    /// Maximum depth of stored entries below the folder. Deeper entries are
+    /// reported with [UpdateEvent::PathTooDeep](crate::UpdateEvent::PathTooDeep).
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub max_depth: Option<usize>,
+    /// Skip entries exceeding [UpdateConfig::max_path_length] or
+    /// [UpdateConfig::max_depth] including the contents of such directories.
+    /// By default the entries are stored anyway.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub skip_exceeding: bool,
+    /// Compress the entries of the database files with zstd. Compressed
+    /// database files are smaller, but searching them needs more CPU time.
+    /// Writing fails without the `compression` feature.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub compress: bool,
+    /// Write byte-identical database files for identical directory trees.
+    /// Directory sizes, which depend on the file system, are stored as
+    /// unknown. Not read from configuration files.
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub reproducible: bool,
+}
+
+fn default_retries() -> u32 {
+    2
+}
+
+fn default_retry_delay_ms() -> u64 {
+    100
+}
+
+impl Default for UpdateConfig {
+    fn default() -> Self {
+        UpdateConfig {
+            retries: default_retries(),
+            retry_delay_ms: default_retry_delay_ms(),
+            modification_times: false,
+            max_path_length: None,
+            max_depth: None,
+            skip_exceeding: false,
+            compress: false,
+            reproducible: false,
+        }
+    }
+}
+
+fn default_update_wait_ms() -> u64 {
+    2000
+}
+
+fn default_buffer_size() -> usize {
+    8 * 1024
 }
 
 fn default_case_sensitive() -> bool {
@@ -66,9 +293,12 @@ fn default_literal_separator() -> bool {
 }
 
 /// Defines in which order plain text must appear in the pathname.
-#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Default)]
-#[serde(deny_unknown_fields)]
-#[serde(rename_all = "kebab-case")]
+#[derive(Debug, PartialEq, Clone, Default)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(deny_unknown_fields, rename_all = "kebab-case")
+)]
 pub enum Order {
     /// Plain text may appear in any order.
     #[default]
@@ -78,9 +308,12 @@ pub enum Order {
 }
 
 /// Defines which parts of the pathname are used to match plain text and glob patterns.
-#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Default)]
-#[serde(deny_unknown_fields)]
-#[serde(rename_all = "kebab-case")]
+#[derive(Debug, PartialEq, Clone, Default)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(deny_unknown_fields, rename_all = "kebab-case")
+)]
 pub enum What {
     /// The whole path is used.
     #[default]
@@ -91,11 +324,15 @@ pub enum What {
 
 /// Defines how subsequent [FilterToken::Text](crate::filter::FilterToken#variant.Text)
 /// filter elements are used.
-#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Copy, Default)]
-#[serde(deny_unknown_fields)]
-#[serde(rename_all = "kebab-case")]
+#[derive(Debug, PartialEq, Clone, Copy, Default)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(deny_unknown_fields, rename_all = "kebab-case")
+)]
 pub enum Mode {
-    /// Glob patterns are autodetected.
+    /// Glob patterns are autodetected. Without the `glob` feature text is
+    /// used as plain text.
     #[default]
     Auto,
     /// [Text](crate::filter::FilterToken#variant.Text) elements are used
@@ -107,6 +344,25 @@ pub enum Mode {
     Glob,
 }
 
+/// Defines how a frontend handles a broad query, i.e. a query matching all or
+/// most database entries.
+#[derive(Debug, PartialEq, Clone, Copy, Default)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(deny_unknown_fields, rename_all = "kebab-case")
+)]
+pub enum BroadQueryAction {
+    /// A warning is printed and the query is run.
+    #[default]
+    Warn,
+    /// An interactive frontend asks before running the query. Other
+    /// frontends print a warning.
+    Confirm,
+    /// The query is run without warning.
+    Allow,
+}
+
 impl Default for LocateConfig {
     fn default() -> Self {
         LocateConfig {
@@ -117,6 +373,15 @@ impl Default for LocateConfig {
             word_boundaries: default_word_boundaries(),
             literal_separator: default_literal_separator(),
             mode: Mode::default(),
+            buffer_size: default_buffer_size(),
+            readahead: false,
+            verify_checksums: false,
+            offset: 0,
+            limit: None,
+            max_entries_scanned: None,
+            ordered: false,
+            broad_query: BroadQueryAction::default(),
+            update_wait_ms: default_update_wait_ms(),
         }
     }
 }
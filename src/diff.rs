@@ -0,0 +1,217 @@
+use crate::locate::{FileIndexReader, LocateError};
+use core::cmp::Ordering;
+use std::ffi::OsStr;
+use std::fmt::Display;
+use std::path::{Path, PathBuf};
+
+/// Summary of the differences between two database files of the same folder.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct DatabaseDiff {
+    /// Number of entries in the old database file.
+    pub old_entries: u64,
+    /// Number of entries in the new database file.
+    pub new_entries: u64,
+    /// Number of entries only found in the new database file.
+    pub added: u64,
+    /// Number of entries only found in the old database file.
+    pub removed: u64,
+    /// Sum of all file sizes in the old database file.
+    pub old_size: u64,
+    /// Sum of all file sizes in the new database file.
+    pub new_size: u64,
+}
+
+impl DatabaseDiff {
+    /// Returns the change of the summed up file sizes in bytes.
+    pub fn size_delta(&self) -> i128 {
+        self.new_size as i128 - self.old_size as i128
+    }
+}
+
+impl Display for DatabaseDiff {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let delta = self.size_delta();
+        f.write_fmt(format_args!(
+            "+{} entries, -{} entries, {}{}",
+            self.added,
+            self.removed,
+            if delta < 0 { '-' } else { '+' },
+            human_readable(delta.unsigned_abs())
+        ))
+    }
+}
+
+fn human_readable(bytes: u128) -> String {
+    const UNITS: [&str; 6] = ["B", "kB", "MB", "GB", "TB", "PB"];
+    let mut value = bytes as f64;
+    let mut unit = 0;
+    while value >= 1000.0 && unit + 1 < UNITS.len() {
+        value /= 1000.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{} {}", bytes, UNITS[0])
+    } else {
+        format!("{:.1} {}", value, UNITS[unit])
+    }
+}
+
+/// Compares two database files of the same folder.
+///
+/// Both files are read sequentially. The entries are merged in the order
+/// in which they are written by [update](crate::update()), i.e. the files are
+/// never loaded completely into memory.
+pub fn diff_databases(old: &Path, new: &Path) -> Result<DatabaseDiff, LocateError> {
+    let mut old = Entries::new(old)?;
+    let mut new = Entries::new(new)?;
+    let mut diff = DatabaseDiff::default();
+    let mut old_entry = old.next(&mut diff.old_entries, &mut diff.old_size)?;
+    let mut new_entry = new.next(&mut diff.new_entries, &mut diff.new_size)?;
+    loop {
+        let ordering = match (&old_entry, &new_entry) {
+            (None, None) => break,
+            (Some(_), None) => Ordering::Less,
+            (None, Some(_)) => Ordering::Greater,
+            (Some(old_path), Some(new_path)) => compare_paths(old_path, new_path),
+        };
+        match ordering {
+            Ordering::Less => diff.removed += 1,
+            Ordering::Greater => diff.added += 1,
+            Ordering::Equal => {}
+        }
+        if ordering != Ordering::Greater {
+            old_entry = old.next(&mut diff.old_entries, &mut diff.old_size)?;
+        }
+        if ordering != Ordering::Less {
+            new_entry = new.next(&mut diff.new_entries, &mut diff.new_size)?;
+        }
+    }
+    Ok(diff)
+}
+
+struct Entries {
+    reader: FileIndexReader,
+}
+
+impl Entries {
+    fn new(database: &Path) -> Result<Entries, LocateError> {
+        Ok(Entries {
+            reader: FileIndexReader::new(database)?,
+        })
+    }
+
+    fn next(&mut self, entries: &mut u64, size: &mut u64) -> Result<Option<PathBuf>, LocateError> {
+        Ok(self.reader.next_entry()?.map(|(path, metadata)| {
+            *entries += 1;
+            *size += metadata.size.unwrap_or(0);
+            path.to_path_buf()
+        }))
+    }
+}
+
+pub(crate) fn compare(a: &OsStr, b: &OsStr) -> Ordering {
+    let a1 = a.to_string_lossy();
+    let b1 = b.to_string_lossy();
+    natord::compare(&a1, &b1)
+}
+
+/// Compares path names in the order of the directory walk, i.e. path
+/// elements are compared one by one with natural ordering. Names equal in
+/// natural order are compared by their bytes.
+pub fn compare_paths(a: &Path, b: &Path) -> Ordering {
+    let mut a = a.components();
+    let mut b = b.components();
+    loop {
+        match (a.next(), b.next()) {
+            (None, None) => return Ordering::Equal,
+            (None, Some(_)) => return Ordering::Less,
+            (Some(_), None) => return Ordering::Greater,
+            (Some(a), Some(b)) => {
+                let ordering = compare(a.as_os_str(), b.as_os_str())
+                    .then_with(|| a.as_os_str().cmp(b.as_os_str()));
+                if ordering != Ordering::Equal {
+                    return ordering;
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::temp_dir::TempDir;
+    use std::fs;
+
+    fn database(dir: &Path, name: &str, entries: &[(&str, u64)]) -> PathBuf {
+        let path = dir.join(format!("{}.fsdb", name));
+        let mut content = b"fsix\x01".to_vec();
+        for (entry, size) in entries {
+            // Entries are written without delta compression.
+            content.extend_from_slice(&[0, entry.len() as u8]);
+            content.extend_from_slice(entry.as_bytes());
+            content.push(*size as u8 + 1);
+        }
+        fs::write(&path, content).unwrap();
+        path
+    }
+
+    #[test]
+    fn diff_added_and_removed() {
+        let dir = TempDir::new("diff");
+        let old = database(
+            &dir,
+            "old",
+            &[("/a", 0), ("/a/file2", 10), ("/a/file10", 20), ("/b", 0)],
+        );
+        let new = database(
+            &dir,
+            "new",
+            &[("/a", 0), ("/a/file2", 10), ("/a/file3", 5), ("/c", 1)],
+        );
+        assert_eq!(
+            diff_databases(&old, &new).unwrap(),
+            DatabaseDiff {
+                old_entries: 4,
+                new_entries: 4,
+                added: 2,
+                removed: 2,
+                old_size: 30,
+                new_size: 16,
+            }
+        );
+    }
+
+    #[test]
+    fn display() {
+        let diff = DatabaseDiff {
+            added: 1234,
+            removed: 56,
+            old_size: 1_000,
+            new_size: 12_300_001_000,
+            ..DatabaseDiff::default()
+        };
+        assert_eq!(diff.to_string(), "+1234 entries, -56 entries, +12.3 GB");
+        let diff = DatabaseDiff {
+            old_size: 512,
+            ..DatabaseDiff::default()
+        };
+        assert_eq!(diff.to_string(), "+0 entries, -0 entries, -512 B");
+    }
+
+    #[test]
+    fn walk_order() {
+        assert_eq!(
+            compare_paths(Path::new("/a/file2"), Path::new("/a/file10")),
+            Ordering::Less
+        );
+        assert_eq!(
+            compare_paths(Path::new("/a"), Path::new("/a/b")),
+            Ordering::Less
+        );
+        assert_eq!(
+            compare_paths(Path::new("/a/z"), Path::new("/b")),
+            Ordering::Less
+        );
+    }
+}
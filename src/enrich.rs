@@ -0,0 +1,351 @@
+use crate::meta::ValueType;
+use std::fmt::Display;
+use std::fs::{self, File};
+use std::io::{BufReader, Read, Result as IOResult};
+use std::path::Path;
+use std::sync::Arc;
+
+/// Capability an [EnrichmentProvider] requires from the update.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Capability {
+    /// The provider reads the content of files. It is only invoked for
+    /// regular files.
+    FileContent,
+    /// The provider needs the file system metadata of an entry. It is not
+    /// invoked when reading the metadata failed.
+    FileMetadata,
+}
+
+/// An EnrichmentProvider contributes metadata for database entries.
+///
+/// Providers are invoked by [update_with_providers](crate::update_with_providers())
+/// for every scanned entry. The returned value is stored in the database file
+/// as a TLV record with the tag of the provider and is reported with the
+/// [Metadata](crate::Metadata) of a query result.
+pub trait EnrichmentProvider: Send + Sync {
+    /// Unique name of the provider, e.g. used in configuration files.
+    fn name(&self) -> &str;
+
+    /// Unique tag of the TLV records written by the provider. Tag 0 is
+    /// reserved.
+    fn tag(&self) -> u64;
+
+    /// Capabilities required by the provider.
+    fn capabilities(&self) -> &[Capability] {
+        &[]
+    }
+
+    /// Type of the contributed values. The name of the provider is the key
+    /// for querying the values with a [MetaPredicate](crate::MetaPredicate).
+    fn value_type(&self) -> ValueType {
+        ValueType::Bytes
+    }
+
+    /// Returns the value stored for an entry. None is returned, if the
+    /// provider has nothing to contribute for the entry. An error is reported
+    /// as [UpdateEvent::EnrichmentFailed](crate::UpdateEvent::EnrichmentFailed)
+    /// and the entry is stored without the value.
+    fn enrich(&self, path: &Path, metadata: Option<&fs::Metadata>) -> IOResult<Option<Vec<u8>>>;
+}
+
+/// Registry of the enrichment providers used for an update.
+#[derive(Clone, Default)]
+pub struct ProviderRegistry {
+    providers: Vec<Arc<dyn EnrichmentProvider>>,
+}
+
+impl ProviderRegistry {
+    /// Creates an empty registry.
+    pub fn new() -> ProviderRegistry {
+        ProviderRegistry::default()
+    }
+
+    /// Adds a provider. Name and tag must be unique.
+    pub fn register(&mut self, provider: Arc<dyn EnrichmentProvider>) -> Result<(), RegistryError> {
+        if provider.tag() == 0 {
+            return Err(RegistryError::ReservedTag(provider.name().to_string()));
+        }
+        for registered in &self.providers {
+            if registered.name() == provider.name() {
+                return Err(RegistryError::DuplicateName(provider.name().to_string()));
+            }
+            if registered.tag() == provider.tag() {
+                return Err(RegistryError::DuplicateTag(
+                    provider.name().to_string(),
+                    registered.name().to_string(),
+                ));
+            }
+        }
+        self.providers.push(provider);
+        Ok(())
+    }
+
+    /// Creates a registry with the providers implemented in this crate.
+    pub fn builtin() -> ProviderRegistry {
+        let mut registry = ProviderRegistry::new();
+        registry
+            .register(Arc::new(ContentHash))
+            .expect("builtin providers are unique");
+        registry
+    }
+
+    /// Returns a registry with the providers of the given names. Unknown
+    /// names are ignored. Frontends are expected to validate the names with
+    /// [ProviderRegistry::get].
+    pub fn select(&self, names: &[String]) -> ProviderRegistry {
+        ProviderRegistry {
+            providers: self
+                .providers
+                .iter()
+                .filter(|provider| names.iter().any(|name| name == provider.name()))
+                .cloned()
+                .collect(),
+        }
+    }
+
+    /// Returns the provider with the given name.
+    pub fn get(&self, name: &str) -> Option<&Arc<dyn EnrichmentProvider>> {
+        self.providers
+            .iter()
+            .find(|provider| provider.name() == name)
+    }
+
+    /// Returns all providers in the order of registration.
+    pub fn providers(&self) -> &[Arc<dyn EnrichmentProvider>] {
+        &self.providers
+    }
+
+    /// Returns true, if no provider is registered.
+    pub fn is_empty(&self) -> bool {
+        self.providers.is_empty()
+    }
+}
+
+/// RegistryError reports why a provider cannot be registered.
+#[derive(Debug, PartialEq)]
+#[non_exhaustive]
+pub enum RegistryError {
+    /// A provider with the same name is already registered.
+    DuplicateName(String),
+    /// The provider uses the same tag as an already registered provider.
+    DuplicateTag(String, String),
+    /// The provider uses the reserved tag 0.
+    ReservedTag(String),
+}
+
+impl Display for RegistryError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RegistryError::DuplicateName(name) => {
+                f.write_fmt(format_args!("Provider '{}' is already registered.", name))
+            }
+            RegistryError::DuplicateTag(name, registered) => f.write_fmt(format_args!(
+                "Provider '{}' uses the same tag as provider '{}'.",
+                name, registered
+            )),
+            RegistryError::ReservedTag(name) => {
+                f.write_fmt(format_args!("Provider '{}' uses the reserved tag 0.", name))
+            }
+        }
+    }
+}
+
+/// The ContentHash provider stores the 64 bit FNV-1a hash of the file content
+/// in big endian byte order.
+pub struct ContentHash;
+
+impl EnrichmentProvider for ContentHash {
+    fn name(&self) -> &str {
+        "content-hash"
+    }
+
+    fn tag(&self) -> u64 {
+        1
+    }
+
+    fn capabilities(&self) -> &[Capability] {
+        &[Capability::FileContent]
+    }
+
+    fn enrich(&self, path: &Path, _metadata: Option<&fs::Metadata>) -> IOResult<Option<Vec<u8>>> {
+        let mut reader = BufReader::new(File::open(path)?);
+        let mut hash = Fnv1a::new();
+        let mut buffer = [0u8; 65536];
+        loop {
+            let len = reader.read(&mut buffer)?;
+            if len == 0 {
+                return Ok(Some(hash.0.to_be_bytes().to_vec()));
+            }
+            hash.write(&buffer[..len]);
+        }
+    }
+}
+
+struct Fnv1a(u64);
+
+impl Fnv1a {
+    fn new() -> Fnv1a {
+        Fnv1a(0xcbf29ce484222325)
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.0 ^= *byte as u64;
+            self.0 = self.0.wrapping_mul(0x100000001b3);
+        }
+    }
+}
+
+// Returns true, if the provider is invoked for the entry.
+#[cfg(feature = "update")]
+pub(crate) fn applies(
+    provider: &dyn EnrichmentProvider,
+    is_file: bool,
+    metadata: Option<&fs::Metadata>,
+) -> bool {
+    provider
+        .capabilities()
+        .iter()
+        .all(|capability| match capability {
+            Capability::FileContent => is_file,
+            Capability::FileMetadata => metadata.is_some(),
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[cfg(feature = "update")]
+    use crate::locate::FileIndexReader;
+    #[cfg(feature = "update")]
+    use crate::temp_dir::TempDir;
+    #[cfg(feature = "update")]
+    use crate::{update_with_providers, Settings, UpdateConfig, UpdateEvent, VolumeInfo};
+    use std::io::ErrorKind;
+
+    struct Length;
+
+    impl EnrichmentProvider for Length {
+        fn name(&self) -> &str {
+            "length"
+        }
+
+        fn tag(&self) -> u64 {
+            11
+        }
+
+        fn capabilities(&self) -> &[Capability] {
+            &[Capability::FileContent]
+        }
+
+        fn enrich(
+            &self,
+            path: &Path,
+            _metadata: Option<&fs::Metadata>,
+        ) -> IOResult<Option<Vec<u8>>> {
+            let content = fs::read(path)?;
+            Ok(Some(content.len().to_string().into_bytes()))
+        }
+    }
+
+    struct Failing;
+
+    impl EnrichmentProvider for Failing {
+        fn name(&self) -> &str {
+            "failing"
+        }
+
+        fn tag(&self) -> u64 {
+            12
+        }
+
+        fn enrich(
+            &self,
+            _path: &Path,
+            _metadata: Option<&fs::Metadata>,
+        ) -> IOResult<Option<Vec<u8>>> {
+            Err(ErrorKind::Unsupported.into())
+        }
+    }
+
+    #[test]
+    fn registry() {
+        let mut registry = ProviderRegistry::new();
+        assert!(registry.is_empty());
+        registry.register(Arc::new(Length)).unwrap();
+        assert_eq!(
+            registry.register(Arc::new(Length)),
+            Err(RegistryError::DuplicateName(String::from("length")))
+        );
+        registry.register(Arc::new(Failing)).unwrap();
+        assert!(registry.get("failing").is_some());
+        assert!(registry.get("exif").is_none());
+        assert_eq!(registry.providers().len(), 2);
+        let selected = registry.select(&[String::from("length"), String::from("exif")]);
+        assert_eq!(selected.providers().len(), 1);
+        assert!(selected.get("length").is_some());
+        assert_eq!(
+            registry
+                .register(Arc::new(ContentHash))
+                .map_err(|err| err.to_string()),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn content_hash() {
+        let mut hash = Fnv1a::new();
+        assert_eq!(hash.0, 0xcbf29ce484222325);
+        hash.write(b"a");
+        assert_eq!(hash.0, 0xaf63dc4c8601ec8c);
+        hash.write(b"bc");
+        let mut abc = Fnv1a::new();
+        abc.write(b"abc");
+        assert_eq!(hash.0, abc.0);
+    }
+
+    #[cfg(feature = "update")]
+    #[test]
+    fn update_with_metadata() {
+        let dir = TempDir::new("enrich");
+        let folder = dir.join("folder");
+        fs::create_dir_all(folder.join("sub")).unwrap();
+        fs::write(folder.join("sub/file"), b"content").unwrap();
+        let volume_info = VolumeInfo::in_dir(&dir, "folder")
+            .with_providers(vec![String::from("length"), String::from("failing")]);
+        let mut registry = ProviderRegistry::new();
+        registry.register(Arc::new(Length)).unwrap();
+        registry.register(Arc::new(Failing)).unwrap();
+        let mut failures = 0;
+        update_with_providers(
+            vec![volume_info.clone()],
+            Settings::WithFileSizes,
+            &UpdateConfig::default(),
+            &registry,
+            None,
+            |event| {
+                if let UpdateEvent::EnrichmentFailed(_, name, _) = &event {
+                    assert_eq!(name, "failing");
+                    failures += 1;
+                }
+                Ok(())
+            },
+        );
+        let mut entries = Vec::new();
+        let mut reader = FileIndexReader::new(&volume_info.database).unwrap();
+        while let Some((path, metadata)) = reader.next_entry().unwrap() {
+            let relative = path.strip_prefix(&folder).unwrap().to_owned();
+            let length = metadata.attribute(11).map(|value| value.to_vec());
+            entries.push((relative, metadata.size.is_some(), length));
+        }
+        assert_eq!(failures, 3);
+        assert_eq!(
+            entries,
+            vec![
+                (Path::new("").to_owned(), true, None),
+                (Path::new("sub").to_owned(), true, None),
+                (Path::new("sub/file").to_owned(), true, Some(b"7".to_vec())),
+            ]
+        );
+    }
+}
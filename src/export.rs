@@ -0,0 +1,227 @@
+use crate::config::VolumeInfo;
+use crate::locate::{is_shared, EntryType, FileIndexReader, LocateError};
+use crate::report::map_write_error;
+use std::io::{Result as IOResult, Write};
+use std::os::unix::ffi::OsStrExt;
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// ExportFormat selects the output format of the export function.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ExportFormat {
+    /// One path per line.
+    Text,
+    /// The database format of mlocate and plocate-build, which is read with
+    /// `locate -d <file>`.
+    Locatedb,
+}
+
+/// The export function writes the entries of all database files in a format
+/// read by other tools.
+///
+/// Folders sharing a database file are exported once. Paths are exported as
+/// stored by the last update. Entries are identified as directories by their
+/// stored type or, for database files written by earlier versions, if they
+/// are followed by entries below them.
+pub fn export<W: Write>(
+    volume_info: Vec<VolumeInfo>,
+    format: ExportFormat,
+    writer: &mut W,
+) -> Result<(), LocateError> {
+    let mut locatedb = match format {
+        ExportFormat::Text => None,
+        ExportFormat::Locatedb => {
+            write_locatedb_header(writer).map_err(map_write_error)?;
+            Some(Vec::new())
+        }
+    };
+    for (position, vi) in volume_info.iter().enumerate() {
+        if is_shared(&volume_info[..position], vi) {
+            continue;
+        }
+        let mut reader = FileIndexReader::new(&vi.database)?;
+        match locatedb.as_mut() {
+            None => {
+                while let Some((path, _)) = reader.next_entry()? {
+                    writer
+                        .write_all(path.as_os_str().as_bytes())
+                        .and_then(|_| writer.write_all(b"\n"))
+                        .map_err(map_write_error)?;
+                }
+            }
+            Some(directories) => export_locatedb(&mut reader, directories, writer)?,
+        }
+    }
+    // Directories are kept across database files, i.e. common ancestors of
+    // the folders are written once.
+    if let Some(directories) = locatedb.as_mut() {
+        while let Some(directory) = directories.pop() {
+            write_directory(writer, &directory).map_err(map_write_error)?;
+        }
+    }
+    Ok(())
+}
+
+// A directory of the mlocate format lists the names of its entries. It is
+// written when all entries are known, i.e. after its subdirectories.
+struct Directory {
+    path: PathBuf,
+    modified: Option<SystemTime>,
+    // Type and name of each entry, the name terminated by NUL.
+    entries: Vec<u8>,
+}
+
+const LOCATEDB_FILE: u8 = 0;
+const LOCATEDB_DIRECTORY: u8 = 1;
+const LOCATEDB_END: u8 = 2;
+
+// Magic number, size of the empty configuration block, file format version,
+// no visibility check, padding and the root "/".
+fn write_locatedb_header(writer: &mut dyn Write) -> IOResult<()> {
+    writer.write_all(b"\0mlocate")?;
+    writer.write_all(&0u32.to_be_bytes())?;
+    writer.write_all(&[0, 0, 0, 0])?;
+    writer.write_all(b"/\0")
+}
+
+fn export_locatedb<W: Write>(
+    reader: &mut FileIndexReader,
+    directories: &mut Vec<Directory>,
+    writer: &mut W,
+) -> Result<(), LocateError> {
+    // An entry without type is exported when the next entry tells whether
+    // it is a directory.
+    let mut pending: Option<(PathBuf, Option<SystemTime>)> = None;
+    loop {
+        let next = reader.next_entry()?;
+        let next_path = next.as_ref().map(|(path, _)| *path);
+        if let Some((path, modified)) = pending.take() {
+            let is_directory =
+                next_path.is_some_and(|next| next != path && next.starts_with(&path));
+            add_entry(directories, writer, path, is_directory, modified)?;
+        }
+        let Some((path, metadata)) = next else {
+            break;
+        };
+        match metadata.entry_type {
+            Some(entry_type) => {
+                let is_directory = entry_type == EntryType::Directory;
+                add_entry(
+                    directories,
+                    writer,
+                    path.to_owned(),
+                    is_directory,
+                    metadata.modified,
+                )?;
+            }
+            None => pending = Some((path.to_owned(), metadata.modified)),
+        }
+    }
+    Ok(())
+}
+
+fn add_entry<W: Write>(
+    directories: &mut Vec<Directory>,
+    writer: &mut W,
+    path: PathBuf,
+    is_directory: bool,
+    modified: Option<SystemTime>,
+) -> Result<(), LocateError> {
+    while let Some(directory) = directories.last() {
+        if path.starts_with(&directory.path) {
+            break;
+        }
+        if let Some(directory) = directories.pop() {
+            write_directory(writer, &directory).map_err(map_write_error)?;
+        }
+    }
+    if let (Some(parent), Some(name)) = (path.parent(), path.file_name()) {
+        // The scanned folder is listed in its parent directory.
+        if directories.last().map(|directory| directory.path.as_path()) != Some(parent) {
+            directories.push(Directory {
+                path: parent.to_owned(),
+                modified: None,
+                entries: Vec::new(),
+            });
+        }
+        if let Some(directory) = directories.last_mut() {
+            directory.entries.push(if is_directory {
+                LOCATEDB_DIRECTORY
+            } else {
+                LOCATEDB_FILE
+            });
+            directory.entries.extend_from_slice(name.as_bytes());
+            directory.entries.push(0);
+        }
+    }
+    if is_directory {
+        directories.push(Directory {
+            path,
+            modified,
+            entries: Vec::new(),
+        });
+    }
+    Ok(())
+}
+
+// The modification time is used by updatedb of mlocate to rescan only
+// changed directories. Without time the directory is always rescanned.
+fn write_directory(writer: &mut dyn Write, directory: &Directory) -> IOResult<()> {
+    let time = directory
+        .modified
+        .and_then(|modified| modified.duration_since(UNIX_EPOCH).ok())
+        .unwrap_or_default();
+    writer.write_all(&time.as_secs().to_be_bytes())?;
+    writer.write_all(&time.subsec_nanos().to_be_bytes())?;
+    writer.write_all(&[0, 0, 0, 0])?;
+    writer.write_all(directory.path.as_os_str().as_bytes())?;
+    writer.write_all(b"\0")?;
+    writer.write_all(&directory.entries)?;
+    writer.write_all(&[LOCATEDB_END])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::temp_dir::TempDir;
+    use std::fs;
+
+    #[test]
+    fn formats() {
+        let dir = TempDir::new("export");
+        let database = dir.join("data.fsdb");
+        // Entries without type, i.e. written by an earlier version.
+        fs::write(
+            &database,
+            b"fsix\x00\x00\x02/a\x00\x02/b\x00\x02/c\x04\x03/d2",
+        )
+        .unwrap();
+        let other = dir.join("other.fsdb");
+        fs::write(&other, b"fsix\x00\x00\x02/f\x00\x02/g").unwrap();
+        // The root directory is shared by both folders.
+        let volume_info = vec![
+            VolumeInfo::new(PathBuf::from("/a"), database.clone()),
+            VolumeInfo::new(PathBuf::from("/f"), other),
+        ];
+        let mut text = Vec::new();
+        let res = export(volume_info.clone(), ExportFormat::Text, &mut text);
+        let mut locatedb = Vec::new();
+        let res = res.and_then(|_| export(volume_info, ExportFormat::Locatedb, &mut locatedb));
+        res.unwrap();
+        assert_eq!(text, b"/a\n/a/b\n/a/b/c\n/a/d2\n/f\n/f/g\n");
+        let mut expected = b"\0mlocate\0\0\0\0\0\0\0\0/\0".to_vec();
+        for (path, entries) in [
+            ("/a/b", &b"\0c\0"[..]),
+            ("/a", b"\x01b\0\0d2\0"),
+            ("/f", b"\0g\0"),
+            ("/", b"\x01a\0\x01f\0"),
+        ] {
+            expected.extend_from_slice(&[0; 16]);
+            expected.extend_from_slice(path.as_bytes());
+            expected.push(0);
+            expected.extend_from_slice(entries);
+            expected.push(LOCATEDB_END);
+        }
+        assert_eq!(locatedb, expected);
+    }
+}
@@ -1,7 +1,11 @@
 use crate::config::{LocateConfig, Mode};
 use crate::find::FindExt;
-use crate::locate::LocateError;
+use crate::locate::{EntryType, LocateError, Metadata};
+use crate::meta::MetaPredicate;
+#[cfg(feature = "glob")]
 use globset::{GlobBuilder, GlobMatcher};
+use std::ops::Range;
+use std::path::Path;
 
 /// A locate data base query is defined as a sequence of FilterToken elements.
 #[derive(Clone, Debug, PartialEq)]
@@ -32,11 +36,197 @@ pub enum FilterToken {
     Plain,
     /// Sets the mode to glob. Subsequent Text items are used as glob pattern.
     Glob,
+    /// Entries must have metadata matching the predicate.
+    Meta(MetaPredicate),
+    /// Entries must have a stored file size of at least this number of bytes.
+    /// Entries without a stored size never match.
+    MinSize(u64),
+    /// Entries must have a stored file size of at most this number of bytes.
+    /// Entries without a stored size never match.
+    MaxSize(u64),
+    /// Entries must have one of the given types. Entries without a stored
+    /// type never match.
+    EntryType(Vec<EntryType>),
+    /// Entries must not match the term, e.g. `Not(Box::new(Text("live")))`
+    /// excludes paths containing "live". The term is evaluated with the
+    /// options set by preceding tokens.
+    Not(Box<FilterToken>),
+    /// Separates alternatives, e.g. `[Text("mp3"), Or, Text("flac")]` matches
+    /// paths containing "mp3" or "flac". Terms before and after are combined
+    /// with AND as usual. Options set before carry over to later alternatives.
+    Or,
 }
 
+/// BroadQuery tells why a query matches all or most database entries.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum BroadQuery {
+    /// The query contains only options. It matches no entry and the
+    /// database files aren't searched.
+    OptionsOnly,
+    /// The plain text is a single character, which most paths contain.
+    SingleCharacter(String),
+    /// The glob pattern matches every path, e.g. `*`.
+    MatchAll(String),
+}
+
+impl std::fmt::Display for BroadQuery {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BroadQuery::OptionsOnly => f.write_str("Query contains only options"),
+            BroadQuery::SingleCharacter(text) => f.write_fmt(format_args!(
+                "Query '{}' is a single character matching most paths",
+                text
+            )),
+            BroadQuery::MatchAll(text) => {
+                f.write_fmt(format_args!("Glob pattern '{}' matches every path", text))
+            }
+        }
+    }
+}
+
+/// GlobError describes why a glob pattern can't be compiled. Without the
+/// `glob` feature every glob pattern fails with this error.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct GlobError {
+    message: String,
+}
+
+impl std::fmt::Display for GlobError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+#[cfg(feature = "glob")]
+impl From<globset::Error> for GlobError {
+    fn from(err: globset::Error) -> Self {
+        GlobError {
+            message: err.to_string(),
+        }
+    }
+}
+
+/// The broad_query function detects queries matching all or most database
+/// entries, e.g. to warn before running them. Returns None for other queries.
+///
+/// A query is broad, if one of its alternatives consists only of single
+/// characters and glob patterns matching every path. Size, type and metadata
+/// filters restrict an alternative. Negated terms don't.
+pub fn broad_query(filter: &[FilterToken], config: &LocateConfig) -> Option<BroadQuery> {
+    if let Err(LocateError::Trivial) = compile(filter, config) {
+        return Some(BroadQuery::OptionsOnly);
+    }
+    let mut mode = config.mode;
+    // The first broad term of the alternative and whether another term
+    // restricts it.
+    let mut broad = None;
+    let mut restricted = false;
+    for token in filter {
+        match token {
+            FilterToken::Auto => mode = Mode::Auto,
+            FilterToken::Plain => mode = Mode::Plain,
+            FilterToken::Glob => mode = Mode::Glob,
+            FilterToken::Text(text) if text.is_empty() => {}
+            FilterToken::Text(text) => match text_mode(text, mode) {
+                Mode::Glob if text.chars().all(|c| c == '*') => {
+                    broad.get_or_insert_with(|| BroadQuery::MatchAll(text.clone()));
+                }
+                Mode::Plain if text.chars().count() == 1 => {
+                    broad.get_or_insert_with(|| BroadQuery::SingleCharacter(text.clone()));
+                }
+                _ => restricted = true,
+            },
+            FilterToken::Meta(_)
+            | FilterToken::MinSize(_)
+            | FilterToken::MaxSize(_)
+            | FilterToken::EntryType(_) => restricted = true,
+            FilterToken::Or => {
+                if !restricted && broad.is_some() {
+                    return broad;
+                }
+                broad = None;
+                restricted = false;
+            }
+            _ => {}
+        }
+    }
+    broad.filter(|_| !restricted)
+}
+
+// In auto mode text with glob characters is a glob pattern. Without the glob
+// feature it is plain text.
+fn text_mode(text: &str, mode: Mode) -> Mode {
+    if mode == Mode::Auto {
+        if cfg!(feature = "glob") && text.contains(['*', '?', '[', ']', '{', '}']) {
+            Mode::Glob
+        } else {
+            Mode::Plain
+        }
+    } else {
+        mode
+    }
+}
+
+/// A query compiled by [CompiledFilter::compile]. It is reused for any number
+/// of entries and database files, e.g. with
+/// [locate_compiled](crate::locate_compiled).
 #[derive(Clone, Debug)]
-pub(crate) struct CompiledFilter {
+pub struct CompiledFilter {
     token: Vec<CompiledFilterToken>,
+    meta: Vec<MetaFilter>,
+    // Negated terms. Each matches either the path or the metadata.
+    not: Vec<CompiledFilter>,
+    // Further branches, if the query contains alternatives. An entry matches,
+    // if any branch matches.
+    alternatives: Vec<CompiledFilter>,
+}
+
+impl CompiledFilter {
+    /// Compiles a query with the options of the config. A query without
+    /// plain text, glob patterns and metadata filters fails with
+    /// [LocateError::Trivial].
+    pub fn compile(
+        filter: &[FilterToken],
+        config: &LocateConfig,
+    ) -> Result<CompiledFilter, LocateError> {
+        compile(filter, config)
+    }
+
+    /// Returns whether an entry matches the query. The metadata is only
+    /// evaluated by size, type and metadata filters.
+    pub fn apply(&self, path: &Path, metadata: &Metadata) -> bool {
+        let text = path.to_string_lossy();
+        self.branches().any(|branch| {
+            (matches_all_paths(branch) || apply(&text, branch)) && apply_meta(metadata, branch)
+        })
+    }
+
+    // This branch followed by the alternative branches.
+    pub(crate) fn branches(&self) -> impl Iterator<Item = &CompiledFilter> {
+        std::iter::once(self).chain(self.alternatives.iter())
+    }
+}
+
+// Conditions evaluated on the metadata of an entry.
+#[derive(Clone, Debug)]
+enum MetaFilter {
+    Predicate(MetaPredicate),
+    MinSize(u64),
+    MaxSize(u64),
+    EntryType(Vec<EntryType>),
+}
+
+impl MetaFilter {
+    fn matches(&self, metadata: &Metadata) -> bool {
+        match self {
+            MetaFilter::Predicate(predicate) => predicate.matches(metadata),
+            MetaFilter::MinSize(min) => metadata.size.is_some_and(|size| size >= *min),
+            MetaFilter::MaxSize(max) => metadata.size.is_some_and(|size| size <= *max),
+            MetaFilter::EntryType(types) => metadata
+                .entry_type
+                .is_some_and(|entry_type| types.contains(&entry_type)),
+        }
+    }
 }
 
 #[derive(Clone, Debug)]
@@ -44,6 +234,7 @@ enum CompiledFilterToken {
     GoToStart,
     GoToLastElement,
     EnsureLastElement,
+    #[cfg(feature = "glob")]
     Glob(GlobMatcher, bool),
     FindCaseInsensitive(String),
     FindCaseSensitive(String),
@@ -60,6 +251,7 @@ struct Options {
     same_order: bool,
     last_element: bool,
     smart_spaces: bool,
+    #[cfg_attr(not(feature = "glob"), allow(dead_code))]
     literal_separator: bool,
     word_boundaries: bool,
 }
@@ -87,12 +279,23 @@ pub(crate) fn compile(
     filter: &[FilterToken],
     config: &LocateConfig,
 ) -> Result<CompiledFilter, LocateError> {
-    let mut options = Options::new(config);
-    let mut compiled = CompiledFilter { token: Vec::new() };
-    let mut mode: Mode = config.mode;
+    compile_tokens(filter, Options::new(config), config.mode)
+}
+
+fn compile_tokens(
+    filter: &[FilterToken],
+    mut options: Options,
+    mut mode: Mode,
+) -> Result<CompiledFilter, LocateError> {
+    let mut compiled = CompiledFilter {
+        token: Vec::new(),
+        meta: Vec::new(),
+        not: Vec::new(),
+        alternatives: Vec::new(),
+    };
     let mut nothing = true;
-    for token in filter {
-        match token {
+    for (index, token) in filter.iter().enumerate() {
+        match strip_double_negation(token) {
             FilterToken::CaseSensitive => {
                 options.case_sensitive = true;
             }
@@ -100,15 +303,7 @@ pub(crate) fn compile(
                 options.case_sensitive = false;
             }
             FilterToken::Text(text) => {
-                let mode = if mode == Mode::Auto {
-                    if text.contains(['*', '?', '[', ']', '{', '}']) {
-                        Mode::Glob
-                    } else {
-                        Mode::Plain
-                    }
-                } else {
-                    mode
-                };
+                let mode = text_mode(text, mode);
                 if mode == Mode::Plain {
                     if options.same_order {
                         if options.last_element {
@@ -198,18 +393,7 @@ pub(crate) fn compile(
                     if options.last_element {
                         compiled.token.push(CompiledFilterToken::GoToLastElement);
                     }
-                    let glob_matcher = GlobBuilder::new(text.as_str())
-                        .case_insensitive(options.case_sensitive)
-                        .literal_separator(options.literal_separator)
-                        .backslash_escape(true)
-                        .empty_alternates(true)
-                        .build()
-                        .map_err(|err| LocateError::GlobPatternError(text.clone(), err))?
-                        .compile_matcher();
-                    compiled.token.push(CompiledFilterToken::Glob(
-                        glob_matcher,
-                        options.last_element,
-                    ));
+                    compiled.token.push(glob_token(text, &options)?);
                     nothing = false;
                 };
             }
@@ -243,6 +427,49 @@ pub(crate) fn compile(
             FilterToken::Glob => {
                 mode = Mode::Glob;
             }
+            FilterToken::Meta(predicate) => {
+                compiled.meta.push(MetaFilter::Predicate(predicate.clone()));
+                nothing = false;
+            }
+            FilterToken::MinSize(size) => {
+                compiled.meta.push(MetaFilter::MinSize(*size));
+                nothing = false;
+            }
+            FilterToken::MaxSize(size) => {
+                compiled.meta.push(MetaFilter::MaxSize(*size));
+                nothing = false;
+            }
+            FilterToken::EntryType(types) => {
+                compiled.meta.push(MetaFilter::EntryType(types.clone()));
+                nothing = false;
+            }
+            FilterToken::Not(term) => {
+                // Negated options are ignored.
+                match compile_tokens(std::slice::from_ref(term), options.clone(), mode) {
+                    Err(LocateError::Trivial) => {}
+                    res => {
+                        compiled.not.push(res?);
+                        nothing = false;
+                    }
+                }
+            }
+            FilterToken::Or => {
+                // Empty alternatives are ignored.
+                let alternative = match compile_tokens(&filter[index + 1..], options, mode) {
+                    Err(LocateError::Trivial) => None,
+                    res => Some(res?),
+                };
+                return match (nothing, alternative) {
+                    (true, None) => Err(LocateError::Trivial),
+                    (true, Some(alternative)) => Ok(alternative),
+                    (false, None) => Ok(compiled),
+                    (false, Some(mut alternative)) => {
+                        compiled.alternatives = std::mem::take(&mut alternative.alternatives);
+                        compiled.alternatives.insert(0, alternative);
+                        Ok(compiled)
+                    }
+                };
+            }
         }
     }
     if nothing {
@@ -251,6 +478,31 @@ pub(crate) fn compile(
     Ok(compiled)
 }
 
+// Not(Not(term)) is the same as term.
+fn strip_double_negation(token: &FilterToken) -> &FilterToken {
+    match token {
+        FilterToken::Not(term) => match term.as_ref() {
+            FilterToken::Not(term) => strip_double_negation(term),
+            _ => token,
+        },
+        _ => token,
+    }
+}
+
+pub(crate) fn apply_meta(metadata: &Metadata, filter: &CompiledFilter) -> bool {
+    filter.meta.iter().all(|meta| meta.matches(metadata))
+        && !filter
+            .not
+            .iter()
+            .any(|not| !not.meta.is_empty() && apply_meta(metadata, not))
+}
+
+// Filters without plain text and glob patterns match all paths, e.g. filters
+// with size limits only.
+pub(crate) fn matches_all_paths(filter: &CompiledFilter) -> bool {
+    filter.token.is_empty() && filter.not.iter().all(|not| not.token.is_empty())
+}
+
 #[derive(Clone, Copy, Debug)]
 struct State {
     filter_index: usize,
@@ -258,13 +510,44 @@ struct State {
 }
 
 pub(crate) fn apply(text: &str, filter: &CompiledFilter) -> bool {
+    apply_impl(text, filter, None)
+}
+
+// A negated term matches either the path or the metadata. Negated metadata
+// is evaluated by apply_meta.
+fn apply_not(text: &str, filter: &CompiledFilter) -> bool {
+    !filter
+        .not
+        .iter()
+        .any(|not| !not.token.is_empty() && apply_impl(text, not, None))
+}
+
+// Like apply, but additionally returns the byte ranges of the text matched by
+// plain text. Glob patterns don't contribute ranges.
+pub(crate) fn apply_ranges(
+    text: &str,
+    filter: &CompiledFilter,
+    ranges: &mut Vec<Range<usize>>,
+) -> bool {
+    apply_impl(text, filter, Some(ranges))
+}
+
+fn apply_impl(
+    text: &str,
+    filter: &CompiledFilter,
+    mut ranges: Option<&mut Vec<Range<usize>>>,
+) -> bool {
+    // Ranges found after the back tracking state are discarded on fallback.
+    let mut back_tracking_ranges = 0;
     let mut pos_last: Option<usize> = None;
     let mut state = State {
         filter_index: 0,
         pos: 0,
     };
     let mut back_tracking = state;
+    #[cfg_attr(not(feature = "glob"), allow(unused_mut))]
     let mut has_glob = false;
+    #[cfg_attr(not(feature = "glob"), allow(unused_mut))]
     let mut has_matched_glob = false;
     while state.filter_index < filter.token.len() {
         let token = &filter.token[state.filter_index];
@@ -295,6 +578,7 @@ pub(crate) fn apply(text: &str, filter: &CompiledFilter) -> bool {
                     state.pos = pos_last.unwrap();
                 }
             }
+            #[cfg(feature = "glob")]
             CompiledFilterToken::Glob(glob, last_element) => {
                 let text = if *last_element {
                     if pos_last.is_none() {
@@ -315,6 +599,7 @@ pub(crate) fn apply(text: &str, filter: &CompiledFilter) -> bool {
                 if let Some(range) = text.find_case_insensitive(state.pos, pattern) {
                     state.pos = range.end;
                     back_tracking = state;
+                    back_tracking_ranges = push_range(&mut ranges, range);
                 } else {
                     return false;
                 }
@@ -323,6 +608,7 @@ pub(crate) fn apply(text: &str, filter: &CompiledFilter) -> bool {
                 if let Some(range) = text.find_case_sensitive(state.pos, pattern) {
                     state.pos = range.end;
                     back_tracking = state;
+                    back_tracking_ranges = push_range(&mut ranges, range);
                 } else {
                     return false;
                 }
@@ -331,6 +617,7 @@ pub(crate) fn apply(text: &str, filter: &CompiledFilter) -> bool {
                 if let Some(pos) = text.find_word_start_boundary(state.pos) {
                     state.pos = pos;
                     back_tracking = state;
+                    back_tracking_ranges = ranges.as_ref().map_or(0, |ranges| ranges.len());
                     back_tracking.pos = text.skip_character(back_tracking.pos);
                 } else {
                     return false;
@@ -342,6 +629,7 @@ pub(crate) fn apply(text: &str, filter: &CompiledFilter) -> bool {
             CompiledFilterToken::ExpectCaseInsensitive(pattern) => {
                 if let Some(range) = text.tag_case_insensitive(state.pos, pattern) {
                     state.pos = range.end;
+                    push_range(&mut ranges, range);
                 } else {
                     fallback = true;
                 }
@@ -349,6 +637,7 @@ pub(crate) fn apply(text: &str, filter: &CompiledFilter) -> bool {
             CompiledFilterToken::ExpectCaseSensitive(pattern) => {
                 if let Some(range) = text.tag_case_sensitive(state.pos, pattern) {
                     state.pos = range.end;
+                    push_range(&mut ranges, range);
                 } else {
                     fallback = true;
                 }
@@ -364,11 +653,51 @@ pub(crate) fn apply(text: &str, filter: &CompiledFilter) -> bool {
                 filter_index: back_tracking.filter_index,
                 pos: back_tracking.pos,
             };
+            if let Some(ranges) = ranges.as_deref_mut() {
+                ranges.truncate(back_tracking_ranges);
+            }
         } else {
             state.filter_index += 1;
         }
     }
-    !has_glob || has_matched_glob
+    (!has_glob || has_matched_glob) && apply_not(text, filter)
+}
+
+#[cfg(feature = "glob")]
+fn glob_token(text: &str, options: &Options) -> Result<CompiledFilterToken, LocateError> {
+    let glob_matcher = GlobBuilder::new(text)
+        .case_insensitive(options.case_sensitive)
+        .literal_separator(options.literal_separator)
+        .backslash_escape(true)
+        .empty_alternates(true)
+        .build()
+        .map_err(|err| LocateError::GlobPatternError(text.to_string(), err.into()))?
+        .compile_matcher();
+    Ok(CompiledFilterToken::Glob(
+        glob_matcher,
+        options.last_element,
+    ))
+}
+
+#[cfg(not(feature = "glob"))]
+fn glob_token(text: &str, _options: &Options) -> Result<CompiledFilterToken, LocateError> {
+    Err(LocateError::GlobPatternError(
+        text.to_string(),
+        GlobError {
+            message: String::from("glob patterns are not supported by this build"),
+        },
+    ))
+}
+
+// Returns the number of ranges before the range is added.
+fn push_range(ranges: &mut Option<&mut Vec<Range<usize>>>, range: Range<usize>) -> usize {
+    match ranges {
+        Some(ranges) => {
+            ranges.push(range);
+            ranges.len() - 1
+        }
+        None => 0,
+    }
 }
 
 #[cfg(test)]
@@ -415,6 +744,60 @@ mod tests {
         assert!(matches!(compile(&[], &config), Err(LocateError::Trivial)));
     }
 
+    #[test]
+    fn compiled_filter() {
+        let config = LocateConfig::default();
+        let filter = CompiledFilter::compile(
+            &[
+                t("/xyz"),
+                FilterToken::Or,
+                t("eins"),
+                FilterToken::MinSize(10),
+            ],
+            &config,
+        )
+        .unwrap();
+        let metadata = |size| Metadata {
+            size,
+            ..Default::default()
+        };
+        assert!(filter.apply(Path::new(S6), &metadata(None)));
+        assert!(filter.apply(Path::new(S1), &metadata(Some(10))));
+        assert!(!filter.apply(Path::new(S1), &metadata(Some(9))));
+        assert!(!filter.apply(Path::new(S2), &metadata(Some(10))));
+        assert!(matches!(
+            CompiledFilter::compile(&[FilterToken::LastElement], &config),
+            Err(LocateError::Trivial)
+        ));
+    }
+
+    #[cfg(feature = "glob")]
+    #[test]
+    fn broad_queries() {
+        let config = LocateConfig::default();
+        let broad = |flt: &[FilterToken]| broad_query(flt, &config);
+        assert_eq!(
+            broad(&[FilterToken::CaseSensitive]),
+            Some(BroadQuery::OptionsOnly)
+        );
+        assert_eq!(
+            broad(&[t("a")]),
+            Some(BroadQuery::SingleCharacter(String::from("a")))
+        );
+        assert_eq!(
+            broad(&[t("**"), t("b")]),
+            Some(BroadQuery::MatchAll(String::from("**")))
+        );
+        assert_eq!(
+            broad(&[t("abc"), FilterToken::Or, t("*")]),
+            Some(BroadQuery::MatchAll(String::from("*")))
+        );
+        assert_eq!(broad(&[t("a"), t("bc")]), None);
+        assert_eq!(broad(&[t("*"), FilterToken::MinSize(1)]), None);
+        assert_eq!(broad(&[FilterToken::Plain, t("**")]), None);
+        assert_eq!(broad(&[t("*.mp3")]), None);
+    }
+
     #[test]
     fn default() {
         assert_eq!(process(&[t("Y"), t("G"), t("A")]), [S1, S2, S3, S4]);
@@ -802,6 +1185,9 @@ mod tests {
                 CompiledFilterToken::GoToStart,
                 CompiledFilterToken::FindCaseInsensitive("E".to_string()),
             ],
+            meta: Vec::new(),
+            not: Vec::new(),
+            alternatives: Vec::new(),
         };
         // Can't use assert_eq! here, since PartialEq is not implemented for GlobMatcher.
         check_compiled_filter(actual, expected);
@@ -818,6 +1204,9 @@ mod tests {
                 CompiledFilterToken::SkipSmartSpace,
                 CompiledFilterToken::ExpectCaseInsensitive("BAR".to_string()),
             ],
+            meta: Vec::new(),
+            not: Vec::new(),
+            alternatives: Vec::new(),
         };
         // Can't use assert_eq! here, since PartialEq is not implemented for GlobMatcher.
         check_compiled_filter(actual, expected);
@@ -838,6 +1227,9 @@ mod tests {
                 CompiledFilterToken::SkipSmartSpace,
                 CompiledFilterToken::ExpectCaseInsensitive("D".to_string()),
             ],
+            meta: Vec::new(),
+            not: Vec::new(),
+            alternatives: Vec::new(),
         };
         check_compiled_filter(actual, expected);
     }
@@ -850,6 +1242,7 @@ mod tests {
                 (CompiledFilterToken::GoToLastElement, CompiledFilterToken::GoToLastElement) => {
                     true
                 }
+                #[cfg(feature = "glob")]
                 (CompiledFilterToken::Glob(a1, a2), CompiledFilterToken::Glob(b1, b2)) => {
                     a1.glob() == b1.glob() && a2 == b2
                 }
@@ -884,11 +1277,23 @@ mod tests {
         }
     }
 
+    #[cfg(feature = "glob")]
     #[test]
     fn glob_star() {
         assert_eq!(process(&[FilterToken::Glob, t("*i")]), [S2, S3]);
     }
 
+    #[cfg(not(feature = "glob"))]
+    #[test]
+    fn glob_disabled() {
+        assert_eq!(process(&[t("*i")]), EMPTY);
+        assert!(matches!(
+            compile(&[FilterToken::Glob, t("*i")], &LocateConfig::default()),
+            Err(LocateError::GlobPatternError(_, _))
+        ));
+    }
+
+    #[cfg(feature = "glob")]
     #[test]
     fn glob_recursive_wildcard() {
         assert_eq!(
@@ -901,11 +1306,13 @@ mod tests {
         );
     }
 
+    #[cfg(feature = "glob")]
     #[test]
     fn glob_question_mark() {
         assert_eq!(process(&[FilterToken::Glob, t("*/???i")]), [S2, S3]);
     }
 
+    #[cfg(feature = "glob")]
     #[test]
     fn glob_require_literal_separator() {
         assert_eq!(
@@ -1004,6 +1411,7 @@ mod tests {
         );
     }
 
+    #[cfg(feature = "glob")]
     #[test]
     fn glob_on_last_element_only() {
         assert_eq!(
@@ -1025,6 +1433,7 @@ mod tests {
         );
     }
 
+    #[cfg(feature = "glob")]
     #[test]
     fn glob_case() {
         let check = |text, filter| -> bool {
@@ -1054,6 +1463,7 @@ mod tests {
         assert_eq!(check("File.mp4", &filter), true);
     }
 
+    #[cfg(feature = "glob")]
     #[test]
     fn multiple_globs_accumulate_results() {
         let config = LocateConfig::default();
@@ -1228,6 +1638,146 @@ mod tests {
         );
     }
 
+    #[test]
+    fn size_filter() {
+        let config = LocateConfig::default();
+        let filter = compile(
+            &[FilterToken::MinSize(10), FilterToken::MaxSize(20)],
+            &config,
+        )
+        .unwrap();
+        let metadata = |size| Metadata {
+            size,
+            ..Default::default()
+        };
+        assert!(apply_meta(&metadata(Some(10)), &filter));
+        assert!(apply_meta(&metadata(Some(20)), &filter));
+        assert!(!apply_meta(&metadata(Some(9)), &filter));
+        assert!(!apply_meta(&metadata(Some(21)), &filter));
+        assert!(!apply_meta(&metadata(None), &filter));
+    }
+
+    #[test]
+    fn entry_type_filter() {
+        let config = LocateConfig::default();
+        let filter = compile(
+            &[FilterToken::EntryType(vec![
+                EntryType::Directory,
+                EntryType::Symlink,
+            ])],
+            &config,
+        )
+        .unwrap();
+        let metadata = |entry_type| Metadata {
+            entry_type,
+            ..Default::default()
+        };
+        assert!(apply_meta(&metadata(Some(EntryType::Directory)), &filter));
+        assert!(apply_meta(&metadata(Some(EntryType::Symlink)), &filter));
+        assert!(!apply_meta(&metadata(Some(EntryType::File)), &filter));
+        assert!(!apply_meta(&metadata(None), &filter));
+    }
+
+    #[cfg(feature = "glob")]
+    #[test]
+    fn negation() {
+        let config = LocateConfig::default();
+        let not = |token| FilterToken::Not(Box::new(token));
+        let filter = compile(&[t("flac"), not(t("live"))], &config).unwrap();
+        assert!(apply("/Music/Album/01.flac", &filter));
+        assert!(!apply("/Music/Album (Live)/01.flac", &filter));
+        assert!(!apply("/Music/Album/01.mp3", &filter));
+        // Options apply to the negated term.
+        let filter = compile(
+            &[t("flac"), FilterToken::CaseSensitive, not(t("live"))],
+            &config,
+        )
+        .unwrap();
+        assert!(apply("/Music/Album (Live)/01.flac", &filter));
+        let filter = compile(&[not(t("*.mp3"))], &config).unwrap();
+        assert!(!matches_all_paths(&filter));
+        assert!(apply("/Music/Album/01.flac", &filter));
+        assert!(!apply("/Music/Album/01.mp3", &filter));
+        let filter = compile(&[t("flac"), not(FilterToken::MinSize(10))], &config).unwrap();
+        assert!(matches_all_paths(
+            &compile(&[not(FilterToken::MinSize(10))], &config).unwrap()
+        ));
+        let metadata = |size| Metadata {
+            size: Some(size),
+            ..Default::default()
+        };
+        assert!(apply_meta(&metadata(9), &filter));
+        assert!(!apply_meta(&metadata(10), &filter));
+        let filter = compile(&[not(not(t("flac")))], &config).unwrap();
+        assert!(apply("/Music/Album/01.flac", &filter));
+        assert!(!apply("/Music/Album/01.mp3", &filter));
+        assert!(matches!(
+            compile(&[not(FilterToken::CaseSensitive)], &config),
+            Err(LocateError::Trivial)
+        ));
+    }
+
+    #[test]
+    fn alternatives() {
+        let config = LocateConfig::default();
+        let matches = |filter: &CompiledFilter, text: &str| {
+            filter.branches().any(|branch| apply(text, branch))
+        };
+        let filter = compile(
+            &[t("album"), t(".mp3"), FilterToken::Or, t(".flac")],
+            &config,
+        )
+        .unwrap();
+        assert_eq!(filter.branches().count(), 2);
+        assert!(matches(&filter, "/Music/Album/01.mp3"));
+        assert!(matches(&filter, "/Music/Single/01.flac"));
+        assert!(!matches(&filter, "/Music/Single/01.mp3"));
+        // Options carry over to later alternatives.
+        let filter = compile(
+            &[FilterToken::CaseSensitive, t("A"), FilterToken::Or, t("B")],
+            &config,
+        )
+        .unwrap();
+        assert!(matches(&filter, "/B"));
+        assert!(!matches(&filter, "/b"));
+        // Empty alternatives are ignored.
+        let filter = compile(
+            &[FilterToken::Or, t("a"), FilterToken::Or, FilterToken::Or],
+            &config,
+        )
+        .unwrap();
+        assert_eq!(filter.branches().count(), 1);
+        assert!(matches!(
+            compile(&[FilterToken::Plain, FilterToken::Or], &config),
+            Err(LocateError::Trivial)
+        ));
+        let filter = compile(
+            &[t("a"), FilterToken::Or, t("b"), FilterToken::Or, t("c")],
+            &config,
+        )
+        .unwrap();
+        assert_eq!(filter.branches().count(), 3);
+        assert!(matches(&filter, "/c"));
+    }
+
+    #[cfg(feature = "glob")]
+    #[test]
+    fn ranges() {
+        let config = LocateConfig::default();
+        let ranges = |filter: &[FilterToken], text: &str| {
+            let mut ranges = Vec::new();
+            apply_ranges(text, &compile(filter, &config).unwrap(), &mut ranges).then_some(ranges)
+        };
+        assert_eq!(ranges(&[t("abc"), t("ei")], S1), Some(vec![1..4, 30..32]));
+        // The partial match of "def" is discarded when backtracking.
+        assert_eq!(
+            ranges(&[t("De fi")], "/ab/DeX/DEFIN"),
+            Some(vec![8..10, 10..12])
+        );
+        assert_eq!(ranges(&[t("*.txt")], S7), Some(Vec::new()));
+        assert_eq!(ranges(&[t("xyz"), t("klmn")], S6), None);
+    }
+
     #[test]
     fn utf8_slice() {
         let text = "öäüÄÖÜß";
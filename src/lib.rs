@@ -1,15 +1,66 @@
 #![warn(missing_docs)]
 
 //! The fsidx crate scans file system folders to store pathnames and optionally file sizes in database files. For these database files efficient search queries are implemented to locate files.
+//!
+//! The default cargo features can be disabled for applications, which only
+//! search database files written elsewhere:
+//!
+//! - `compression`: reading and writing database files compressed with zstd.
+//! - `glob`: glob patterns in queries.
+//! - `serde`: `Serialize` and `Deserialize` for [LocateConfig] and [UpdateConfig].
+//! - `update`: scanning folders with `update` and `update_with_providers`.
+//!
+//! The public API consists of the items exported at the crate root. Error and
+//! event enums are `#[non_exhaustive]`, i.e. new variants may be added without
+//! a breaking release and matches need a wildcard arm. The compatibility of
+//! the database files is independent of the API and described in the README.
 
+mod cache;
+mod checksum;
+mod compression;
 mod config;
+mod diff;
+mod enrich;
+mod export;
 mod filter;
 mod find;
 mod locate;
+mod meta;
+mod pool;
+mod reader;
+mod report;
+mod severity;
+#[cfg(test)]
+mod temp_dir;
+mod tree;
+#[cfg(feature = "update")]
 mod update;
+mod volume;
+mod writer;
 
+pub use cache::{warm, DatabaseCache};
+pub use checksum::{check_portability, verify, Portability, Verification};
 pub use config::VolumeInfo;
-pub use config::{LocateConfig, Mode, Order, Settings, What};
-pub use filter::FilterToken;
-pub use locate::{locate, LocateError, LocateEvent, Metadata};
-pub use update::{update, UpdateEvent};
+pub use config::{BroadQueryAction, LocateConfig, Mode, Order, Settings, UpdateConfig, What};
+pub use diff::{compare_paths, diff_databases, DatabaseDiff};
+pub use enrich::{Capability, ContentHash, EnrichmentProvider, ProviderRegistry, RegistryError};
+pub use export::{export, ExportFormat};
+pub use filter::{broad_query, BroadQuery, CompiledFilter, FilterToken, GlobError};
+pub use locate::{
+    check_header, locate, locate_batch, locate_cached, locate_compiled, locate_parallel,
+    locate_pooled, locate_with_context, EntryType, HeaderCheck, LocateError, LocateEvent,
+    LocateStatistics, Match, Metadata,
+};
+pub use meta::{Comparison, MetaError, MetaPredicate, ValueType};
+pub use pool::LocatePool;
+pub use reader::DbReader;
+pub use report::{
+    report, Aggregator, BadNames, CaseCollisions, EmptyDirectories, Finding, ReportEvent,
+    ZeroByteFiles,
+};
+pub use severity::Severity;
+pub use tree::{tree, TreeEvent};
+#[cfg(feature = "update")]
+pub use update::{update, update_with_providers, ScanStatistics, ScanSummary, UpdateEvent};
+pub use volume::VolumeId;
+pub use writer::DbWriter;
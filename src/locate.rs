@@ -1,18 +1,31 @@
+use crate::cache::DatabaseCache;
+use crate::checksum::verify_checksum;
+use crate::compression::PayloadReader;
 use crate::config::LocateConfig;
-use crate::filter::CompiledFilter;
-use crate::{filter, FilterToken, Settings, VolumeInfo};
+use crate::filter::{CompiledFilter, GlobError};
+use crate::pool::LocatePool;
+use crate::volume::{VolumeHeader, VolumeId};
+use crate::{filter, FilterToken, Settings, Severity, VolumeInfo};
 use fastvlq::ReadVu64Ext;
+use nix::errno::Errno;
+use nix::fcntl::{Flock, FlockArg};
+use std::borrow::Cow;
 use std::convert::TryFrom;
 use std::ffi::OsStr;
 use std::fmt::Display;
 use std::fs::File;
-use std::io::{BufReader, ErrorKind, Read, Result as IOResult};
+use std::io::{BufReader, Cursor, ErrorKind, Read, Result as IOResult};
+use std::ops::Range;
 use std::os::unix::prelude::OsStrExt;
 use std::path::{Path, PathBuf};
-use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::sync::mpsc::{channel, sync_channel, Receiver, SendError, SyncSender};
 use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 
 /// LocateEvent indicates events to a callback function.
+#[non_exhaustive]
 pub enum LocateEvent<'a> {
     /// A database entry that matches the query.
     Entry(&'a Path, &'a Metadata),
@@ -22,10 +35,43 @@ pub enum LocateEvent<'a> {
     Searching(&'a Path),
     /// All entries in a database file are evaluated against the query.
     SearchingFinished(&'a Path),
+    /// Number of evaluated entries and matches of a database file. Sent
+    /// after all entries of the database file are evaluated.
+    Statistics(&'a Path, &'a LocateStatistics),
+    /// A database entry that matches a query of [locate_batch] with the
+    /// index of the query.
+    BatchEntry(usize, &'a Path, &'a Metadata),
+    /// A database entry that matches the query of [locate_with_context] with
+    /// the context of the match.
+    Match(&'a Match<'a>),
+    /// An update is writing a new database file for the folder. The previous
+    /// database file is searched. Sent after [LocateEvent::Searching].
+    UpdateInProgress(&'a Path),
+    /// An update is writing the first database file for the folder. The
+    /// query waits up to [LocateConfig::update_wait_ms] for the update to
+    /// finish. Sent after [LocateEvent::Searching].
+    WaitingForUpdate(&'a Path),
+    /// The database file of the folder is missing or invalid and not
+    /// searched. Sent by frontends, which run [check_header] once and skip
+    /// these folders in the following queries.
+    Skipped(&'a Path, &'a HeaderCheck),
+}
+
+/// Number of evaluated entries and matches and the elapsed time of a query
+/// against a database file.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct LocateStatistics {
+    /// Number of database entries evaluated against the query.
+    pub entries: u64,
+    /// Number of matching entries.
+    pub matches: u64,
+    /// Time since searching the database file started.
+    pub elapsed: Duration,
 }
 
 /// LocateError reports errors related to processing a query.
 #[derive(Debug)]
+#[non_exhaustive]
 pub enum LocateError {
     /// File is not a database file.
     ExpectedFsdbFile(PathBuf),
@@ -37,24 +83,258 @@ pub enum LocateError {
     WritingResultFailed(std::io::Error),
     /// Database file was written with an incompatible (e.g. newer) fsidx version.
     UnsupportedFileFormat(PathBuf),
+    /// Database file is compressed, but the `compression` feature is
+    /// disabled.
+    CompressionUnsupported(PathBuf),
+    /// The checksum in the footer doesn't match the content of the database
+    /// file, i.e. the file is damaged.
+    ChecksumMismatch(PathBuf),
+    /// An entry refers to more bytes of the previous path than it has or a
+    /// stored length is implausibly large, i.e. the database file is damaged.
+    InvalidEntry(PathBuf),
     /// Query was aborted.
     Aborted,
     /// Writing failed due to a broken pipe. This error is reported when the
     /// cli frontend is piping its output to another program which is
     /// terminated before reading the complete input.
     BrokenPipe,
-    /// Failed to compile a glob pattern.
-    GlobPatternError(String, globset::Error),
+    /// Failed to compile a glob pattern. Without the `glob` feature each
+    /// glob pattern fails.
+    GlobPatternError(String, GlobError),
     /// Reports a trivial search query that will by definition not match any
     /// database entry.
     Trivial,
+    /// The query scanned more database entries than allowed by
+    /// [LocateConfig::max_entries_scanned].
+    QueryTooBroad(u64),
+}
+
+impl LocateEvent<'_> {
+    /// Returns the severity of the event.
+    pub fn severity(&self) -> Severity {
+        Severity::Info
+    }
+}
+
+impl LocateError {
+    /// Returns the severity of the error.
+    ///
+    /// An aborted query or a closed output pipe is not considered as an
+    /// error.
+    pub fn severity(&self) -> Severity {
+        match self {
+            LocateError::Aborted | LocateError::BrokenPipe => Severity::Info,
+            LocateError::Trivial => Severity::Warning,
+            _ => Severity::Error,
+        }
+    }
+
+    /// Returns a stable identifier of the kind of error, e.g. for
+    /// machine-readable error messages.
+    pub fn code(&self) -> &'static str {
+        match self {
+            LocateError::ExpectedFsdbFile(_) => "expected-fsdb-file",
+            LocateError::UnexpectedEof(_) => "unexpected-eof",
+            LocateError::ReadingFileFailed(_, _) => "reading-file-failed",
+            LocateError::WritingResultFailed(_) => "writing-result-failed",
+            LocateError::UnsupportedFileFormat(_) => "unsupported-file-format",
+            LocateError::CompressionUnsupported(_) => "compression-unsupported",
+            LocateError::ChecksumMismatch(_) => "checksum-mismatch",
+            LocateError::InvalidEntry(_) => "invalid-entry",
+            LocateError::Aborted => "aborted",
+            LocateError::BrokenPipe => "broken-pipe",
+            LocateError::GlobPatternError(_, _) => "glob-pattern-error",
+            LocateError::Trivial => "trivial",
+            LocateError::QueryTooBroad(_) => "query-too-broad",
+        }
+    }
+
+    /// Returns the database file related to the error.
+    pub fn path(&self) -> Option<&Path> {
+        match self {
+            LocateError::ExpectedFsdbFile(path)
+            | LocateError::UnexpectedEof(path)
+            | LocateError::ReadingFileFailed(path, _)
+            | LocateError::UnsupportedFileFormat(path)
+            | LocateError::CompressionUnsupported(path)
+            | LocateError::ChecksumMismatch(path)
+            | LocateError::InvalidEntry(path) => Some(path),
+            _ => None,
+        }
+    }
+}
+
+/// HeaderCheck reports the result of checking the header of a database file.
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum HeaderCheck {
+    /// The database file is valid and stores the given information.
+    Ok(Settings),
+    /// The database file does not exist, e.g. before the first update.
+    Missing,
+    /// The database file does not exist yet, but an update is writing it.
+    Updating,
+    /// The database file can't be read or has an incompatible format.
+    Invalid(LocateError),
+}
+
+impl HeaderCheck {
+    /// Returns true, if the database file can be searched.
+    pub fn is_ok(&self) -> bool {
+        matches!(self, HeaderCheck::Ok(_))
+    }
+}
+
+/// Checks the header of a database file without reading any entries.
+///
+/// This allows frontends to report missing or incompatible database files
+/// once before running queries.
+pub fn check_header(database: &Path) -> HeaderCheck {
+    match FileIndexReader::new(database) {
+        Ok(reader) => HeaderCheck::Ok(reader.settings),
+        Err(LocateError::ReadingFileFailed(_, err)) if err.kind() == ErrorKind::NotFound => {
+            if update_in_progress(database) {
+                HeaderCheck::Updating
+            } else {
+                HeaderCheck::Missing
+            }
+        }
+        Err(LocateError::ReadingFileFailed(path, err))
+            if err.kind() == ErrorKind::UnexpectedEof =>
+        {
+            HeaderCheck::Invalid(LocateError::ExpectedFsdbFile(path))
+        }
+        Err(err) => HeaderCheck::Invalid(err),
+    }
+}
+
+// Updates write the new database file into a temporary file, which replaces
+// the database file when it is complete.
+pub(crate) fn temporary_file(database: &Path) -> PathBuf {
+    database.with_extension("~")
+}
+
+// An update holds an exclusive lock of the temporary file while writing it.
+// A temporary file left by an interrupted update isn't locked.
+fn update_in_progress(database: &Path) -> bool {
+    let Ok(file) = File::open(temporary_file(database)) else {
+        return false;
+    };
+    matches!(
+        Flock::lock(file, FlockArg::LockSharedNonblock),
+        Err((_, Errno::EWOULDBLOCK))
+    )
+}
+
+// Queries search the previous database file while an update is writing the
+// new one. The database file is replaced atomically, i.e. the query reads
+// either the previous or the new one. Without previous database file the
+// query waits for the update.
+fn check_update<F: FnMut(LocateEvent) -> IOResult<()>>(
+    vi: &VolumeInfo,
+    config: &LocateConfig,
+    abort: &Option<Arc<AtomicBool>>,
+    f: &mut F,
+) -> Result<(), LocateError> {
+    if !update_in_progress(&vi.database) {
+        return Ok(());
+    }
+    if vi.database.exists() {
+        return f(LocateEvent::UpdateInProgress(&vi.folder))
+            .map_err(LocateError::WritingResultFailed);
+    }
+    f(LocateEvent::WaitingForUpdate(&vi.folder)).map_err(LocateError::WritingResultFailed)?;
+    let start = Instant::now();
+    let wait = Duration::from_millis(config.update_wait_ms);
+    while update_in_progress(&vi.database) && start.elapsed() < wait {
+        if abort
+            .as_ref()
+            .is_some_and(|abort| abort.load(Ordering::Relaxed))
+        {
+            return Err(LocateError::Aborted);
+        }
+        thread::sleep(UPDATE_POLL_INTERVAL);
+    }
+    // Fails like before, if the database file is still missing.
+    Ok(())
+}
+
+const UPDATE_POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// A database entry matching a query with its context. Reported by
+/// [locate_with_context].
+pub struct Match<'a> {
+    /// Volume of the database file containing the entry.
+    pub volume: &'a VolumeInfo,
+    /// Index of the entry in the database file. The configured folder has
+    /// index 0.
+    pub index: u64,
+    /// Path of the entry.
+    pub path: &'a Path,
+    /// Metadata of the entry.
+    pub metadata: &'a Metadata,
+    /// Byte ranges of the path matched by plain text of the query, e.g. for
+    /// highlighting. Glob patterns and metadata filters don't contribute
+    /// ranges. None is returned for paths, which are not valid UTF-8.
+    pub ranges: Option<Vec<Range<usize>>>,
 }
 
 /// Metadata of a single locate query result.
+///
+/// Fields may be added in any release. Start with [Metadata::default].
+#[derive(Clone, Default)]
+#[non_exhaustive]
 pub struct Metadata {
     /// File size. The field is optional, since the database file may not
     /// contain the file sizes.
     pub size: Option<u64>,
+    /// Modification time with a resolution of seconds. Only available if
+    /// the database file was written with
+    /// [UpdateConfig::modification_times](crate::UpdateConfig::modification_times).
+    pub modified: Option<SystemTime>,
+    /// Values contributed by [EnrichmentProvider](crate::EnrichmentProvider)s
+    /// with the tag of the provider.
+    pub attributes: Vec<(u64, Vec<u8>)>,
+    /// Type of the entry. Not available in database files written by
+    /// earlier versions.
+    pub entry_type: Option<EntryType>,
+}
+
+/// Type of a database entry.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum EntryType {
+    /// A regular file.
+    File = 1,
+    /// A directory.
+    Directory = 2,
+    /// A symbolic link. Links followed by the update are stored with the
+    /// type of their target.
+    Symlink = 3,
+    /// Any other entry, e.g. a named pipe, a socket or a device file.
+    Other = 4,
+}
+
+impl EntryType {
+    fn from_u8(value: u8) -> Option<EntryType> {
+        match value {
+            1 => Some(EntryType::File),
+            2 => Some(EntryType::Directory),
+            3 => Some(EntryType::Symlink),
+            4 => Some(EntryType::Other),
+            _ => None,
+        }
+    }
+}
+
+impl Metadata {
+    /// Returns the value contributed by the provider with the given tag.
+    pub fn attribute(&self, tag: u64) -> Option<&[u8]> {
+        self.attributes
+            .iter()
+            .find(|(t, _)| *t == tag)
+            .map(|(_, value)| value.as_slice())
+    }
 }
 
 /// The locate function runs a query on all configured database files.
@@ -62,6 +342,9 @@ pub struct Metadata {
 /// The matching entries are reported with a callback function. The abort
 /// parameter may be used by a frontend to abort a query.
 ///
+/// The query is compiled with the given config. Volumes with their own
+/// [VolumeInfo::locate_config] use a query compiled with that config instead.
+///
 /// Design decision: The locate function is using a callback interface. This
 /// allows to use references. With an iterator interface this is not possible
 /// due to lifetime restrictions of Rust. The pathname is only available until
@@ -73,35 +356,639 @@ pub fn locate<F: FnMut(LocateEvent) -> IOResult<()>>(
     filter: Vec<FilterToken>,
     config: &LocateConfig,
     abort: Option<Arc<AtomicBool>>,
+    f: F,
+) -> Result<(), LocateError> {
+    locate_impl(
+        volume_info,
+        Queries::Tokens(vec![filter]),
+        Report::Entry,
+        config,
+        abort,
+        None,
+        f,
+    )
+}
+
+/// The locate_with_context function runs a query like [locate], but reports
+/// matching entries with [LocateEvent::Match] instead of
+/// [LocateEvent::Entry].
+///
+/// The [Match] contains the volume, the index of the entry in the database
+/// file and the ranges of the path matched by the query. Existing callbacks
+/// of [locate] don't need to handle [LocateEvent::Match].
+pub fn locate_with_context<F: FnMut(LocateEvent) -> IOResult<()>>(
+    volume_info: Vec<VolumeInfo>,
+    filter: Vec<FilterToken>,
+    config: &LocateConfig,
+    abort: Option<Arc<AtomicBool>>,
+    f: F,
+) -> Result<(), LocateError> {
+    locate_impl(
+        volume_info,
+        Queries::Tokens(vec![filter]),
+        Report::Context,
+        config,
+        abort,
+        None,
+        f,
+    )
+}
+
+/// The locate_batch function runs multiple queries in a single pass over the
+/// database files.
+///
+/// Each database file is read once and every entry is evaluated against all
+/// queries. Matching entries are reported with [LocateEvent::BatchEntry] and
+/// the index of the query. An entry matching several queries is reported once
+/// for each query. Queries without any filter are ignored. The matches of
+/// [LocateStatistics] count entries matching at least one query.
+pub fn locate_batch<F: FnMut(LocateEvent) -> IOResult<()>>(
+    volume_info: Vec<VolumeInfo>,
+    queries: Vec<Vec<FilterToken>>,
+    config: &LocateConfig,
+    abort: Option<Arc<AtomicBool>>,
+    f: F,
+) -> Result<(), LocateError> {
+    locate_impl(
+        volume_info,
+        Queries::Tokens(queries),
+        Report::Batch,
+        config,
+        abort,
+        None,
+        f,
+    )
+}
+
+/// The locate_cached function runs a query like [locate], but reads the
+/// database files from a [DatabaseCache].
+///
+/// Database files are read once and kept in memory. A database file is read
+/// again, when it was modified since it was cached. This reduces the latency
+/// of repeated queries, e.g. in an interactive frontend. Database files
+/// exceeding the limit of the cache are read from disk.
+pub fn locate_cached<F: FnMut(LocateEvent) -> IOResult<()>>(
+    cache: &mut DatabaseCache,
+    volume_info: Vec<VolumeInfo>,
+    filter: Vec<FilterToken>,
+    config: &LocateConfig,
+    abort: Option<Arc<AtomicBool>>,
+    f: F,
+) -> Result<(), LocateError> {
+    locate_impl(
+        volume_info,
+        Queries::Tokens(vec![filter]),
+        Report::Entry,
+        config,
+        abort,
+        Some(cache),
+        f,
+    )
+}
+
+/// The locate_compiled function runs a query compiled with
+/// [CompiledFilter::compile] like [locate].
+///
+/// The filter is compiled once and reused for all database files and calls.
+/// [VolumeInfo::locate_config] affects reading the database files, but the
+/// query isn't compiled again with it.
+pub fn locate_compiled<F: FnMut(LocateEvent) -> IOResult<()>>(
+    volume_info: Vec<VolumeInfo>,
+    filter: &CompiledFilter,
+    config: &LocateConfig,
+    abort: Option<Arc<AtomicBool>>,
+    f: F,
+) -> Result<(), LocateError> {
+    locate_impl(
+        volume_info,
+        Queries::Compiled(filter),
+        Report::Entry,
+        config,
+        abort,
+        None,
+        f,
+    )
+}
+
+/// The locate_parallel function runs a query like [locate], but searches the
+/// database files of multiple volumes in parallel.
+///
+/// Database files are searched by threads started for the query with one
+/// thread per volume, up to the available parallelism. The callback is invoked in the
+/// calling thread. Events of different volumes are interleaved, but the events
+/// of each volume keep their order: [LocateEvent::Searching], the matching
+/// entries in the order of the database file and [LocateEvent::Statistics].
+/// With [LocateConfig::ordered] the events are reported in the order of
+/// [locate]. Events of a volume are held back until the preceding volumes
+/// are finished.
+///
+/// Matching entries are passed from the searching threads with owned copies
+/// of path and metadata, i.e. the function is slower than [locate] for a
+/// single volume.
+pub fn locate_parallel<F: FnMut(LocateEvent) -> IOResult<()>>(
+    volume_info: Vec<VolumeInfo>,
+    filter: Vec<FilterToken>,
+    config: &LocateConfig,
+    abort: Option<Arc<AtomicBool>>,
+    f: F,
+) -> Result<(), LocateError> {
+    locate_parallel_impl(volume_info, filter, config, abort, None, f)
+}
+
+/// The locate_pooled function runs a query like [locate_parallel], but
+/// searches the database files with the threads of a [LocatePool].
+///
+/// The threads are kept between queries, i.e. this reduces the latency of
+/// repeated queries on many volumes, e.g. in an interactive frontend. At
+/// most [LocatePool::size] volumes are searched at the same time.
+pub fn locate_pooled<F: FnMut(LocateEvent) -> IOResult<()>>(
+    pool: &LocatePool,
+    volume_info: Vec<VolumeInfo>,
+    filter: Vec<FilterToken>,
+    config: &LocateConfig,
+    abort: Option<Arc<AtomicBool>>,
+    f: F,
+) -> Result<(), LocateError> {
+    locate_parallel_impl(volume_info, filter, config, abort, Some(pool), f)
+}
+
+// Searches with one thread per volume, up to the available parallelism or
+// the size of the pool.
+fn locate_parallel_impl<F: FnMut(LocateEvent) -> IOResult<()>>(
+    volume_info: Vec<VolumeInfo>,
+    filter: Vec<FilterToken>,
+    config: &LocateConfig,
+    abort: Option<Arc<AtomicBool>>,
+    pool: Option<&LocatePool>,
     mut f: F,
 ) -> Result<(), LocateError> {
-    let filter = filter::compile(&filter, config);
-    if matches!(filter, Err(LocateError::Trivial)) {
+    let queries = vec![filter];
+    let filters = compile_queries(&queries, config)?;
+    if filters.is_empty() {
         return Ok(());
     }
-    let filter = filter?;
-    for vi in &volume_info {
-        f(LocateEvent::Searching(&vi.folder)).map_err(LocateError::WritingResultFailed)?;
-        let res = locate_volume(vi, &filter, &abort, &mut f);
-        if let Err(ref err) = res {
-            match err {
-                LocateError::WritingResultFailed(err) if err.kind() == ErrorKind::BrokenPipe => {
-                    return Err(LocateError::BrokenPipe)
+    let mut volumes = Vec::new();
+    for (position, vi) in volume_info.iter().enumerate() {
+        if is_shared(&volume_info[..position], vi) {
+            continue;
+        }
+        let filters = match &vi.locate_config {
+            Some(volume_config) => compile_queries(&queries, volume_config)?,
+            None => filters.clone(),
+        };
+        if !filters.is_empty() {
+            volumes.push((vi.clone(), filters));
+        }
+    }
+    let threads = match pool {
+        Some(pool) => pool.size(),
+        None => thread::available_parallelism().map_or(1, usize::from),
+    }
+    .min(volumes.len());
+    // The searching threads outlive this function, if they are kept in a
+    // pool. Therefore they own the search.
+    let search = Arc::new(ParallelSearch {
+        volumes,
+        config: config.clone(),
+        abort,
+        next: AtomicUsize::new(0),
+        scan_limit: ScanLimit::new(config),
+    });
+    let (tx, rx) = sync_channel(PARALLEL_CAPACITY);
+    // Disconnected when all searching threads are done.
+    let (done_tx, done_rx) = channel::<()>();
+    for _ in 0..threads {
+        let (search, tx, done_tx) = (search.clone(), tx.clone(), done_tx.clone());
+        let job = move || {
+            search.run(&tx);
+            drop(done_tx);
+        };
+        match pool {
+            Some(pool) => pool.execute(Box::new(job)),
+            None => {
+                thread::spawn(job);
+            }
+        }
+    }
+    drop(tx);
+    drop(done_tx);
+    // The searching threads stop, when the receiver is dropped.
+    let res = receive_parallel(
+        &search.volumes,
+        rx,
+        Window::new(config),
+        config.ordered,
+        &mut f,
+    );
+    let _ = done_rx.recv();
+    res
+}
+
+// Volumes and state of a query searched by multiple threads.
+struct ParallelSearch {
+    volumes: Vec<(VolumeInfo, Vec<(usize, CompiledFilter)>)>,
+    config: LocateConfig,
+    abort: Option<Arc<AtomicBool>>,
+    // Index of the next volume to search.
+    next: AtomicUsize,
+    scan_limit: ScanLimit,
+}
+
+impl ParallelSearch {
+    // Searches volumes until all are taken by a thread.
+    fn run(&self, tx: &SyncSender<ParallelEvent>) {
+        loop {
+            let index = self.next.fetch_add(1, Ordering::Relaxed);
+            let Some((vi, filters)) = self.volumes.get(index) else {
+                break;
+            };
+            let config = vi.locate_config.as_ref().unwrap_or(&self.config);
+            let res = search_volume(
+                index,
+                vi,
+                filters,
+                config,
+                &self.abort,
+                &self.scan_limit,
+                tx,
+            );
+            if res.is_err() {
+                // The receiver stopped.
+                break;
+            }
+        }
+    }
+}
+
+// Maximum number of events queued by the searching threads of
+// locate_parallel.
+const PARALLEL_CAPACITY: usize = 1024;
+
+// Events of locate_parallel with the index of the volume.
+enum ParallelEvent {
+    Searching(usize),
+    UpdateInProgress(usize),
+    WaitingForUpdate(usize),
+    Entry(usize, PathBuf, Metadata),
+    Finished(usize, Result<LocateStatistics, LocateError>),
+}
+
+impl ParallelEvent {
+    fn volume(&self) -> usize {
+        match self {
+            ParallelEvent::Searching(index)
+            | ParallelEvent::UpdateInProgress(index)
+            | ParallelEvent::WaitingForUpdate(index)
+            | ParallelEvent::Entry(index, _, _)
+            | ParallelEvent::Finished(index, _) => *index,
+        }
+    }
+}
+
+fn search_volume(
+    index: usize,
+    vi: &VolumeInfo,
+    filters: &[(usize, CompiledFilter)],
+    config: &LocateConfig,
+    abort: &Option<Arc<AtomicBool>>,
+    scan_limit: &ScanLimit,
+    tx: &SyncSender<ParallelEvent>,
+) -> Result<(), SendError<ParallelEvent>> {
+    tx.send(ParallelEvent::Searching(index))?;
+    let start = Instant::now();
+    let mut send_update = |event: LocateEvent| {
+        let event = match event {
+            LocateEvent::WaitingForUpdate(_) => ParallelEvent::WaitingForUpdate(index),
+            _ => ParallelEvent::UpdateInProgress(index),
+        };
+        tx.send(event)
+            .map_err(|_| std::io::Error::from(ErrorKind::BrokenPipe))
+    };
+    let query = Query {
+        volume: vi,
+        shared: Vec::new(),
+        filters,
+        report: Report::Entry,
+    };
+    // Offset and limit are applied by the receiver.
+    let mut window = Window::default();
+    let res = check_update(vi, config, abort, &mut send_update);
+    let res = res.and_then(|_| FileIndexReader::open(&vi.database, config));
+    let res = res.and_then(|reader| {
+        locate_volume(
+            reader,
+            &query,
+            &mut window,
+            abort,
+            scan_limit,
+            &mut |event| {
+                if let LocateEvent::Entry(path, metadata) = event {
+                    tx.send(ParallelEvent::Entry(
+                        index,
+                        path.to_owned(),
+                        metadata.clone(),
+                    ))
+                    .map_err(|_| std::io::Error::from(ErrorKind::BrokenPipe))?;
                 }
-                _ => return res,
+                Ok(())
+            },
+        )
+    });
+    let res = res.map(|statistics| LocateStatistics {
+        elapsed: start.elapsed(),
+        ..statistics
+    });
+    tx.send(ParallelEvent::Finished(index, res))
+}
+
+fn receive_parallel<F: FnMut(LocateEvent) -> IOResult<()>>(
+    volumes: &[(VolumeInfo, Vec<(usize, CompiledFilter)>)],
+    rx: Receiver<ParallelEvent>,
+    mut window: Window,
+    ordered: bool,
+    f: &mut F,
+) -> Result<(), LocateError> {
+    // Events of volumes after the current one are held back, if the results
+    // are ordered. Offset and limit are applied, when the events are
+    // reported.
+    let mut held: Vec<Vec<ParallelEvent>> = volumes.iter().map(|_| Vec::new()).collect();
+    let mut current = 0;
+    for event in rx {
+        if !ordered {
+            report_parallel(volumes, event, &mut window, f)?;
+            if window.exhausted() {
+                // The searching threads stop, when the receiver is dropped.
+                break;
+            }
+            continue;
+        }
+        held[event.volume()].push(event);
+        while let Some(events) = held.get_mut(current) {
+            // Finished is the last event of a volume.
+            let finished = matches!(events.last(), Some(ParallelEvent::Finished(_, _)));
+            for event in events.drain(..) {
+                report_parallel(volumes, event, &mut window, f)?;
+                if window.exhausted() {
+                    return Ok(());
+                }
+            }
+            if !finished {
+                break;
             }
+            current += 1;
         }
     }
     Ok(())
 }
 
-fn locate_volume<F: FnMut(LocateEvent) -> IOResult<()>>(
-    volume_info: &VolumeInfo,
-    filter: &CompiledFilter,
-    abort: &Option<Arc<AtomicBool>>,
+fn report_parallel<F: FnMut(LocateEvent) -> IOResult<()>>(
+    volumes: &[(VolumeInfo, Vec<(usize, CompiledFilter)>)],
+    event: ParallelEvent,
+    window: &mut Window,
     f: &mut F,
 ) -> Result<(), LocateError> {
-    let mut reader = FileIndexReader::new(&volume_info.database)?;
+    let res = match event {
+        ParallelEvent::Searching(index) => f(LocateEvent::Searching(&volumes[index].0.folder)),
+        ParallelEvent::UpdateInProgress(index) => {
+            f(LocateEvent::UpdateInProgress(&volumes[index].0.folder))
+        }
+        ParallelEvent::WaitingForUpdate(index) => {
+            f(LocateEvent::WaitingForUpdate(&volumes[index].0.folder))
+        }
+        ParallelEvent::Entry(_, _, _) if !window.take() => Ok(()),
+        ParallelEvent::Entry(_, path, metadata) => f(LocateEvent::Entry(&path, &metadata)),
+        ParallelEvent::Finished(index, res) => {
+            let statistics = res?;
+            f(LocateEvent::Statistics(
+                &volumes[index].0.folder,
+                &statistics,
+            ))
+        }
+    };
+    match res {
+        Err(err) if err.kind() == ErrorKind::BrokenPipe => Err(LocateError::BrokenPipe),
+        Err(err) => Err(LocateError::WritingResultFailed(err)),
+        Ok(()) => Ok(()),
+    }
+}
+
+// Compiles the queries. Trivial queries are skipped, but keep their index.
+fn compile_queries(
+    queries: &[Vec<FilterToken>],
+    config: &LocateConfig,
+) -> Result<Vec<(usize, CompiledFilter)>, LocateError> {
+    let mut filters = Vec::new();
+    for (index, query) in queries.iter().enumerate() {
+        match filter::compile(query, config) {
+            Err(LocateError::Trivial) => {}
+            res => filters.push((index, res?)),
+        }
+    }
+    Ok(filters)
+}
+
+// Queries compiled for each volume with its config, or a filter compiled by
+// the caller.
+enum Queries<'a> {
+    Tokens(Vec<Vec<FilterToken>>),
+    Compiled(&'a CompiledFilter),
+}
+
+fn locate_impl<F: FnMut(LocateEvent) -> IOResult<()>>(
+    volume_info: Vec<VolumeInfo>,
+    queries: Queries,
+    report: Report,
+    config: &LocateConfig,
+    abort: Option<Arc<AtomicBool>>,
+    mut cache: Option<&mut DatabaseCache>,
+    mut f: F,
+) -> Result<(), LocateError> {
+    let filters = match &queries {
+        Queries::Tokens(queries) => compile_queries(queries, config)?,
+        Queries::Compiled(filter) => vec![(0, (*filter).clone())],
+    };
+    if filters.is_empty() {
+        return Ok(());
+    }
+    let mut window = Window::new(config);
+    let scan_limit = ScanLimit::new(config);
+    for (position, vi) in volume_info.iter().enumerate() {
+        if window.exhausted() {
+            break;
+        }
+        if is_shared(&volume_info[..position], vi) {
+            continue;
+        }
+        let volume_filters = match (&vi.locate_config, &queries) {
+            (Some(volume_config), Queries::Tokens(queries)) => {
+                Some(compile_queries(queries, volume_config)?)
+            }
+            _ => None,
+        };
+        let filters = volume_filters.as_ref().unwrap_or(&filters);
+        if filters.is_empty() {
+            continue;
+        }
+        let shared = match report {
+            Report::Context => volume_info
+                .iter()
+                .filter(|other| other.database == vi.database)
+                .collect(),
+            _ => Vec::new(),
+        };
+        let query = Query {
+            volume: vi,
+            shared,
+            filters,
+            report,
+        };
+        f(LocateEvent::Searching(&vi.folder)).map_err(LocateError::WritingResultFailed)?;
+        let start = Instant::now();
+        let volume_config = vi.locate_config.as_ref().unwrap_or(config);
+        check_update(vi, volume_config, &abort, &mut f)?;
+        let content = match cache.as_deref_mut() {
+            Some(cache) => cache.get(&vi.database, volume_config.verify_checksums)?,
+            None => None,
+        };
+        let res = match content {
+            Some(content) => {
+                let reader = FileIndexReader::with_reader(&vi.database, Cursor::new(content))?;
+                locate_volume(reader, &query, &mut window, &abort, &scan_limit, &mut f)
+            }
+            None => {
+                let reader = FileIndexReader::open(&vi.database, volume_config)?;
+                locate_volume(reader, &query, &mut window, &abort, &scan_limit, &mut f)
+            }
+        };
+        let statistics = match res {
+            Ok(statistics) => LocateStatistics {
+                elapsed: start.elapsed(),
+                ..statistics
+            },
+            Err(LocateError::WritingResultFailed(err)) if err.kind() == ErrorKind::BrokenPipe => {
+                return Err(LocateError::BrokenPipe)
+            }
+            Err(err) => return Err(err),
+        };
+        f(LocateEvent::Statistics(&vi.folder, &statistics))
+            .map_err(LocateError::WritingResultFailed)?;
+    }
+    Ok(())
+}
+
+// Compiled filters of a volume with the index of their query.
+struct Query<'a> {
+    volume: &'a VolumeInfo,
+    // Folders sharing the database file, only used for Report::Context.
+    shared: Vec<&'a VolumeInfo>,
+    filters: &'a [(usize, CompiledFilter)],
+    report: Report,
+}
+
+impl Query<'_> {
+    // The longest folder containing the path, if the database file is
+    // shared by several folders.
+    fn volume(&self, path: &Path) -> &VolumeInfo {
+        self.shared
+            .iter()
+            .filter(|vi| path.starts_with(&vi.folder))
+            .max_by_key(|vi| vi.folder.as_os_str().len())
+            .copied()
+            .unwrap_or(self.volume)
+    }
+}
+
+// Folders sharing a database file are searched once with the first of them.
+pub(crate) fn is_shared(previous: &[VolumeInfo], vi: &VolumeInfo) -> bool {
+    previous.iter().any(|other| other.database == vi.database)
+}
+
+// Offset and limit of the reported entries over all volumes. An entry
+// matching several queries of a batch counts once.
+#[derive(Default)]
+struct Window {
+    skip: u64,
+    remaining: Option<u64>,
+}
+
+impl Window {
+    fn new(config: &LocateConfig) -> Window {
+        Window {
+            skip: config.offset,
+            remaining: config.limit,
+        }
+    }
+
+    // Returns whether the next matching entry is reported.
+    fn take(&mut self) -> bool {
+        if self.skip > 0 {
+            self.skip -= 1;
+            return false;
+        }
+        if let Some(remaining) = &mut self.remaining {
+            *remaining = remaining.saturating_sub(1);
+        }
+        true
+    }
+
+    fn exhausted(&self) -> bool {
+        self.remaining == Some(0)
+    }
+}
+
+// Number of database entries scanned by a query over all volumes. The
+// counter is shared by the threads of locate_parallel.
+struct ScanLimit {
+    max: Option<u64>,
+    scanned: AtomicU64,
+}
+
+impl ScanLimit {
+    fn new(config: &LocateConfig) -> ScanLimit {
+        ScanLimit {
+            max: config.max_entries_scanned,
+            scanned: AtomicU64::new(0),
+        }
+    }
+
+    // Counts the next entry and fails when the maximum is exceeded.
+    fn scan(&self) -> Result<(), LocateError> {
+        match self.max {
+            Some(max) if self.scanned.fetch_add(1, Ordering::Relaxed) >= max => {
+                Err(LocateError::QueryTooBroad(max))
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+// Event used for reporting matching entries.
+#[derive(Clone, Copy, PartialEq)]
+enum Report {
+    Entry,
+    Batch,
+    Context,
+}
+
+fn locate_volume<R: Read, F: FnMut(LocateEvent) -> IOResult<()>>(
+    mut reader: FileIndexReader<R>,
+    query: &Query,
+    window: &mut Window,
+    abort: &Option<Arc<AtomicBool>>,
+    scan_limit: &ScanLimit,
+    f: &mut F,
+) -> Result<LocateStatistics, LocateError> {
+    let mut statistics = LocateStatistics::default();
+    // Entries of a volume mounted at a different path since the update are
+    // reported at the current location.
+    let moved = reader.volume.as_ref().and_then(|volume| {
+        let folder = volume.moved_folder()?;
+        Some((volume.folder.clone(), folder))
+    });
+    // Entries stored relative to the folder are moved at once.
+    let moved = moved.filter(|(_, to)| !reader.relocate(to));
+    // Reused for all entries of a moved volume.
+    let mut moved_path = PathBuf::new();
     loop {
         if abort
             .as_ref()
@@ -110,33 +997,198 @@ fn locate_volume<F: FnMut(LocateEvent) -> IOResult<()>>(
         {
             return Err(LocateError::Aborted);
         }
-        match reader.next_entry() {
-            Ok(Some((path, metadata))) => {
+        match reader.advance() {
+            Ok(true) => {
+                scan_limit.scan()?;
+                let entry_index = statistics.entries;
+                statistics.entries += 1;
+                let path = match &moved {
+                    Some((from, to)) => {
+                        move_path(&mut moved_path, reader.path(), from, to);
+                        moved_path.as_path()
+                    }
+                    None => reader.path(),
+                };
                 let bytes = path.as_os_str().as_bytes();
-                let text = String::from_utf8_lossy(bytes);
-                if filter::apply(&text, filter) {
-                    f(LocateEvent::Entry(path, &metadata))
-                        .map_err(LocateError::WritingResultFailed)?;
+                let mut matched = false;
+                let mut reported = false;
+                // The path is borrowed as text, unless it isn't valid UTF-8.
+                // Text and metadata are decoded for the first filter needing
+                // them.
+                let mut text = None;
+                let mut metadata = None;
+                for (index, filter) in query.filters {
+                    // Ranges are reported for the first matching branch.
+                    let mut ranges = Vec::new();
+                    let mut branch_matches = false;
+                    for branch in filter.branches() {
+                        ranges.clear();
+                        let text_matches = filter::matches_all_paths(branch) || {
+                            let text = text.get_or_insert_with(|| String::from_utf8_lossy(bytes));
+                            match query.report {
+                                Report::Context => filter::apply_ranges(text, branch, &mut ranges),
+                                _ => filter::apply(text, branch),
+                            }
+                        };
+                        if !text_matches {
+                            continue;
+                        }
+                        let metadata = metadata.get_or_insert_with(|| reader.metadata());
+                        if filter::apply_meta(metadata, branch) {
+                            branch_matches = true;
+                            break;
+                        }
+                    }
+                    if !branch_matches {
+                        continue;
+                    }
+                    let metadata = metadata.get_or_insert_with(|| reader.metadata());
+                    if !matched {
+                        matched = true;
+                        reported = window.take();
+                    }
+                    if !reported {
+                        continue;
+                    }
+                    let res = match query.report {
+                        Report::Entry => f(LocateEvent::Entry(path, metadata)),
+                        Report::Batch => f(LocateEvent::BatchEntry(*index, path, metadata)),
+                        Report::Context => f(LocateEvent::Match(&Match {
+                            volume: query.volume(path),
+                            index: entry_index,
+                            path,
+                            metadata,
+                            // Ranges of a lossy conversion don't refer to the path.
+                            ranges: (!matches!(text, Some(Cow::Owned(_)))).then_some(ranges),
+                        })),
+                    };
+                    res.map_err(LocateError::WritingResultFailed)?;
+                }
+                if matched {
+                    statistics.matches += 1;
+                    if window.exhausted() {
+                        return Ok(statistics);
+                    }
                 }
             }
-            Ok(None) => return Ok(()),
+            Ok(false) => return Ok(statistics),
             Err(err) => return Err(err),
         }
     }
 }
 
-struct FileIndexReader {
+// Flag in the settings byte of the header. A volume header follows.
+pub(crate) const VOLUME_HEADER: u8 = 0x80;
+
+// Flag in the settings byte of the header. The scanned folder follows the
+// volume header. The first entry is delta encoded against the folder, i.e.
+// all entries are stored relative to the folder. Database files without this
+// flag, e.g. written by earlier versions, store absolute paths.
+pub(crate) const ROOT_HEADER: u8 = 0x40;
+
+// Flag in the settings byte of the header. The path of each entry is
+// followed by a byte with its EntryType.
+pub(crate) const ENTRY_TYPES: u8 = 0x20;
+
+// Flag in the settings byte of the header. The entries are terminated by
+// END_OF_ENTRIES instead of the end of the file, followed by the CRC-32 of
+// all preceding bytes.
+pub(crate) const FOOTER: u8 = 0x10;
+
+// Flag in the settings byte of the header. The entries and the end marker
+// are compressed with zstd.
+pub(crate) const COMPRESSED: u8 = 0x08;
+
+// Flag in the settings byte of the header. The size of each entry, if any,
+// is followed by its modification time.
+pub(crate) const MODIFICATION_TIMES: u8 = 0x04;
+
+// Stored instead of the number of discarded bytes of the next entry.
+pub(crate) const END_OF_ENTRIES: u64 = u64::MAX;
+
+// Upper limit of the byte strings of the header, of the path of an entry and
+// of all metadata values of an entry. A corrupt length is reported as an
+// invalid entry instead of allocating its size.
+const MAX_LENGTH: usize = 1 << 20;
+
+fn move_path(moved: &mut PathBuf, path: &Path, from: &Path, to: &Path) {
+    moved.as_mut_os_string().clear();
+    match path.strip_prefix(from) {
+        Ok(relative) if relative.as_os_str().is_empty() => moved.push(to),
+        Ok(relative) => {
+            moved.push(to);
+            moved.push(relative);
+        }
+        Err(_) => moved.push(path),
+    }
+}
+
+pub(crate) struct FileIndexReader<R: Read = BufReader<File>> {
     database: PathBuf,
-    reader: BufReader<File>,
+    reader: PayloadReader<R>,
     path: Vec<u8>,
-    settings: Settings,
+    pub(crate) settings: Settings,
+    pub(crate) volume: Option<VolumeHeader>,
+    // Entries are stored relative to the scanned folder, which is the
+    // initial path.
+    relative: bool,
+    entry_types: bool,
+    pub(crate) modification_times: bool,
+    // The entries are followed by a footer. The end of the file before the
+    // end marker means that the file is truncated.
+    footer: bool,
+    finished: bool,
+    // Metadata of the current entry. The values of the attributes are
+    // stored in a single buffer.
+    size: Option<u64>,
+    modified: Option<SystemTime>,
+    entry_type: Option<EntryType>,
+    attributes: Vec<(u64, Range<usize>)>,
+    values: Vec<u8>,
 }
 
 impl FileIndexReader {
-    fn new(database: &Path) -> Result<FileIndexReader, LocateError> {
+    pub(crate) fn new(database: &Path) -> Result<FileIndexReader, LocateError> {
+        FileIndexReader::open(database, &LocateConfig::default())
+    }
+
+    // Uses the buffer size and read-ahead of the locate configuration. The
+    // checksum is verified before, if configured.
+    pub(crate) fn open(
+        database: &Path,
+        config: &LocateConfig,
+    ) -> Result<FileIndexReader, LocateError> {
+        if config.verify_checksums {
+            verify_checksum(database)?;
+        }
         let file = File::open(database)
             .map_err(|err| LocateError::ReadingFileFailed(database.to_owned(), err))?;
-        let mut reader = BufReader::new(file);
+        if config.readahead {
+            advise_sequential(&file);
+        }
+        let reader = BufReader::with_capacity(config.buffer_size, file);
+        FileIndexReader::with_reader(database, reader)
+    }
+}
+
+// The advice is only a hint. Errors are ignored.
+#[cfg(target_os = "linux")]
+pub(crate) fn advise_sequential(file: &File) {
+    use nix::fcntl::{posix_fadvise, PosixFadviseAdvice};
+    use std::os::unix::io::AsRawFd;
+    let _ = posix_fadvise(
+        file.as_raw_fd(),
+        0,
+        0,
+        PosixFadviseAdvice::POSIX_FADV_SEQUENTIAL,
+    );
+}
+
+#[cfg(not(target_os = "linux"))]
+pub(crate) fn advise_sequential(_file: &File) {}
+
+impl<R: Read> FileIndexReader<R> {
+    fn with_reader(database: &Path, mut reader: R) -> Result<FileIndexReader<R>, LocateError> {
         let mut fourcc: [u8; 4] = [0; 4];
         reader
             .read_exact(&mut fourcc)
@@ -148,57 +1200,219 @@ impl FileIndexReader {
         reader
             .read_exact(&mut flags)
             .map_err(|err| LocateError::ReadingFileFailed(database.to_owned(), err))?;
-        let settings = Settings::try_from(flags[0])
-            .map_err(|_err| LocateError::UnsupportedFileFormat(database.to_owned()))?;
-        let path: Vec<u8> = Vec::new();
+        let settings = Settings::try_from(
+            flags[0]
+                & !(VOLUME_HEADER
+                    | ROOT_HEADER
+                    | ENTRY_TYPES
+                    | FOOTER
+                    | COMPRESSED
+                    | MODIFICATION_TIMES),
+        )
+        .map_err(|_err| LocateError::UnsupportedFileFormat(database.to_owned()))?;
+        let volume = if flags[0] & VOLUME_HEADER != 0 {
+            Some(read_volume_header(database, &mut reader)?)
+        } else {
+            None
+        };
+        let compressed = flags[0] & COMPRESSED != 0;
+        if compressed && !cfg!(feature = "compression") {
+            return Err(LocateError::CompressionUnsupported(database.to_owned()));
+        }
+        let relative = flags[0] & ROOT_HEADER != 0;
+        let path: Vec<u8> = if relative {
+            read_bytes(database, &mut reader)?
+        } else {
+            Vec::new()
+        };
+        let reader = PayloadReader::new(reader, compressed)
+            .map_err(|err| LocateError::ReadingFileFailed(database.to_owned(), err))?;
         let database = database.to_owned();
         Ok(FileIndexReader {
             database,
             reader,
             path,
             settings,
+            volume,
+            relative,
+            entry_types: flags[0] & ENTRY_TYPES != 0,
+            modification_times: flags[0] & MODIFICATION_TIMES != 0,
+            footer: flags[0] & FOOTER != 0,
+            finished: false,
+            size: None,
+            modified: None,
+            entry_type: None,
+            attributes: Vec::new(),
+            values: Vec::new(),
         })
     }
 
-    fn next_entry(&mut self) -> Result<Option<(&Path, Metadata)>, LocateError> {
+    pub(crate) fn next_entry(&mut self) -> Result<Option<(&Path, Metadata)>, LocateError> {
+        if !self.advance()? {
+            return Ok(None);
+        }
+        Ok(Some((self.path(), self.metadata())))
+    }
+
+    // Reads the next entry and returns false at the end of the file. The
+    // entry is accessed with path and metadata. The buffers of the reader are
+    // reused, i.e. reading an entry doesn't allocate memory.
+    pub(crate) fn advance(&mut self) -> Result<bool, LocateError> {
+        let map_err = |err| LocateError::ReadingFileFailed(self.database.clone(), err);
+        if self.finished {
+            return Ok(false);
+        }
         let discard = match self.reader.read_vu64() {
             Ok(val) => val,
             Err(err) => match err.kind() {
-                ErrorKind::UnexpectedEof => return Ok(None),
-                _ => return Err(LocateError::ReadingFileFailed(self.database.clone(), err)),
+                ErrorKind::UnexpectedEof if self.footer => {
+                    return Err(LocateError::UnexpectedEof(self.database.clone()))
+                }
+                ErrorKind::UnexpectedEof => return Ok(false),
+                _ => return Err(map_err(err)),
             },
         };
-        let length = self
-            .reader
-            .read_vu64()
-            .map_err(|err| LocateError::ReadingFileFailed(self.database.clone(), err))?;
-        let mut delta = vec![0u8; length as usize];
+        if self.footer && discard == END_OF_ENTRIES {
+            // The checksum is verified by verify_checksum.
+            self.finished = true;
+            return Ok(false);
+        }
+        let length = self.reader.read_vu64().map_err(map_err)?;
+        // The delta is read directly into the path. Lengths are stored with
+        // 64 bits, which may exceed the address space of 32-bit platforms.
+        let Some((reuse, end)) = usize::try_from(discard)
+            .ok()
+            .and_then(|discard| self.path.len().checked_sub(discard))
+            .zip(usize::try_from(length).ok())
+            .and_then(|(reuse, length)| Some((reuse, reuse.checked_add(length)?)))
+            .filter(|(_, end)| *end <= MAX_LENGTH)
+        else {
+            return Err(LocateError::InvalidEntry(self.database.clone()));
+        };
+        self.path.resize(end, 0);
         self.reader
-            .read_exact(&mut delta)
-            .map_err(|err| LocateError::ReadingFileFailed(self.database.clone(), err))?;
-        delta_decode(&mut self.path, discard, &delta);
-        let size = if self.settings == Settings::WithFileSizes {
-            let size_plus_one = self
-                .reader
-                .read_vu64()
-                .map_err(|err| LocateError::ReadingFileFailed(self.database.clone(), err))?;
-            if size_plus_one == 0 {
-                None
-            } else {
-                Some(size_plus_one - 1)
-            }
+            .read_exact(&mut self.path[reuse..])
+            .map_err(map_err)?;
+        self.entry_type = if self.entry_types {
+            let mut entry_type = [0u8; 1];
+            self.reader.read_exact(&mut entry_type).map_err(map_err)?;
+            EntryType::from_u8(entry_type[0])
+        } else {
+            None
+        };
+        self.size = if self.settings.has_sizes() {
+            let size_plus_one = self.reader.read_vu64().map_err(map_err)?;
+            size_plus_one.checked_sub(1)
+        } else {
+            None
+        };
+        self.modified = if self.modification_times {
+            let modified_plus_one = self.reader.read_vu64().map_err(map_err)?;
+            // Times which the platform can't represent, e.g. after January
+            // 2038 with a 32-bit time_t, are unknown.
+            modified_plus_one
+                .checked_sub(1)
+                .and_then(|seconds| UNIX_EPOCH.checked_add(Duration::from_secs(seconds)))
         } else {
             None
         };
-        let path = Path::new(OsStr::from_bytes(self.path.as_slice()));
-        Ok(Some((path, Metadata { size })))
+        self.attributes.clear();
+        self.values.clear();
+        if self.settings.has_metadata() {
+            // TLV records terminated by tag 0.
+            loop {
+                let tag = self.reader.read_vu64().map_err(map_err)?;
+                if tag == 0 {
+                    break;
+                }
+                let length = self.reader.read_vu64().map_err(map_err)?;
+                let start = self.values.len();
+                let Some(end) = usize::try_from(length)
+                    .ok()
+                    .and_then(|length| start.checked_add(length))
+                    .filter(|end| *end <= MAX_LENGTH)
+                else {
+                    return Err(LocateError::InvalidEntry(self.database.clone()));
+                };
+                self.values.resize(end, 0);
+                self.reader
+                    .read_exact(&mut self.values[start..])
+                    .map_err(map_err)?;
+                self.attributes.push((tag, start..self.values.len()));
+            }
+        }
+        Ok(true)
+    }
+
+    pub(crate) fn path(&self) -> &Path {
+        Path::new(OsStr::from_bytes(self.path.as_slice()))
+    }
+
+    // Reports all entries below the given folder instead of the scanned
+    // folder. Must be called before reading the first entry. Returns false,
+    // if the entries are stored with absolute paths.
+    pub(crate) fn relocate(&mut self, folder: &Path) -> bool {
+        // Entries of the root directory aren't separated from the folder.
+        if !self.relative || self.path.ends_with(b"/") {
+            return false;
+        }
+        self.path.clear();
+        self.path.extend_from_slice(folder.as_os_str().as_bytes());
+        true
+    }
+
+    // Decodes the metadata of the current entry. Queries only decode the
+    // metadata of entries matching the path.
+    pub(crate) fn metadata(&self) -> Metadata {
+        Metadata {
+            size: self.size,
+            modified: self.modified,
+            entry_type: self.entry_type,
+            attributes: self
+                .attributes
+                .iter()
+                .map(|(tag, range)| (*tag, self.values[range.clone()].to_vec()))
+                .collect(),
+        }
     }
 }
 
-fn delta_decode(path: &mut Vec<u8>, discard: u64, delta: &[u8]) {
-    let len = path.len();
-    let reuse = len - (discard as usize);
-    path.splice(reuse..len, delta.iter().cloned());
+// The volume header contains the kind and value of the volume id, the mount
+// point and the scanned folder, each with a vu64 length.
+fn read_volume_header<R: Read>(
+    database: &Path,
+    reader: &mut R,
+) -> Result<VolumeHeader, LocateError> {
+    let map_err = |err| LocateError::ReadingFileFailed(database.to_owned(), err);
+    let mut kind = [0u8; 1];
+    reader.read_exact(&mut kind).map_err(map_err)?;
+    let value = read_bytes(database, reader)?;
+    let mount_point = read_bytes(database, reader)?;
+    let folder = read_bytes(database, reader)?;
+    let id = String::from_utf8(value)
+        .ok()
+        .and_then(|value| VolumeId::from_kind(kind[0], value))
+        .ok_or_else(|| LocateError::UnsupportedFileFormat(database.to_owned()))?;
+    Ok(VolumeHeader {
+        id,
+        mount_point: PathBuf::from(OsStr::from_bytes(&mount_point)),
+        folder: PathBuf::from(OsStr::from_bytes(&folder)),
+    })
+}
+
+// Reads a byte string of the header preceded by its length.
+fn read_bytes<R: Read>(database: &Path, reader: &mut R) -> Result<Vec<u8>, LocateError> {
+    let map_err = |err| LocateError::ReadingFileFailed(database.to_owned(), err);
+    let length = reader.read_vu64().map_err(map_err)?;
+    let Some(length) = usize::try_from(length)
+        .ok()
+        .filter(|length| *length <= MAX_LENGTH)
+    else {
+        return Err(LocateError::InvalidEntry(database.to_owned()));
+    };
+    let mut bytes = vec![0u8; length];
+    reader.read_exact(&mut bytes).map_err(map_err)?;
+    Ok(bytes)
 }
 
 impl Display for LocateError {
@@ -224,12 +1438,821 @@ impl Display for LocateError {
                 "Database has unsupported file format: '{}'",
                 path.to_string_lossy()
             )),
+            LocateError::CompressionUnsupported(path) => f.write_fmt(format_args!(
+                "Database is compressed, but fsidx was built without the compression feature: '{}'",
+                path.to_string_lossy()
+            )),
+            LocateError::ChecksumMismatch(path) => f.write_fmt(format_args!(
+                "Database is damaged, the checksum doesn't match: '{}'",
+                path.to_string_lossy()
+            )),
+            LocateError::InvalidEntry(path) => f.write_fmt(format_args!(
+                "Database is damaged, an entry is invalid: '{}'",
+                path.to_string_lossy()
+            )),
             LocateError::Aborted => f.write_str("Aborted"),
             LocateError::BrokenPipe => f.write_str("Broken pipe"),
             LocateError::GlobPatternError(glob, err) => {
                 f.write_fmt(format_args!("Glob pattern error for `{}`: {}", glob, err))
             }
             LocateError::Trivial => f.write_str("Trivial"),
+            LocateError::QueryTooBroad(max) => f.write_fmt(format_args!(
+                "Query too broad: more than {} database entries scanned",
+                max
+            )),
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::temp_dir::TempDir;
+    use std::fs;
+
+    // Writes a database file with the given content into a new temporary
+    // directory.
+    fn database(name: &str, content: &[u8]) -> (TempDir, PathBuf) {
+        let dir = TempDir::new(name);
+        let database = dir.join("test.fsdb");
+        fs::write(&database, content).unwrap();
+        (dir, database)
+    }
+
+    fn check(name: &str, content: &[u8]) -> HeaderCheck {
+        let (_dir, database) = database(&format!("check-{}", name), content);
+        check_header(&database)
+    }
+
+    // Reads all entries of the database file.
+    fn read(name: &str, content: &[u8]) -> Result<(), LocateError> {
+        let (_dir, database) = database(&format!("read-{}", name), content);
+        let mut reader = FileIndexReader::new(&database)?;
+        while reader.advance()? {}
+        Ok(())
+    }
+
+    #[test]
+    fn check_header_valid() {
+        assert!(matches!(
+            check("valid", b"fsix\x01"),
+            HeaderCheck::Ok(Settings::WithFileSizes)
+        ));
+    }
+
+    #[test]
+    fn check_header_missing() {
+        assert!(matches!(
+            check_header(Path::new("/nonexistent/fsidx.fsdb")),
+            HeaderCheck::Missing
+        ));
+    }
+
+    #[test]
+    fn check_header_other_file() {
+        assert!(matches!(
+            check("other", b"abcd\x01"),
+            HeaderCheck::Invalid(LocateError::ExpectedFsdbFile(_))
+        ));
+    }
+
+    #[test]
+    fn check_header_short() {
+        assert!(matches!(
+            check("short", b"fs"),
+            HeaderCheck::Invalid(LocateError::ExpectedFsdbFile(_))
+        ));
+    }
+
+    #[test]
+    fn check_header_unsupported() {
+        assert!(matches!(
+            check("unsupported", b"fsix\xff"),
+            HeaderCheck::Invalid(LocateError::UnsupportedFileFormat(_))
+        ));
+    }
+
+    #[cfg(not(feature = "compression"))]
+    #[test]
+    fn compression_unsupported() {
+        assert!(matches!(
+            check("compressed", b"fsix\x18"),
+            HeaderCheck::Invalid(LocateError::CompressionUnsupported(_))
+        ));
+    }
+
+    // A length far beyond the limit, followed by a byte of padding.
+    const OVERSIZED: &[u8] = b"\xff\xff\xff\xff\xff\xff\xff\xff\xff\x01";
+
+    #[test]
+    fn metadata_lengths() {
+        assert!(read("valid", b"fsix\x02\x00\x02/a\x01\x01\x01x\x00").is_ok());
+    }
+
+    #[test]
+    fn oversized_header_length() {
+        // Volume header with the length of the id.
+        assert!(matches!(
+            read("header", &[b"fsix\x80\x00".as_slice(), OVERSIZED].concat()),
+            Err(LocateError::InvalidEntry(_))
+        ));
+    }
+
+    #[test]
+    fn oversized_path_length() {
+        assert!(matches!(
+            read("path", &[b"fsix\x00\x00".as_slice(), OVERSIZED].concat()),
+            Err(LocateError::InvalidEntry(_))
+        ));
+    }
+
+    #[test]
+    fn oversized_value_length() {
+        assert!(matches!(
+            read(
+                "value",
+                &[b"fsix\x02\x00\x02/a\x01\x01".as_slice(), OVERSIZED].concat()
+            ),
+            Err(LocateError::InvalidEntry(_))
+        ));
+    }
+
+    #[test]
+    fn moved_paths() {
+        let mut moved = PathBuf::new();
+        let (from, to) = (Path::new("/media/old"), Path::new("/media/new"));
+        move_path(&mut moved, Path::new("/media/old/a/b"), from, to);
+        assert_eq!(moved, Path::new("/media/new/a/b"));
+        move_path(&mut moved, Path::new("/media/old"), from, to);
+        assert_eq!(moved, Path::new("/media/new"));
+        move_path(&mut moved, Path::new("/media/other"), from, to);
+        assert_eq!(moved, Path::new("/media/other"));
+    }
+
+    // Creates folders with the same empty files and their database files in
+    // a new temporary directory.
+    #[cfg(feature = "update")]
+    fn fixture(name: &str, folders: &[&str], files: &[&str]) -> (TempDir, Vec<VolumeInfo>) {
+        let dir = TempDir::new(name);
+        let volume_info: Vec<VolumeInfo> = folders
+            .iter()
+            .map(|folder| VolumeInfo::in_dir(&dir, folder))
+            .collect();
+        for vi in &volume_info {
+            fs::create_dir_all(&vi.folder).unwrap();
+            for file in files {
+                fs::write(vi.folder.join(file), b"").unwrap();
+            }
+        }
+        crate::update(
+            volume_info.clone(),
+            Settings::WithFileSizes,
+            &crate::UpdateConfig::default(),
+            None,
+            |_| Ok(()),
+        );
+        (dir, volume_info)
+    }
+
+    // Files of the folders x and y searched by query().
+    #[cfg(feature = "update")]
+    const FILES: [&str; 4] = ["a1", "a2", "a3", "b1"];
+
+    // Searches files starting with a.
+    #[cfg(feature = "update")]
+    fn query() -> Vec<FilterToken> {
+        vec![
+            FilterToken::LastElement,
+            FilterToken::Text(String::from("a")),
+        ]
+    }
+
+    // Describes the searched folders, entries and matches per folder.
+    #[cfg(feature = "update")]
+    fn describe(events: &mut Vec<String>) -> impl FnMut(LocateEvent) -> IOResult<()> + '_ {
+        |event| {
+            match event {
+                LocateEvent::Searching(folder) => events.push(format!("{}", folder.display())),
+                LocateEvent::Entry(path, _) => events.push(format!("{}", path.display())),
+                LocateEvent::Statistics(folder, statistics) => {
+                    events.push(format!("{} {}", folder.display(), statistics.matches))
+                }
+                _ => {}
+            }
+            Ok(())
+        }
+    }
+
+    // Names of the searched folders and entries.
+    #[cfg(feature = "update")]
+    fn names(events: &mut Vec<String>) -> impl FnMut(LocateEvent) -> IOResult<()> + '_ {
+        |event| {
+            let name = |path: &Path| path.file_name().unwrap().to_string_lossy().to_string();
+            match event {
+                LocateEvent::Searching(folder) => events.push(name(folder)),
+                LocateEvent::Entry(path, _) => events.push(name(path)),
+                _ => {}
+            }
+            Ok(())
+        }
+    }
+
+    // Lock held by a running update.
+    #[cfg(feature = "update")]
+    fn lock(vi: &VolumeInfo) -> Flock<File> {
+        let file = File::create(temporary_file(&vi.database)).unwrap();
+        Flock::lock(file, FlockArg::LockExclusiveNonblock).unwrap()
+    }
+
+    // Without database file the query waits for the first update.
+    #[cfg(feature = "update")]
+    fn first_update(name: &str) -> (TempDir, VolumeInfo, Flock<File>) {
+        let (dir, mut volume_info) = fixture(name, &["x"], &["a"]);
+        let vi = volume_info.remove(0);
+        fs::remove_file(&vi.database).unwrap();
+        let lock = lock(&vi);
+        (dir, vi, lock)
+    }
+
+    #[cfg(feature = "update")]
+    #[test]
+    fn update_in_progress() {
+        let (_dir, volume_info) = fixture("updating", &["x"], &["a"]);
+        let _lock = lock(&volume_info[0]);
+        let mut events = Vec::new();
+        locate(
+            volume_info.clone(),
+            query(),
+            &LocateConfig::default(),
+            None,
+            |event| {
+                match event {
+                    LocateEvent::UpdateInProgress(_) => events.push(String::from("updating")),
+                    LocateEvent::Entry(path, _) => events.push(format!("{}", path.display())),
+                    _ => {}
+                }
+                Ok(())
+            },
+        )
+        .unwrap();
+        // The previous database file is searched.
+        assert_eq!(
+            events,
+            vec![
+                String::from("updating"),
+                format!("{}", volume_info[0].folder.join("a").display()),
+            ]
+        );
+    }
+
+    #[cfg(feature = "update")]
+    #[test]
+    fn waiting_for_update() {
+        let (_dir, vi, _lock) = first_update("waiting");
+        let config = LocateConfig {
+            update_wait_ms: 100,
+            ..LocateConfig::default()
+        };
+        let mut waiting = false;
+        let res = locate(vec![vi], query(), &config, None, |event| {
+            waiting |= matches!(event, LocateEvent::WaitingForUpdate(_));
+            Ok(())
+        });
+        assert!(waiting);
+        assert!(matches!(res, Err(LocateError::ReadingFileFailed(_, _))));
+    }
+
+    #[cfg(feature = "update")]
+    #[test]
+    fn check_header_updating() {
+        let (_dir, vi, lock) = first_update("check-updating");
+        assert!(matches!(check_header(&vi.database), HeaderCheck::Updating));
+        drop(lock);
+        assert!(matches!(check_header(&vi.database), HeaderCheck::Missing));
+    }
+
+    #[cfg(feature = "update")]
+    #[test]
+    fn concurrent_update() {
+        let (_dir, volume_info) = fixture("concurrent", &["x"], &["a"]);
+        let _lock = lock(&volume_info[0]);
+        let mut events = Vec::new();
+        crate::update(
+            volume_info.clone(),
+            Settings::WithFileSizes,
+            &crate::UpdateConfig::default(),
+            None,
+            |event| {
+                events.push(event.to_string());
+                Ok(())
+            },
+        );
+        assert!(events.contains(&format!(
+            "Database '{}' is written by another update",
+            volume_info[0].database.display()
+        )));
+    }
+
+    #[cfg(feature = "update")]
+    #[test]
+    fn update_wait_per_volume() {
+        let (_dir, vi, _lock) = first_update("update-wait");
+        let wait = LocateConfig {
+            update_wait_ms: 200,
+            ..LocateConfig::default()
+        };
+        let config = LocateConfig {
+            update_wait_ms: 0,
+            ..LocateConfig::default()
+        };
+        let start = Instant::now();
+        let vi = vi.with_locate_config(Some(wait));
+        let res = locate(vec![vi], query(), &config, None, |_| Ok(()));
+        // The query waits as configured for the folder.
+        assert!(start.elapsed() >= Duration::from_millis(200));
+        assert!(matches!(res, Err(LocateError::ReadingFileFailed(_, _))));
+    }
+
+    #[cfg(feature = "update")]
+    #[test]
+    fn compiled() {
+        let (_dir, volume_info) = fixture("compiled", &["x", "y"], &["a.jpg", "b.txt"]);
+        let config = LocateConfig::default();
+        let filter =
+            CompiledFilter::compile(&[FilterToken::Text(String::from("*.jpg"))], &config).unwrap();
+        let mut results = Vec::new();
+        // The compiled filter is reused for each query.
+        for vi in &volume_info {
+            locate_compiled(
+                vec![vi.clone()],
+                &filter,
+                &config,
+                None,
+                names(&mut results),
+            )
+            .unwrap();
+        }
+        assert_eq!(results, vec!["x", "a.jpg", "y", "a.jpg"]);
+    }
+
+    // Runs the queries *.jpg, an empty query and a. as batch.
+    #[cfg(feature = "update")]
+    fn batch(name: &str) -> (Vec<(usize, String)>, u64) {
+        let (_dir, volume_info) = fixture(name, &["folder"], &["a.jpg", "b.jpg", "a.txt"]);
+        let folder = volume_info[0].folder.clone();
+        let text = |text: &str| vec![FilterToken::Text(text.to_string())];
+        let queries = vec![text("*.jpg"), Vec::new(), text("a.")];
+        let mut results = Vec::new();
+        let mut matches = 0;
+        locate_batch(
+            volume_info,
+            queries,
+            &LocateConfig::default(),
+            None,
+            |event| {
+                match event {
+                    LocateEvent::BatchEntry(index, path, _) => {
+                        let name = path.strip_prefix(&folder).unwrap();
+                        results.push((index, name.to_string_lossy().to_string()));
+                    }
+                    LocateEvent::Statistics(_, statistics) => matches = statistics.matches,
+                    _ => {}
+                }
+                Ok(())
+            },
+        )
+        .unwrap();
+        results.sort();
+        (results, matches)
+    }
+
+    #[cfg(feature = "update")]
+    #[test]
+    fn batch_entries() {
+        // The empty query is skipped.
+        assert_eq!(
+            batch("batch").0,
+            vec![
+                (0, String::from("a.jpg")),
+                (0, String::from("b.jpg")),
+                (2, String::from("a.jpg")),
+                (2, String::from("a.txt")),
+            ]
+        );
+    }
+
+    #[cfg(feature = "update")]
+    #[test]
+    fn batch_matches() {
+        // Entries matching several queries are counted once.
+        assert_eq!(batch("batch-matches").1, 3);
+    }
+
+    #[cfg(feature = "update")]
+    #[test]
+    fn context() {
+        let (_dir, volume_info) = fixture("context", &["folder"], &["a.jpg", "b.txt"]);
+        let filter = vec![
+            FilterToken::LastElement,
+            FilterToken::Text(String::from("b")),
+        ];
+        let mut matches = Vec::new();
+        locate_with_context(
+            volume_info.clone(),
+            filter,
+            &LocateConfig::default(),
+            None,
+            |event| {
+                if let LocateEvent::Match(m) = event {
+                    let name = m.path.file_name().unwrap().to_owned();
+                    let ranges = m.ranges.clone().unwrap();
+                    let matched = ranges
+                        .iter()
+                        .map(|range| &m.path.to_str().unwrap()[range.clone()])
+                        .collect::<Vec<_>>()
+                        .concat();
+                    matches.push((m.volume.folder.clone(), m.index, name, matched));
+                }
+                Ok(())
+            },
+        )
+        .unwrap();
+        assert_eq!(
+            matches,
+            vec![(
+                volume_info[0].folder.clone(),
+                2,
+                "b.txt".into(),
+                String::from("b")
+            )]
+        );
+    }
+
+    #[cfg(feature = "update")]
+    #[test]
+    fn serial() {
+        let (_dir, volume_info) = fixture("serial", &["x", "y"], &FILES);
+        let mut serial = Vec::new();
+        locate(
+            volume_info,
+            query(),
+            &LocateConfig::default(),
+            None,
+            describe(&mut serial),
+        )
+        .unwrap();
+        // Folder, three entries and statistics of each folder.
+        assert_eq!(serial.len(), 10);
+    }
+
+    #[cfg(feature = "update")]
+    #[test]
+    fn parallel() {
+        let (_dir, volume_info) = fixture("parallel", &["x", "y"], &FILES);
+        let config = LocateConfig::default();
+        let mut serial = Vec::new();
+        let mut parallel = Vec::new();
+        locate(
+            volume_info.clone(),
+            query(),
+            &config,
+            None,
+            describe(&mut serial),
+        )
+        .unwrap();
+        locate_parallel(
+            volume_info.clone(),
+            query(),
+            &config,
+            None,
+            describe(&mut parallel),
+        )
+        .unwrap();
+        assert_eq!(parallel.len(), serial.len());
+        // Events of each volume keep their order.
+        for vi in &volume_info {
+            let prefix = format!("{}", vi.folder.display());
+            let of_volume = |events: &[String]| -> Vec<String> {
+                events
+                    .iter()
+                    .filter(|event| event.starts_with(&prefix))
+                    .cloned()
+                    .collect()
+            };
+            assert_eq!(of_volume(&parallel), of_volume(&serial));
+        }
+    }
+
+    #[cfg(feature = "update")]
+    #[test]
+    fn parallel_ordered() {
+        let (_dir, volume_info) = fixture("ordered", &["x", "y"], &FILES);
+        let ordered = LocateConfig {
+            ordered: true,
+            ..LocateConfig::default()
+        };
+        let mut serial = Vec::new();
+        let mut parallel = Vec::new();
+        locate(
+            volume_info.clone(),
+            query(),
+            &ordered,
+            None,
+            describe(&mut serial),
+        )
+        .unwrap();
+        locate_parallel(
+            volume_info,
+            query(),
+            &ordered,
+            None,
+            describe(&mut parallel),
+        )
+        .unwrap();
+        assert_eq!(parallel, serial);
+    }
+
+    #[cfg(feature = "update")]
+    #[test]
+    fn pooled() {
+        let (_dir, volume_info) = fixture("pooled", &["x", "y"], &FILES);
+        let config = LocateConfig::default();
+        let pool = LocatePool::new(1, Duration::from_secs(1));
+        let mut serial = Vec::new();
+        locate(
+            volume_info.clone(),
+            query(),
+            &config,
+            None,
+            describe(&mut serial),
+        )
+        .unwrap();
+        // The pool searches the volumes one after the other with the same
+        // thread for both queries.
+        for _ in 0..2 {
+            let mut pooled = Vec::new();
+            let f = describe(&mut pooled);
+            locate_pooled(&pool, volume_info.clone(), query(), &config, None, f).unwrap();
+            assert_eq!(pooled, serial);
+        }
+        assert_eq!(pool.threads(), 1);
+    }
+
+    // Searches the folders x and y with offset and limit.
+    #[cfg(feature = "update")]
+    fn window(
+        name: &str,
+        offset: u64,
+        limit: Option<u64>,
+        ordered: bool,
+        parallel: bool,
+    ) -> Vec<String> {
+        let (_dir, volume_info) = fixture(name, &["x", "y"], &FILES);
+        let config = LocateConfig {
+            offset,
+            limit,
+            ordered,
+            ..LocateConfig::default()
+        };
+        let mut events = Vec::new();
+        if parallel {
+            locate_parallel(volume_info, query(), &config, None, names(&mut events)).unwrap();
+        } else {
+            locate(volume_info, query(), &config, None, names(&mut events)).unwrap();
+        }
+        events
+    }
+
+    #[cfg(feature = "update")]
+    #[test]
+    fn window_serial() {
+        // The second volume is searched, but not read completely.
+        assert_eq!(
+            window("window", 2, Some(2), false, false),
+            vec!["x", "a3", "y", "a1"]
+        );
+    }
+
+    #[cfg(feature = "update")]
+    #[test]
+    fn window_all() {
+        assert_eq!(window("window-all", 0, None, false, false).len(), 8);
+    }
+
+    #[cfg(feature = "update")]
+    #[test]
+    fn window_ordered() {
+        // Ordered pages are the same as pages of a serial query.
+        assert_eq!(
+            window("window-ordered", 2, Some(2), true, true),
+            vec!["x", "a3", "y", "a1"]
+        );
+    }
+
+    #[cfg(feature = "update")]
+    #[test]
+    fn window_parallel() {
+        let events = window("window-parallel", 1, Some(3), false, true);
+        let entries = events.iter().filter(|name| name.starts_with('a'));
+        assert_eq!(entries.count(), 3);
+    }
+
+    #[cfg(feature = "update")]
+    #[test]
+    fn window_empty() {
+        assert!(window("window-empty", 0, Some(0), false, false).is_empty());
+    }
+
+    // Searches files starting with b in the folders x and y. Each database
+    // file contains the folder and four files.
+    #[cfg(feature = "update")]
+    fn scan_limit(name: &str, max: u64, parallel: bool) -> Result<(), &'static str> {
+        let (_dir, volume_info) = fixture(name, &["x", "y"], &FILES);
+        let filter = vec![FilterToken::Text(String::from("b"))];
+        let config = LocateConfig {
+            max_entries_scanned: Some(max),
+            ..LocateConfig::default()
+        };
+        let res = if parallel {
+            locate_parallel(volume_info, filter, &config, None, |_| Ok(()))
+        } else {
+            locate(volume_info, filter, &config, None, |_| Ok(()))
+        };
+        res.map_err(|err| err.code())
+    }
+
+    #[cfg(feature = "update")]
+    #[test]
+    fn scan_limit_reached() {
+        assert_eq!(scan_limit("scan-limit", 10, false), Ok(()));
+    }
+
+    #[cfg(feature = "update")]
+    #[test]
+    fn scan_limit_exceeded() {
+        assert_eq!(
+            scan_limit("scan-limit-exceeded", 9, false),
+            Err("query-too-broad")
+        );
+    }
+
+    #[cfg(feature = "update")]
+    #[test]
+    fn scan_limit_parallel() {
+        assert_eq!(
+            scan_limit("scan-limit-parallel", 9, true),
+            Err("query-too-broad")
+        );
+    }
+
+    // Reads the paths of the database file, optionally relocated.
+    #[cfg(feature = "update")]
+    fn stored_paths(vi: &VolumeInfo, relocate: Option<&str>) -> Vec<PathBuf> {
+        let mut reader = FileIndexReader::new(&vi.database).unwrap();
+        if let Some(folder) = relocate {
+            assert!(reader.relocate(Path::new(folder)));
+        }
+        let mut paths = Vec::new();
+        while let Some((path, _)) = reader.next_entry().unwrap() {
+            paths.push(path.to_owned());
+        }
+        paths
+    }
+
+    #[cfg(feature = "update")]
+    #[test]
+    fn root_header() {
+        let (_dir, volume_info) = fixture("root-header", &["folder"], &["a"]);
+        let vi = &volume_info[0];
+        let content = fs::read(&vi.database).unwrap();
+        // The folder is stored once in the header.
+        let folder = vi.folder.as_os_str().as_bytes();
+        assert_eq!(
+            content[4],
+            Settings::WithFileSizes as u8 | ROOT_HEADER | ENTRY_TYPES | FOOTER
+        );
+        assert_eq!(&content[6..6 + folder.len()], folder);
+        // The folder entry is empty, followed by the file a with size 0, the
+        // end marker and the checksum.
+        let entries = &content[6 + folder.len()..content.len() - 4];
+        assert!(entries.starts_with(b"\x00\x00\x02"));
+        assert!(entries.ends_with(b"\x00\x02/a\x01\x01\xff\xff\xff\xff\xff\xff\xff\xff\xff\x01"));
+        assert!(verify_checksum(&vi.database).unwrap());
+    }
+
+    #[cfg(feature = "update")]
+    #[test]
+    fn relative_paths() {
+        let (_dir, volume_info) = fixture("relative", &["folder"], &["a"]);
+        let vi = &volume_info[0];
+        assert_eq!(
+            stored_paths(vi, None),
+            vec![vi.folder.clone(), vi.folder.join("a")]
+        );
+    }
+
+    #[cfg(feature = "update")]
+    #[test]
+    fn relocated_paths() {
+        let (_dir, volume_info) = fixture("relocated", &["folder"], &["a"]);
+        assert_eq!(
+            stored_paths(&volume_info[0], Some("/Volumes/moved")),
+            vec![
+                PathBuf::from("/Volumes/moved"),
+                PathBuf::from("/Volumes/moved/a")
+            ]
+        );
+    }
+
+    // The folders y and x share a database file. Returns the events of the
+    // update without the timing dependent ones.
+    #[cfg(feature = "update")]
+    fn shared(name: &str) -> (TempDir, Vec<VolumeInfo>, Vec<String>) {
+        let (dir, volume_info) = fixture(name, &["y", "x"], &["a"]);
+        let volume_info: Vec<VolumeInfo> = volume_info
+            .into_iter()
+            .map(|vi| VolumeInfo::new(vi.folder, dir.join("misc.fsdb")))
+            .collect();
+        let mut events = Vec::new();
+        crate::update(
+            volume_info.clone(),
+            Settings::WithFileSizes,
+            &crate::UpdateConfig::default(),
+            None,
+            |event| {
+                if !matches!(
+                    event,
+                    crate::UpdateEvent::Statistics(_, _) | crate::UpdateEvent::ScanSummary(_)
+                ) {
+                    events.push(event.to_string().replace(dir.to_str().unwrap(), ""));
+                }
+                Ok(())
+            },
+        );
+        (dir, volume_info, events)
+    }
+
+    #[cfg(feature = "update")]
+    #[test]
+    fn shared_database_update() {
+        let (_dir, _, events) = shared("shared");
+        // Both folders are written in walk order into the shared file.
+        assert_eq!(
+            events,
+            vec![
+                "Scanning: /x",
+                "Scanning: /y",
+                "Finished: /x",
+                "Finished: /y"
+            ]
+        );
+    }
+
+    #[cfg(feature = "update")]
+    #[test]
+    fn shared_database_context() {
+        let (_dir, volume_info, _) = shared("shared-context");
+        let mut matches = Vec::new();
+        locate_with_context(
+            volume_info,
+            query(),
+            &LocateConfig::default(),
+            None,
+            |event| {
+                if let LocateEvent::Match(m) = event {
+                    let name = |path: &Path| path.file_name().unwrap().to_owned();
+                    matches.push((name(&m.volume.folder), name(m.path)));
+                }
+                Ok(())
+            },
+        )
+        .unwrap();
+        // Each entry is reported with its folder.
+        assert_eq!(
+            matches,
+            vec![("x".into(), "a".into()), ("y".into(), "a".into())]
+        );
+    }
+
+    #[cfg(feature = "update")]
+    #[test]
+    fn shared_database_parallel() {
+        let (_dir, volume_info, _) = shared("shared-parallel");
+        let mut entries = 0;
+        locate_parallel(
+            volume_info,
+            query(),
+            &LocateConfig::default(),
+            None,
+            |event| {
+                if let LocateEvent::Entry(_, _) = event {
+                    entries += 1;
+                }
+                Ok(())
+            },
+        )
+        .unwrap();
+        // The shared database file is searched once.
+        assert_eq!(entries, 2);
+    }
+}
@@ -0,0 +1,305 @@
+use crate::enrich::ProviderRegistry;
+use crate::locate::Metadata;
+use std::cmp::Ordering;
+use std::fmt::Display;
+
+/// Type of the values contributed by an [EnrichmentProvider](crate::EnrichmentProvider).
+/// The type defines how values are stored and compared.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ValueType {
+    /// UTF-8 text compared byte by byte.
+    Text,
+    /// Unsigned integer stored with up to 8 bytes in big endian byte order.
+    Integer,
+    /// Date stored as ISO 8601 text starting with YYYY-MM-DD. Only the date
+    /// is compared.
+    Date,
+    /// Raw bytes. Values in queries are written in hexadecimal.
+    Bytes,
+}
+
+/// Comparison operator of a [MetaPredicate].
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Comparison {
+    /// `=`
+    Equal,
+    /// `!=`
+    NotEqual,
+    /// `<`
+    Less,
+    /// `<=`
+    LessOrEqual,
+    /// `>`
+    Greater,
+    /// `>=`
+    GreaterOrEqual,
+}
+
+impl Comparison {
+    fn accepts(self, ordering: Ordering) -> bool {
+        match self {
+            Comparison::Equal => ordering == Ordering::Equal,
+            Comparison::NotEqual => ordering != Ordering::Equal,
+            Comparison::Less => ordering == Ordering::Less,
+            Comparison::LessOrEqual => ordering != Ordering::Greater,
+            Comparison::Greater => ordering == Ordering::Greater,
+            Comparison::GreaterOrEqual => ordering != Ordering::Less,
+        }
+    }
+}
+
+// Operators ordered such that two character operators are found first.
+const OPERATORS: [(&str, Comparison); 6] = [
+    ("!=", Comparison::NotEqual),
+    ("<=", Comparison::LessOrEqual),
+    (">=", Comparison::GreaterOrEqual),
+    ("=", Comparison::Equal),
+    ("<", Comparison::Less),
+    (">", Comparison::Greater),
+];
+
+/// A MetaPredicate compares the metadata value of a provider with a value
+/// given in a query, e.g. `content-hash=af63dc4c8601ec8c`.
+///
+/// Entries without a value of the provider never match.
+#[derive(Clone, Debug, PartialEq)]
+pub struct MetaPredicate {
+    tag: u64,
+    value_type: ValueType,
+    comparison: Comparison,
+    value: Vec<u8>,
+}
+
+impl MetaPredicate {
+    /// Parses an expression `key<operator>value`. The key is the name of a
+    /// provider in the registry. The value is parsed according to the
+    /// [ValueType] of the provider.
+    pub fn parse(
+        expression: &str,
+        registry: &ProviderRegistry,
+    ) -> Result<MetaPredicate, MetaError> {
+        let (pos, operator, comparison) = expression
+            .find(['=', '!', '<', '>'])
+            .and_then(|pos| {
+                OPERATORS
+                    .iter()
+                    .find(|(operator, _)| expression[pos..].starts_with(operator))
+                    .map(|(operator, comparison)| (pos, operator, *comparison))
+            })
+            .ok_or_else(|| MetaError::MissingComparison(expression.to_string()))?;
+        let key = &expression[..pos];
+        let text = &expression[pos + operator.len()..];
+        let provider = registry
+            .get(key)
+            .ok_or_else(|| MetaError::UnknownKey(key.to_string()))?;
+        let value_type = provider.value_type();
+        let value = parse_value(text, value_type)
+            .ok_or_else(|| MetaError::InvalidValue(key.to_string(), text.to_string()))?;
+        Ok(MetaPredicate {
+            tag: provider.tag(),
+            value_type,
+            comparison,
+            value,
+        })
+    }
+
+    /// Returns true, if the metadata matches the predicate.
+    pub fn matches(&self, metadata: &Metadata) -> bool {
+        let Some(value) = metadata.attribute(self.tag) else {
+            return false;
+        };
+        let ordering = match self.value_type {
+            ValueType::Text | ValueType::Bytes => value.cmp(&self.value),
+            ValueType::Integer => {
+                if value.len() > 8 {
+                    return false;
+                }
+                integer(value).cmp(&integer(&self.value))
+            }
+            ValueType::Date => match parse_date(value) {
+                Some(date) => date.as_slice().cmp(&self.value),
+                None => return false,
+            },
+        };
+        self.comparison.accepts(ordering)
+    }
+}
+
+fn parse_value(text: &str, value_type: ValueType) -> Option<Vec<u8>> {
+    match value_type {
+        ValueType::Text => Some(text.as_bytes().to_vec()),
+        ValueType::Integer => text
+            .parse::<u64>()
+            .ok()
+            .map(|value| value.to_be_bytes().to_vec()),
+        ValueType::Date => parse_date(text.as_bytes()).map(|date| date.to_vec()),
+        ValueType::Bytes => parse_hex(text),
+    }
+}
+
+fn integer(bytes: &[u8]) -> u64 {
+    bytes
+        .iter()
+        .fold(0, |value, byte| (value << 8) | *byte as u64)
+}
+
+// Returns the YYYY-MM-DD prefix of an ISO 8601 date.
+fn parse_date(bytes: &[u8]) -> Option<[u8; 10]> {
+    let date: [u8; 10] = bytes.get(..10)?.try_into().ok()?;
+    let valid = date.iter().enumerate().all(|(index, byte)| match index {
+        4 | 7 => *byte == b'-',
+        _ => byte.is_ascii_digit(),
+    });
+    valid.then_some(date)
+}
+
+fn parse_hex(text: &str) -> Option<Vec<u8>> {
+    text.as_bytes()
+        .chunks(2)
+        .map(|pair| {
+            if pair.len() != 2 || !pair.iter().all(u8::is_ascii_hexdigit) {
+                return None;
+            }
+            let pair = std::str::from_utf8(pair).ok()?;
+            u8::from_str_radix(pair, 16).ok()
+        })
+        .collect()
+}
+
+/// MetaError reports an invalid metadata filter expression.
+#[derive(Debug, PartialEq)]
+#[non_exhaustive]
+pub enum MetaError {
+    /// The expression does not contain a comparison operator.
+    MissingComparison(String),
+    /// No provider with this name is registered.
+    UnknownKey(String),
+    /// The value does not match the type of the provider.
+    InvalidValue(String, String),
+}
+
+impl Display for MetaError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MetaError::MissingComparison(expression) => f.write_fmt(format_args!(
+                "Expected key=value, key!=value, key<value, key<=value, key>value or key>=value: {}",
+                expression
+            )),
+            MetaError::UnknownKey(key) => {
+                f.write_fmt(format_args!("Unknown metadata key: {}", key))
+            }
+            MetaError::InvalidValue(key, value) => f.write_fmt(format_args!(
+                "Invalid value for metadata key '{}': {}",
+                key, value
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{EnrichmentProvider, ProviderRegistry};
+    use std::fs;
+    use std::io::Result as IOResult;
+    use std::path::Path;
+    use std::sync::Arc;
+
+    struct Typed(&'static str, u64, ValueType);
+
+    impl EnrichmentProvider for Typed {
+        fn name(&self) -> &str {
+            self.0
+        }
+
+        fn tag(&self) -> u64 {
+            self.1
+        }
+
+        fn value_type(&self) -> ValueType {
+            self.2
+        }
+
+        fn enrich(
+            &self,
+            _path: &Path,
+            _metadata: Option<&fs::Metadata>,
+        ) -> IOResult<Option<Vec<u8>>> {
+            Ok(None)
+        }
+    }
+
+    fn registry() -> ProviderRegistry {
+        let mut registry = ProviderRegistry::builtin();
+        for provider in [
+            Typed("artist", 10, ValueType::Text),
+            Typed("year", 11, ValueType::Integer),
+            Typed("taken", 12, ValueType::Date),
+        ] {
+            registry.register(Arc::new(provider)).unwrap();
+        }
+        registry
+    }
+
+    fn metadata(attributes: &[(u64, &[u8])]) -> Metadata {
+        Metadata {
+            attributes: attributes
+                .iter()
+                .map(|(tag, value)| (*tag, value.to_vec()))
+                .collect(),
+            ..Default::default()
+        }
+    }
+
+    fn matches(expression: &str, metadata: &Metadata) -> bool {
+        MetaPredicate::parse(expression, &registry())
+            .unwrap()
+            .matches(metadata)
+    }
+
+    #[test]
+    fn typed_comparisons() {
+        let entry = metadata(&[
+            (1, &[0xaf, 0x63]),
+            (10, b"Beatles"),
+            (11, &[0x07, 0xb1]),
+            (12, b"2021-06-30T12:00:00"),
+        ]);
+        assert!(matches("artist=Beatles", &entry));
+        assert!(!matches("artist=beatles", &entry));
+        assert!(matches("artist!=Stones", &entry));
+        assert!(matches("year=1969", &entry));
+        assert!(matches("year>=1969", &entry));
+        assert!(!matches("year<1969", &entry));
+        assert!(matches("year>200", &entry));
+        assert!(matches("taken>=2020-01-01", &entry));
+        assert!(matches("taken<2021-07-01", &entry));
+        assert!(!matches("taken>2021-06-30", &entry));
+        assert!(matches("content-hash=af63", &entry));
+        assert!(matches("content-hash=AF63", &entry));
+        assert!(!matches("artist=Beatles", &metadata(&[])));
+    }
+
+    #[test]
+    fn invalid_expressions() {
+        let registry = registry();
+        let parse = |expression| MetaPredicate::parse(expression, &registry);
+        assert_eq!(
+            parse("artist"),
+            Err(MetaError::MissingComparison(String::from("artist")))
+        );
+        assert_eq!(
+            parse("genre=Rock"),
+            Err(MetaError::UnknownKey(String::from("genre")))
+        );
+        assert_eq!(
+            parse("year=MCMLXIX"),
+            Err(MetaError::InvalidValue(
+                String::from("year"),
+                String::from("MCMLXIX")
+            ))
+        );
+        assert!(parse("taken>2020-1-1").is_err());
+        assert!(parse("content-hash=abc").is_err());
+    }
+}
@@ -0,0 +1,145 @@
+use std::collections::VecDeque;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread;
+use std::time::Duration;
+
+pub(crate) type Job = Box<dyn FnOnce() + Send>;
+
+/// LocatePool keeps the threads searching database files in parallel
+/// between queries.
+///
+/// The pool is used by [locate_pooled](crate::locate_pooled()). Threads are
+/// started on demand up to the size of the pool and exit after being idle
+/// for the idle timeout. This avoids starting threads for each query of an
+/// interactive frontend. Idle threads exit when the pool is dropped.
+pub struct LocatePool {
+    shared: Arc<Shared>,
+}
+
+struct Shared {
+    size: usize,
+    idle_timeout: Duration,
+    state: Mutex<State>,
+    available: Condvar,
+}
+
+#[derive(Default)]
+struct State {
+    jobs: VecDeque<Job>,
+    threads: usize,
+    idle: usize,
+    closed: bool,
+}
+
+impl LocatePool {
+    /// Creates a pool with at most size threads. A size of zero uses the
+    /// available parallelism.
+    pub fn new(size: usize, idle_timeout: Duration) -> LocatePool {
+        let size = match size {
+            0 => thread::available_parallelism().map_or(1, usize::from),
+            size => size,
+        };
+        LocatePool {
+            shared: Arc::new(Shared {
+                size,
+                idle_timeout,
+                state: Mutex::new(State::default()),
+                available: Condvar::new(),
+            }),
+        }
+    }
+
+    /// Maximum number of threads.
+    pub fn size(&self) -> usize {
+        self.shared.size
+    }
+
+    /// Number of running threads, i.e. busy threads and idle threads which
+    /// haven't reached the idle timeout yet.
+    pub fn threads(&self) -> usize {
+        self.shared.state.lock().unwrap().threads
+    }
+
+    // Runs the job in a thread of the pool. A thread is started, if there
+    // are more queued jobs than idle threads.
+    pub(crate) fn execute(&self, job: Job) {
+        let mut state = self.shared.state.lock().unwrap();
+        state.jobs.push_back(job);
+        if state.jobs.len() > state.idle && state.threads < self.shared.size {
+            state.threads += 1;
+            let shared = self.shared.clone();
+            thread::spawn(move || work(&shared));
+        }
+        self.shared.available.notify_one();
+    }
+}
+
+impl Drop for LocatePool {
+    fn drop(&mut self) {
+        self.shared.state.lock().unwrap().closed = true;
+        self.shared.available.notify_all();
+    }
+}
+
+fn work(shared: &Shared) {
+    let mut state = shared.state.lock().unwrap();
+    loop {
+        if let Some(job) = state.jobs.pop_front() {
+            drop(state);
+            // A panicking job doesn't end the thread. Otherwise the thread
+            // would be counted forever and queued jobs would never run.
+            let _ = panic::catch_unwind(AssertUnwindSafe(job));
+            state = shared.state.lock().unwrap();
+            continue;
+        }
+        if state.closed {
+            break;
+        }
+        state.idle += 1;
+        let (next, timeout) = shared
+            .available
+            .wait_timeout(state, shared.idle_timeout)
+            .unwrap();
+        state = next;
+        state.idle -= 1;
+        if timeout.timed_out() && state.jobs.is_empty() {
+            break;
+        }
+    }
+    state.threads -= 1;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::mpsc::channel;
+
+    #[test]
+    fn reuse_and_idle_timeout() {
+        let pool = LocatePool::new(2, Duration::from_millis(200));
+        assert_eq!(pool.size(), 2);
+        let (tx, rx) = channel();
+        for _ in 0..4 {
+            let tx = tx.clone();
+            pool.execute(Box::new(move || tx.send(thread::current().id()).unwrap()));
+        }
+        let mut ids: Vec<_> = rx.iter().take(4).collect();
+        ids.sort_by_key(|id| format!("{:?}", id));
+        ids.dedup();
+        assert!(ids.len() <= 2);
+        assert!(pool.threads() <= 2);
+        thread::sleep(Duration::from_millis(600));
+        assert_eq!(pool.threads(), 0);
+    }
+
+    #[test]
+    fn panicking_job() {
+        let pool = LocatePool::new(1, Duration::from_secs(10));
+        let (tx, rx) = channel();
+        pool.execute(Box::new(|| panic!("job failed")));
+        pool.execute(Box::new(move || tx.send(()).unwrap()));
+        assert!(rx.recv_timeout(Duration::from_secs(5)).is_ok());
+        assert_eq!(pool.threads(), 1);
+    }
+}
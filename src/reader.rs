@@ -0,0 +1,86 @@
+use crate::locate::{FileIndexReader, LocateError, Metadata};
+use crate::volume::VolumeId;
+use crate::{LocateConfig, Settings};
+use std::path::Path;
+
+/// DbReader walks the entries of a database file without evaluating a
+/// query, e.g. for statistics or exports.
+///
+/// Entries are read in the stored order, i.e. sorted like a directory tree.
+/// Paths are reported as stored by the last update, even if the volume of
+/// the folder is mounted at a different path now.
+pub struct DbReader {
+    reader: FileIndexReader,
+}
+
+impl DbReader {
+    /// Opens a database file and reads its header.
+    pub fn open(database: &Path) -> Result<DbReader, LocateError> {
+        let reader = FileIndexReader::new(database)?;
+        Ok(DbReader { reader })
+    }
+
+    /// Opens a database file with the buffer size and read-ahead of the
+    /// locate configuration.
+    pub fn open_with_config(
+        database: &Path,
+        config: &LocateConfig,
+    ) -> Result<DbReader, LocateError> {
+        let reader = FileIndexReader::open(database, config)?;
+        Ok(DbReader { reader })
+    }
+
+    /// Returns the information stored in the database file.
+    pub fn settings(&self) -> Settings {
+        self.reader.settings.clone()
+    }
+
+    /// Returns whether the modification times of the entries are stored.
+    pub fn modification_times(&self) -> bool {
+        self.reader.modification_times
+    }
+
+    /// Returns the volume of the folder, if the folder is on a removable
+    /// volume identified by UUID or label.
+    pub fn volume_id(&self) -> Option<&VolumeId> {
+        self.reader.volume.as_ref().map(|volume| &volume.id)
+    }
+
+    /// Returns the next entry or None after the last entry. The path is
+    /// borrowed from the reader and is valid until the next call.
+    pub fn next_entry(&mut self) -> Result<Option<(&Path, Metadata)>, LocateError> {
+        self.reader.next_entry()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::temp_dir::TempDir;
+    use std::fs;
+    use std::path::PathBuf;
+
+    #[test]
+    fn entries() {
+        let dir = TempDir::new("reader");
+        let database = dir.join("reader.fsdb");
+        // Entries /a with size 1 and /a/b without size, delta compressed.
+        fs::write(&database, b"fsix\x01\x00\x02/a\x02\x00\x02/b\x00").unwrap();
+        let mut reader = DbReader::open(&database).unwrap();
+        let settings = reader.settings();
+        let volume_id = reader.volume_id().cloned();
+        let mut entries = Vec::new();
+        while let Some((path, metadata)) = reader.next_entry().unwrap() {
+            entries.push((path.to_owned(), metadata.size));
+        }
+        assert_eq!(settings, Settings::WithFileSizes);
+        assert_eq!(volume_id, None);
+        assert_eq!(
+            entries,
+            vec![
+                (PathBuf::from("/a"), Some(1)),
+                (PathBuf::from("/a/b"), None)
+            ]
+        );
+    }
+}
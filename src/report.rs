@@ -0,0 +1,443 @@
+use crate::locate::{is_shared, FileIndexReader, LocateError, Metadata};
+use crate::VolumeInfo;
+use std::collections::HashMap;
+use std::ffi::OsStr;
+use std::fmt::Display;
+use std::io::{ErrorKind, Result as IOResult};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+/// ReportEvent indicates events to a callback function.
+#[non_exhaustive]
+pub enum ReportEvent<'a> {
+    /// Starts analyzing a database file.
+    Searching(&'a Path),
+    /// A problem found in a database file.
+    Finding(&'a Finding),
+}
+
+/// A problem found by a report.
+#[derive(Debug, PartialEq)]
+pub struct Finding {
+    /// Path name of the database entry.
+    pub path: PathBuf,
+    /// What the problem is.
+    pub description: String,
+}
+
+impl Display for Finding {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_fmt(format_args!(
+            "{}: {}",
+            self.path.to_string_lossy(),
+            self.description
+        ))
+    }
+}
+
+/// An Aggregator analyzes the entries of database files for a report.
+///
+/// Entries are passed in database order, i.e. a directory is followed by its
+/// content, and the entries of a directory are sorted.
+pub trait Aggregator {
+    /// Analyzes the next entry of a database file.
+    fn entry(&mut self, path: &Path, metadata: &Metadata, findings: &mut Vec<Finding>);
+
+    /// Called after the last entry of a database file.
+    fn end_volume(&mut self, _findings: &mut Vec<Finding>) {}
+}
+
+/// The report function passes all entries of the database files to an
+/// aggregator and reports its findings with a callback function.
+///
+/// The abort parameter may be used by a frontend to abort a report.
+pub fn report<F: FnMut(ReportEvent) -> IOResult<()>>(
+    volume_info: Vec<VolumeInfo>,
+    aggregator: &mut dyn Aggregator,
+    abort: Option<Arc<AtomicBool>>,
+    mut f: F,
+) -> Result<(), LocateError> {
+    let mut findings = Vec::new();
+    for (position, vi) in volume_info.iter().enumerate() {
+        if is_shared(&volume_info[..position], vi) {
+            continue;
+        }
+        f(ReportEvent::Searching(&vi.folder)).map_err(map_write_error)?;
+        let mut reader = FileIndexReader::new(&vi.database)?;
+        while let Some((path, metadata)) = reader.next_entry()? {
+            if abort
+                .as_ref()
+                .map(|v| v.load(Ordering::Relaxed))
+                .unwrap_or(false)
+            {
+                return Err(LocateError::Aborted);
+            }
+            aggregator.entry(path, &metadata, &mut findings);
+            report_findings(&mut f, &mut findings)?;
+        }
+        aggregator.end_volume(&mut findings);
+        report_findings(&mut f, &mut findings)?;
+    }
+    Ok(())
+}
+
+fn report_findings<F: FnMut(ReportEvent) -> IOResult<()>>(
+    f: &mut F,
+    findings: &mut Vec<Finding>,
+) -> Result<(), LocateError> {
+    for finding in findings.drain(..) {
+        f(ReportEvent::Finding(&finding)).map_err(map_write_error)?;
+    }
+    Ok(())
+}
+
+pub(crate) fn map_write_error(err: std::io::Error) -> LocateError {
+    if err.kind() == ErrorKind::BrokenPipe {
+        LocateError::BrokenPipe
+    } else {
+        LocateError::WritingResultFailed(err)
+    }
+}
+
+/// CaseCollisions finds entries of a directory whose names differ only by
+/// case. Such entries can't be stored together on a case-insensitive file
+/// system, e.g. when syncing between Linux and macOS volumes.
+#[derive(Default)]
+pub struct CaseCollisions {
+    // The directories from the root to the current directory with the case
+    // folded names of their entries. Since a directory is followed by its
+    // content, directories left are never entered again.
+    directories: Vec<(PathBuf, HashMap<String, PathBuf>)>,
+}
+
+impl CaseCollisions {
+    /// Creates the aggregator.
+    pub fn new() -> CaseCollisions {
+        CaseCollisions::default()
+    }
+}
+
+impl Aggregator for CaseCollisions {
+    fn entry(&mut self, path: &Path, _metadata: &Metadata, findings: &mut Vec<Finding>) {
+        let (Some(parent), Some(name)) = (path.parent(), path.file_name()) else {
+            return;
+        };
+        while let Some((directory, _)) = self.directories.last() {
+            if parent.starts_with(directory) {
+                break;
+            }
+            self.directories.pop();
+        }
+        if self
+            .directories
+            .last()
+            .map(|(directory, _)| directory.as_path())
+            != Some(parent)
+        {
+            self.directories.push((parent.to_owned(), HashMap::new()));
+        }
+        if let Some((_, names)) = self.directories.last_mut() {
+            match names.get(&fold_case(name)) {
+                Some(other) => findings.push(Finding {
+                    path: path.to_owned(),
+                    description: format!("collides with '{}'", other.to_string_lossy()),
+                }),
+                None => {
+                    names.insert(fold_case(name), path.to_owned());
+                }
+            }
+        }
+    }
+
+    fn end_volume(&mut self, _findings: &mut Vec<Finding>) {
+        self.directories.clear();
+    }
+}
+
+fn fold_case(name: &OsStr) -> String {
+    name.to_string_lossy().to_lowercase()
+}
+
+/// Characters not allowed in file names on Windows.
+const ILLEGAL_CHARACTERS: &[char] = &[':', '*', '?', '"', '<', '>', '|', '\\'];
+
+/// Maximum length of a file name in bytes on most file systems.
+const MAX_NAME_LENGTH: usize = 255;
+
+/// BadNames finds entries with names which are not portable to other
+/// platforms: characters not allowed on Windows, trailing spaces or dots,
+/// names exceeding 255 bytes and names which are not valid UTF-8.
+#[derive(Default)]
+pub struct BadNames;
+
+impl BadNames {
+    /// Creates the aggregator.
+    pub fn new() -> BadNames {
+        BadNames
+    }
+}
+
+impl Aggregator for BadNames {
+    fn entry(&mut self, path: &Path, _metadata: &Metadata, findings: &mut Vec<Finding>) {
+        let Some(name) = path.file_name() else {
+            return;
+        };
+        let mut problems = Vec::new();
+        let text = name.to_string_lossy();
+        if name.to_str().is_none() {
+            problems.push(String::from("not UTF-8"));
+        }
+        let mut illegal: Vec<char> = text
+            .chars()
+            .filter(|ch| ILLEGAL_CHARACTERS.contains(ch) || ch.is_control())
+            .collect();
+        illegal.dedup();
+        if !illegal.is_empty() {
+            let illegal: String = illegal.iter().flat_map(|ch| ch.escape_debug()).collect();
+            problems.push(format!("illegal characters '{}'", illegal));
+        }
+        if text.ends_with(' ') {
+            problems.push(String::from("trailing space"));
+        }
+        if text.ends_with('.') {
+            problems.push(String::from("trailing dot"));
+        }
+        if name.len() > MAX_NAME_LENGTH {
+            problems.push(format!("name longer than {} bytes", MAX_NAME_LENGTH));
+        }
+        if !problems.is_empty() {
+            findings.push(Finding {
+                path: path.to_owned(),
+                description: problems.join(", "),
+            });
+        }
+    }
+}
+
+// Detects entries without content. A directory is followed by its content,
+// so an entry is a leaf, if the next entry is not below it.
+#[derive(Default)]
+struct Leaves {
+    previous: Option<(PathBuf, Option<u64>)>,
+}
+
+impl Leaves {
+    // Returns the previous entry, if it is a leaf.
+    fn next(&mut self, path: &Path, size: Option<u64>) -> Option<(PathBuf, Option<u64>)> {
+        let previous = self.previous.replace((path.to_owned(), size));
+        previous.filter(|(previous, _)| !path.starts_with(previous))
+    }
+
+    // Returns the last entry, which is always a leaf.
+    fn finish(&mut self) -> Option<(PathBuf, Option<u64>)> {
+        self.previous.take()
+    }
+}
+
+/// EmptyDirectories finds directories without any entries.
+///
+/// The database files don't store whether an entry is a directory. Entries
+/// without content are checked in the file system. Hence, empty directories
+/// are only found on mounted volumes.
+#[derive(Default)]
+pub struct EmptyDirectories {
+    leaves: Leaves,
+}
+
+impl EmptyDirectories {
+    /// Creates the aggregator.
+    pub fn new() -> EmptyDirectories {
+        EmptyDirectories::default()
+    }
+
+    fn check(leaf: Option<(PathBuf, Option<u64>)>, findings: &mut Vec<Finding>) {
+        if let Some((path, _)) = leaf.filter(|(path, _)| path.is_dir()) {
+            findings.push(Finding {
+                path,
+                description: String::from("empty directory"),
+            });
+        }
+    }
+}
+
+impl Aggregator for EmptyDirectories {
+    fn entry(&mut self, path: &Path, metadata: &Metadata, findings: &mut Vec<Finding>) {
+        EmptyDirectories::check(self.leaves.next(path, metadata.size), findings);
+    }
+
+    fn end_volume(&mut self, findings: &mut Vec<Finding>) {
+        EmptyDirectories::check(self.leaves.finish(), findings);
+    }
+}
+
+/// ZeroByteFiles finds files with a size of zero bytes.
+///
+/// Only database files with file sizes contain zero-byte files. Entries with
+/// content are directories and never reported. Entries without content are
+/// skipped if they are a directory on a mounted volume, since some file
+/// systems report a size of zero for empty directories.
+#[derive(Default)]
+pub struct ZeroByteFiles {
+    leaves: Leaves,
+}
+
+impl ZeroByteFiles {
+    /// Creates the aggregator.
+    pub fn new() -> ZeroByteFiles {
+        ZeroByteFiles::default()
+    }
+
+    fn check(leaf: Option<(PathBuf, Option<u64>)>, findings: &mut Vec<Finding>) {
+        if let Some((path, _)) = leaf.filter(|(path, size)| *size == Some(0) && !path.is_dir()) {
+            findings.push(Finding {
+                path,
+                description: String::from("zero bytes"),
+            });
+        }
+    }
+}
+
+impl Aggregator for ZeroByteFiles {
+    fn entry(&mut self, path: &Path, metadata: &Metadata, findings: &mut Vec<Finding>) {
+        ZeroByteFiles::check(self.leaves.next(path, metadata.size), findings);
+    }
+
+    fn end_volume(&mut self, findings: &mut Vec<Finding>) {
+        ZeroByteFiles::check(self.leaves.finish(), findings);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::temp_dir::TempDir;
+    use std::os::unix::prelude::OsStrExt;
+
+    fn run_sized(
+        aggregator: &mut dyn Aggregator,
+        entries: &[(&Path, Option<u64>)],
+    ) -> Vec<Finding> {
+        let mut findings = Vec::new();
+        for (path, size) in entries {
+            aggregator.entry(
+                path,
+                &Metadata {
+                    size: *size,
+                    ..Default::default()
+                },
+                &mut findings,
+            );
+        }
+        aggregator.end_volume(&mut findings);
+        findings
+    }
+
+    fn run(aggregator: &mut dyn Aggregator, paths: &[&str]) -> Vec<Finding> {
+        let mut findings = Vec::new();
+        for path in paths {
+            aggregator.entry(Path::new(path), &Metadata::default(), &mut findings);
+        }
+        aggregator.end_volume(&mut findings);
+        findings
+    }
+
+    #[test]
+    fn case_collisions() {
+        let findings = run(
+            &mut CaseCollisions::new(),
+            &[
+                "/Music",
+                "/Music/ABBA",
+                "/Music/ABBA/Waterloo.flac",
+                "/Music/Abba",
+                "/Music/Abba/waterloo.flac",
+                "/Music/Beatles",
+                "/Music/Beatles/Help.flac",
+                "/Music/Beatles/help.FLAC",
+                "/Music/Stones/Help.flac",
+            ],
+        );
+        assert_eq!(
+            findings,
+            vec![
+                Finding {
+                    path: PathBuf::from("/Music/Abba"),
+                    description: String::from("collides with '/Music/ABBA'"),
+                },
+                Finding {
+                    path: PathBuf::from("/Music/Beatles/help.FLAC"),
+                    description: String::from("collides with '/Music/Beatles/Help.flac'"),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn bad_names() {
+        let long = format!("/Music/{}.flac", "x".repeat(MAX_NAME_LENGTH));
+        let findings = run(
+            &mut BadNames::new(),
+            &[
+                "/Music",
+                "/Music/AC:DC",
+                "/Music/AC:DC/What?.flac",
+                "/Music/Live ",
+                "/Music/Vol.",
+                "/Music/Tab\tTab",
+                "/Music/a\\b<c>.flac",
+                &long,
+            ],
+        );
+        let descriptions: Vec<&str> = findings
+            .iter()
+            .map(|finding| finding.description.as_str())
+            .collect();
+        assert_eq!(
+            descriptions,
+            vec![
+                "illegal characters ':'",
+                "illegal characters '?'",
+                "trailing space",
+                "trailing dot",
+                "illegal characters '\\t'",
+                "illegal characters '\\\\<>'",
+                "name longer than 255 bytes",
+            ]
+        );
+        let mut findings = Vec::new();
+        let path = Path::new(OsStr::from_bytes(b"/Music/\xff.flac"));
+        BadNames::new().entry(path, &Metadata::default(), &mut findings);
+        assert_eq!(findings[0].description, "not UTF-8");
+    }
+
+    #[test]
+    fn empty_directories_and_zero_byte_files() {
+        let root = TempDir::new("report");
+        let empty = root.join("empty");
+        let full = root.join("full");
+        let file = full.join("file");
+        let missing = root.join("missing");
+        std::fs::create_dir_all(&empty).unwrap();
+        std::fs::create_dir_all(&full).unwrap();
+        std::fs::write(&file, b"").unwrap();
+        let entries = [
+            (&*root, Some(4096)),
+            (empty.as_path(), Some(0)),
+            (full.as_path(), Some(0)),
+            (file.as_path(), Some(0)),
+            (missing.as_path(), Some(0)),
+        ];
+        let paths = |findings: Vec<Finding>| -> Vec<PathBuf> {
+            findings.into_iter().map(|finding| finding.path).collect()
+        };
+        assert_eq!(
+            paths(run_sized(&mut EmptyDirectories::new(), &entries)),
+            vec![empty.clone()]
+        );
+        assert_eq!(
+            paths(run_sized(&mut ZeroByteFiles::new(), &entries)),
+            vec![file.clone(), missing.clone()]
+        );
+    }
+}
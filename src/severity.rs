@@ -0,0 +1,13 @@
+/// Severity of an event or error reported to a frontend.
+///
+/// Frontends may use the severity to decide where and how a message is
+/// printed and if it affects the exit code.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    /// Progress information.
+    Info,
+    /// A problem which does not prevent the operation from completing.
+    Warning,
+    /// The operation failed.
+    Error,
+}
@@ -0,0 +1,40 @@
+// Temporary directory of a unit test. Also included by the tests of the CLI.
+
+use std::fs;
+use std::ops::Deref;
+use std::path::{Path, PathBuf};
+
+// The directory is removed with its content when the guard is dropped, i.e.
+// also when an assertion of the test fails.
+pub(crate) struct TempDir(PathBuf);
+
+impl TempDir {
+    // The name is unique per test. The process id separates concurrent runs.
+    pub(crate) fn new(name: &str) -> TempDir {
+        let path = std::env::temp_dir().join(format!("fsidx-test-{}-{}", std::process::id(), name));
+        // Left by a killed run with the same process id.
+        let _ = fs::remove_dir_all(&path);
+        fs::create_dir_all(&path).unwrap();
+        TempDir(path)
+    }
+}
+
+impl Deref for TempDir {
+    type Target = Path;
+
+    fn deref(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl AsRef<Path> for TempDir {
+    fn as_ref(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}
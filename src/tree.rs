@@ -0,0 +1,112 @@
+use crate::locate::{FileIndexReader, LocateError};
+use crate::report::map_write_error;
+use std::io::Result as IOResult;
+use std::path::{Path, PathBuf};
+
+/// TreeEvent reports the directory tree stored in a database file to a
+/// callback function.
+#[derive(Debug, PartialEq)]
+#[non_exhaustive]
+pub enum TreeEvent<'a> {
+    /// A directory with its size. The entries of the directory follow.
+    EnterDirectory(&'a Path, Option<u64>),
+    /// All entries of the directory are reported. Provides the total size of
+    /// the directory and all its entries.
+    LeaveDirectory(&'a Path, u64),
+    /// An entry without content, i.e. a file or an empty directory.
+    File(&'a Path, Option<u64>),
+}
+
+/// The tree function reports the entries of a database file as a directory
+/// tree with the total size of each directory.
+///
+/// The database file does not store which entries are directories. An entry
+/// followed by entries below it is reported as directory. Other entries are
+/// reported as files.
+pub fn tree<F: FnMut(TreeEvent) -> IOResult<()>>(
+    database: &Path,
+    mut f: F,
+) -> Result<(), LocateError> {
+    let mut reader = FileIndexReader::new(database)?;
+    // Open directories with their total size so far.
+    let mut directories: Vec<(PathBuf, u64)> = Vec::new();
+    // An entry is reported when the next entry tells whether it has content.
+    let mut pending: Option<(PathBuf, Option<u64>)> = None;
+    loop {
+        let next = reader.next_entry()?;
+        let next_path = next.as_ref().map(|(path, _)| *path);
+        if let Some((path, size)) = pending.take() {
+            if next_path.is_some_and(|next| next != path && next.starts_with(&path)) {
+                f(TreeEvent::EnterDirectory(&path, size)).map_err(map_write_error)?;
+                directories.push((path, size.unwrap_or(0)));
+            } else {
+                f(TreeEvent::File(&path, size)).map_err(map_write_error)?;
+                if let Some((_, total)) = directories.last_mut() {
+                    *total += size.unwrap_or(0);
+                }
+            }
+        }
+        while let Some((directory, _)) = directories.last() {
+            if next_path.is_some_and(|next| next.starts_with(directory)) {
+                break;
+            }
+            if let Some((directory, size)) = directories.pop() {
+                f(TreeEvent::LeaveDirectory(&directory, size)).map_err(map_write_error)?;
+                if let Some((_, total)) = directories.last_mut() {
+                    *total += size;
+                }
+            }
+        }
+        match next {
+            Some((path, metadata)) => pending = Some((path.to_owned(), metadata.size)),
+            None => return Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::temp_dir::TempDir;
+    use std::fs;
+
+    #[test]
+    fn directory_sizes() {
+        let dir = TempDir::new("tree");
+        let database = dir.join("tree.fsdb");
+        let mut content = b"fsix\x01".to_vec();
+        let mut previous = 0;
+        for (entry, size) in [
+            ("/a", 1),
+            ("/a/b", 1),
+            ("/a/b/c", 10),
+            ("/a/d", 20),
+            ("/e", 0),
+        ] {
+            // Entries are written without delta compression.
+            content.extend_from_slice(&[previous, entry.len() as u8]);
+            previous = entry.len() as u8;
+            content.extend_from_slice(entry.as_bytes());
+            content.push(size + 1);
+        }
+        fs::write(&database, content).unwrap();
+        let mut events = Vec::new();
+        tree(&database, |event| {
+            events.push(format!("{:?}", event));
+            Ok(())
+        })
+        .unwrap();
+        assert_eq!(
+            events,
+            vec![
+                "EnterDirectory(\"/a\", Some(1))",
+                "EnterDirectory(\"/a/b\", Some(1))",
+                "File(\"/a/b/c\", Some(10))",
+                "LeaveDirectory(\"/a/b\", 11)",
+                "File(\"/a/d\", Some(20))",
+                "LeaveDirectory(\"/a\", 32)",
+                "File(\"/e\", Some(0))",
+            ]
+        );
+    }
+}
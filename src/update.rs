@@ -1,20 +1,40 @@
-use super::{Settings, VolumeInfo};
-use core::cmp::Ordering;
+use super::{Settings, Severity, UpdateConfig, VolumeInfo};
+use crate::checksum::ChecksumWriter;
+use crate::compression::PayloadWriter;
+use crate::diff::{compare, compare_paths, diff_databases, DatabaseDiff};
+use crate::enrich::{applies, ProviderRegistry};
+use crate::locate::{
+    temporary_file, EntryType, FileIndexReader, Metadata, COMPRESSED, END_OF_ENTRIES, ENTRY_TYPES,
+    FOOTER, MODIFICATION_TIMES, ROOT_HEADER, VOLUME_HEADER,
+};
+use crate::volume::{VolumeHeader, VolumeId};
+use crate::writer::write_entry;
 use fastvlq::WriteVu64Ext;
+use globset::{Glob, GlobSet, GlobSetBuilder};
+use nix::errno::Errno;
+use nix::fcntl::{Flock, FlockArg};
 use nix::sys::stat::stat;
 use std::collections::BTreeMap;
-use std::ffi::OsStr;
-use std::fs::{self, File};
-use std::io::{Error, Result as IOResult, Write};
+use std::fmt::Display;
+use std::fs::{self, File, OpenOptions};
+use std::io::{Error, ErrorKind, Result as IOResult, Write};
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::fs::MetadataExt;
 use std::path::{Path, PathBuf};
+use std::sync::atomic::{self, AtomicBool};
 use std::sync::mpsc::{channel, Sender};
+use std::sync::Arc;
 use std::thread::{self};
-use walkdir::WalkDir;
+use std::time::{Duration, Instant};
+use walkdir::{DirEntry, WalkDir};
 
-type GroupedVolumes = Vec<Vec<VolumeInfo>>;
+// Folders scanned by the same thread. Each inner list contains the folders
+// written into the same database file.
+type GroupedVolumes = Vec<Vec<Vec<ScanTarget>>>;
 
 /// UpdateEvent indicate events to a callback function.
 #[derive(Debug)]
+#[non_exhaustive]
 pub enum UpdateEvent {
     /// Starts scanning a configured folder.
     Scanning(PathBuf),
@@ -30,8 +50,276 @@ pub enum UpdateEvent {
     RemovingTemporaryFileFailed(PathBuf, Error),
     /// Creating the temporary database file failed.
     CreatingTemporaryFileFailed(PathBuf, Error),
+    /// Another update is writing the database file. The database file is
+    /// not updated.
+    DatabaseLocked(PathBuf),
     /// Scanning the directory tree failed.
     ScanError(PathBuf, walkdir::Error),
+    /// Reading a directory or file metadata failed with a transient error
+    /// and is retried. The attempt number starts at 1.
+    Retrying(PathBuf, u32, Error),
+    /// Scanning a configured folder is in progress. Sent about once per second.
+    Progress(PathBuf, ScanStatistics),
+    /// Scanning a configured folder is complete. Sent before the database file
+    /// is replaced.
+    Statistics(PathBuf, ScanStatistics),
+    /// The database file of a configured folder was replaced. Compares the
+    /// new database file with the previous one.
+    Changes(PathBuf, DatabaseDiff),
+    /// The database file of a configured folder was replaced. Sent for each
+    /// folder after [UpdateEvent::Changes].
+    ScanSummary(ScanSummary),
+    /// An enrichment provider failed for an entry. The entry is stored
+    /// without the metadata of the provider.
+    EnrichmentFailed(PathBuf, String, Error),
+    /// The volume of a configured folder is not mounted. The folder is
+    /// skipped and its database file is kept.
+    VolumeNotMounted(PathBuf, VolumeId),
+    /// The volume is mounted, but the configured folder is not on the volume
+    /// and its location cannot be derived from the previous database file.
+    VolumeMismatch(PathBuf, VolumeId),
+    /// An exclude pattern of a configured folder is invalid. The folder is
+    /// skipped and its database file is kept.
+    InvalidExcludePattern(PathBuf, String, globset::Error),
+    /// A followed symbolic link points to one of its parent directories.
+    /// The link is skipped. Contains the configured
+    /// folder, the link and the parent directory.
+    SymlinkLoop(PathBuf, PathBuf, PathBuf),
+    /// The path of an entry is longer than [UpdateConfig::max_path_length].
+    /// Only the topmost entry of a directory tree exceeding the limit is
+    /// reported. Contains the configured folder, the path and the limit.
+    PathTooLong(PathBuf, PathBuf, usize),
+    /// An entry is nested deeper than [UpdateConfig::max_depth] below the
+    /// configured folder. Only the topmost entry of a directory tree
+    /// exceeding the limit is reported. Contains the configured folder, the
+    /// path and the limit.
+    PathTooDeep(PathBuf, PathBuf, usize),
+    /// The update was aborted. The database file of the configured folder
+    /// is kept and the temporary file is removed.
+    Aborted(PathBuf),
+}
+
+/// Number of scanned directories and entries and the elapsed time.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ScanStatistics {
+    /// Number of scanned directories, including the configured folder.
+    pub directories: u64,
+    /// Number of scanned entries, i.e. files, directories and symbolic links.
+    pub entries: u64,
+    /// Time since scanning the folder started.
+    pub elapsed: Duration,
+}
+
+impl ScanStatistics {
+    /// Returns the number of scanned directories per second.
+    pub fn directories_per_second(&self) -> f64 {
+        rate(self.directories, self.elapsed)
+    }
+
+    /// Returns the number of scanned entries per second.
+    pub fn entries_per_second(&self) -> f64 {
+        rate(self.entries, self.elapsed)
+    }
+}
+
+/// Size of the index of a configured folder and the time scanning it took.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ScanSummary {
+    /// The configured folder.
+    pub folder: PathBuf,
+    /// The database file of the folder.
+    pub database: PathBuf,
+    /// Number of stored entries.
+    pub entries: u64,
+    /// Time scanning the folder took.
+    pub duration: Duration,
+    /// Size of the database file. Folders sharing a database file report
+    /// the size of the shared file.
+    pub db_bytes: u64,
+}
+
+fn rate(count: u64, elapsed: Duration) -> f64 {
+    let seconds = elapsed.as_secs_f64();
+    if seconds > 0.0 {
+        count as f64 / seconds
+    } else {
+        0.0
+    }
+}
+
+impl Display for ScanStatistics {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_fmt(format_args!(
+            "{} entries in {} directories in {:.1} s ({:.0} entries/s, {:.0} directories/s)",
+            self.entries,
+            self.directories,
+            self.elapsed.as_secs_f64(),
+            self.entries_per_second(),
+            self.directories_per_second()
+        ))
+    }
+}
+
+impl UpdateEvent {
+    /// Returns the severity of the event.
+    pub fn severity(&self) -> Severity {
+        match self {
+            UpdateEvent::Scanning(_)
+            | UpdateEvent::ScanningFinished(_)
+            | UpdateEvent::Progress(_, _)
+            | UpdateEvent::Statistics(_, _)
+            | UpdateEvent::Changes(_, _)
+            | UpdateEvent::ScanSummary(_)
+            | UpdateEvent::VolumeNotMounted(_, _) => Severity::Info,
+            UpdateEvent::RemovingTemporaryFileFailed(_, _)
+            | UpdateEvent::ScanError(_, _)
+            | UpdateEvent::Retrying(_, _, _)
+            | UpdateEvent::EnrichmentFailed(_, _, _)
+            | UpdateEvent::SymlinkLoop(_, _, _)
+            | UpdateEvent::PathTooLong(_, _, _)
+            | UpdateEvent::PathTooDeep(_, _, _)
+            | UpdateEvent::Aborted(_) => Severity::Warning,
+            UpdateEvent::ScanningFailed(_)
+            | UpdateEvent::DbWriteError(_, _)
+            | UpdateEvent::ReplacingDatabaseFailed(_, _, _)
+            | UpdateEvent::CreatingTemporaryFileFailed(_, _)
+            | UpdateEvent::DatabaseLocked(_)
+            | UpdateEvent::VolumeMismatch(_, _)
+            | UpdateEvent::InvalidExcludePattern(_, _, _) => Severity::Error,
+        }
+    }
+}
+
+impl Display for UpdateEvent {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            UpdateEvent::Scanning(path) => {
+                f.write_fmt(format_args!("Scanning: {}", path.to_string_lossy()))
+            }
+            UpdateEvent::ScanningFinished(path) => {
+                f.write_fmt(format_args!("Finished: {}", path.to_string_lossy()))
+            }
+            UpdateEvent::ScanningFailed(path) => {
+                f.write_fmt(format_args!("Scanning failed: {}", path.to_string_lossy()))
+            }
+            UpdateEvent::Aborted(path) => {
+                f.write_fmt(format_args!("Aborted: {}", path.to_string_lossy()))
+            }
+            UpdateEvent::DbWriteError(path, err) => f.write_fmt(format_args!(
+                "Writing database '{}' failed: {}",
+                path.to_string_lossy(),
+                err
+            )),
+            UpdateEvent::ReplacingDatabaseFailed(tmp_path, path, err) => f.write_fmt(format_args!(
+                "Replacing database '{}' with '{}' failed: {}",
+                path.to_string_lossy(),
+                tmp_path.to_string_lossy(),
+                err
+            )),
+            UpdateEvent::RemovingTemporaryFileFailed(path, err) => f.write_fmt(format_args!(
+                "Removing temporary file '{}' failed: {}",
+                path.to_string_lossy(),
+                err
+            )),
+            UpdateEvent::CreatingTemporaryFileFailed(path, err) => f.write_fmt(format_args!(
+                "Creating temporary file '{}' failed: {}",
+                path.to_string_lossy(),
+                err
+            )),
+            UpdateEvent::DatabaseLocked(path) => f.write_fmt(format_args!(
+                "Database '{}' is written by another update",
+                path.to_string_lossy()
+            )),
+            UpdateEvent::ScanError(path, err) => {
+                f.write_fmt(format_args!(
+                    "Scanning directory '{}' failed at depth {}",
+                    path.to_string_lossy(),
+                    err.depth()
+                ))?;
+                if let Some(io_error) = err.io_error() {
+                    f.write_fmt(format_args!(": {}", io_error))?;
+                }
+                if let Some(associated_path) = err.path() {
+                    f.write_fmt(format_args!(
+                        "\n       Associated path: '{}'",
+                        associated_path.to_string_lossy()
+                    ))?;
+                }
+                if let Some(cycle_path) = err.loop_ancestor() {
+                    f.write_fmt(format_args!(
+                        "\n       Cycle detected at path: '{}'",
+                        cycle_path.to_string_lossy()
+                    ))?;
+                }
+                Ok(())
+            }
+            UpdateEvent::Retrying(path, attempt, err) => f.write_fmt(format_args!(
+                "Retrying '{}' (attempt {}): {}",
+                path.to_string_lossy(),
+                attempt,
+                err
+            )),
+            UpdateEvent::Progress(path, statistics) => f.write_fmt(format_args!(
+                "Scanning '{}': {}",
+                path.to_string_lossy(),
+                statistics
+            )),
+            UpdateEvent::Statistics(path, statistics) => f.write_fmt(format_args!(
+                "Scanned '{}': {}",
+                path.to_string_lossy(),
+                statistics
+            )),
+            UpdateEvent::Changes(path, diff) => f.write_fmt(format_args!(
+                "Changes in '{}': {}",
+                path.to_string_lossy(),
+                diff
+            )),
+            UpdateEvent::ScanSummary(summary) => f.write_fmt(format_args!(
+                "Indexed '{}': {} entries in {:.1} s, database file has {} bytes",
+                summary.folder.to_string_lossy(),
+                summary.entries,
+                summary.duration.as_secs_f64(),
+                summary.db_bytes
+            )),
+            UpdateEvent::EnrichmentFailed(path, provider, err) => f.write_fmt(format_args!(
+                "Provider '{}' failed for '{}': {}",
+                provider,
+                path.to_string_lossy(),
+                err
+            )),
+            UpdateEvent::VolumeNotMounted(path, id) => f.write_fmt(format_args!(
+                "Skipping '{}': volume with {} is not mounted",
+                path.to_string_lossy(),
+                id
+            )),
+            UpdateEvent::VolumeMismatch(path, id) => f.write_fmt(format_args!(
+                "Folder '{}' is not on the volume with {}",
+                path.to_string_lossy(),
+                id
+            )),
+            UpdateEvent::InvalidExcludePattern(path, pattern, err) => f.write_fmt(format_args!(
+                "Skipping '{}': invalid exclude pattern '{}': {}",
+                path.to_string_lossy(),
+                pattern,
+                err
+            )),
+            UpdateEvent::SymlinkLoop(_, link, ancestor) => f.write_fmt(format_args!(
+                "Not following '{}': link to parent directory '{}'",
+                link.to_string_lossy(),
+                ancestor.to_string_lossy()
+            )),
+            UpdateEvent::PathTooLong(_, path, limit) => f.write_fmt(format_args!(
+                "Path '{}' exceeds the maximum length of {} bytes",
+                path.to_string_lossy(),
+                limit
+            )),
+            UpdateEvent::PathTooDeep(_, path, limit) => f.write_fmt(format_args!(
+                "Path '{}' exceeds the maximum depth of {}",
+                path.to_string_lossy(),
+                limit
+            )),
+        }
+    }
 }
 
 /// The update function recursively scans multiple folders and updates database
@@ -42,21 +330,57 @@ pub enum UpdateEvent {
 /// The implementations uses multiple threads to scan folders on different
 /// physical devices in parallel.
 ///
+/// Reading a directory or file metadata is retried with an increasing delay
+/// when it fails with a transient error, as configured by [UpdateConfig].
+///
+/// The abort parameter may be used by a frontend to abort an update. Folders
+/// not completely scanned are reported with [UpdateEvent::Aborted] and their
+/// database files are kept.
+///
 /// The provided closure is used to notify the caller about the scanning state
 /// and error.
 pub fn update<F: FnMut(UpdateEvent) -> IOResult<()>>(
     volume_info: Vec<VolumeInfo>,
     settings: Settings,
+    config: &UpdateConfig,
+    abort: Option<Arc<AtomicBool>>,
+    f: F,
+) {
+    update_with_providers(
+        volume_info,
+        settings,
+        config,
+        &ProviderRegistry::new(),
+        abort,
+        f,
+    )
+}
+
+/// The update_with_providers function updates database files like [update]
+/// and additionally stores the metadata contributed by enrichment providers.
+///
+/// Each folder is scanned with the providers of the registry, which are
+/// enabled in [VolumeInfo::providers]. Database files of folders with enabled
+/// providers are written with [Settings::WithMetadata].
+pub fn update_with_providers<F: FnMut(UpdateEvent) -> IOResult<()>>(
+    volume_info: Vec<VolumeInfo>,
+    settings: Settings,
+    config: &UpdateConfig,
+    providers: &ProviderRegistry,
+    abort: Option<Arc<AtomicBool>>,
     mut f: F,
 ) {
-    let grouped = group_volumes(volume_info);
+    let grouped = group_volumes(volume_info, &mut f);
     let mut handles = vec![];
     let (tx, rx) = channel();
     for group in grouped {
         let settings = settings.clone();
+        let config = config.clone();
+        let providers = providers.clone();
         let tx = tx.clone();
-        let handle = thread::spawn(|| {
-            update_volume_group(group, settings, tx);
+        let abort = abort.clone();
+        let handle = thread::spawn(move || {
+            update_volume_group(group, settings, &config, &providers, abort.as_deref(), tx);
         });
         handles.push(handle);
     }
@@ -77,56 +401,253 @@ pub fn update<F: FnMut(UpdateEvent) -> IOResult<()>>(
     }
 }
 
-fn group_volumes(volume_info: Vec<VolumeInfo>) -> GroupedVolumes {
-    let mut map = BTreeMap::<_, Vec<VolumeInfo>>::new();
+// A configured folder and the path it is scanned at.
+#[derive(Clone)]
+struct ScanTarget {
+    volume_info: VolumeInfo,
+    // Differs from the configured folder, if the volume is mounted at a
+    // different path.
+    root: PathBuf,
+    volume: Option<VolumeHeader>,
+    exclude: GlobSet,
+}
+
+fn group_volumes<F: FnMut(UpdateEvent) -> IOResult<()>>(
+    volume_info: Vec<VolumeInfo>,
+    f: &mut F,
+) -> GroupedVolumes {
+    let mut map = BTreeMap::<_, Vec<Vec<ScanTarget>>>::new();
+    // Device and index of the folders sharing a database file. Shared
+    // database files are scanned by the thread of the first folder.
+    let mut shared = BTreeMap::<PathBuf, (_, usize)>::new();
     for vi in volume_info {
-        let st = stat(&vi.folder);
+        let target = match scan_target(vi) {
+            Ok(target) => target,
+            Err(event) => {
+                let _ = f(event);
+                continue;
+            }
+        };
+        let st = stat(&target.root);
         if let Ok(f_stat) = st {
-            let dev = f_stat.st_dev; // MacOS: i32, Linux: u64
-            map.entry(dev).or_default().push(vi);
+            // The device only groups the folders in memory and is not
+            // stored. Its type differs, e.g. i32 on macOS and u64 on Linux.
+            let dev = f_stat.st_dev;
+            let database = target.volume_info.database.clone();
+            if let Some((dev, index)) = shared.get(&database) {
+                map.entry(*dev).or_default()[*index].push(target);
+            } else {
+                let databases = map.entry(dev).or_default();
+                shared.insert(database, (dev, databases.len()));
+                databases.push(vec![target]);
+            }
+        }
+    }
+    map.into_values()
+        .map(|databases| {
+            databases
+                .into_iter()
+                .map(|mut targets| {
+                    // The entries of the folders are stored in walk order.
+                    targets.sort_by(|a, b| compare_paths(&a.root, &b.root));
+                    targets
+                })
+                .collect()
+        })
+        .collect()
+}
+
+fn scan_target(volume_info: VolumeInfo) -> Result<ScanTarget, UpdateEvent> {
+    let exclude = match exclude_set(&volume_info.exclude) {
+        Ok(exclude) => exclude,
+        Err((pattern, err)) => {
+            return Err(UpdateEvent::InvalidExcludePattern(
+                volume_info.folder,
+                pattern,
+                err,
+            ))
+        }
+    };
+    let Some(id) = volume_info.volume_id.clone() else {
+        return Ok(ScanTarget {
+            root: volume_info.folder.clone(),
+            volume: None,
+            volume_info,
+            exclude,
+        });
+    };
+    let Some(mount_point) = id.mount_point() else {
+        return Err(UpdateEvent::VolumeNotMounted(volume_info.folder, id));
+    };
+    let root = if id.contains(&volume_info.folder) {
+        volume_info.folder.clone()
+    } else {
+        // The folder is found relative to the mount point of the last update.
+        let relative = FileIndexReader::new(&volume_info.database)
+            .ok()
+            .and_then(|reader| reader.volume)
+            .filter(|previous| previous.id == id)
+            .and_then(|previous| {
+                let relative = previous.folder.strip_prefix(&previous.mount_point).ok()?;
+                Some(relative.to_owned())
+            });
+        match relative {
+            Some(relative) if relative.as_os_str().is_empty() => mount_point.clone(),
+            Some(relative) => mount_point.join(relative),
+            None => return Err(UpdateEvent::VolumeMismatch(volume_info.folder, id)),
         }
+    };
+    Ok(ScanTarget {
+        volume: Some(VolumeHeader {
+            id,
+            mount_point,
+            folder: root.clone(),
+        }),
+        root,
+        volume_info,
+        exclude,
+    })
+}
+
+// An asterisk matches path separators, i.e. *.tmp excludes files in all
+// directories.
+fn exclude_set(patterns: &[String]) -> Result<GlobSet, (String, globset::Error)> {
+    let mut builder = GlobSetBuilder::new();
+    for pattern in patterns {
+        let glob = Glob::new(pattern).map_err(|err| (pattern.clone(), err))?;
+        builder.add(glob);
     }
-    map.values().cloned().collect()
+    builder.build().map_err(|err| (patterns.join(", "), err))
 }
 
-fn update_volume_group(group: Vec<VolumeInfo>, settings: Settings, tx: Sender<UpdateEvent>) {
-    for volume_info in group {
-        update_volume(volume_info, settings.clone(), &tx);
+fn update_volume_group(
+    group: Vec<Vec<ScanTarget>>,
+    settings: Settings,
+    config: &UpdateConfig,
+    providers: &ProviderRegistry,
+    abort: Option<&AtomicBool>,
+    tx: Sender<UpdateEvent>,
+) {
+    for targets in group {
+        update_volume(targets, settings.clone(), config, providers, abort, &tx);
     }
 }
 
-fn update_volume(volume_info: VolumeInfo, settings: Settings, tx: &Sender<UpdateEvent>) {
-    let _ = tx.send(UpdateEvent::Scanning(volume_info.folder.clone()));
-    if update_volume_impl(&volume_info, settings, tx) {
-        // Database file is updated.
-        let _ = tx.send(UpdateEvent::ScanningFinished(volume_info.folder.clone()));
+fn is_aborted(abort: Option<&AtomicBool>) -> bool {
+    abort.is_some_and(|abort| abort.load(atomic::Ordering::Relaxed))
+}
+
+// Updates a database file with the entries of one or more folders. The
+// folders of a shared database file are updated together, i.e. the database
+// file is kept when scanning any of them fails.
+fn update_volume(
+    targets: Vec<ScanTarget>,
+    settings: Settings,
+    config: &UpdateConfig,
+    providers: &ProviderRegistry,
+    abort: Option<&AtomicBool>,
+    tx: &Sender<UpdateEvent>,
+) {
+    let _ = tx.send(UpdateEvent::Scanning(targets[0].volume_info.folder.clone()));
+    let without_providers = targets
+        .iter()
+        .all(|target| providers.select(&target.volume_info.providers).is_empty());
+    let settings = if without_providers || settings.has_metadata() {
+        settings
     } else {
-        // Database file is not updated.
-        let _ = tx.send(UpdateEvent::ScanningFailed(volume_info.folder.clone()));
+        Settings::WithMetadata
+    };
+    let updated = update_volume_impl(&targets, settings, config, providers, abort, tx);
+    for target in &targets {
+        let folder = target.volume_info.folder.clone();
+        if updated {
+            // Database file is updated.
+            let _ = tx.send(UpdateEvent::ScanningFinished(folder));
+        } else if is_aborted(abort) {
+            let _ = tx.send(UpdateEvent::Aborted(folder));
+        } else {
+            // Database file is not updated.
+            let _ = tx.send(UpdateEvent::ScanningFailed(folder));
+        }
     }
 }
 
 fn update_volume_impl(
-    volume_info: &VolumeInfo,
+    targets: &[ScanTarget],
     settings: Settings,
+    config: &UpdateConfig,
+    providers: &ProviderRegistry,
+    abort: Option<&AtomicBool>,
     tx: &Sender<UpdateEvent>,
 ) -> bool {
+    let volume_info = &targets[0].volume_info;
     let db_file_name = &volume_info.database;
-    let mut tmp_file_name = db_file_name.clone();
-    tmp_file_name.set_extension("~");
+    let tmp_file_name = temporary_file(db_file_name);
 
-    let mut file = match File::create(&tmp_file_name) {
+    let mut file = match create_locked(&tmp_file_name) {
         Ok(file) => file,
+        Err(err) if err.kind() == ErrorKind::WouldBlock => {
+            let _ = tx.send(UpdateEvent::DatabaseLocked(db_file_name.clone()));
+            return false;
+        }
         Err(err) => {
             let _ = tx.send(UpdateEvent::CreatingTemporaryFileFailed(tmp_file_name, err));
             return false;
         }
     };
-    let result = scan_folder(&mut file, &volume_info.folder, settings, tx);
-    drop(file); // close file
+    let result = scan_folder(&mut *file, targets, settings, config, providers, abort, tx);
+    // The lock is released when the file is closed, i.e. after the database
+    // file is replaced or the temporary file is removed.
+    let updated = replace_database(result, volume_info, &tmp_file_name, targets, abort, tx);
+    drop(file);
+    updated
+}
+
+// Creates the temporary file locked exclusively. Queries check the lock to
+// detect an update in progress. A second update of the same database file
+// fails with WouldBlock instead of truncating the file of the first one.
+fn create_locked(tmp_file_name: &Path) -> IOResult<Flock<File>> {
+    loop {
+        let file = OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(tmp_file_name)?;
+        let file = Flock::lock(file, FlockArg::LockExclusiveNonblock).map_err(|(_, errno)| {
+            if errno == Errno::EWOULDBLOCK {
+                Error::from(ErrorKind::WouldBlock)
+            } else {
+                Error::from(errno)
+            }
+        })?;
+        // The update holding the lock before may have renamed the file to
+        // the database file meanwhile.
+        let locked = file.metadata()?;
+        let current = fs::metadata(tmp_file_name).ok();
+        if current
+            .is_some_and(|current| current.dev() == locked.dev() && current.ino() == locked.ino())
+        {
+            // A temporary file left by an interrupted update is truncated.
+            file.set_len(0)?;
+            return Ok(file);
+        }
+    }
+}
 
+fn replace_database(
+    result: IOResult<Vec<ScanStatistics>>,
+    volume_info: &VolumeInfo,
+    tmp_file_name: &Path,
+    targets: &[ScanTarget],
+    abort: Option<&AtomicBool>,
+    tx: &Sender<UpdateEvent>,
+) -> bool {
+    let db_file_name = &volume_info.database;
+    let tmp_file_name = tmp_file_name.to_owned();
     match result {
-        Ok(_) => {
+        Ok(statistics) => {
+            // The old database file does not exist after the first scan.
+            let diff = diff_databases(db_file_name, &tmp_file_name).ok();
             if let Err(err) = fs::rename(&tmp_file_name, db_file_name) {
                 let _ = tx.send(UpdateEvent::ReplacingDatabaseFailed(
                     tmp_file_name,
@@ -135,11 +656,26 @@ fn update_volume_impl(
                 ));
                 false
             } else {
+                if let Some(diff) = diff {
+                    let _ = tx.send(UpdateEvent::Changes(volume_info.folder.clone(), diff));
+                }
+                let db_bytes = fs::metadata(db_file_name).map_or(0, |metadata| metadata.len());
+                for (target, statistics) in targets.iter().zip(statistics) {
+                    let _ = tx.send(UpdateEvent::ScanSummary(ScanSummary {
+                        folder: target.volume_info.folder.clone(),
+                        database: db_file_name.clone(),
+                        entries: statistics.entries,
+                        duration: statistics.elapsed,
+                        db_bytes,
+                    }));
+                }
                 true
             }
         }
         Err(err) => {
-            let _ = tx.send(UpdateEvent::DbWriteError(volume_info.database.clone(), err));
+            if !is_aborted(abort) {
+                let _ = tx.send(UpdateEvent::DbWriteError(volume_info.database.clone(), err));
+            }
             if let Err(err) = fs::remove_file(&tmp_file_name) {
                 let _ = tx.send(UpdateEvent::RemovingTemporaryFileFailed(tmp_file_name, err));
             }
@@ -148,57 +684,390 @@ fn update_volume_impl(
     }
 }
 
+// The entries of a folder are stored relative to the folder in the header.
+// Folders sharing a database file are written one after another with
+// absolute paths. The entry of each folder is the boundary to the entries of
+// the previous folder. Shared database files are written without volume
+// header. The footer with the checksum is written after the last folder.
 fn scan_folder(
-    mut writer: &mut dyn Write,
-    folder: &Path,
+    file: &mut dyn Write,
+    targets: &[ScanTarget],
     settings: Settings,
+    config: &UpdateConfig,
+    providers: &ProviderRegistry,
+    abort: Option<&AtomicBool>,
     tx: &Sender<UpdateEvent>,
-) -> IOResult<()> {
-    // An Err(_) return value always indicates that writing the database file failed.
+) -> IOResult<Vec<ScanStatistics>> {
+    // Returns the statistics of each folder.
+    // An Err(_) return value always indicates that writing the database file
+    // failed or that the update was aborted.
     // When scanning the folder fails the error is sent as an event.
-    let flags: &[u8] = &[settings.clone() as u8];
+    let (volume, root) = match targets {
+        [target] => (target.volume.as_ref(), Some(&target.root)),
+        _ => (None, None),
+    };
+    let mut checksum = ChecksumWriter::new(file);
+    let mut flags = settings.clone() as u8 | ENTRY_TYPES | FOOTER;
+    if config.compress {
+        flags |= COMPRESSED;
+    }
+    if config.modification_times {
+        flags |= MODIFICATION_TIMES;
+    }
+    if volume.is_some() {
+        flags |= VOLUME_HEADER;
+    }
+    if root.is_some() {
+        flags |= ROOT_HEADER;
+    }
     // The written file should be removed when this function returns an Err.
     // Either the device was not mounted (ErrorKind::NotFound) or writing the
     // file failed, i.e. the file content is corrupt.
-    writer.write_all("fsix".as_bytes())?;
-    writer.write_all(flags)?;
-    let mut previous: Vec<u8> = Vec::new();
-    for entry in WalkDir::new(folder).sort_by(|a, b| compare(a.file_name(), b.file_name())) {
-        match entry {
-            Ok(entry) => {
-                let bytes = byte_slice(entry.path());
-                let (discard, delta) = delta_encode(&previous, bytes);
-                // println!("{}: {}", discard, String::from_utf8_lossy(delta));
-                // println!("{}: {}", bytes.len(), entry.path().display());
-                writer.write_vu64(discard as u64)?;
-                writer.write_vu64(delta.len() as u64)?;
-                writer.write_all(delta)?;
-
-                if settings == Settings::WithFileSizes {
-                    let size_plus_one = if let Ok(metadata) = entry.metadata() {
-                        metadata.len() + 1
-                    } else {
-                        0
-                    };
-                    writer.write_vu64(size_plus_one)?;
+    checksum.write_all("fsix".as_bytes())?;
+    checksum.write_all(&[flags])?;
+    if let Some(volume) = volume {
+        write_volume_header(&mut checksum, volume)?;
+    }
+    // The delta encoding continues across folders.
+    let mut previous = Vec::new();
+    if let Some(root) = root {
+        previous = byte_slice(root).to_vec();
+        checksum.write_vu64(previous.len() as u64)?;
+        checksum.write_all(&previous)?;
+    }
+    let mut payload = PayloadWriter::new(&mut checksum, config.compress)?;
+    let writer: &mut dyn Write = &mut payload;
+    let mut statistics = Vec::new();
+    for (index, target) in targets.iter().enumerate() {
+        let folder = &target.volume_info.folder;
+        if index > 0 {
+            let _ = tx.send(UpdateEvent::Scanning(folder.clone()));
+        }
+        let providers = providers.select(&target.volume_info.providers);
+        let mut scanner = Scanner {
+            writer: &mut *writer,
+            folder,
+            root: &target.root,
+            exclude: &target.exclude,
+            follow_symlinks: target.volume_info.follow_symlinks,
+            settings: settings.clone(),
+            config,
+            providers: &providers,
+            abort,
+            tx,
+            previous,
+            start: Instant::now(),
+            last_progress: Instant::now(),
+            statistics: ScanStatistics::default(),
+        };
+        scanner.scan_tree(&target.root, 0)?;
+        scanner.statistics.elapsed = scanner.start.elapsed();
+        let _ = tx.send(UpdateEvent::Statistics(
+            folder.to_path_buf(),
+            scanner.statistics.clone(),
+        ));
+        statistics.push(scanner.statistics);
+        previous = scanner.previous;
+    }
+    writer.write_vu64(END_OF_ENTRIES)?;
+    payload.finish()?;
+    checksum.finish()?;
+    Ok(statistics)
+}
+
+// The volume header is read by FileIndexReader.
+fn write_volume_header(writer: &mut dyn Write, volume: &VolumeHeader) -> IOResult<()> {
+    writer.write_all(&[volume.id.kind()])?;
+    for bytes in [
+        volume.id.value().as_bytes(),
+        volume.mount_point.as_os_str().as_bytes(),
+        volume.folder.as_os_str().as_bytes(),
+    ] {
+        writer.write_vu64(bytes.len() as u64)?;
+        writer.write_all(bytes)?;
+    }
+    Ok(())
+}
+
+struct Scanner<'a> {
+    writer: &'a mut dyn Write,
+    folder: &'a Path,
+    root: &'a Path,
+    exclude: &'a GlobSet,
+    follow_symlinks: bool,
+    settings: Settings,
+    config: &'a UpdateConfig,
+    providers: &'a ProviderRegistry,
+    abort: Option<&'a AtomicBool>,
+    tx: &'a Sender<UpdateEvent>,
+    previous: Vec<u8>,
+    start: Instant,
+    last_progress: Instant,
+    statistics: ScanStatistics,
+}
+
+const PROGRESS_INTERVAL: Duration = Duration::from_secs(1);
+
+impl Scanner<'_> {
+    fn scan_tree(&mut self, root: &Path, min_depth: usize) -> IOResult<()> {
+        let mut it = WalkDir::new(root)
+            .min_depth(min_depth)
+            .follow_links(self.follow_symlinks)
+            // Names equal in natural order, e.g. a1 and a01, are sorted by
+            // their bytes. The order never depends on the file system.
+            .sort_by(|a, b| {
+                compare(a.file_name(), b.file_name()).then_with(|| a.file_name().cmp(b.file_name()))
+            })
+            .into_iter();
+        while let Some(entry) = it.next() {
+            if is_aborted(self.abort) {
+                return Err(Error::new(ErrorKind::Interrupted, "update aborted"));
+            }
+            match entry {
+                Ok(entry) if self.is_excluded(&entry) => {
+                    if entry.file_type().is_dir() {
+                        it.skip_current_dir();
+                    }
+                }
+                Ok(entry) => {
+                    if self.exceeds_limits(&entry) && self.config.skip_exceeding {
+                        if entry.file_type().is_dir() {
+                            it.skip_current_dir();
+                        }
+                        continue;
+                    }
+                    self.write_entry(&entry)?;
+                }
+                Err(error) if error.loop_ancestor().is_some() => {
+                    if let (Some(link), Some(ancestor)) = (error.path(), error.loop_ancestor()) {
+                        let _ = self.tx.send(UpdateEvent::SymlinkLoop(
+                            self.folder.to_path_buf(),
+                            link.to_path_buf(),
+                            ancestor.to_path_buf(),
+                        ));
+                    }
+                }
+                Err(error) => {
+                    // Reading a directory failed. Errors are sorted before
+                    // the entries of the directory. When a retry succeeds the
+                    // directory is scanned again and the remaining entries of
+                    // the failed read are skipped.
+                    if let (Some(path), Some(io_error)) = (error.path(), error.io_error()) {
+                        if self.retry(path, io_error, || fs::read_dir(path)).is_some() {
+                            let path = path.to_path_buf();
+                            it.skip_current_dir();
+                            self.scan_tree(&path, 1)?;
+                            continue;
+                        }
+                    }
+                    // This function is not called if a folder is not mounted.
+                    // Unmounted volumes are already filtered ou by group_volumes.
+                    let _ = self
+                        .tx
+                        .send(UpdateEvent::ScanError(self.folder.to_path_buf(), error));
                 }
+            }
+        }
+        Ok(())
+    }
 
-                previous = bytes.to_vec();
+    // Reports the topmost entries of a directory tree exceeding a limit. The
+    // parent of a reported entry doesn't exceed the limit.
+    fn exceeds_limits(&self, entry: &DirEntry) -> bool {
+        let path = entry.path();
+        if let Some(limit) = self.config.max_path_length {
+            let length = path.as_os_str().len();
+            if length > limit {
+                let parent = path.parent().map_or(0, |parent| parent.as_os_str().len());
+                if parent <= limit {
+                    let _ = self.tx.send(UpdateEvent::PathTooLong(
+                        self.folder.to_path_buf(),
+                        path.to_path_buf(),
+                        limit,
+                    ));
+                }
+                return true;
             }
-            Err(error) => {
-                // This function is not called if a folder is not mounted.
-                // Unmounted volumes are already filtered ou by group_volumes.
-                let _ = tx.send(UpdateEvent::ScanError(folder.to_path_buf(), error));
+        }
+        if let Some(limit) = self.config.max_depth {
+            // The depth of the walk differs after retrying a directory.
+            let depth = path
+                .strip_prefix(self.root)
+                .map_or(0, |relative| relative.components().count());
+            if depth > limit {
+                if depth == limit + 1 {
+                    let _ = self.tx.send(UpdateEvent::PathTooDeep(
+                        self.folder.to_path_buf(),
+                        path.to_path_buf(),
+                        limit,
+                    ));
+                }
+                return true;
             }
         }
+        false
+    }
+
+    // The scanned folder itself is never excluded.
+    fn is_excluded(&self, entry: &DirEntry) -> bool {
+        match entry.path().strip_prefix(self.root) {
+            Ok(relative) if !relative.as_os_str().is_empty() => self.exclude.is_match(relative),
+            _ => false,
+        }
+    }
+
+    fn write_entry(&mut self, entry: &DirEntry) -> IOResult<()> {
+        let mut stored = Metadata {
+            size: None,
+            modified: None,
+            attributes: Vec::new(),
+            entry_type: Some(entry_type(entry)),
+        };
+        if self.settings.has_sizes() || self.config.modification_times {
+            let metadata = match entry.metadata() {
+                Ok(metadata) => Some(metadata),
+                Err(error) => error.io_error().and_then(|io_error| {
+                    self.retry(entry.path(), io_error, || {
+                        fs::symlink_metadata(entry.path())
+                    })
+                }),
+            };
+            // The size of a directory depends on the file system and on
+            // removed entries. It is stored as unknown in reproducible mode.
+            if self.settings.has_sizes() {
+                stored.size = match &metadata {
+                    Some(metadata) if self.config.reproducible && metadata.is_dir() => None,
+                    Some(metadata) => Some(metadata.len()),
+                    None => None,
+                };
+            }
+            if self.config.modification_times {
+                stored.modified = metadata
+                    .as_ref()
+                    .and_then(|metadata| metadata.modified().ok());
+            }
+            if self.settings.has_metadata() {
+                stored.attributes = self.enrich(entry, metadata.as_ref());
+            }
+        }
+        write_entry(
+            self.writer,
+            &mut self.previous,
+            byte_slice(entry.path()),
+            &self.settings,
+            self.config.modification_times,
+            &stored,
+        )?;
+        self.count(entry);
+        Ok(())
+    }
+
+    // Returns the values of the providers with their tags.
+    fn enrich(&mut self, entry: &DirEntry, metadata: Option<&fs::Metadata>) -> Vec<(u64, Vec<u8>)> {
+        let is_file = entry.file_type().is_file();
+        let mut attributes = Vec::new();
+        for provider in self.providers.providers() {
+            if !applies(provider.as_ref(), is_file, metadata) {
+                continue;
+            }
+            match provider.enrich(entry.path(), metadata) {
+                Ok(Some(value)) => attributes.push((provider.tag(), value)),
+                Ok(None) => {}
+                Err(err) => {
+                    let _ = self.tx.send(UpdateEvent::EnrichmentFailed(
+                        entry.path().to_path_buf(),
+                        provider.name().to_string(),
+                        err,
+                    ));
+                }
+            }
+        }
+        attributes
+    }
+
+    fn count(&mut self, entry: &DirEntry) {
+        self.statistics.entries += 1;
+        if entry.file_type().is_dir() {
+            self.statistics.directories += 1;
+        }
+        if self.last_progress.elapsed() >= PROGRESS_INTERVAL {
+            self.last_progress = Instant::now();
+            self.statistics.elapsed = self.start.elapsed();
+            let _ = self.tx.send(UpdateEvent::Progress(
+                self.folder.to_path_buf(),
+                self.statistics.clone(),
+            ));
+        }
+    }
+
+    // Retries the operation with an increasing delay as long as it fails
+    // with a transient error. Returns None when giving up.
+    fn retry<T, F: FnMut() -> IOResult<T>>(
+        &self,
+        path: &Path,
+        error: &Error,
+        mut operation: F,
+    ) -> Option<T> {
+        let mut error = copy_error(error);
+        let mut delay = Duration::from_millis(self.config.retry_delay_ms);
+        for attempt in 1..=self.config.retries {
+            if !is_transient(&error) {
+                return None;
+            }
+            let _ = self
+                .tx
+                .send(UpdateEvent::Retrying(path.to_path_buf(), attempt, error));
+            thread::sleep(delay);
+            delay *= 2;
+            match operation() {
+                Ok(value) => return Some(value),
+                Err(err) => error = err,
+            }
+        }
+        None
     }
-    Ok(())
 }
 
-fn compare(a: &OsStr, b: &OsStr) -> Ordering {
-    let a1 = a.to_string_lossy();
-    let b1 = b.to_string_lossy();
-    natord::compare(&a1, &b1)
+// The type of a followed link is the type of its target.
+fn entry_type(entry: &DirEntry) -> EntryType {
+    let file_type = entry.file_type();
+    if file_type.is_file() {
+        EntryType::File
+    } else if file_type.is_dir() {
+        EntryType::Directory
+    } else if file_type.is_symlink() {
+        EntryType::Symlink
+    } else {
+        EntryType::Other
+    }
+}
+
+fn is_transient(error: &Error) -> bool {
+    use nix::errno::Errno;
+    if let Some(errno) = error.raw_os_error() {
+        matches!(
+            Errno::from_raw(errno),
+            Errno::EIO
+                | Errno::EAGAIN
+                | Errno::EBUSY
+                | Errno::EINTR
+                | Errno::ESTALE
+                | Errno::ETIMEDOUT
+        )
+    } else {
+        matches!(
+            error.kind(),
+            ErrorKind::Interrupted | ErrorKind::TimedOut | ErrorKind::WouldBlock
+        )
+    }
+}
+
+fn copy_error(error: &Error) -> Error {
+    if let Some(errno) = error.raw_os_error() {
+        Error::from_raw_os_error(errno)
+    } else {
+        Error::new(error.kind(), error.to_string())
+    }
 }
 
 fn byte_slice(path: &Path) -> &[u8] {
@@ -208,23 +1077,531 @@ fn byte_slice(path: &Path) -> &[u8] {
     bytes
 }
 
-fn delta_encode<'a>(a: &'a [u8], b: &'a [u8]) -> (usize, &'a [u8]) {
-    let mut idx: usize = 0;
-    for (a, b) in a.iter().zip(b.iter()) {
-        if a != b {
-            break;
-        }
-        idx += 1;
-    }
-    let discard = a.len() - idx;
-    let delta: &[u8] = &b[idx..];
-    (discard, delta)
-}
-
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::locate::FileIndexReader;
+    use crate::temp_dir::TempDir;
+    use core::cmp::Ordering;
     use std::ffi::OsString;
+    use std::time::UNIX_EPOCH;
+
+    // Creates the folder of a volume with the given files in a new temporary
+    // directory. Paths ending with a slash are directories. Each file
+    // contains its name.
+    fn fixture(name: &str, paths: &[&str]) -> (TempDir, VolumeInfo) {
+        let dir = TempDir::new(name);
+        let volume_info = VolumeInfo::in_dir(&dir, "folder");
+        fs::create_dir_all(&volume_info.folder).unwrap();
+        for path in paths {
+            let path = volume_info.folder.join(path);
+            if path.as_os_str().as_bytes().ends_with(b"/") {
+                fs::create_dir_all(&path).unwrap();
+            } else {
+                fs::create_dir_all(path.parent().unwrap()).unwrap();
+                fs::write(&path, path.file_name().unwrap().as_bytes()).unwrap();
+            }
+        }
+        (dir, volume_info)
+    }
+
+    // Updates the database file and returns the events.
+    fn scan(
+        volume_info: &VolumeInfo,
+        settings: Settings,
+        config: &UpdateConfig,
+    ) -> Vec<UpdateEvent> {
+        let mut events = Vec::new();
+        update(vec![volume_info.clone()], settings, config, None, |event| {
+            events.push(event);
+            Ok(())
+        });
+        events
+    }
+
+    // Returns the stored entries relative to the folder.
+    fn entries(volume_info: &VolumeInfo) -> Vec<(PathBuf, Metadata)> {
+        let mut entries = Vec::new();
+        let mut reader = FileIndexReader::new(&volume_info.database).unwrap();
+        while let Some((path, metadata)) = reader.next_entry().unwrap() {
+            let path = path.strip_prefix(&volume_info.folder).unwrap().to_owned();
+            entries.push((path, metadata));
+        }
+        entries
+    }
+
+    fn paths(volume_info: &VolumeInfo) -> Vec<PathBuf> {
+        entries(volume_info)
+            .into_iter()
+            .map(|(path, _)| path)
+            .collect()
+    }
+
+    fn relative(paths: &[&str]) -> Vec<PathBuf> {
+        paths.iter().map(PathBuf::from).collect()
+    }
+
+    #[test]
+    fn test_severity() {
+        let path = PathBuf::from("/foo");
+        assert_eq!(
+            UpdateEvent::Scanning(path.clone()).severity(),
+            Severity::Info
+        );
+        assert_eq!(
+            UpdateEvent::RemovingTemporaryFileFailed(path.clone(), ErrorKind::Other.into())
+                .severity(),
+            Severity::Warning
+        );
+        assert_eq!(
+            UpdateEvent::ScanningFailed(path).severity(),
+            Severity::Error
+        );
+    }
+
+    #[test]
+    fn test_event_display() {
+        assert_eq!(
+            UpdateEvent::DbWriteError(PathBuf::from("/foo"), ErrorKind::Other.into()).to_string(),
+            "Writing database '/foo' failed: other error"
+        );
+    }
+
+    #[test]
+    fn test_scan_statistics_rates() {
+        let statistics = ScanStatistics {
+            directories: 10,
+            entries: 250,
+            elapsed: Duration::from_millis(500),
+        };
+        assert_eq!(statistics.directories_per_second(), 20.0);
+        assert_eq!(statistics.entries_per_second(), 500.0);
+        assert_eq!(ScanStatistics::default().entries_per_second(), 0.0);
+    }
+
+    #[test]
+    fn test_scan_statistics_display() {
+        let statistics = ScanStatistics {
+            directories: 10,
+            entries: 250,
+            elapsed: Duration::from_millis(500),
+        };
+        assert_eq!(
+            statistics.to_string(),
+            "250 entries in 10 directories in 0.5 s (500 entries/s, 20 directories/s)"
+        );
+    }
+
+    #[test]
+    fn test_is_transient() {
+        assert!(is_transient(&Error::from_raw_os_error(nix::libc::EIO)));
+        assert!(is_transient(&Error::from_raw_os_error(
+            nix::libc::ETIMEDOUT
+        )));
+        assert!(!is_transient(&Error::from_raw_os_error(nix::libc::EACCES)));
+        assert!(!is_transient(&Error::from_raw_os_error(nix::libc::ENOENT)));
+        assert!(is_transient(&ErrorKind::TimedOut.into()));
+        assert!(!is_transient(&ErrorKind::Other.into()));
+    }
+
+    #[test]
+    fn test_copy_error() {
+        assert_eq!(
+            copy_error(&Error::from_raw_os_error(nix::libc::EIO)).raw_os_error(),
+            Some(nix::libc::EIO)
+        );
+    }
+
+    #[test]
+    fn test_modification_times() {
+        let (_dir, volume_info) = fixture("mtime", &["file"]);
+        let config = UpdateConfig {
+            modification_times: true,
+            ..Default::default()
+        };
+        scan(&volume_info, Settings::WithFileSizes, &config);
+        let modified = fs::metadata(volume_info.folder.join("file"))
+            .unwrap()
+            .modified()
+            .unwrap();
+        let seconds = modified.duration_since(UNIX_EPOCH).unwrap().as_secs();
+        let entries = entries(&volume_info);
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[1].0, PathBuf::from("file"));
+        assert_eq!(entries[1].1.size, Some(4));
+        assert_eq!(
+            entries[1].1.modified,
+            Some(UNIX_EPOCH + Duration::from_secs(seconds))
+        );
+    }
+
+    #[test]
+    fn test_modification_times_keep_settings() {
+        let (_dir, volume_info) = fixture("mtime-settings", &["file"]);
+        let config = UpdateConfig {
+            modification_times: true,
+            ..Default::default()
+        };
+        scan(&volume_info, Settings::FileNamesOnly, &config);
+        let entries = entries(&volume_info);
+        let reader = FileIndexReader::new(&volume_info.database).unwrap();
+        assert_eq!(reader.settings, Settings::FileNamesOnly);
+        assert_eq!(entries[1].1.size, None);
+        assert!(entries[1].1.modified.is_some());
+    }
+
+    #[test]
+    fn test_exclude() {
+        let (_dir, volume_info) = fixture(
+            "exclude",
+            &[
+                ".git/objects/",
+                "src/.git/",
+                "a.tmp",
+                "src/b.tmp",
+                "src/node_modules/lib/c",
+                "src/main/d",
+            ],
+        );
+        let volume_info = volume_info.with_exclude(vec![
+            String::from("**/.git"),
+            String::from("**/node_modules"),
+            String::from("*.tmp"),
+        ]);
+        scan(
+            &volume_info,
+            Settings::WithFileSizes,
+            &UpdateConfig::default(),
+        );
+        assert_eq!(
+            paths(&volume_info),
+            relative(&["", "src", "src/main", "src/main/d"])
+        );
+    }
+
+    #[test]
+    fn test_invalid_exclude() {
+        let (_dir, volume_info) = fixture("exclude-invalid", &["a"]);
+        let volume_info = volume_info.with_exclude(vec![String::from("[a")]);
+        let events = scan(
+            &volume_info,
+            Settings::WithFileSizes,
+            &UpdateConfig::default(),
+        );
+        let severities: Vec<Severity> = events.iter().map(UpdateEvent::severity).collect();
+        assert_eq!(severities, vec![Severity::Error]);
+    }
+
+    // Returns the stored paths and the reported loops of a folder with a
+    // symlink to a directory and a symlink loop.
+    fn symlinks(name: &str, follow_symlinks: bool) -> (Vec<PathBuf>, Vec<PathBuf>) {
+        let (_dir, volume_info) = fixture(name, &["a/file"]);
+        let folder = volume_info.folder.clone();
+        std::os::unix::fs::symlink("..", folder.join("a/loop")).unwrap();
+        std::os::unix::fs::symlink("a", folder.join("b")).unwrap();
+        let volume_info = volume_info.with_follow_symlinks(follow_symlinks);
+        let loops = scan(
+            &volume_info,
+            Settings::WithFileSizes,
+            &UpdateConfig::default(),
+        )
+        .into_iter()
+        .filter_map(|event| match event {
+            UpdateEvent::SymlinkLoop(_, link, _) => {
+                Some(link.strip_prefix(&folder).unwrap().to_owned())
+            }
+            _ => None,
+        })
+        .collect();
+        (paths(&volume_info), loops)
+    }
+
+    #[test]
+    fn test_symlinks_not_followed() {
+        assert_eq!(
+            symlinks("symlinks", false),
+            (relative(&["", "a", "a/file", "a/loop", "b"]), Vec::new())
+        );
+    }
+
+    #[test]
+    fn test_follow_symlinks() {
+        assert_eq!(
+            symlinks("symlinks-followed", true),
+            (
+                relative(&["", "a", "a/file", "b", "b/file"]),
+                relative(&["a/loop", "b/loop"])
+            )
+        );
+    }
+
+    // Writes a reproducible database file of a directory with the files
+    // created in the given order.
+    fn reproducible(volume_info: &VolumeInfo, names: &[&str]) -> Vec<u8> {
+        let dir = volume_info.folder.join("dir");
+        fs::create_dir_all(&dir).unwrap();
+        for name in names {
+            fs::write(dir.join(name), name.as_bytes()).unwrap();
+        }
+        let config = UpdateConfig {
+            reproducible: true,
+            ..Default::default()
+        };
+        scan(volume_info, Settings::WithFileSizes, &config);
+        fs::remove_dir_all(&dir).unwrap();
+        fs::read(&volume_info.database).unwrap()
+    }
+
+    #[test]
+    fn test_reproducible() {
+        let (_dir, volume_info) = fixture("reproducible", &[]);
+        // Names equal in natural order are created in different orders.
+        let first = reproducible(&volume_info, &["a1", "a01", "a001", "b"]);
+        let second = reproducible(&volume_info, &["b", "a001", "a01", "a1"]);
+        assert_eq!(first, second);
+    }
+
+    #[test]
+    fn test_reproducible_entries() {
+        let (_dir, volume_info) = fixture("reproducible-entries", &[]);
+        let content = reproducible(&volume_info, &["a1", "a001"]);
+        // Directories are stored without size. a001 is sorted first.
+        let entries: &[u8] = b"\x00\x00\x02\x00\x00\x04/dir\x02\x00\x00\x05/a001\x01\x05";
+        assert!(content
+            .windows(entries.len())
+            .any(|window| window == entries));
+    }
+
+    // Folder with 100 similar directories, which compress well.
+    #[cfg(feature = "compression")]
+    fn compression(name: &str) -> (TempDir, VolumeInfo, VolumeInfo) {
+        let files: Vec<String> = (0..100)
+            .map(|index| format!("directory-{}/file.txt", index))
+            .collect();
+        let files: Vec<&str> = files.iter().map(String::as_str).collect();
+        let (dir, plain) = fixture(name, &files);
+        let compressed = VolumeInfo::new(plain.folder.clone(), dir.join("compressed.fsdb"));
+        let config = UpdateConfig {
+            compress: true,
+            ..Default::default()
+        };
+        scan(&plain, Settings::WithFileSizes, &UpdateConfig::default());
+        scan(&compressed, Settings::WithFileSizes, &config);
+        (dir, plain, compressed)
+    }
+
+    #[cfg(feature = "compression")]
+    #[test]
+    fn test_compression() {
+        let (_dir, plain, compressed) = compression("compression");
+        let plain_size = fs::metadata(&plain.database).unwrap().len();
+        let content = fs::read(&compressed.database).unwrap();
+        assert_eq!(content[4] & COMPRESSED, COMPRESSED);
+        assert!((content.len() as u64) < plain_size);
+    }
+
+    #[cfg(feature = "compression")]
+    #[test]
+    fn test_compressed_entries() {
+        let (_dir, plain, compressed) = compression("compression-entries");
+        let sizes = |volume_info| {
+            entries(volume_info)
+                .into_iter()
+                .map(|(path, metadata)| (path, metadata.size))
+                .collect::<Vec<_>>()
+        };
+        let compressed_entries = sizes(&compressed);
+        assert_eq!(compressed_entries.len(), 201);
+        assert_eq!(compressed_entries, sizes(&plain));
+        assert!(crate::checksum::verify_checksum(&compressed.database).unwrap());
+    }
+
+    #[cfg(feature = "compression")]
+    #[test]
+    fn test_truncated_compression() {
+        let (dir, _, compressed) = compression("compression-truncated");
+        let content = fs::read(&compressed.database).unwrap();
+        let truncated = dir.join("truncated.fsdb");
+        fs::write(&truncated, &content[..content.len() - 10]).unwrap();
+        let mut reader = FileIndexReader::new(&truncated).unwrap();
+        let res = loop {
+            match reader.advance() {
+                Ok(true) => {}
+                res => break res,
+            }
+        };
+        assert!(res.is_err());
+    }
+
+    #[test]
+    fn test_scan_summary() {
+        let (_dir, volume_info) = fixture("summary", &["a", "b"]);
+        let summaries: Vec<ScanSummary> = scan(
+            &volume_info,
+            Settings::WithFileSizes,
+            &UpdateConfig::default(),
+        )
+        .into_iter()
+        .filter_map(|event| match event {
+            UpdateEvent::ScanSummary(summary) => Some(summary),
+            _ => None,
+        })
+        .collect();
+        let db_bytes = fs::metadata(&volume_info.database).unwrap().len();
+        assert_eq!(summaries.len(), 1);
+        assert_eq!(summaries[0].folder, volume_info.folder);
+        assert_eq!(summaries[0].database, volume_info.database);
+        assert_eq!(summaries[0].entries, 3);
+        assert_eq!(summaries[0].db_bytes, db_bytes);
+    }
+
+    // Aborts the update of a folder with a previous database file.
+    fn aborted(name: &str) -> (TempDir, VolumeInfo, Vec<String>) {
+        let (dir, volume_info) = fixture(name, &[]);
+        fs::write(&volume_info.database, b"previous").unwrap();
+        let mut events = Vec::new();
+        update(
+            vec![volume_info.clone()],
+            Settings::WithFileSizes,
+            &UpdateConfig::default(),
+            Some(Arc::new(AtomicBool::new(true))),
+            |event| {
+                events.push(event.to_string());
+                Ok(())
+            },
+        );
+        (dir, volume_info, events)
+    }
+
+    #[test]
+    fn test_abort() {
+        let (_dir, volume_info, events) = aborted("abort");
+        assert_eq!(
+            events.last(),
+            Some(&format!("Aborted: {}", volume_info.folder.display()))
+        );
+        assert_eq!(fs::read(&volume_info.database).unwrap(), b"previous");
+    }
+
+    #[test]
+    fn test_abort_removes_temporary_file() {
+        let (dir, _, _) = aborted("abort-temporary");
+        // Only the folder and the previous database file are left.
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 2);
+    }
+
+    // Returns the stored paths and the warnings with a maximum depth of 2
+    // and a maximum path length of 12 bytes below the folder.
+    fn path_limits(name: &str, skip_exceeding: bool) -> (Vec<PathBuf>, Vec<String>) {
+        let (_dir, volume_info) = fixture(name, &["a/b/c/file", "a/long-file-name"]);
+        let folder = volume_info.folder.to_str().unwrap();
+        let config = UpdateConfig {
+            max_path_length: Some(folder.len() + 12),
+            max_depth: Some(2),
+            skip_exceeding,
+            ..Default::default()
+        };
+        let warnings = scan(&volume_info, Settings::WithFileSizes, &config)
+            .into_iter()
+            .filter(|event| event.severity() == Severity::Warning)
+            .map(|event| {
+                event
+                    .to_string()
+                    .replace(folder, "")
+                    .replace(&(folder.len() + 12).to_string(), "12 + folder")
+            })
+            .collect();
+        (paths(&volume_info), warnings)
+    }
+
+    // Only the topmost entries exceeding a limit are reported.
+    const PATH_LIMIT_WARNINGS: [&str; 2] = [
+        "Path '/a/b/c' exceeds the maximum depth of 2",
+        "Path '/a/long-file-name' exceeds the maximum length of 12 + folder bytes",
+    ];
+
+    #[test]
+    fn test_path_limits() {
+        assert_eq!(
+            path_limits("limits", false),
+            (
+                relative(&["", "a", "a/b", "a/b/c", "a/b/c/file", "a/long-file-name"]),
+                PATH_LIMIT_WARNINGS.map(String::from).to_vec()
+            )
+        );
+    }
+
+    #[test]
+    fn test_skip_exceeding() {
+        assert_eq!(
+            path_limits("limits-skipped", true),
+            (
+                relative(&["", "a", "a/b"]),
+                PATH_LIMIT_WARNINGS.map(String::from).to_vec()
+            )
+        );
+    }
+
+    fn unmounted(volume_info: VolumeInfo) -> VolumeInfo {
+        let id = VolumeId::Uuid(String::from("0000-0000-not-mounted"));
+        volume_info.with_volume_id(Some(id))
+    }
+
+    #[test]
+    fn test_unmounted_volume() {
+        let (_dir, volume_info) = fixture("unmounted", &["file"]);
+        let volume_info = unmounted(volume_info);
+        let events: Vec<String> = scan(
+            &volume_info,
+            Settings::WithFileSizes,
+            &UpdateConfig::default(),
+        )
+        .iter()
+        .map(UpdateEvent::to_string)
+        .collect();
+        assert_eq!(
+            events,
+            vec![format!(
+                "Skipping '{}': volume with UUID 0000-0000-not-mounted is not mounted",
+                volume_info.folder.display()
+            )]
+        );
+        assert!(!volume_info.database.exists());
+    }
+
+    #[test]
+    fn test_volume_header() {
+        let (dir, volume_info) = fixture("volume", &["file"]);
+        let volume_info = unmounted(volume_info);
+        let folder = volume_info.folder.clone();
+        let header = VolumeHeader {
+            id: volume_info.volume_id.clone().unwrap(),
+            mount_point: dir.to_path_buf(),
+            folder: folder.clone(),
+        };
+        let target = ScanTarget {
+            volume_info: volume_info.clone(),
+            root: folder.clone(),
+            volume: Some(header.clone()),
+            exclude: GlobSet::empty(),
+        };
+        let (tx, _rx) = channel();
+        let mut file = File::create(&volume_info.database).unwrap();
+        scan_folder(
+            &mut file,
+            &[target],
+            Settings::WithFileSizes,
+            &UpdateConfig::default(),
+            &ProviderRegistry::new(),
+            None,
+            &tx,
+        )
+        .unwrap();
+        drop(file);
+        let reader = FileIndexReader::new(&volume_info.database).unwrap();
+        assert_eq!(reader.volume, Some(header));
+        assert_eq!(paths(&volume_info), relative(&["", "file"]));
+    }
 
     #[test]
     fn test_compare() {
@@ -236,6 +1613,10 @@ mod tests {
             compare(&OsString::from("foo2"), &OsString::from("foo10")),
             Ordering::Less
         );
+    }
+
+    #[test]
+    fn test_compare_large_numbers() {
         // lexical_sort::natural_lexical_cmp panics with these large numbers:
         assert_eq!(
             compare(
@@ -0,0 +1,227 @@
+use nix::sys::stat::stat;
+use std::ffi::OsString;
+use std::fmt::Display;
+use std::os::unix::prelude::OsStringExt;
+use std::path::{Path, PathBuf};
+
+/// VolumeId identifies a file system independent of the path it is mounted
+/// at, e.g. a removable disk mounted at different paths.
+///
+/// Mount points are found with `/dev/disk/by-uuid`, `/dev/disk/by-label` and
+/// `/proc/self/mountinfo` on Linux and with `diskutil` on macOS. Labels are
+/// expected to be mounted at `/Volumes/<label>` on macOS.
+#[derive(Debug, Clone, PartialEq)]
+pub enum VolumeId {
+    /// UUID of the file system.
+    Uuid(String),
+    /// Label of the file system.
+    Label(String),
+}
+
+impl VolumeId {
+    /// Returns the path the file system is currently mounted at. None is
+    /// returned, if the file system is not mounted or identifying file
+    /// systems is not supported on the platform.
+    pub fn mount_point(&self) -> Option<PathBuf> {
+        mount_point(self)
+    }
+
+    /// Returns true, if the path is on the file system.
+    pub fn contains(&self, path: &Path) -> bool {
+        match (self.mount_point(), stat(path)) {
+            (Some(mount_point), Ok(path_stat)) => {
+                stat(&mount_point).is_ok_and(|mount_stat| mount_stat.st_dev == path_stat.st_dev)
+            }
+            _ => false,
+        }
+    }
+
+    #[cfg(feature = "update")]
+    pub(crate) fn kind(&self) -> u8 {
+        match self {
+            VolumeId::Uuid(_) => 1,
+            VolumeId::Label(_) => 2,
+        }
+    }
+
+    pub(crate) fn value(&self) -> &str {
+        match self {
+            VolumeId::Uuid(value) | VolumeId::Label(value) => value,
+        }
+    }
+
+    pub(crate) fn from_kind(kind: u8, value: String) -> Option<VolumeId> {
+        match kind {
+            1 => Some(VolumeId::Uuid(value)),
+            2 => Some(VolumeId::Label(value)),
+            _ => None,
+        }
+    }
+}
+
+impl Display for VolumeId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            VolumeId::Uuid(uuid) => f.write_fmt(format_args!("UUID {}", uuid)),
+            VolumeId::Label(label) => f.write_fmt(format_args!("label {}", label)),
+        }
+    }
+}
+
+/// Location of a scanned folder on an identified file system. Stored in the
+/// header of database files, such that the entries can be found when the
+/// file system is mounted at a different path.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct VolumeHeader {
+    pub(crate) id: VolumeId,
+    // Mount point while scanning.
+    pub(crate) mount_point: PathBuf,
+    // Scanned folder, i.e. the prefix of all entries.
+    pub(crate) folder: PathBuf,
+}
+
+impl VolumeHeader {
+    // Returns the current location of the scanned folder, if the file system
+    // is mounted at a different path now.
+    pub(crate) fn moved_folder(&self) -> Option<PathBuf> {
+        let mount_point = self.id.mount_point()?;
+        if mount_point == self.mount_point {
+            return None;
+        }
+        let relative = self.folder.strip_prefix(&self.mount_point).ok()?;
+        Some(mount_point.join(relative))
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn mount_point(id: &VolumeId) -> Option<PathBuf> {
+    use nix::sys::stat::{major, minor};
+    let dir = match id {
+        VolumeId::Uuid(_) => "/dev/disk/by-uuid",
+        VolumeId::Label(_) => "/dev/disk/by-label",
+    };
+    // The link to the device node is followed.
+    let device = stat(&Path::new(dir).join(id.value())).ok()?.st_rdev;
+    let mountinfo = std::fs::read_to_string("/proc/self/mountinfo").ok()?;
+    parse_mountinfo(&mountinfo, major(device), minor(device))
+}
+
+#[cfg(target_os = "macos")]
+fn mount_point(id: &VolumeId) -> Option<PathBuf> {
+    match id {
+        VolumeId::Uuid(uuid) => {
+            let output = std::process::Command::new("diskutil")
+                .arg("info")
+                .arg(uuid)
+                .output()
+                .ok()?;
+            parse_diskutil(&String::from_utf8_lossy(&output.stdout))
+        }
+        VolumeId::Label(label) => {
+            let path = Path::new("/Volumes").join(label);
+            let volumes = stat("/Volumes").ok()?;
+            // A folder on the same file system as /Volumes is not mounted.
+            (stat(&path).ok()?.st_dev != volumes.st_dev).then_some(path)
+        }
+    }
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos")))]
+fn mount_point(_id: &VolumeId) -> Option<PathBuf> {
+    None
+}
+
+// Returns the mount point of the device with the major and minor number. The
+// fields of a line are: mount ID, parent ID, major:minor, root, mount point.
+// Mounts of a subfolder, e.g. bind mounts, are skipped.
+#[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+fn parse_mountinfo(mountinfo: &str, major: u64, minor: u64) -> Option<PathBuf> {
+    let device = format!("{}:{}", major, minor);
+    mountinfo.lines().find_map(|line| {
+        let mut fields = line.split(' ').skip(2);
+        let (number, root, mount_point) = (fields.next()?, fields.next()?, fields.next()?);
+        (number == device && root == "/").then(|| unescape(mount_point))
+    })
+}
+
+// Spaces, tabs, newlines and backslashes are written as octal escapes.
+fn unescape(text: &str) -> PathBuf {
+    let bytes = text.as_bytes();
+    let mut path = Vec::with_capacity(bytes.len());
+    let mut pos = 0;
+    while pos < bytes.len() {
+        let octal = bytes
+            .get(pos + 1..pos + 4)
+            .filter(|digits| {
+                bytes[pos] == b'\\' && digits.iter().all(|d| (b'0'..=b'7').contains(d))
+            })
+            .map(|digits| digits.iter().fold(0u8, |value, d| value * 8 + (d - b'0')));
+        match octal {
+            Some(byte) => {
+                path.push(byte);
+                pos += 4;
+            }
+            None => {
+                path.push(bytes[pos]);
+                pos += 1;
+            }
+        }
+    }
+    PathBuf::from(OsString::from_vec(path))
+}
+
+#[cfg_attr(not(target_os = "macos"), allow(dead_code))]
+fn parse_diskutil(output: &str) -> Option<PathBuf> {
+    output.lines().find_map(|line| {
+        let mount_point = line.trim().strip_prefix("Mount Point:")?.trim();
+        (!mount_point.is_empty()).then(|| PathBuf::from(mount_point))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mountinfo() {
+        let mountinfo = concat!(
+            "22 1 8:2 / / rw,relatime shared:1 - ext4 /dev/sda2 rw\n",
+            "41 22 8:17 /Photos /srv/photos rw shared:3 - vfat /dev/sdb1 rw\n",
+            "40 22 8:17 / /media/My\\040Disk rw shared:2 - vfat /dev/sdb1 rw\n",
+        );
+        assert_eq!(
+            parse_mountinfo(mountinfo, 8, 17),
+            Some(PathBuf::from("/media/My Disk"))
+        );
+        assert_eq!(parse_mountinfo(mountinfo, 8, 2), Some(PathBuf::from("/")));
+        assert_eq!(parse_mountinfo(mountinfo, 8, 3), None);
+        assert_eq!(unescape("a\\134b\\011"), PathBuf::from("a\\b\t"));
+        assert_eq!(unescape("a\\9"), PathBuf::from("a\\9"));
+    }
+
+    #[test]
+    fn diskutil() {
+        let output = concat!(
+            "   Device Identifier:         disk4s1\n",
+            "   Volume Name:               Backup\n",
+            "   Mounted:                   Yes\n",
+            "   Mount Point:               /Volumes/Backup\n",
+        );
+        assert_eq!(
+            parse_diskutil(output),
+            Some(PathBuf::from("/Volumes/Backup"))
+        );
+        assert_eq!(parse_diskutil("   Mount Point:\n"), None);
+    }
+
+    #[test]
+    fn moved_folder() {
+        let header = VolumeHeader {
+            id: VolumeId::Uuid(String::from("0000-0000-not-mounted")),
+            mount_point: PathBuf::from("/media/usb"),
+            folder: PathBuf::from("/media/usb/Photos"),
+        };
+        assert_eq!(header.moved_folder(), None);
+        assert!(!header.id.contains(Path::new("/")));
+    }
+}
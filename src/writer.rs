@@ -0,0 +1,198 @@
+use crate::checksum::ChecksumWriter;
+use crate::locate::{Metadata, END_OF_ENTRIES, ENTRY_TYPES, FOOTER, MODIFICATION_TIMES};
+use crate::Settings;
+use fastvlq::WriteVu64Ext;
+use std::io::{Result as IOResult, Write};
+use std::os::unix::ffi::OsStrExt;
+use std::path::Path;
+use std::time::UNIX_EPOCH;
+
+/// DbWriter writes a database file entry by entry, e.g. to import a list of
+/// paths created by another tool.
+///
+/// Entries are expected in the stored order, i.e. sorted like a directory
+/// tree with [compare_paths](crate::compare_paths). Paths are stored as
+/// given, i.e. they should be absolute. Metadata not covered by the settings
+/// is not stored.
+pub struct DbWriter<W: Write> {
+    writer: ChecksumWriter<W>,
+    settings: Settings,
+    modification_times: bool,
+    previous: Vec<u8>,
+}
+
+impl<W: Write> DbWriter<W> {
+    /// Writes the header of a database file with the given settings.
+    pub fn new(writer: W, settings: Settings) -> IOResult<DbWriter<W>> {
+        DbWriter::create(writer, settings, false)
+    }
+
+    /// Writes the header of a database file with the given settings, which
+    /// additionally stores the modification time of each entry.
+    pub fn with_modification_times(writer: W, settings: Settings) -> IOResult<DbWriter<W>> {
+        DbWriter::create(writer, settings, true)
+    }
+
+    fn create(writer: W, settings: Settings, modification_times: bool) -> IOResult<DbWriter<W>> {
+        let mut writer = ChecksumWriter::new(writer);
+        let mut flags = settings.clone() as u8 | ENTRY_TYPES | FOOTER;
+        if modification_times {
+            flags |= MODIFICATION_TIMES;
+        }
+        writer.write_all("fsix".as_bytes())?;
+        writer.write_all(&[flags])?;
+        Ok(DbWriter {
+            writer,
+            settings,
+            modification_times,
+            previous: Vec::new(),
+        })
+    }
+
+    /// Writes the next entry.
+    pub fn add_entry(&mut self, path: &Path, metadata: &Metadata) -> IOResult<()> {
+        write_entry(
+            &mut self.writer,
+            &mut self.previous,
+            path.as_os_str().as_bytes(),
+            &self.settings,
+            self.modification_times,
+            metadata,
+        )
+    }
+
+    /// Writes the footer after the last entry and returns the underlying
+    /// writer.
+    pub fn finish(mut self) -> IOResult<W> {
+        self.writer.write_vu64(END_OF_ENTRIES)?;
+        self.writer.finish()
+    }
+}
+
+// Writes an entry delta encoded to the previous path, which is replaced with
+// the path. The format is read by FileIndexReader::advance.
+pub(crate) fn write_entry(
+    writer: &mut dyn Write,
+    previous: &mut Vec<u8>,
+    path: &[u8],
+    settings: &Settings,
+    modification_times: bool,
+    metadata: &Metadata,
+) -> IOResult<()> {
+    let (discard, delta) = delta_encode(previous, path);
+    writer.write_vu64(discard as u64)?;
+    writer.write_vu64(delta.len() as u64)?;
+    writer.write_all(delta)?;
+    // Zero for an unknown type.
+    writer.write_all(&[metadata.entry_type.map_or(0, |entry_type| entry_type as u8)])?;
+    if settings.has_sizes() {
+        writer.write_vu64(metadata.size.map_or(0, |size| size + 1))?;
+    }
+    if modification_times {
+        // Seconds since the epoch plus one. Zero for unknown times.
+        let modified_plus_one = metadata
+            .modified
+            .and_then(|modified| modified.duration_since(UNIX_EPOCH).ok())
+            .map_or(0, |modified| modified.as_secs() + 1);
+        writer.write_vu64(modified_plus_one)?;
+    }
+    if settings.has_metadata() {
+        // TLV records of the providers terminated by tag 0.
+        for (tag, value) in &metadata.attributes {
+            writer.write_vu64(*tag)?;
+            writer.write_vu64(value.len() as u64)?;
+            writer.write_all(value)?;
+        }
+        writer.write_vu64(0)?;
+    }
+    previous.clear();
+    previous.extend_from_slice(path);
+    Ok(())
+}
+
+fn delta_encode<'a>(a: &'a [u8], b: &'a [u8]) -> (usize, &'a [u8]) {
+    let mut idx: usize = 0;
+    for (a, b) in a.iter().zip(b.iter()) {
+        if a != b {
+            break;
+        }
+        idx += 1;
+    }
+    let discard = a.len() - idx;
+    let delta: &[u8] = &b[idx..];
+    (discard, delta)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::checksum::{verify_checksum, Crc32};
+    use crate::locate::EntryType;
+    use crate::temp_dir::TempDir;
+    use crate::DbReader;
+    use std::fs;
+    use std::path::PathBuf;
+
+    // The format has no values in native byte order or with the word size of
+    // the platform, i.e. these bytes are written on all platforms. Values
+    // below 128 are stored as vu64 in a single byte.
+    #[test]
+    fn platform_independent_format() {
+        let mut writer = DbWriter::new(Vec::new(), Settings::WithFileSizes).unwrap();
+        for (path, size, entry_type) in [
+            ("/a", None, EntryType::Directory),
+            ("/a/b", Some(5), EntryType::File),
+        ] {
+            let metadata = Metadata {
+                size,
+                modified: None,
+                attributes: Vec::new(),
+                entry_type: Some(entry_type),
+            };
+            writer.add_entry(Path::new(path), &metadata).unwrap();
+        }
+        let bytes = writer.finish().unwrap();
+        assert!(bytes.starts_with(b"fsix\x31\x00\x02/a\x02\x00\x00\x02/b\x01\x06"));
+        // The CRC-32 follows in big-endian byte order.
+        let (content, checksum) = bytes.split_at(bytes.len() - 4);
+        let mut crc = Crc32::new();
+        crc.update(content);
+        assert_eq!(checksum, crc.value().to_be_bytes());
+    }
+
+    #[test]
+    fn entries() {
+        let dir = TempDir::new("writer");
+        let database = dir.join("writer.fsdb");
+        let mut writer = DbWriter::new(Vec::new(), Settings::WithFileSizes).unwrap();
+        for (path, size, entry_type) in [
+            ("/a", None, Some(EntryType::Directory)),
+            ("/a/b", Some(1), Some(EntryType::File)),
+            ("/a/c", None, None),
+        ] {
+            let metadata = Metadata {
+                size,
+                modified: None,
+                attributes: Vec::new(),
+                entry_type,
+            };
+            writer.add_entry(Path::new(path), &metadata).unwrap();
+        }
+        fs::write(&database, writer.finish().unwrap()).unwrap();
+        let checksum = verify_checksum(&database);
+        let mut reader = DbReader::open(&database).unwrap();
+        let mut entries = Vec::new();
+        while let Some((path, metadata)) = reader.next_entry().unwrap() {
+            entries.push((path.to_owned(), metadata.size, metadata.entry_type));
+        }
+        assert!(checksum.unwrap());
+        assert_eq!(
+            entries,
+            vec![
+                (PathBuf::from("/a"), None, Some(EntryType::Directory)),
+                (PathBuf::from("/a/b"), Some(1), Some(EntryType::File)),
+                (PathBuf::from("/a/c"), None, None),
+            ]
+        );
+    }
+}